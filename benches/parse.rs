@@ -0,0 +1,109 @@
+// Parse and call benchmarks - run with `cargo bench`
+// A plain timing loop rather than a framework, so benchmarking adds no dependencies
+
+use duck_lang::ast::Expr;
+use duck_lang::interpreter::Interpreter;
+use duck_lang::lexer::lex;
+use duck_lang::parser::{parse_str, Parser};
+use duck_lang::visit::{walk_blocks_mut, walk_expr_mut, VisitorMut};
+use std::hint::black_box;
+use std::time::{Duration, Instant};
+
+/// A big program of the kind a code generator might spit out
+fn generated_program(functions: usize) -> String {
+    let mut source = String::new();
+    for i in 0..functions {
+        source.push_str(&format!(
+            concat!(
+                "-- helper number {i}\n",
+                "quack [define helper-{i} taking [a, b] as\n",
+                "  quack [let total be a * {i} + b - (a / 2)]\n",
+                "  quack [if total > 100 and b != 0 then\n",
+                "    quack [return f\"big {{total}} from helper-{i}\"]\n",
+                "  ]\n",
+                "  quack [return list(a, b, total, \"helper-{i}\")]\n",
+                "]\n",
+            ),
+            i = i
+        ));
+    }
+    source
+}
+
+/// Turns builtins back into plain names, as they were before the parser
+/// looked them up, so the interpreter has to find them on every call
+struct Unresolve;
+
+impl VisitorMut for Unresolve {
+    fn visit_expr_mut(&mut self, expr: &mut Expr) {
+        if let Expr::Builtin(builtin) = expr {
+            *expr = Expr::Identifier(builtin.name().to_string());
+        }
+        walk_expr_mut(self, expr);
+    }
+}
+
+/// Run `f` repeatedly for about a second and print the average time per run
+fn bench(name: &str, mut f: impl FnMut()) {
+    f();
+    let started = Instant::now();
+    let mut runs = 0u32;
+    while started.elapsed() < Duration::from_secs(1) {
+        f();
+        runs += 1;
+    }
+    let each = started.elapsed() / runs;
+    println!("{:<32} {:>10.3} ms/run ({} runs)", name, each.as_secs_f64() * 1000.0, runs);
+}
+
+fn main() {
+    let source = generated_program(2_000);
+    println!("generated program: {} lines, {} KiB", source.lines().count(), source.len() / 1024);
+
+    bench("lex", || {
+        black_box(lex(black_box(&source)).unwrap());
+    });
+    let tokens = lex(&source).unwrap();
+    bench("parse (tokens already lexed)", || {
+        black_box(Parser::new(black_box(tokens.clone())).parse().unwrap());
+    });
+    bench("lex + parse", || {
+        black_box(parse_str(black_box(&source)).unwrap());
+    });
+
+    // Every call looks the function up again, which used to copy its whole body
+    let calls = parse_str(concat!(
+        "quack [define fib taking [n] as\n",
+        "  quack [if n < 2 then quack [return n]]\n",
+        "  quack [return fib(n - 1) + fib(n - 2)]\n",
+        "]\n",
+        "quack [let answer be fib(18)]",
+    ))
+    .unwrap();
+    bench("fib(18)", || {
+        let mut interpreter = Interpreter::new();
+        interpreter.set_instruction_limit(None);
+        interpreter.run(black_box(calls.clone())).unwrap();
+    });
+
+    // A hot loop that is mostly builtin calls
+    let resolved = parse_str(concat!(
+        "quack [let word be \"goose\"]\n",
+        "quack [let total be 0]\n",
+        "quack [let i be 0]\n",
+        "quack [while i < 50000 do\n",
+        "  quack [total becomes total + abs(i - 100) + len(word)]\n",
+        "  quack [i becomes max(i, 0) + 1]\n",
+        "]",
+    ))
+    .unwrap();
+    let mut by_name = resolved.clone();
+    walk_blocks_mut(&mut Unresolve, &mut by_name);
+    for (name, program) in [("builtin calls (resolved)", &resolved), ("builtin calls (by name)", &by_name)] {
+        bench(name, || {
+            let mut interpreter = Interpreter::new();
+            interpreter.set_instruction_limit(None);
+            interpreter.run(black_box(program.clone())).unwrap();
+        });
+    }
+}
@@ -0,0 +1,11 @@
+// Throws arbitrary text at the lexer and parser, which must report errors
+// rather than panic however broken the input is
+#![no_main]
+
+use libfuzzer_sys::fuzz_target;
+
+fuzz_target!(|data: &[u8]| {
+    if let Ok(source) = std::str::from_utf8(data) {
+        let _ = duck_lang::parser::parse_str(source);
+    }
+});
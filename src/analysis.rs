@@ -0,0 +1,330 @@
+// Program analysis - what a Duck program defines and where each name is used
+//
+// `SymbolTable::build` resolves names the way the interpreter would: parameters,
+// loop variables, match bindings and anything `let` inside a function or loop
+// body are local, so a local `x` never counts as a use of a global `x`. Tools
+// that need to know what a name means (check, editors, docs) should start here
+// rather than walking the AST for definitions themselves.
+//
+// The AST only records lines, so definitions and references are lines too.
+
+use std::collections::{HashMap, HashSet};
+
+use serde::Serialize;
+
+use crate::ast::{AssignTarget, Block, Expr, MatchArm, Pattern, Statement};
+use crate::visit::{walk_assign_target, walk_block, walk_blocks, walk_expr, walk_pattern, walk_statement, Visitor};
+
+/// What a top-level name stands for
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "lowercase")]
+pub enum SymbolKind {
+    Function,
+    Struct,
+    /// A variable made with `let`
+    Global,
+    /// A variable made with `let ... always`
+    Constant,
+}
+
+/// A name the program defines outside any function or loop body
+#[derive(Debug, Clone, PartialEq, Serialize)]
+pub struct Symbol {
+    pub name: String,
+    pub kind: SymbolKind,
+    /// Where it's first defined
+    pub line: usize,
+    /// Parameters of a function or fields of a struct (empty for variables)
+    pub members: Vec<String>,
+    /// Every line that reads, assigns, calls or redefines it, in program order
+    pub references: Vec<usize>,
+}
+
+/// A use of a top-level function: a call, or passing it around by name
+#[derive(Debug, Clone, PartialEq)]
+pub struct Call {
+    /// The top-level function (or `Struct.method`) the use is inside, or
+    /// None for the top level of the program
+    pub caller: Option<String>,
+    pub callee: String,
+    pub line: usize,
+}
+
+/// Everything a program defines at the top level, in the order it's defined
+#[derive(Debug, Clone, Default, Serialize)]
+pub struct SymbolTable {
+    symbols: Vec<Symbol>,
+    #[serde(skip)]
+    calls: Vec<Call>,
+}
+
+impl SymbolTable {
+    /// Resolve the names in a program. Pass only the quacked blocks to see
+    /// what actually gets defined when it runs.
+    pub fn build<'a>(blocks: impl IntoIterator<Item = &'a Block>) -> SymbolTable {
+        let mut resolver = Resolver::default();
+        for block in blocks {
+            resolver.visit_block(block);
+        }
+        resolver.finish()
+    }
+
+    pub fn symbols(&self) -> &[Symbol] {
+        &self.symbols
+    }
+
+    pub fn get(&self, name: &str) -> Option<&Symbol> {
+        self.symbols.iter().find(|symbol| symbol.name == name)
+    }
+
+    /// The symbols of one kind, in definition order
+    pub fn of_kind(&self, kind: SymbolKind) -> impl Iterator<Item = &Symbol> {
+        self.symbols.iter().filter(move |symbol| symbol.kind == kind)
+    }
+
+    /// Every use of a top-level function, in program order
+    pub fn calls(&self) -> &[Call] {
+        &self.calls
+    }
+}
+
+/// Walks a program keeping track of which names are local at each point
+#[derive(Default)]
+struct Resolver {
+    symbols: Vec<Symbol>,
+    index: HashMap<String, usize>,
+    /// Names local to each function, lambda, loop or match arm we're inside
+    scopes: Vec<HashSet<String>>,
+    /// Uses of names that weren't local, with the function they're in,
+    /// matched up with symbols at the end (a function can be called above its definition)
+    uses: Vec<(String, usize, Option<String>)>,
+    line: usize,
+    /// The top-level function or method we're inside
+    caller: Option<String>,
+}
+
+impl Resolver {
+    fn finish(mut self) -> SymbolTable {
+        let mut calls = Vec::new();
+        for (name, line, caller) in std::mem::take(&mut self.uses) {
+            if let Some(&i) = self.index.get(&name) {
+                self.symbols[i].references.push(line);
+                if self.symbols[i].kind == SymbolKind::Function {
+                    calls.push(Call { caller, callee: name, line });
+                }
+            }
+        }
+        SymbolTable { symbols: self.symbols, calls }
+    }
+
+    fn is_local(&self, name: &str) -> bool {
+        self.scopes.iter().any(|scope| scope.contains(name))
+    }
+
+    fn use_name(&mut self, name: &str) {
+        if !self.is_local(name) {
+            self.uses.push((name.to_string(), self.line, self.caller.clone()));
+        }
+    }
+
+    /// Define a name here: a symbol at the top level, a local inside a body.
+    /// Defining a top-level name again counts as using it.
+    fn define(&mut self, name: &str, kind: SymbolKind, members: Vec<String>) {
+        if let Some(scope) = self.scopes.last_mut() {
+            scope.insert(name.to_string());
+        } else if self.index.contains_key(name) {
+            self.use_name(name);
+        } else {
+            self.index.insert(name.to_string(), self.symbols.len());
+            self.symbols.push(Symbol { name: name.to_string(), kind, line: self.line, members, references: Vec::new() });
+        }
+    }
+
+    /// Run `visit` with `names` in a new local scope
+    fn scoped(&mut self, names: impl IntoIterator<Item = String>, visit: impl FnOnce(&mut Self)) {
+        self.scopes.push(names.into_iter().collect());
+        visit(self);
+        self.scopes.pop();
+    }
+
+    /// Visit a function or method body, remembering whose body it is if it's at the top level
+    fn body_of(&mut self, name: String, params: Vec<String>, body: &[Block]) {
+        let outer = self.caller.clone();
+        if self.scopes.is_empty() {
+            self.caller = Some(name);
+        }
+        self.scoped(params, |resolver| walk_blocks(resolver, body));
+        self.caller = outer;
+    }
+
+    fn visit_arm(&mut self, arm: &MatchArm) {
+        self.visit_pattern(&arm.pattern);
+        self.scoped(bound_names(&arm.pattern), |resolver| {
+            if let Some(guard) = &arm.guard {
+                resolver.visit_expr(guard);
+            }
+            if let Some(expression) = &arm.expression {
+                resolver.visit_expr(expression);
+            }
+            if let Some(body) = &arm.body {
+                walk_blocks(resolver, body);
+            }
+        });
+    }
+}
+
+/// The names a pattern binds
+fn bound_names(pattern: &Pattern) -> Vec<String> {
+    match pattern {
+        Pattern::Variable(name) => vec![name.clone()],
+        Pattern::List { items, rest } => items.iter().chain(rest.as_deref()).flat_map(bound_names).collect(),
+        Pattern::Struct { fields, .. } => fields.iter().flat_map(|(_, field)| bound_names(field)).collect(),
+        Pattern::Literal(_) | Pattern::Wildcard => Vec::new(),
+    }
+}
+
+fn parameters(params: &[String], rest: &Option<String>) -> Vec<String> {
+    params.iter().chain(rest).cloned().collect()
+}
+
+impl Visitor for Resolver {
+    fn visit_block(&mut self, block: &Block) {
+        let outer = std::mem::replace(&mut self.line, block.line);
+        walk_block(self, block);
+        self.line = outer;
+    }
+
+    fn visit_statement(&mut self, stmt: &Statement) {
+        match stmt {
+            Statement::Let { name, value, constant } => {
+                self.visit_expr(value);
+                let kind = if *constant { SymbolKind::Constant } else { SymbolKind::Global };
+                self.define(name, kind, Vec::new());
+            }
+            Statement::LetPattern { pattern, value, constant } => {
+                self.visit_expr(value);
+                self.visit_pattern(pattern);
+                let kind = if *constant { SymbolKind::Constant } else { SymbolKind::Global };
+                for name in bound_names(pattern) {
+                    self.define(&name, kind, Vec::new());
+                }
+            }
+            Statement::Assign { target: AssignTarget::Variable(name), value }
+            | Statement::CompoundAssign { target: AssignTarget::Variable(name), amount: value, .. } => {
+                self.visit_expr(value);
+                self.use_name(name);
+            }
+            Statement::Assign { target, value } | Statement::CompoundAssign { target, amount: value, .. } => {
+                walk_assign_target(self, target);
+                self.visit_expr(value);
+            }
+            Statement::FunctionDef { name, params, rest, body } => {
+                let params = parameters(params, rest);
+                self.define(name, SymbolKind::Function, params.clone());
+                self.body_of(name.clone(), params, body);
+            }
+            Statement::MethodDef { struct_name, name, params, rest, body } => {
+                self.use_name(struct_name);
+                let names = parameters(params, rest).into_iter().chain(["self".to_string()]).collect();
+                self.body_of(format!("{}.{}", struct_name, name), names, body);
+            }
+            Statement::StructDef { name, fields } => self.define(name, SymbolKind::Struct, fields.clone()),
+            Statement::ForEach { pattern, iterable, body } => {
+                self.visit_expr(iterable);
+                self.visit_pattern(pattern);
+                self.scoped(bound_names(pattern), |resolver| walk_blocks(resolver, body));
+            }
+            Statement::Attempt { try_block, rescue_var, rescue_block } => {
+                walk_blocks(self, try_block);
+                self.scoped([rescue_var.clone()], |resolver| walk_blocks(resolver, rescue_block));
+            }
+            Statement::Match { value, arms } => {
+                self.visit_expr(value);
+                for arm in arms {
+                    self.visit_arm(arm);
+                }
+            }
+            // Bodies that run in an environment of their own
+            Statement::Block(body) | Statement::Test { body, .. } | Statement::BeforeEach(body) | Statement::AfterEach(body) => {
+                self.scoped([], |resolver| walk_blocks(resolver, body));
+            }
+            _ => walk_statement(self, stmt),
+        }
+    }
+
+    fn visit_expr(&mut self, expr: &Expr) {
+        match expr {
+            Expr::Identifier(name) => self.use_name(name),
+            Expr::StructInit { name, .. } => {
+                self.use_name(name);
+                walk_expr(self, expr);
+            }
+            Expr::Lambda { params, body } => self.scoped(params.clone(), |resolver| resolver.visit_expr(body)),
+            Expr::BlockLambda { params, body } => self.scoped(params.clone(), |resolver| walk_blocks(resolver, body)),
+            Expr::Match { value, arms } => {
+                self.visit_expr(value);
+                for arm in arms {
+                    self.visit_arm(arm);
+                }
+            }
+            _ => walk_expr(self, expr),
+        }
+    }
+
+    /// Struct patterns use the struct's name; the names a pattern binds are
+    /// handled by whatever statement the pattern belongs to
+    fn visit_pattern(&mut self, pattern: &Pattern) {
+        if let Pattern::Struct { name, .. } = pattern {
+            self.use_name(name);
+        }
+        walk_pattern(self, pattern);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::parser::parse_str;
+
+    #[test]
+    fn test_locals_shadow_globals() {
+        let source = concat!(
+            "quack [let total be 0]\n",
+            "quack [define add taking [total, n] as\n",
+            "    quack [return total + n]\n",
+            "]\n",
+            "quack [for each [n] in list(1, 2) do\n",
+            "    quack [total becomes add(total, n)]\n",
+            "]\n",
+            "quack [let doubled be map(list(1), [total] -> total * 2)]\n",
+            "quack [print total]",
+        );
+        let table = SymbolTable::build(&parse_str(source).unwrap());
+        let names: Vec<&str> = table.symbols().iter().map(|symbol| symbol.name.as_str()).collect();
+        assert_eq!(names, ["total", "add", "doubled"]);
+
+        let total = table.get("total").unwrap();
+        assert_eq!((total.kind, total.line), (SymbolKind::Global, 1));
+        assert_eq!(total.references, [6, 6, 9]);
+        let add = table.get("add").unwrap();
+        assert_eq!((add.kind, add.members.clone(), add.references.clone()), (SymbolKind::Function, vec!["total".to_string(), "n".to_string()], vec![6]));
+    }
+
+    #[test]
+    fn test_structs_and_constants() {
+        let source = concat!(
+            "quack [let limit be 3 always]\n",
+            "quack [struct Duck with [name]]\n",
+            "quack [define method greet on Duck taking [] as quack [return self.name]]\n",
+            "quack [match Duck(\"pip\") with [when Duck with [name] then quack [print name]]]\n",
+            "quack [define helper taking [] as quack [let inner be limit]]",
+        );
+        let table = SymbolTable::build(&parse_str(source).unwrap());
+        assert_eq!(table.get("limit").unwrap().kind, SymbolKind::Constant);
+        assert_eq!(table.get("limit").unwrap().references, [5]);
+        let duck = table.get("Duck").unwrap();
+        assert_eq!((duck.members.clone(), duck.references.clone()), (vec!["name".to_string()], vec![3, 4, 4]));
+        assert!(table.get("inner").is_none() && table.get("name").is_none());
+        assert_eq!(table.of_kind(SymbolKind::Function).count(), 1);
+    }
+}
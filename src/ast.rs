@@ -1,5 +1,10 @@
 // AST node types for Duck language
 
+use std::sync::Arc;
+
+use crate::builtins::Builtin;
+use crate::format_spec::FormatSpec;
+
 /// Binary operators for arithmetic, comparison, and logical operations
 #[derive(Debug, Clone, PartialEq)]
 pub enum BinaryOp {
@@ -25,6 +30,10 @@ pub enum BinaryOp {
 
     // String
     Concat,   // ++
+
+    // Nil-coalescing: the left side unless it's nil (the right is only
+    // evaluated when needed)
+    Coalesce, // ??
 }
 
 /// Unary operators
@@ -41,6 +50,8 @@ pub enum StringPart {
     Literal(String),
     /// Expression to be evaluated and converted to string
     Expr(Expr),
+    /// Expression shown with a format spec: `{x:.2}`
+    Formatted(Expr, FormatSpec),
 }
 
 /// Assignment targets - where values can be assigned
@@ -63,8 +74,12 @@ pub enum Pattern {
     Variable(String),
     /// Wildcard - _ (catch-all, don't bind)
     Wildcard,
-    /// Match a list structure
-    List(Vec<Pattern>),
+    /// Match a list structure: exactly these items, or at least these items
+    /// when there's a `rest...` pattern to take whatever is left (as a list)
+    List {
+        items: Vec<Pattern>,
+        rest: Option<Box<Pattern>>,
+    },
     /// Match a struct
     Struct {
         name: String,
@@ -77,7 +92,7 @@ pub enum Pattern {
 pub enum Literal {
     Int(i64),
     Float(f64),
-    String(String),
+    String(Arc<str>),
     Bool(bool),
     Nil,
 }
@@ -86,10 +101,12 @@ pub enum Literal {
 #[derive(Debug, Clone, PartialEq)]
 pub struct MatchArm {
     pub pattern: Pattern,
+    /// Extra condition after `if`, checked with the pattern's bindings in scope
+    pub guard: Option<Expr>,
     /// Expression result (for expression-form match)
     pub expression: Option<Expr>,
     /// Block body (for statement-form match)
-    pub body: Option<Vec<Statement>>,
+    pub body: Option<Vec<Block>>,
 }
 
 /// Expressions - anything that produces a value
@@ -101,6 +118,9 @@ pub enum Expr {
     /// A variable reference
     Identifier(String),
 
+    /// A builtin function, looked up once when parsed
+    Builtin(Builtin),
+
     /// Binary operation: left op right
     Binary {
         left: Box<Expr>,
@@ -132,19 +152,43 @@ pub enum Expr {
         index: Box<Expr>,
     },
 
+    /// Optional field access: object?.field is nil when object is nil
+    OptionalFieldAccess {
+        object: Box<Expr>,
+        field: String,
+    },
+
+    /// Optional indexing: object ?at index is nil when object is nil
+    /// (the index isn't evaluated then)
+    OptionalIndex {
+        object: Box<Expr>,
+        index: Box<Expr>,
+    },
+
+    /// Slicing: items at start to end (or through end, to include it)
+    Slice {
+        object: Box<Expr>,
+        start: Box<Expr>,
+        end: Box<Expr>,
+        inclusive: bool,
+    },
+
     /// List literal: [1, 2, 3]
     List(Vec<Expr>),
 
+    /// Map literal: map("a" -> 1, "b" -> 2)
+    MapLiteral(Vec<(Expr, Expr)>),
+
     /// Lambda expression: [params] -> expr
     Lambda {
         params: Vec<String>,
-        body: Box<Expr>,
+        body: Arc<Expr>,
     },
 
     /// Block lambda: [params] => [ quack [...] ... ]
     BlockLambda {
         params: Vec<String>,
-        body: Vec<Statement>,
+        body: Arc<[Block]>,
     },
 
     /// Struct instantiation: StructName { field: value, ... }
@@ -180,10 +224,20 @@ pub enum Expr {
 /// Statements - things that do something but may not produce a value
 #[derive(Debug, Clone, PartialEq)]
 pub enum Statement {
-    /// Variable declaration: let name = value
+    /// Variable declaration: let name = value, or `let name be value always`
+    /// for a constant that can never be reassigned
     Let {
         name: String,
         value: Expr,
+        constant: bool,
+    },
+
+    /// Unpacking declaration: let [a, rest...] be value, binding every name
+    /// in the list pattern (all as constants with `always`)
+    LetPattern {
+        pattern: Pattern,
+        value: Expr,
+        constant: bool,
     },
 
     /// Assignment: target = value (variable, field, or index)
@@ -192,6 +246,14 @@ pub enum Statement {
         value: Expr,
     },
 
+    /// Compound assignment: `x grows by n`, `xs at i shrinks by n`. The
+    /// target's object and index are evaluated once, not once per read and write
+    CompoundAssign {
+        target: AssignTarget,
+        operator: BinaryOp,
+        amount: Expr,
+    },
+
     /// Expression as a statement (for side effects)
     Expression(Expr),
 
@@ -199,20 +261,34 @@ pub enum Statement {
     Print(Expr),
 
     /// Block of statements
-    Block(Vec<Statement>),
+    Block(Vec<Block>),
 
-    /// Function definition: define name taking [params] as body
+    /// Function definition: define name taking [params] as body.
+    /// A last parameter written `rest...` collects any extra arguments as a list.
+    /// The body is shared with every function value made from it, so defining
+    /// and calling a function never copies its statements.
     FunctionDef {
         name: String,
         params: Vec<String>,
-        body: Vec<Statement>,
+        rest: Option<String>,
+        body: Arc<[Block]>,
+    },
+
+    /// Method definition: define method name on Struct taking [params] as body.
+    /// The body also sees the struct instance as `self`.
+    MethodDef {
+        struct_name: String,
+        name: String,
+        params: Vec<String>,
+        rest: Option<String>,
+        body: Arc<[Block]>,
     },
 
     /// If statement: if condition then ... otherwise ...
     If {
         condition: Expr,
-        then_block: Vec<Statement>,
-        otherwise_block: Option<Vec<Statement>>,
+        then_block: Vec<Block>,
+        otherwise_block: Option<Vec<Block>>,
     },
 
     /// Match statement
@@ -224,20 +300,21 @@ pub enum Statement {
     /// Repeat loop: repeat count times ...
     Repeat {
         count: Expr,
-        body: Vec<Statement>,
+        body: Vec<Block>,
     },
 
     /// While loop: while condition do ...
     While {
         condition: Expr,
-        body: Vec<Statement>,
+        body: Vec<Block>,
     },
 
-    /// For-each loop: for var in iterable do ...
+    /// For-each loop: for each [item] in iterable do ..., or for each [i, item]
+    /// to unpack every item with a list pattern
     ForEach {
-        variable: String,
+        pattern: Pattern,
         iterable: Expr,
-        body: Vec<Statement>,
+        body: Vec<Block>,
     },
 
     /// Struct definition: struct Name with [fields]
@@ -269,9 +346,9 @@ pub enum Statement {
 
     /// Error handling: attempt ... rescue err ...
     Attempt {
-        try_block: Vec<Statement>,
+        try_block: Vec<Block>,
         rescue_var: String,
-        rescue_block: Vec<Statement>,
+        rescue_block: Vec<Block>,
     },
 
     /// Module import: migrate "path" [as alias]
@@ -279,6 +356,18 @@ pub enum Statement {
         path: String,
         alias: Option<String>,
     },
+
+    /// Test case: test "name" do ... (only run by `goose test`)
+    Test {
+        name: String,
+        body: Vec<Block>,
+    },
+
+    /// Fixture run before every test: before each do ...
+    BeforeEach(Vec<Block>),
+
+    /// Fixture run after every test: after each do ...
+    AfterEach(Vec<Block>),
 }
 
 /// A block is a statement with metadata about parsing
@@ -312,6 +401,219 @@ impl Block {
     }
 }
 
+/// A generated file can chain tens of thousands of operators into one long
+/// left spine, which the default drop would tear down by recursion. This one
+/// unhooks nested operands onto a work list and drops them one at a time.
+impl Drop for Expr {
+    fn drop(&mut self) {
+        let mut pending = Vec::new();
+        self.unhook_operands(&mut pending);
+        while let Some(mut expr) = pending.pop() {
+            expr.unhook_operands(&mut pending);
+        }
+    }
+}
+
+impl Expr {
+    /// Move the operands of an operator, call or access out onto `pending`,
+    /// leaving nil in their place (leaves are left alone, they don't nest)
+    fn unhook_operands(&mut self, pending: &mut Vec<Expr>) {
+        let mut unhook = |operand: &mut Box<Expr>| {
+            if !matches!(**operand, Expr::Literal(_) | Expr::Identifier(_) | Expr::Builtin(_)) {
+                pending.push(std::mem::replace(&mut **operand, Expr::Literal(Literal::Nil)));
+            }
+        };
+        match self {
+            Expr::Binary { left, right, .. } => {
+                unhook(left);
+                unhook(right);
+            }
+            Expr::Index { object, index } | Expr::OptionalIndex { object, index } => {
+                unhook(object);
+                unhook(index);
+            }
+            Expr::Unary { operand, .. } => unhook(operand),
+            Expr::Call { callee, .. } => unhook(callee),
+            Expr::FieldAccess { object, .. } | Expr::OptionalFieldAccess { object, .. } => unhook(object),
+            _ => {}
+        }
+    }
+}
+
+// Constructor helpers for building ASTs by hand (tools, codemods, tests)
+
+impl Expr {
+    /// Integer literal
+    pub fn int(value: i64) -> Self {
+        Expr::Literal(Literal::Int(value))
+    }
+
+    /// Float literal
+    pub fn float(value: f64) -> Self {
+        Expr::Literal(Literal::Float(value))
+    }
+
+    /// String literal
+    pub fn string(value: impl Into<Arc<str>>) -> Self {
+        Expr::Literal(Literal::String(value.into()))
+    }
+
+    /// Boolean literal
+    pub fn boolean(value: bool) -> Self {
+        Expr::Literal(Literal::Bool(value))
+    }
+
+    /// The nil literal
+    pub fn nil() -> Self {
+        Expr::Literal(Literal::Nil)
+    }
+
+    /// Variable reference, or the builtin by that name
+    pub fn identifier(name: impl Into<String>) -> Self {
+        let name = name.into();
+        match Builtin::lookup(&name) {
+            Some(builtin) => Expr::Builtin(builtin),
+            None => Expr::Identifier(name),
+        }
+    }
+
+    /// The name a variable reference or builtin was written as
+    pub fn as_name(&self) -> Option<&str> {
+        match self {
+            Expr::Identifier(name) => Some(name),
+            Expr::Builtin(builtin) => Some(builtin.name()),
+            _ => None,
+        }
+    }
+
+    /// Binary operation: left op right
+    pub fn binary(left: Expr, operator: BinaryOp, right: Expr) -> Self {
+        Expr::Binary {
+            left: Box::new(left),
+            operator,
+            right: Box::new(right),
+        }
+    }
+
+    /// Unary operation: op operand
+    pub fn unary(operator: UnaryOp, operand: Expr) -> Self {
+        Expr::Unary {
+            operator,
+            operand: Box::new(operand),
+        }
+    }
+
+    /// Call a named function: name(args)
+    pub fn call(name: impl Into<String>, arguments: Vec<Expr>) -> Self {
+        Expr::Call {
+            callee: Box::new(Expr::identifier(name)),
+            arguments,
+        }
+    }
+
+    /// Field access: object.field
+    pub fn field(object: Expr, field: impl Into<String>) -> Self {
+        Expr::FieldAccess {
+            object: Box::new(object),
+            field: field.into(),
+        }
+    }
+
+    /// Indexing: object at index
+    pub fn index(object: Expr, index: Expr) -> Self {
+        Expr::Index {
+            object: Box::new(object),
+            index: Box::new(index),
+        }
+    }
+}
+
+impl Statement {
+    /// Variable declaration: let name be value
+    pub fn let_var(name: impl Into<String>, value: Expr) -> Self {
+        Statement::Let {
+            name: name.into(),
+            value,
+            constant: false,
+        }
+    }
+
+    /// Variable assignment: name becomes value
+    pub fn assign_var(name: impl Into<String>, value: Expr) -> Self {
+        Statement::Assign {
+            target: AssignTarget::Variable(name.into()),
+            value,
+        }
+    }
+
+    /// A short human-readable description of what this statement does
+    pub fn summary(&self) -> String {
+        match self {
+            Statement::Let { name, .. } => format!("let {}", name),
+            Statement::LetPattern { .. } => "unpack list".to_string(),
+            Statement::Assign { target: AssignTarget::Variable(name), .. } => format!("assign {}", name),
+            Statement::Assign { target: AssignTarget::Field { field, .. }, .. } => format!("assign field {}", field),
+            Statement::Assign { target: AssignTarget::Index { .. }, .. } => "assign index".to_string(),
+            Statement::CompoundAssign { target: AssignTarget::Variable(name), .. } => format!("update {}", name),
+            Statement::CompoundAssign { target: AssignTarget::Field { field, .. }, .. } => format!("update field {}", field),
+            Statement::CompoundAssign { target: AssignTarget::Index { .. }, .. } => "update index".to_string(),
+            Statement::Expression(Expr::Call { callee, .. }) => match callee.as_name() {
+                Some(name) => format!("call {}", name),
+                None => "call".to_string(),
+            },
+            Statement::Expression(_) => "expression".to_string(),
+            Statement::Print(_) => "print".to_string(),
+            Statement::Block(_) => "block".to_string(),
+            Statement::FunctionDef { name, .. } => format!("define function {}", name),
+            Statement::MethodDef { struct_name, name, .. } => format!("define method {} on {}", name, struct_name),
+            Statement::If { .. } => "if".to_string(),
+            Statement::Match { .. } => "match".to_string(),
+            Statement::Repeat { .. } => "repeat loop".to_string(),
+            Statement::While { .. } => "while loop".to_string(),
+            Statement::ForEach { pattern: Pattern::Variable(name), .. } => format!("for each {}", name),
+            Statement::ForEach { .. } => "for each, unpacking".to_string(),
+            Statement::StructDef { name, .. } => format!("define struct {}", name),
+            Statement::Return(_) => "return".to_string(),
+            Statement::Break => "break".to_string(),
+            Statement::Continue => "continue".to_string(),
+            Statement::Honk { .. } => "honk".to_string(),
+            Statement::Push { .. } => "push".to_string(),
+            Statement::Attempt { .. } => "attempt".to_string(),
+            Statement::Migrate { path, .. } => format!("migrate \"{}\"", path),
+            Statement::Test { name, .. } => format!("test \"{}\"", name),
+            Statement::BeforeEach(_) => "before each".to_string(),
+            Statement::AfterEach(_) => "after each".to_string(),
+        }
+    }
+
+    /// The nested block bodies this statement owns (loop bodies, branches, arms, ...)
+    pub fn bodies(&self) -> Vec<&[Block]> {
+        match self {
+            Statement::Block(body)
+            | Statement::Repeat { body, .. }
+            | Statement::While { body, .. }
+            | Statement::ForEach { body, .. }
+            | Statement::Test { body, .. }
+            | Statement::BeforeEach(body)
+            | Statement::AfterEach(body) => vec![body],
+            Statement::FunctionDef { body, .. } | Statement::MethodDef { body, .. } => vec![body],
+            Statement::If { then_block, otherwise_block, .. } => {
+                let mut bodies: Vec<&[Block]> = vec![then_block];
+                if let Some(otherwise) = otherwise_block {
+                    bodies.push(otherwise);
+                }
+                bodies
+            }
+            Statement::Match { arms, .. } => arms
+                .iter()
+                .filter_map(|arm| arm.body.as_deref())
+                .collect(),
+            Statement::Attempt { try_block, rescue_block, .. } => vec![try_block, rescue_block],
+            _ => Vec::new(),
+        }
+    }
+}
+
 /// A complete Duck program is a list of blocks
 #[derive(Debug, Clone, PartialEq)]
 pub struct Program {
@@ -344,6 +646,7 @@ impl std::fmt::Display for BinaryOp {
             BinaryOp::And => write!(f, "and"),
             BinaryOp::Or => write!(f, "or"),
             BinaryOp::Concat => write!(f, "++"),
+            BinaryOp::Coalesce => write!(f, "??"),
         }
     }
 }
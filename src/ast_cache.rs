@@ -0,0 +1,124 @@
+// AST cache - remembers recently parsed files so the flock doesn't re-lex them
+// Looked up by a hash of the whole source text and confirmed against the text
+// itself, so an edited file is lexed and parsed again from scratch (nothing is
+// re-parsed incrementally)
+
+use std::cell::RefCell;
+use std::collections::hash_map::DefaultHasher;
+use std::hash::{Hash, Hasher};
+use std::rc::Rc;
+
+use crate::ast::Block;
+use crate::lexer;
+use crate::parser;
+
+/// How many parsed programs to keep; past that, the least recently used goes
+const CAPACITY: usize = 32;
+
+/// One parsed program and the exact source it came from
+struct Entry {
+    hash: u64,
+    source: Rc<str>,
+    blocks: Rc<Vec<Block>>,
+}
+
+thread_local! {
+    /// Parsed programs for this thread, least recently used first
+    static CACHE: RefCell<Vec<Entry>> = const { RefCell::new(Vec::new()) };
+}
+
+/// Hash source text into a cache key
+fn source_hash(source: &str) -> u64 {
+    let mut hasher = DefaultHasher::new();
+    source.hash(&mut hasher);
+    hasher.finish()
+}
+
+/// Lex and parse source, reusing an earlier parse of identical source.
+/// `path` is only used to label error messages.
+pub fn parse_cached(source: &str, path: &str) -> Result<Rc<Vec<Block>>, String> {
+    let key = source_hash(source);
+
+    let hit = CACHE.with(|cache| {
+        let mut cache = cache.borrow_mut();
+        // Two sources can share a hash, so the text has to match too
+        let position = cache.iter().position(|entry| entry.hash == key && *entry.source == *source)?;
+        let entry = cache.remove(position);
+        let blocks = Rc::clone(&entry.blocks);
+        cache.push(entry);
+        Some(blocks)
+    });
+    if let Some(blocks) = hit {
+        return Ok(blocks);
+    }
+
+    let tokens = lexer::lex(source).map_err(|e| {
+        format!("Syntax error in '{}': {}", path, e)
+    })?;
+    let mut parser = parser::Parser::new(tokens);
+    let blocks = parser.parse().map_err(|errors| {
+        format!("Parse error in '{}': {}", path, errors.join(", "))
+    })?;
+
+    let blocks = Rc::new(blocks);
+    CACHE.with(|cache| {
+        let mut cache = cache.borrow_mut();
+        if cache.len() == CAPACITY {
+            cache.remove(0);
+        }
+        cache.push(Entry { hash: key, source: source.into(), blocks: Rc::clone(&blocks) });
+    });
+    Ok(blocks)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_identical_source_reuses_parse() {
+        let first = parse_cached("quack [print \"cached\"]", "a.duck").unwrap();
+        let second = parse_cached("quack [print \"cached\"]", "b.duck").unwrap();
+        assert!(Rc::ptr_eq(&first, &second));
+    }
+
+    #[test]
+    fn test_changed_source_is_reparsed() {
+        let first = parse_cached("quack [let x be 1]", "a.duck").unwrap();
+        let second = parse_cached("quack [let x be 2]", "a.duck").unwrap();
+        assert!(!Rc::ptr_eq(&first, &second));
+    }
+
+    #[test]
+    fn test_hash_collision_is_not_a_hit() {
+        let real = "quack [let x be \"real\"]";
+        let impostor = parse_cached("quack [let x be \"impostor\"]", "a.duck").unwrap();
+        // Pretend another source landed on the same hash
+        CACHE.with(|cache| {
+            cache.borrow_mut().push(Entry { hash: source_hash(real), source: "quack [print 1]".into(), blocks: Rc::clone(&impostor) })
+        });
+        assert!(!Rc::ptr_eq(&impostor, &parse_cached(real, "a.duck").unwrap()));
+    }
+
+    #[test]
+    fn test_least_recently_used_is_dropped_when_full() {
+        let source = |i: usize| format!("quack [let x be {}]", i);
+        let first = parse_cached(&source(0), "a.duck").unwrap();
+        let second = parse_cached(&source(1), "a.duck").unwrap();
+        for i in 2..=CAPACITY {
+            // Keep the first one in use, so the second is the oldest
+            parse_cached(&source(0), "a.duck").unwrap();
+            parse_cached(&source(i), "a.duck").unwrap();
+        }
+        assert!(Rc::ptr_eq(&first, &parse_cached(&source(0), "a.duck").unwrap()));
+        assert!(!Rc::ptr_eq(&second, &parse_cached(&source(1), "a.duck").unwrap()));
+        CACHE.with(|cache| assert_eq!(cache.borrow().len(), CAPACITY));
+    }
+
+    #[test]
+    fn test_errors_are_not_cached() {
+        let err = parse_cached("quack [let x be]", "broken.duck").unwrap_err();
+        assert!(err.contains("broken.duck"));
+        assert!(parse_cached("quack [let x be]", "broken.duck").is_err());
+    }
+}
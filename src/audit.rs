@@ -0,0 +1,136 @@
+// Audit log - a record of every file, network, input, and environment access a run makes
+// Built on ExecutionHooks, so it sees exactly what the program asked for, in order
+
+use std::cell::RefCell;
+use std::rc::Rc;
+
+use serde::Serialize;
+
+use crate::ast::Statement;
+use crate::hooks::ExecutionHooks;
+use crate::report;
+use crate::values::Value;
+
+/// What sort of outside-world access an entry records
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "lowercase")]
+pub enum AuditKind {
+    File,
+    Network,
+    Input,
+    Environment,
+    Import,
+    /// Clipboard and notifications
+    Desktop,
+    /// Running other programs
+    Process,
+}
+
+/// One I/O request made by the program
+#[derive(Debug, Clone, PartialEq, Serialize)]
+pub struct AuditEntry {
+    pub line: usize,
+    pub kind: AuditKind,
+    /// The builtin (or statement) that made the request, e.g. "write-file"
+    pub action: String,
+    /// The path, URL, prompt, or variable it was about, when there is one
+    pub target: Option<String>,
+}
+
+/// Hooks that collect an `AuditEntry` for every I/O builtin call and import.
+/// Clones share one log, so keep a clone to read it after the run.
+#[derive(Debug, Clone, Default)]
+pub struct AuditLog {
+    entries: Rc<RefCell<Vec<AuditEntry>>>,
+}
+
+impl AuditLog {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Everything recorded so far, in the order it happened
+    pub fn entries(&self) -> Vec<AuditEntry> {
+        self.entries.borrow().clone()
+    }
+
+    /// The log as a pretty-printed JSON report, with the entries under "entries"
+    pub fn to_json(&self) -> Result<String, String> {
+        #[derive(Serialize)]
+        struct Entries<'a> {
+            entries: &'a [AuditEntry],
+        }
+        report::to_json(&Entries { entries: &self.entries.borrow() })
+    }
+
+    fn record(&self, line: usize, kind: AuditKind, action: &str, target: Option<String>) {
+        self.entries.borrow_mut().push(AuditEntry {
+            line,
+            kind,
+            action: action.to_string(),
+            target,
+        });
+    }
+}
+
+/// Which builtins touch the outside world, and what kind of access they are
+fn audit_kind(builtin: &str) -> Option<AuditKind> {
+    match builtin {
+        "read-file" | "write-file" | "append-file" | "file-exists" | "glob" | "file-size" | "file-modified"
+        | "file-hash" | "watch-path" => Some(AuditKind::File),
+        "http-get" | "http-post" | "serve" | "ws-connect" => Some(AuditKind::Network),
+        "input" | "input-number" | "input-choice" | "input-yes-no" | "confirm" | "secret-input" => {
+            Some(AuditKind::Input)
+        }
+        "env" | "cwd" | "set-cwd" | "home-dir" | "temp-dir" => Some(AuditKind::Environment),
+        "clipboard-get" | "clipboard-set" | "notify" => Some(AuditKind::Desktop),
+        "shell" => Some(AuditKind::Process),
+        _ => None,
+    }
+}
+
+impl ExecutionHooks for AuditLog {
+    fn on_statement(&mut self, line: usize, statement: &Statement) {
+        if let Statement::Migrate { path, .. } = statement {
+            self.record(line, AuditKind::Import, "migrate", Some(path.clone()));
+        }
+    }
+
+    fn on_call(&mut self, name: &str, args: &[Value], line: usize) {
+        if let Some(kind) = audit_kind(name) {
+            self.record(line, kind, name, args.first().map(|target| target.to_string()));
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::interpreter::Interpreter;
+    use crate::lexer::lex;
+    use crate::parser::Parser;
+
+    #[test]
+    fn test_records_io_calls_in_order() {
+        let source = concat!(
+            "quack [let here be file-exists(\"no-such-pond.txt\")]\n",
+            "quack [let len-of be len(\"not io\")]\n",
+            "quack [let home be env(\"GOOSE_AUDIT_TEST\")]",
+        );
+        let blocks = Parser::new(lex(source).unwrap()).parse().unwrap();
+        let log = AuditLog::new();
+        let mut interpreter = Interpreter::new();
+        interpreter.add_hooks(Box::new(log.clone()));
+        interpreter.run(blocks).unwrap();
+
+        let entries = log.entries();
+        assert_eq!(entries.len(), 2);
+        assert_eq!(entries[0].kind, AuditKind::File);
+        assert_eq!(entries[0].target.as_deref(), Some("no-such-pond.txt"));
+        assert_eq!(entries[1].line, 3);
+        assert_eq!(entries[1].action, "env");
+        let json = log.to_json().unwrap();
+        assert!(json.contains("\"kind\": \"environment\""));
+        assert!(json.contains("\"schema_version\": 1"));
+    }
+}
@@ -1,164 +1,315 @@
 // Built-in functions for Duck language
 
-use crate::values::Value;
+use crate::capability::Capability;
+use crate::decimal::Decimal;
+use crate::format_spec::{check_width, FormatSpec};
+use crate::fraction::Fraction;
+use crate::glob;
+use crate::http;
+use crate::matrix;
+use crate::plot;
+use crate::property::Generator;
+use crate::schema;
+use crate::values::{sorted_entries, MapKey, Value};
+use regex::{Captures, Regex};
+use std::cell::RefCell;
+use std::cmp::Ordering;
 use std::collections::HashMap;
-use std::io::{self, Write};
-use std::thread;
-use std::time::Duration;
+use std::io;
 use std::fs;
-use std::path::{Path, Component};
+use std::path::{Component, Path, PathBuf};
+use std::sync::OnceLock;
+
+/// How a builtin runs
+#[derive(Clone, Copy)]
+enum Dispatch {
+    /// A plain function of its arguments
+    Native(fn(Vec<Value>) -> Result<Value, String>),
+    /// Needs the interpreter (callbacks, the clock, input, output...), which matches on which one
+    Interpreter(Hosted),
+}
 
-/// Check if a function name is a built-in function
-pub fn is_builtin(name: &str) -> bool {
-    matches!(
-        name,
-        "print"
-            | "input"
-            | "random"
-            | "floor"
-            | "ceil"
-            | "abs"
-            | "type-of"
-            | "len"
-            | "push"
-            | "pop"
-            | "string"
-            | "number"
-            | "sqrt"
-            | "pow"
-            | "min"
-            | "max"
-            | "range"
-            // Phase 1: String/list operations
-            | "reverse"
-            | "sort"
-            | "join"
-            | "split"
-            | "trim"
-            | "uppercase"
-            | "lowercase"
-            | "contains"
-            | "sleep"
-            | "keys"
-            | "values"
-            // Phase 2: File I/O
-            | "read-file"
-            | "write-file"
-            | "append-file"
-            | "file-exists"
-            // Phase 2: Higher-order functions (handled in interpreter)
-            | "map"
-            | "filter"
-            | "fold"
-            | "find"
-            | "any"
-            | "all"
-            // Environment and system
-            | "env"
-            // JSON support
-            | "json-parse"
-            | "json-stringify"
-            // HTTP client
-            | "http-get"
-            | "http-post"
-            // Base64 encoding
-            | "base64-encode"
-            | "base64-decode"
-    )
+/// The builtins the interpreter runs itself (see `Interpreter::call_function`)
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum Hosted {
+    Print,
+    Input,
+    InputNumber,
+    InputChoice,
+    InputYesNo,
+    Serve,
+    WatchPath,
+    ParseArgs,
+    Confirm,
+    SecretInput,
+    Random,
+    InsertAt,
+    RemoveAt,
+    Sleep,
+    Now,
+    Map,
+    Filter,
+    Fold,
+    Reduce,
+    Find,
+    Any,
+    All,
+    Args,
+    Exit,
+    Shell,
+    WsConnect,
+    WsSend,
+    WsReceive,
+    WsClose,
+    ClipboardGet,
+    ClipboardSet,
+    Notify,
+    GooseBonus,
+    GoosePenalty,
+    ForAll,
+    AssertEq,
+    AssertNe,
+    AssertClose,
 }
 
-/// Call a built-in function with the given arguments
-pub fn call_builtin(name: &str, args: Vec<Value>) -> Result<Value, String> {
-    match name {
-        "print" => builtin_print(args),
-        "input" => builtin_input(args),
-        "random" => builtin_random(args),
-        "floor" => builtin_floor(args),
-        "ceil" => builtin_ceil(args),
-        "abs" => builtin_abs(args),
-        "type-of" => builtin_type_of(args),
-        "len" => builtin_len(args),
-        "push" => builtin_push(args),
-        "pop" => builtin_pop(args),
-        "string" => builtin_string(args),
-        "number" => builtin_number(args),
-        "sqrt" => builtin_sqrt(args),
-        "pow" => builtin_pow(args),
-        "min" => builtin_min(args),
-        "max" => builtin_max(args),
-        "range" => builtin_range(args),
-        // Phase 1: String/list operations
-        "reverse" => builtin_reverse(args),
-        "sort" => builtin_sort(args),
-        "join" => builtin_join(args),
-        "split" => builtin_split(args),
-        "trim" => builtin_trim(args),
-        "uppercase" => builtin_uppercase(args),
-        "lowercase" => builtin_lowercase(args),
-        "contains" => builtin_contains(args),
-        "sleep" => builtin_sleep(args),
-        "keys" => builtin_keys(args),
-        "values" => builtin_values(args),
-        // Phase 2: File I/O
-        "read-file" => builtin_read_file(args),
-        "write-file" => builtin_write_file(args),
-        "append-file" => builtin_append_file(args),
-        "file-exists" => builtin_file_exists(args),
-        // Environment and system
-        "env" => builtin_env(args),
-        // JSON support
-        "json-parse" => builtin_json_parse(args),
-        "json-stringify" => builtin_json_stringify(args),
-        // HTTP client
-        "http-get" => builtin_http_get(args),
-        "http-post" => builtin_http_post(args),
-        // Base64 encoding
-        "base64-encode" => builtin_base64_encode(args),
-        "base64-decode" => builtin_base64_decode(args),
-        _ => Err(format!("Unknown builtin: {}", name)),
-    }
-}
-
-/// Print all arguments space-separated, then a newline
-fn builtin_print(args: Vec<Value>) -> Result<Value, String> {
-    for (i, arg) in args.iter().enumerate() {
-        if i > 0 {
-            print!(" ");
+/// Every builtin and how to call it
+static BUILTINS: &[(&str, Dispatch)] = &[
+    // I/O (handled in interpreter, which owns the output sink and input source)
+    ("print", Dispatch::Interpreter(Hosted::Print)),
+    ("input", Dispatch::Interpreter(Hosted::Input)),
+    ("input-number", Dispatch::Interpreter(Hosted::InputNumber)),
+    ("input-choice", Dispatch::Interpreter(Hosted::InputChoice)),
+    ("input-yes-no", Dispatch::Interpreter(Hosted::InputYesNo)),
+    ("serve", Dispatch::Interpreter(Hosted::Serve)),
+    ("watch-path", Dispatch::Interpreter(Hosted::WatchPath)),
+    ("parse-args", Dispatch::Interpreter(Hosted::ParseArgs)),
+    ("confirm", Dispatch::Interpreter(Hosted::Confirm)),
+    ("secret-input", Dispatch::Interpreter(Hosted::SecretInput)),
+    // Random numbers (handled in interpreter, which owns the generator)
+    ("random", Dispatch::Interpreter(Hosted::Random)),
+    // Math
+    ("floor", Dispatch::Native(builtin_floor)),
+    ("ceil", Dispatch::Native(builtin_ceil)),
+    ("abs", Dispatch::Native(builtin_abs)),
+    ("type-of", Dispatch::Native(builtin_type_of)),
+    ("len", Dispatch::Native(builtin_len)),
+    ("push", Dispatch::Native(builtin_push)),
+    ("pop", Dispatch::Native(builtin_pop)),
+    ("insert-at", Dispatch::Interpreter(Hosted::InsertAt)),
+    ("remove-at", Dispatch::Interpreter(Hosted::RemoveAt)),
+    ("string", Dispatch::Native(builtin_string)),
+    ("number", Dispatch::Native(builtin_number)),
+    ("sqrt", Dispatch::Native(builtin_sqrt)),
+    ("pow", Dispatch::Native(builtin_pow)),
+    ("min", Dispatch::Native(builtin_min)),
+    ("max", Dispatch::Native(builtin_max)),
+    ("range", Dispatch::Native(builtin_range)),
+    ("is-nan", Dispatch::Native(builtin_is_nan)),
+    ("is-finite", Dispatch::Native(builtin_is_finite)),
+    ("div", Dispatch::Native(builtin_div)),
+    ("divmod", Dispatch::Native(builtin_divmod)),
+    ("decimal", Dispatch::Native(builtin_decimal)),
+    ("fraction", Dispatch::Native(builtin_fraction)),
+    ("numerator", Dispatch::Native(|args| fraction_part(args, "numerator", Fraction::numerator))),
+    ("denominator", Dispatch::Native(|args| fraction_part(args, "denominator", Fraction::denominator))),
+    ("to-fixed", Dispatch::Native(builtin_to_fixed)),
+    ("format", Dispatch::Native(builtin_format)),
+    // Matrices
+    ("matrix", Dispatch::Native(builtin_matrix)),
+    ("transpose", Dispatch::Native(builtin_transpose)),
+    ("mat-mul", Dispatch::Native(builtin_mat_mul)),
+    ("mat-add", Dispatch::Native(|args| builtin_mat_elementwise(args, "mat-add", |a, b| a + b))),
+    ("mat-sub", Dispatch::Native(|args| builtin_mat_elementwise(args, "mat-sub", |a, b| a - b))),
+    ("mat-scale", Dispatch::Native(builtin_mat_scale)),
+    // Phase 1: String/list operations
+    ("reverse", Dispatch::Native(builtin_reverse)),
+    ("slice", Dispatch::Native(builtin_slice)),
+    ("zip", Dispatch::Native(builtin_zip)),
+    ("enumerate", Dispatch::Native(builtin_enumerate)),
+    ("sort", Dispatch::Native(builtin_sort)),
+    ("compare", Dispatch::Native(builtin_compare)),
+    ("compare-strings", Dispatch::Native(builtin_compare_strings)),
+    ("fill", Dispatch::Native(builtin_fill)),
+    ("repeat-list", Dispatch::Native(builtin_repeat_list)),
+    ("concat", Dispatch::Native(builtin_concat)),
+    ("freeze", Dispatch::Native(builtin_freeze)),
+    ("is-frozen", Dispatch::Native(builtin_is_frozen)),
+    ("copy", Dispatch::Native(builtin_copy)),
+    ("join", Dispatch::Native(builtin_join)),
+    ("split", Dispatch::Native(builtin_split)),
+    ("trim", Dispatch::Native(builtin_trim)),
+    ("pad-left", Dispatch::Native(|args| builtin_pad(args, "pad-left", true))),
+    ("pad-right", Dispatch::Native(|args| builtin_pad(args, "pad-right", false))),
+    ("repeat-string", Dispatch::Native(builtin_repeat_string)),
+    ("uppercase", Dispatch::Native(builtin_uppercase)),
+    ("lowercase", Dispatch::Native(builtin_lowercase)),
+    ("contains", Dispatch::Native(builtin_contains)),
+    ("starts-with", Dispatch::Native(|args| builtin_affix(args, "starts-with", |s, prefix| s.starts_with(prefix)))),
+    ("ends-with", Dispatch::Native(|args| builtin_affix(args, "ends-with", |s, suffix| s.ends_with(suffix)))),
+    ("index-of", Dispatch::Native(builtin_index_of)),
+    ("regex-match", Dispatch::Native(builtin_regex_match)),
+    ("regex-find-all", Dispatch::Native(builtin_regex_find_all)),
+    ("regex-replace", Dispatch::Native(builtin_regex_replace)),
+    // Time (handled in interpreter, which owns the clock)
+    ("sleep", Dispatch::Interpreter(Hosted::Sleep)),
+    ("now", Dispatch::Interpreter(Hosted::Now)),
+    ("keys", Dispatch::Native(builtin_keys)),
+    ("values", Dispatch::Native(builtin_values)),
+    // Phase 2: File I/O
+    ("read-file", Dispatch::Native(builtin_read_file)),
+    ("write-file", Dispatch::Native(builtin_write_file)),
+    ("append-file", Dispatch::Native(builtin_append_file)),
+    ("file-exists", Dispatch::Native(builtin_file_exists)),
+    ("glob", Dispatch::Native(builtin_glob)),
+    ("file-size", Dispatch::Native(builtin_file_size)),
+    ("file-modified", Dispatch::Native(builtin_file_modified)),
+    ("file-hash", Dispatch::Native(builtin_file_hash)),
+    ("path-matches", Dispatch::Native(builtin_path_matches)),
+    // Phase 2: Higher-order functions (handled in interpreter)
+    ("map", Dispatch::Interpreter(Hosted::Map)),
+    ("filter", Dispatch::Interpreter(Hosted::Filter)),
+    ("fold", Dispatch::Interpreter(Hosted::Fold)),
+    ("reduce", Dispatch::Interpreter(Hosted::Reduce)),
+    ("find", Dispatch::Interpreter(Hosted::Find)),
+    ("any", Dispatch::Interpreter(Hosted::Any)),
+    ("all", Dispatch::Interpreter(Hosted::All)),
+    // Environment and system
+    ("env", Dispatch::Native(builtin_env)),
+    ("args", Dispatch::Interpreter(Hosted::Args)),
+    ("exit", Dispatch::Interpreter(Hosted::Exit)),
+    ("shell", Dispatch::Interpreter(Hosted::Shell)),
+    // JSON support
+    ("json-parse", Dispatch::Native(builtin_json_parse)),
+    ("json-stringify", Dispatch::Native(builtin_json_stringify)),
+    // Config formats
+    ("toml-parse", Dispatch::Native(builtin_toml_parse)),
+    ("yaml-parse", Dispatch::Native(builtin_yaml_parse)),
+    // HTTP client
+    ("http-get", Dispatch::Native(builtin_http_get)),
+    ("http-post", Dispatch::Native(builtin_http_post)),
+    ("ws-connect", Dispatch::Interpreter(Hosted::WsConnect)),
+    ("ws-send", Dispatch::Interpreter(Hosted::WsSend)),
+    ("ws-receive", Dispatch::Interpreter(Hosted::WsReceive)),
+    ("ws-close", Dispatch::Interpreter(Hosted::WsClose)),
+    ("map-get", Dispatch::Native(builtin_map_get)),
+    ("map-set", Dispatch::Native(builtin_map_set)),
+    ("map-keys", Dispatch::Native(builtin_map_keys)),
+    ("map-values", Dispatch::Native(builtin_map_values)),
+    ("map-remove", Dispatch::Native(builtin_map_remove)),
+    ("has-key", Dispatch::Native(builtin_has_key)),
+    ("get-in", Dispatch::Native(builtin_get_in)),
+    ("set-in", Dispatch::Native(builtin_set_in)),
+    ("validate", Dispatch::Native(builtin_validate)),
+    ("cwd", Dispatch::Native(builtin_cwd)),
+    ("set-cwd", Dispatch::Native(builtin_set_cwd)),
+    ("home-dir", Dispatch::Native(|_| {
+        Ok(dirs::home_dir().map(|p| Value::String(p.to_string_lossy().into_owned().into())).unwrap_or(Value::Null))
+    })),
+    ("temp-dir", Dispatch::Native(|_| Ok(Value::String(std::env::temp_dir().to_string_lossy().into_owned().into())))),
+    ("path-join", Dispatch::Native(builtin_path_join)),
+    ("platform", Dispatch::Native(|_| Ok(Value::String(std::env::consts::OS.into())))),
+    ("clipboard-get", Dispatch::Interpreter(Hosted::ClipboardGet)),
+    ("clipboard-set", Dispatch::Interpreter(Hosted::ClipboardSet)),
+    ("notify", Dispatch::Interpreter(Hosted::Notify)),
+    ("style", Dispatch::Native(builtin_style)),
+    ("plot-bars", Dispatch::Native(builtin_plot_bars)),
+    ("plot-line", Dispatch::Native(builtin_plot_line)),
+    // Base64 encoding
+    ("base64-encode", Dispatch::Native(builtin_base64_encode)),
+    ("base64-decode", Dispatch::Native(builtin_base64_decode)),
+    // Rating adjustments (handled in interpreter)
+    ("goose-bonus", Dispatch::Interpreter(Hosted::GooseBonus)),
+    ("goose-penalty", Dispatch::Interpreter(Hosted::GoosePenalty)),
+    // Property testing (for-all is handled in interpreter)
+    ("gen-int", Dispatch::Native(builtin_gen_int)),
+    ("gen-string", Dispatch::Native(builtin_gen_string)),
+    ("gen-list", Dispatch::Native(builtin_gen_list)),
+    ("for-all", Dispatch::Interpreter(Hosted::ForAll)),
+    // Assertions (handled in interpreter, which honks with the line)
+    ("assert-eq", Dispatch::Interpreter(Hosted::AssertEq)),
+    ("assert-ne", Dispatch::Interpreter(Hosted::AssertNe)),
+    ("assert-close", Dispatch::Interpreter(Hosted::AssertClose)),
+];
+
+/// A builtin resolved by name once, so calling it again doesn't search for it
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub struct Builtin {
+    /// Position in `BUILTINS`
+    index: u16,
+    capability: Option<Capability>,
+}
+
+impl Builtin {
+    /// The builtin called `name`, if there is one
+    pub fn lookup(name: &str) -> Option<Builtin> {
+        static BY_NAME: OnceLock<HashMap<&'static str, Builtin>> = OnceLock::new();
+        let by_name = BY_NAME.get_or_init(|| {
+            BUILTINS
+                .iter()
+                .enumerate()
+                .map(|(index, (name, _))| (*name, Builtin { index: index as u16, capability: capability(name) }))
+                .collect()
+        });
+        by_name.get(name).copied()
+    }
+
+    pub fn name(self) -> &'static str {
+        BUILTINS[self.index as usize].0
+    }
+
+    /// The capability it needs, if it reaches outside the program
+    pub fn capability(self) -> Option<Capability> {
+        self.capability
+    }
+
+    /// Whether it changes the list, map or struct passed as its first argument
+    pub fn mutates_first_argument(self) -> bool {
+        matches!(self.name(), "push" | "pop" | "insert-at" | "remove-at" | "set-in" | "map-set" | "map-remove")
+    }
+
+    /// Which of the interpreter's own builtins it is, if the interpreter has to run it
+    pub fn hosted(self) -> Option<Hosted> {
+        match BUILTINS[self.index as usize].1 {
+            Dispatch::Native(_) => None,
+            Dispatch::Interpreter(hosted) => Some(hosted),
         }
-        print!("{}", arg);
     }
-    println!();
-    io::stdout().flush().ok();
-    Ok(Value::Null)
-}
 
-/// Read a line from stdin. Optional prompt argument
-fn builtin_input(args: Vec<Value>) -> Result<Value, String> {
-    if let Some(prompt) = args.first() {
-        print!("{}", prompt);
-        io::stdout().flush().ok();
+    /// Call it with the given arguments, if it doesn't need the interpreter
+    pub fn call(self, args: Vec<Value>) -> Result<Value, String> {
+        match BUILTINS[self.index as usize].1 {
+            Dispatch::Native(call) => call(args),
+            Dispatch::Interpreter(_) => Err(format!("{}() can only be called from a running program", self.name())),
+        }
     }
+}
+
+/// Check if a function name is a built-in function
+pub fn is_builtin(name: &str) -> bool {
+    Builtin::lookup(name).is_some()
+}
 
-    let mut input = String::new();
-    match io::stdin().read_line(&mut input) {
-        Ok(_) => {
-            let trimmed = input.trim_end_matches('\n').trim_end_matches('\r');
-            Ok(Value::String(trimmed.to_string()))
+/// The capability a builtin needs, if it reaches outside the program
+pub fn capability(name: &str) -> Option<Capability> {
+    match name {
+        "read-file" | "file-exists" | "glob" | "file-size" | "file-modified" | "file-hash" | "watch-path" => {
+            Some(Capability::FsRead)
         }
-        Err(e) => Err(format!("Failed to read input: {}", e)),
+        "write-file" | "append-file" => Some(Capability::FsWrite),
+        "http-get" | "http-post" | "serve" | "ws-connect" | "ws-send" | "ws-receive" | "ws-close" => Some(Capability::Net),
+        "set-cwd" | "shell" | "clipboard-get" | "clipboard-set" | "notify" => Some(Capability::Proc),
+        "env" | "cwd" | "home-dir" | "temp-dir" => Some(Capability::Env),
+        "now" | "sleep" => Some(Capability::Time),
+        _ => None,
     }
 }
 
-/// Return a pseudo-random f64 between 0.0 and 1.0
-fn builtin_random(_args: Vec<Value>) -> Result<Value, String> {
-    // Simple pseudo-random using time-based seed
-    use std::time::{SystemTime, UNIX_EPOCH};
-    let duration = SystemTime::now()
-        .duration_since(UNIX_EPOCH)
-        .unwrap_or_default();
-    let nanos = duration.subsec_nanos() as f64;
-    let rand = (nanos / 1_000_000_000.0).fract();
-    Ok(Value::Number(rand))
+/// Call a built-in function with the given arguments
+pub fn call_builtin(name: &str, args: Vec<Value>) -> Result<Value, String> {
+    match Builtin::lookup(name) {
+        Some(builtin) => builtin.call(args),
+        None => Err(format!("Unknown builtin: {}", name)),
+    }
 }
 
 /// Return the floor of a number
@@ -183,6 +334,10 @@ fn builtin_ceil(args: Vec<Value>) -> Result<Value, String> {
 fn builtin_abs(args: Vec<Value>) -> Result<Value, String> {
     match args.first() {
         Some(Value::Number(n)) => Ok(Value::Number(n.abs())),
+        Some(Value::Decimal(d)) if d.is_negative() => Ok(Value::Decimal(d.checked_neg()?)),
+        Some(Value::Decimal(d)) => Ok(Value::Decimal(*d)),
+        Some(Value::Fraction(f)) if f.numerator() < 0 => Ok(Value::Fraction(f.checked_neg()?)),
+        Some(Value::Fraction(f)) => Ok(Value::Fraction(*f)),
         Some(other) => Err(format!("abs() expects a number, got {}", other.type_name())),
         None => Err("abs() requires 1 argument".to_string()),
     }
@@ -191,7 +346,7 @@ fn builtin_abs(args: Vec<Value>) -> Result<Value, String> {
 /// Return the type of a value as a string
 fn builtin_type_of(args: Vec<Value>) -> Result<Value, String> {
     match args.first() {
-        Some(value) => Ok(Value::String(value.type_name().to_string())),
+        Some(value) => Ok(Value::String(value.type_name().into())),
         None => Err("type-of() requires 1 argument".to_string()),
     }
 }
@@ -201,8 +356,9 @@ fn builtin_len(args: Vec<Value>) -> Result<Value, String> {
     match args.first() {
         Some(Value::List(items)) => Ok(Value::Number(items.borrow().len() as f64)),
         Some(Value::String(s)) => Ok(Value::Number(s.chars().count() as f64)),
+        Some(Value::Map(map)) => Ok(Value::Number(map.borrow().len() as f64)),
         Some(other) => Err(format!(
-            "len() expects a list or string, got {}",
+            "len() expects a list, string or map, got {}",
             other.type_name()
         )),
         None => Err("len() requires 1 argument".to_string()),
@@ -217,6 +373,7 @@ fn builtin_push(args: Vec<Value>) -> Result<Value, String> {
 
     match &args[0] {
         Value::List(items) => {
+            args[0].ensure_mutable().map_err(|e| format!("push() can't {}", e))?;
             items.borrow_mut().push(args[1].clone());
             Ok(Value::Null)
         }
@@ -230,7 +387,8 @@ fn builtin_push(args: Vec<Value>) -> Result<Value, String> {
 /// Pop an item from a list (mutates the list, returns popped item)
 fn builtin_pop(args: Vec<Value>) -> Result<Value, String> {
     match args.first() {
-        Some(Value::List(items)) => {
+        Some(list @ Value::List(items)) => {
+            list.ensure_mutable().map_err(|e| format!("pop() can't {}", e))?;
             items
                 .borrow_mut()
                 .pop()
@@ -244,7 +402,7 @@ fn builtin_pop(args: Vec<Value>) -> Result<Value, String> {
 /// Convert a value to a string
 fn builtin_string(args: Vec<Value>) -> Result<Value, String> {
     match args.first() {
-        Some(value) => Ok(Value::String(format!("{}", value))),
+        Some(value) => Ok(Value::String(format!("{}", value).into())),
         None => Err("string() requires 1 argument".to_string()),
     }
 }
@@ -257,6 +415,8 @@ fn builtin_number(args: Vec<Value>) -> Result<Value, String> {
             .map(Value::Number)
             .map_err(|_| format!("Cannot convert '{}' to number", s)),
         Some(Value::Number(n)) => Ok(Value::Number(*n)),
+        Some(Value::Decimal(d)) => Ok(Value::Number(d.to_f64())),
+        Some(Value::Fraction(f)) => Ok(Value::Number(f.to_f64())),
         Some(Value::Boolean(b)) => Ok(Value::Number(if *b { 1.0 } else { 0.0 })),
         Some(other) => Err(format!("number() cannot convert {}", other.type_name())),
         None => Err("number() requires 1 argument".to_string()),
@@ -373,6 +533,199 @@ fn builtin_range(args: Vec<Value>) -> Result<Value, String> {
     }
 }
 
+/// Check whether a number is NaN ("not a number", e.g. from sqrt(-1))
+fn builtin_is_nan(args: Vec<Value>) -> Result<Value, String> {
+    match args.first() {
+        Some(Value::Number(n)) => Ok(Value::Boolean(n.is_nan())),
+        Some(other) => Err(format!("is-nan() expects a number, got {}", other.type_name())),
+        None => Err("is-nan() requires 1 argument".to_string()),
+    }
+}
+
+/// Check whether a number is an ordinary number (not infinity or NaN)
+fn builtin_is_finite(args: Vec<Value>) -> Result<Value, String> {
+    match args.first() {
+        Some(Value::Number(n)) => Ok(Value::Boolean(n.is_finite())),
+        Some(other) => Err(format!("is-finite() expects a number, got {}", other.type_name())),
+        None => Err("is-finite() requires 1 argument".to_string()),
+    }
+}
+
+/// Remainder that takes the sign of the divisor, so `floor_div(a, b) * b + floored_mod(a, b) == a`.
+/// This is what `%` means in Duck: `-7 % 3` is 2, not -1.
+pub fn floored_mod(a: f64, b: f64) -> f64 {
+    a - b * (a / b).floor()
+}
+
+/// Read the two numbers for div/divmod, rejecting a zero divisor
+fn division_args(args: &[Value], name: &str) -> Result<(f64, f64), String> {
+    match args {
+        [Value::Number(_), Value::Number(b)] if *b == 0.0 => Err(format!("{}() can't divide by zero", name)),
+        [Value::Number(a), Value::Number(b)] => Ok((*a, *b)),
+        [_, _] => Err(format!("{}() expects two numbers", name)),
+        _ => Err(format!("{}() requires 2 arguments, got {}", name, args.len())),
+    }
+}
+
+/// Floor division: divide, then round down (towards negative infinity)
+fn builtin_div(args: Vec<Value>) -> Result<Value, String> {
+    let (a, b) = division_args(&args, "div")?;
+    Ok(Value::Number((a / b).floor()))
+}
+
+/// Floor division and remainder together, as list(quotient, remainder)
+fn builtin_divmod(args: Vec<Value>) -> Result<Value, String> {
+    let (a, b) = division_args(&args, "divmod")?;
+    Ok(Value::new_list(vec![
+        Value::Number((a / b).floor()),
+        Value::Number(floored_mod(a, b)),
+    ]))
+}
+
+/// An exact decimal from text or a number: decimal("19.99"), decimal(5)
+fn builtin_decimal(args: Vec<Value>) -> Result<Value, String> {
+    match args.first() {
+        Some(Value::String(s)) => Decimal::parse(s).map(Value::Decimal),
+        Some(Value::Number(n)) => Decimal::from_f64(*n).map(Value::Decimal),
+        Some(Value::Decimal(d)) => Ok(Value::Decimal(*d)),
+        Some(other) => Err(format!("decimal() expects text or a number, got {}", other.type_name())),
+        None => Err("decimal() requires 1 argument".to_string()),
+    }
+}
+
+/// An exact fraction: fraction(1, 3), fraction("3/4"), or fraction(0.75) for 3/4
+fn builtin_fraction(args: Vec<Value>) -> Result<Value, String> {
+    let whole = |value: &Value| match value {
+        Value::Number(n) if n.fract() == 0.0 && n.abs() < 1e30 => Ok(*n as i128),
+        other => Err(format!("fraction() needs whole numbers on the top and bottom, got {}", other)),
+    };
+    let fraction = match args.as_slice() {
+        [top, bottom] => Fraction::new(whole(top)?, whole(bottom)?)?,
+        [Value::String(text)] => match text.split_once('/') {
+            Some((top, bottom)) => {
+                let part = |s: &str| s.trim().parse::<i128>().map_err(|_| format!("\"{}\" isn't a fraction like \"3/4\"", text));
+                Fraction::new(part(top)?, part(bottom)?)?
+            }
+            None => Fraction::from_decimal(Decimal::parse(text)?)?,
+        },
+        [Value::Number(n)] => Fraction::from_f64(*n)?,
+        [Value::Decimal(d)] => Fraction::from_decimal(*d)?,
+        [Value::Fraction(f)] => *f,
+        [other] => return Err(format!("fraction() expects numbers or text like \"3/4\", got {}", other.type_name())),
+        _ => return Err(format!("fraction() requires 1 or 2 arguments (top, bottom), got {}", args.len())),
+    };
+    Ok(Value::Fraction(fraction))
+}
+
+/// The top or bottom of a fraction, in lowest terms. Whole numbers count as n/1.
+fn fraction_part(args: Vec<Value>, name: &str, part: fn(Fraction) -> i128) -> Result<Value, String> {
+    let fraction = match args.first() {
+        Some(Value::Fraction(f)) => *f,
+        Some(Value::Number(n)) if n.fract() == 0.0 => Fraction::from_f64(*n)?,
+        Some(other) => return Err(format!("{}() expects a fraction, got {}", name, other.type_name())),
+        None => return Err(format!("{}() requires 1 argument", name)),
+    };
+    Ok(Value::Number(part(fraction) as f64))
+}
+
+/// A number or decimal as text with exactly `places` digits after the point,
+/// rounding halves away from zero: to-fixed(2.675, 2) is "2.68"
+fn builtin_to_fixed(args: Vec<Value>) -> Result<Value, String> {
+    let places = size_arg(&args, 1, 0, "to-fixed()")?;
+    if places > crate::decimal::MAX_SCALE as usize {
+        return Err(format!("to-fixed() can show at most {} places", crate::decimal::MAX_SCALE));
+    }
+    let decimal = match args.first() {
+        Some(Value::Decimal(d)) => *d,
+        Some(Value::Fraction(f)) => return Ok(Value::String(f.to_decimal(places as u32)?.to_string().into())),
+        // Infinity and numbers too big for a decimal are shown as they are
+        Some(Value::Number(n)) => match Decimal::from_f64(*n) {
+            Ok(d) => d,
+            Err(_) => return Ok(Value::String(format!("{:.*}", places, n).into())),
+        },
+        Some(other) => return Err(format!("to-fixed() expects a number, got {}", other.type_name())),
+        None => return Err("to-fixed() requires 1 or 2 arguments (number, places)".to_string()),
+    };
+    Ok(Value::String(decimal.round(places as u32)?.to_string().into()))
+}
+
+/// format(value, spec) - a value as text, with a spec like ".2" or "08.2"
+/// (see format_spec.rs); the same specs work inside f-strings as `{x:.2}`
+fn builtin_format(args: Vec<Value>) -> Result<Value, String> {
+    if args.len() != 2 {
+        return Err(format!("format() requires 2 arguments (value, spec), got {}", args.len()));
+    }
+    let spec = match &args[1] {
+        Value::String(spec) => FormatSpec::parse(spec)?,
+        other => return Err(format!("format() expects a spec string like \".2\", got {}", other.type_name())),
+    };
+    spec.apply(&args[0]).map(|s| Value::String(s.into()))
+}
+
+// =============================================================================
+// Matrices
+// =============================================================================
+
+/// A rows x cols matrix filled with one number: matrix(2, 3) or matrix(2, 3, 1)
+fn builtin_matrix(args: Vec<Value>) -> Result<Value, String> {
+    if args.len() < 2 {
+        return Err("matrix() requires 2 or 3 arguments (rows, cols, fill)".to_string());
+    }
+    let rows = size_arg(&args, 0, 0, "matrix()")?;
+    let cols = size_arg(&args, 1, 0, "matrix()")?;
+    built_items(rows, cols.max(1), "matrix()")?;
+    let fill = match args.get(2) {
+        None => 0.0,
+        Some(Value::Number(n)) => *n,
+        Some(other) => return Err(format!("matrix() fills with a number, got {}", other.type_name())),
+    };
+    Ok(matrix::to_value(matrix::filled(rows, cols, fill)))
+}
+
+/// Swap a matrix's rows and columns
+fn builtin_transpose(args: Vec<Value>) -> Result<Value, String> {
+    match args.first() {
+        Some(value) => Ok(matrix::to_value(matrix::transpose(&matrix::from_value(value, "transpose")?))),
+        None => Err("transpose() requires 1 argument (a matrix)".to_string()),
+    }
+}
+
+/// The matrix product of two matrices
+fn builtin_mat_mul(args: Vec<Value>) -> Result<Value, String> {
+    match args.as_slice() {
+        [a, b] => {
+            let a = matrix::from_value(a, "mat-mul")?;
+            let b = matrix::from_value(b, "mat-mul")?;
+            Ok(matrix::to_value(matrix::multiply(&a, &b)?))
+        }
+        _ => Err(format!("mat-mul() requires 2 arguments, got {}", args.len())),
+    }
+}
+
+/// Combine two same-sized matrices cell by cell (mat-add, mat-sub)
+fn builtin_mat_elementwise(args: Vec<Value>, name: &str, op: fn(f64, f64) -> f64) -> Result<Value, String> {
+    match args.as_slice() {
+        [a, b] => {
+            let a = matrix::from_value(a, name)?;
+            let b = matrix::from_value(b, name)?;
+            Ok(matrix::to_value(matrix::elementwise(&a, &b, name, op)?))
+        }
+        _ => Err(format!("{}() requires 2 arguments, got {}", name, args.len())),
+    }
+}
+
+/// Multiply every cell of a matrix by a number
+fn builtin_mat_scale(args: Vec<Value>) -> Result<Value, String> {
+    match args.as_slice() {
+        [m, Value::Number(k)] => {
+            let m = matrix::from_value(m, "mat-scale")?;
+            Ok(matrix::to_value(m.into_iter().map(|row| row.into_iter().map(|x| x * k).collect()).collect()))
+        }
+        [_, other] => Err(format!("mat-scale() multiplies by a number, got {}", other.type_name())),
+        _ => Err(format!("mat-scale() requires 2 arguments, got {}", args.len())),
+    }
+}
+
 // =============================================================================
 // Phase 1: String/List Operations
 // =============================================================================
@@ -387,7 +740,7 @@ fn builtin_reverse(args: Vec<Value>) -> Result<Value, String> {
         }
         Some(Value::String(s)) => {
             let reversed: String = s.chars().rev().collect();
-            Ok(Value::String(reversed))
+            Ok(Value::String(reversed.into()))
         }
         Some(other) => Err(format!(
             "reverse() expects a list or string, got {}",
@@ -397,62 +750,302 @@ fn builtin_reverse(args: Vec<Value>) -> Result<Value, String> {
     }
 }
 
-/// Sort a list of numbers or strings
+/// Pair up two lists item by item: zip(list(1, 2), list("a", "b")) is
+/// [[1, "a"], [2, "b"]]. Stops at the end of the shorter list.
+fn builtin_zip(args: Vec<Value>) -> Result<Value, String> {
+    if args.len() != 2 {
+        return Err(format!("zip() requires 2 lists, got {} arguments", args.len()));
+    }
+    match (&args[0], &args[1]) {
+        (Value::List(a), Value::List(b)) => {
+            let pairs = a
+                .borrow()
+                .iter()
+                .zip(b.borrow().iter())
+                .map(|(x, y)| Value::new_list(vec![x.clone(), y.clone()]))
+                .collect();
+            Ok(Value::new_list(pairs))
+        }
+        (Value::List(_), other) | (other, _) => Err(format!("zip() expects two lists, got {}", other.type_name())),
+    }
+}
+
+/// Each item with its position, counting from 0: enumerate(list("a", "b")) is
+/// [[0, "a"], [1, "b"]], ready for `for each [i, item] in ...`
+fn builtin_enumerate(args: Vec<Value>) -> Result<Value, String> {
+    let items: Vec<Value> = match args.first() {
+        Some(Value::List(items)) => items.borrow().clone(),
+        Some(Value::String(s)) => s.chars().map(|c| Value::String(c.to_string().into())).collect(),
+        Some(other) => return Err(format!("enumerate() expects a list or string, got {}", other.type_name())),
+        None => return Err("enumerate() requires 1 argument".to_string()),
+    };
+    let pairs = items
+        .into_iter()
+        .enumerate()
+        .map(|(i, item)| Value::new_list(vec![Value::Number(i as f64), item]))
+        .collect();
+    Ok(Value::new_list(pairs))
+}
+
+/// Where a slice from `start` to `end` begins and ends in something `len`
+/// long. Negative positions count from the end; ones past either end are
+/// clamped, and no end means the end.
+fn slice_bounds(len: usize, start: i64, end: Option<i64>) -> (usize, usize) {
+    let clamp = |i: i64| {
+        if i < 0 {
+            (len as i64 + i).max(0) as usize
+        } else {
+            (i as usize).min(len)
+        }
+    };
+    let from = clamp(start);
+    (from, end.map_or(len, clamp).max(from))
+}
+
+/// slice(list or string, start, end) - the part from start up to (not
+/// including) end, as a new list or string. A nil or missing end means "to the end".
+fn builtin_slice(args: Vec<Value>) -> Result<Value, String> {
+    let position = |index: usize, what: &str| match args.get(index) {
+        None | Some(Value::Null) => Ok(None),
+        Some(Value::Number(n)) if n.fract() == 0.0 => Ok(Some(*n as i64)),
+        Some(other) => Err(format!("slice() expects the {} to be a whole number, got {}", what, other)),
+    };
+    let start = position(1, "start")?.unwrap_or(0);
+    let end = position(2, "end")?;
+    match args.first() {
+        Some(Value::List(items)) => {
+            let items = items.borrow();
+            let (from, to) = slice_bounds(items.len(), start, end);
+            Ok(Value::new_list(items[from..to].to_vec()))
+        }
+        Some(Value::String(s)) => {
+            let (from, to) = slice_bounds(s.chars().count(), start, end);
+            Ok(Value::String(s.chars().skip(from).take(to - from).collect::<String>().into()))
+        }
+        Some(other) => Err(format!("slice() expects a list or string, got {}", other.type_name())),
+        None => Err("slice() requires a list or string, a start and an end".to_string()),
+    }
+}
+
+/// Sort a list - numbers and strings the usual way, mixed lists by `compare`.
+/// An optional second argument gives string options (see `StringOrder`).
 fn builtin_sort(args: Vec<Value>) -> Result<Value, String> {
+    let order = match args.get(1) {
+        Some(options) => StringOrder::from_value(options, "sort")?,
+        None => StringOrder::default(),
+    };
     match args.first() {
         Some(Value::List(items)) => {
-            let borrowed = items.borrow();
-            if borrowed.is_empty() {
-                return Ok(Value::new_list(vec![]));
+            // Sort a copy, so the original list is never touched
+            let mut sorted: Vec<Value> = items.borrow().clone();
+            sorted.sort_by(|a, b| match (a, b) {
+                (Value::String(a), Value::String(b)) => compare_strings(a, b, order),
+                _ => a.compare(b),
+            });
+            Ok(Value::new_list(sorted))
+        }
+        Some(other) => Err(format!("sort() expects a list, got {}", other.type_name())),
+        None => Err("sort() requires 1 argument".to_string()),
+    }
+}
+
+/// How to compare strings: by default, character by character (so "Banana" < "apple")
+#[derive(Debug, Clone, Copy, Default, PartialEq)]
+pub struct StringOrder {
+    /// "ignore-case": "apple" < "Banana"
+    pub ignore_case: bool,
+    /// "natural": runs of digits compare as numbers, so "file2" < "file10"
+    pub natural: bool,
+}
+
+impl StringOrder {
+    /// Read options given as a string ("natural") or a list of strings
+    fn from_value(value: &Value, function: &str) -> Result<StringOrder, String> {
+        let names: Vec<Value> = match value {
+            Value::List(items) => items.borrow().clone(),
+            other => vec![other.clone()],
+        };
+        let mut order = StringOrder::default();
+        for name in names {
+            match &name {
+                Value::String(s) if &**s == "ignore-case" => order.ignore_case = true,
+                Value::String(s) if &**s == "natural" => order.natural = true,
+                other => return Err(format!(
+                    "{}() doesn't know the option {} - use \"ignore-case\" and/or \"natural\"",
+                    function, other
+                )),
             }
+        }
+        Ok(order)
+    }
+}
 
-            // Check if all numbers or all strings
-            let first = &borrowed[0];
-            let mut sorted: Vec<Value> = borrowed.clone();
+/// Compare two strings. Doesn't depend on the system locale: case folding is
+/// Unicode lowercasing, and ties are broken by the exact characters so the
+/// order is always total.
+pub fn compare_strings(a: &str, b: &str, order: StringOrder) -> Ordering {
+    let fold = |s: &str| -> String {
+        if order.ignore_case { s.to_lowercase() } else { s.to_string() }
+    };
+    let (folded_a, folded_b) = (fold(a), fold(b));
+    let primary = if order.natural {
+        compare_natural(&folded_a, &folded_b)
+    } else {
+        folded_a.cmp(&folded_b)
+    };
+    primary.then_with(|| a.cmp(b))
+}
 
-            match first {
-                Value::Number(_) => {
-                    // Verify all are numbers
-                    for v in &sorted {
-                        if !matches!(v, Value::Number(_)) {
-                            return Err("sort() cannot sort mixed types".to_string());
-                        }
+/// Compare with digit runs treated as whole numbers
+fn compare_natural(a: &str, b: &str) -> Ordering {
+    let (mut a, mut b) = (a.chars().peekable(), b.chars().peekable());
+    loop {
+        match (a.peek().copied(), b.peek().copied()) {
+            (None, None) => return Ordering::Equal,
+            (None, Some(_)) => return Ordering::Less,
+            (Some(_), None) => return Ordering::Greater,
+            (Some(x), Some(y)) if x.is_ascii_digit() && y.is_ascii_digit() => {
+                let take_digits = |chars: &mut std::iter::Peekable<std::str::Chars>| {
+                    let mut digits = String::new();
+                    while let Some(c) = chars.peek().filter(|c| c.is_ascii_digit()) {
+                        digits.push(*c);
+                        chars.next();
                     }
-                    sorted.sort_by(|a, b| {
-                        if let (Value::Number(na), Value::Number(nb)) = (a, b) {
-                            na.partial_cmp(nb).unwrap_or(std::cmp::Ordering::Equal)
-                        } else {
-                            std::cmp::Ordering::Equal
-                        }
-                    });
+                    digits
+                };
+                let (da, db) = (take_digits(&mut a), take_digits(&mut b));
+                let (ta, tb) = (da.trim_start_matches('0'), db.trim_start_matches('0'));
+                let by_value = ta.len().cmp(&tb.len()).then_with(|| ta.cmp(tb));
+                if by_value.is_ne() {
+                    return by_value;
                 }
-                Value::String(_) => {
-                    // Verify all are strings
-                    for v in &sorted {
-                        if !matches!(v, Value::String(_)) {
-                            return Err("sort() cannot sort mixed types".to_string());
-                        }
-                    }
-                    sorted.sort_by(|a, b| {
-                        if let (Value::String(sa), Value::String(sb)) = (a, b) {
-                            sa.cmp(sb)
-                        } else {
-                            std::cmp::Ordering::Equal
-                        }
-                    });
-                }
-                other => {
-                    return Err(format!(
-                        "sort() can only sort numbers or strings, got {}",
-                        other.type_name()
-                    ));
+            }
+            (Some(x), Some(y)) => {
+                if x != y {
+                    return x.cmp(&y);
                 }
+                a.next();
+                b.next();
             }
+        }
+    }
+}
 
-            Ok(Value::new_list(sorted))
+/// compare-strings(a, b, options) - -1, 0 or 1, with optional "ignore-case"/"natural"
+fn builtin_compare_strings(args: Vec<Value>) -> Result<Value, String> {
+    let order = match args.get(2) {
+        Some(options) => StringOrder::from_value(options, "compare-strings")?,
+        None => StringOrder::default(),
+    };
+    match (args.first(), args.get(1)) {
+        (Some(Value::String(a)), Some(Value::String(b))) => Ok(Value::Number(match compare_strings(a, b, order) {
+            Ordering::Less => -1.0,
+            Ordering::Equal => 0.0,
+            Ordering::Greater => 1.0,
+        })),
+        (Some(_), Some(_)) => Err("compare-strings() expects two strings".to_string()),
+        _ => Err(format!("compare-strings() requires at least 2 arguments, got {}", args.len())),
+    }
+}
+
+/// Compare two values of any type: -1, 0 or 1 (see `Value::compare` for the order)
+fn builtin_compare(args: Vec<Value>) -> Result<Value, String> {
+    match args.as_slice() {
+        [a, b] => Ok(Value::Number(match a.compare(b) {
+            Ordering::Less => -1.0,
+            Ordering::Equal => 0.0,
+            Ordering::Greater => 1.0,
+        })),
+        _ => Err(format!("compare() requires 2 arguments, got {}", args.len())),
+    }
+}
+
+/// Read a whole, non-negative count argument
+fn count_arg(value: Option<&Value>, usage: &str) -> Result<usize, String> {
+    match value {
+        Some(Value::Number(n)) if *n >= 0.0 && n.fract() == 0.0 => Ok(*n as usize),
+        Some(other) => Err(format!("{} expects a whole, non-negative count, got {}", usage, other)),
+        None => Err(format!("{} is missing its count", usage)),
+    }
+}
+
+/// The most items fill, repeat-list and matrix build in one go, so a typo'd
+/// count gets an error instead of eating all the memory
+pub const MAX_BUILT_ITEMS: usize = 10_000_000;
+
+/// How many items `count` lots of `each` make, if that's within MAX_BUILT_ITEMS
+fn built_items(count: usize, each: usize, usage: &str) -> Result<usize, String> {
+    count
+        .checked_mul(each)
+        .filter(|total| *total <= MAX_BUILT_ITEMS)
+        .ok_or_else(|| format!("{} would make more than {} items - the goose stops there", usage, MAX_BUILT_ITEMS))
+}
+
+/// fill(n, value) - a list of n copies of value. Each copy is separate, so
+/// `fill(3, fill(3, 0))` gives three rows that can be changed independently
+fn builtin_fill(args: Vec<Value>) -> Result<Value, String> {
+    if args.len() != 2 {
+        return Err(format!("fill() requires 2 arguments, got {}", args.len()));
+    }
+    let n = count_arg(args.first(), "fill(n, value)")?;
+    built_items(n, 1, "fill(n, value)")?;
+    Ok(Value::new_list((0..n).map(|_| args[1].deep_clone()).collect()))
+}
+
+/// repeat-list(list, n) - the list's items repeated n times, with separate copies each time
+fn builtin_repeat_list(args: Vec<Value>) -> Result<Value, String> {
+    if args.len() != 2 {
+        return Err(format!("repeat-list() requires 2 arguments, got {}", args.len()));
+    }
+    let items = match &args[0] {
+        Value::List(items) => items.borrow().clone(),
+        other => return Err(format!("repeat-list() expects a list, got {}", other.type_name())),
+    };
+    let n = count_arg(args.get(1), "repeat-list(list, n)")?;
+    let total = built_items(items.len(), n, "repeat-list(list, n)")?;
+    Ok(Value::new_list(items.iter().cycle().take(total).map(Value::deep_clone).collect()))
+}
+
+/// concat(a, b, ...) - one new list with the items of every list argument, in order
+fn builtin_concat(args: Vec<Value>) -> Result<Value, String> {
+    let mut joined = Vec::new();
+    for (i, arg) in args.iter().enumerate() {
+        match arg {
+            Value::List(items) => joined.extend(items.borrow().iter().cloned()),
+            other => return Err(format!(
+                "concat() expects lists, but argument {} is {}",
+                i + 1,
+                other.type_name()
+            )),
         }
-        Some(other) => Err(format!("sort() expects a list, got {}", other.type_name())),
-        None => Err("sort() requires 1 argument".to_string()),
+    }
+    Ok(Value::new_list(joined))
+}
+
+/// freeze(value) - make a list or struct (and everything in it) immutable; returns it
+fn builtin_freeze(args: Vec<Value>) -> Result<Value, String> {
+    match args.first() {
+        Some(value) => {
+            value.freeze();
+            Ok(value.clone())
+        }
+        None => Err("freeze() requires 1 argument".to_string()),
+    }
+}
+
+/// is-frozen(value) - whether a list or struct has been frozen
+fn builtin_is_frozen(args: Vec<Value>) -> Result<Value, String> {
+    match args.first() {
+        Some(value) => Ok(Value::Boolean(value.is_frozen())),
+        None => Err("is-frozen() requires 1 argument".to_string()),
+    }
+}
+
+/// copy(value) - a separate deep copy of a list or struct, safe to change
+fn builtin_copy(args: Vec<Value>) -> Result<Value, String> {
+    match args.first() {
+        Some(value) => Ok(value.copy()),
+        None => Err("copy() requires 1 argument".to_string()),
     }
 }
 
@@ -465,7 +1058,7 @@ fn builtin_join(args: Vec<Value>) -> Result<Value, String> {
     match (&args[0], &args[1]) {
         (Value::List(items), Value::String(sep)) => {
             let strings: Vec<String> = items.borrow().iter().map(|v| format!("{}", v)).collect();
-            Ok(Value::String(strings.join(sep)))
+            Ok(Value::String(strings.join(sep).into()))
         }
         (Value::List(_), other) => Err(format!(
             "join() expects a string separator, got {}",
@@ -486,7 +1079,7 @@ fn builtin_split(args: Vec<Value>) -> Result<Value, String> {
 
     match (&args[0], &args[1]) {
         (Value::String(s), Value::String(sep)) => {
-            let parts: Vec<Value> = s.split(sep.as_str()).map(|p| Value::String(p.to_string())).collect();
+            let parts: Vec<Value> = s.split(&**sep).map(|p| Value::String(p.into())).collect();
             Ok(Value::new_list(parts))
         }
         (Value::String(_), other) => Err(format!(
@@ -503,16 +1096,52 @@ fn builtin_split(args: Vec<Value>) -> Result<Value, String> {
 /// Trim whitespace from a string
 fn builtin_trim(args: Vec<Value>) -> Result<Value, String> {
     match args.first() {
-        Some(Value::String(s)) => Ok(Value::String(s.trim().to_string())),
+        Some(Value::String(s)) => Ok(Value::String(s.trim().into())),
         Some(other) => Err(format!("trim() expects a string, got {}", other.type_name())),
         None => Err("trim() requires 1 argument".to_string()),
     }
 }
 
+/// pad-left / pad-right(value, width, [char]) - the value as a string, padded
+/// with spaces (or `char`) to at least `width` characters. Longer values are left as they are.
+fn builtin_pad(args: Vec<Value>, name: &str, left: bool) -> Result<Value, String> {
+    if args.len() < 2 || args.len() > 3 {
+        return Err(format!("{}() requires 2 or 3 arguments, got {}", name, args.len()));
+    }
+    let text = match &args[0] {
+        Value::String(s) => s.to_string(),
+        other => other.to_string(),
+    };
+    let width = check_width(count_arg(args.get(1), &format!("{}(value, width, char)", name))?)?;
+    let pad = match args.get(2) {
+        None => ' ',
+        Some(Value::String(s)) if s.chars().count() == 1 => s.chars().next().unwrap_or(' '),
+        Some(Value::String(s)) => return Err(format!("{}() pads with a single character, got \"{}\"", name, s)),
+        Some(other) => return Err(format!("{}() expects a string to pad with, got {}", name, other.type_name())),
+    };
+    let padding: String = std::iter::repeat_n(pad, width.saturating_sub(text.chars().count())).collect();
+    Ok(Value::String(if left { padding + &text } else { text + &padding }.into()))
+}
+
+/// repeat-string(text, n) - the text n times over
+fn builtin_repeat_string(args: Vec<Value>) -> Result<Value, String> {
+    if args.len() != 2 {
+        return Err(format!("repeat-string() requires 2 arguments, got {}", args.len()));
+    }
+    match &args[0] {
+        Value::String(s) => {
+            let n = count_arg(args.get(1), "repeat-string(text, n)")?;
+            check_width(s.chars().count().saturating_mul(n))?;
+            Ok(Value::String(s.repeat(n).into()))
+        }
+        other => Err(format!("repeat-string() expects a string, got {}", other.type_name())),
+    }
+}
+
 /// Convert string to uppercase
 fn builtin_uppercase(args: Vec<Value>) -> Result<Value, String> {
     match args.first() {
-        Some(Value::String(s)) => Ok(Value::String(s.to_uppercase())),
+        Some(Value::String(s)) => Ok(Value::String(s.to_uppercase().into())),
         Some(other) => Err(format!(
             "uppercase() expects a string, got {}",
             other.type_name()
@@ -524,7 +1153,7 @@ fn builtin_uppercase(args: Vec<Value>) -> Result<Value, String> {
 /// Convert string to lowercase
 fn builtin_lowercase(args: Vec<Value>) -> Result<Value, String> {
     match args.first() {
-        Some(Value::String(s)) => Ok(Value::String(s.to_lowercase())),
+        Some(Value::String(s)) => Ok(Value::String(s.to_lowercase().into())),
         Some(other) => Err(format!(
             "lowercase() expects a string, got {}",
             other.type_name()
@@ -547,7 +1176,7 @@ fn builtin_contains(args: Vec<Value>) -> Result<Value, String> {
         }
         Value::String(haystack) => {
             match &args[1] {
-                Value::String(needle) => Ok(Value::Boolean(haystack.contains(needle.as_str()))),
+                Value::String(needle) => Ok(Value::Boolean(haystack.contains(&**needle))),
                 other => Err(format!(
                     "contains() expects a string needle for string search, got {}",
                     other.type_name()
@@ -561,19 +1190,37 @@ fn builtin_contains(args: Vec<Value>) -> Result<Value, String> {
     }
 }
 
-/// Sleep for a specified number of milliseconds
-fn builtin_sleep(args: Vec<Value>) -> Result<Value, String> {
-    match args.first() {
-        Some(Value::Number(ms)) => {
-            if *ms < 0.0 {
-                return Err("sleep() requires a non-negative number".to_string());
-            }
-            thread::sleep(Duration::from_millis(*ms as u64));
-            Ok(Value::Null)
-        }
-        Some(other) => Err(format!("sleep() expects a number, got {}", other.type_name())),
-        None => Err("sleep() requires 1 argument".to_string()),
+/// starts-with / ends-with: whether a string begins or ends with another
+fn builtin_affix(args: Vec<Value>, name: &str, test: fn(&str, &str) -> bool) -> Result<Value, String> {
+    if args.len() != 2 {
+        return Err(format!("{}() requires 2 arguments, got {}", name, args.len()));
     }
+
+    match (&args[0], &args[1]) {
+        (Value::String(s), Value::String(affix)) => Ok(Value::Boolean(test(s, affix))),
+        (Value::String(_), other) | (other, _) => Err(format!("{}() expects a string, got {}", name, other.type_name())),
+    }
+}
+
+/// Where something first appears in a list (or text in a string), or nil if
+/// it doesn't. Not -1, since `items at -1` is the last item.
+fn builtin_index_of(args: Vec<Value>) -> Result<Value, String> {
+    if args.len() != 2 {
+        return Err(format!("index-of() requires 2 arguments, got {}", args.len()));
+    }
+
+    let position = match (&args[0], &args[1]) {
+        (Value::List(items), needle) => items.borrow().iter().position(|item| item == needle),
+        // Counted in characters, to match `at`
+        (Value::String(haystack), Value::String(needle)) => {
+            haystack.find(&**needle).map(|byte| haystack[..byte].chars().count())
+        }
+        (Value::String(_), other) => {
+            return Err(format!("index-of() expects a string to look for in a string, got {}", other.type_name()))
+        }
+        (other, _) => return Err(format!("index-of() expects a list or string, got {}", other.type_name())),
+    };
+    Ok(position.map_or(Value::Null, |i| Value::Number(i as f64)))
 }
 
 /// Get keys from a struct
@@ -583,7 +1230,7 @@ fn builtin_keys(args: Vec<Value>) -> Result<Value, String> {
             let keys: Vec<Value> = fields
                 .borrow()
                 .keys()
-                .map(|k| Value::String(k.clone()))
+                .map(|k| Value::String(k.clone().into()))
                 .collect();
             Ok(Value::new_list(keys))
         }
@@ -607,13 +1254,246 @@ fn builtin_values(args: Vec<Value>) -> Result<Value, String> {
     }
 }
 
+/// A map's entries, as held inside `Value::Map`
+type MapCell = RefCell<HashMap<MapKey, Value>>;
+
+/// The map and key arguments shared by the map- builtins
+fn map_and_key<'a>(name: &str, args: &'a [Value]) -> Result<(&'a Value, &'a MapCell, MapKey), String> {
+    match args {
+        [map @ Value::Map(entries), key, ..] => {
+            let key = MapKey::from_value(key).map_err(|e| format!("{}(): {}", name, e))?;
+            Ok((map, entries, key))
+        }
+        [other, _, ..] => Err(format!("{}() expects a map as first argument, got {}", name, other.type_name())),
+        _ => Err(format!("{}() requires a map and a key", name)),
+    }
+}
+
+/// The entries of a map argument, in key order
+fn map_entries(name: &str, args: &[Value]) -> Result<Vec<(Value, Value)>, String> {
+    match args.first() {
+        Some(Value::Map(map)) => Ok(sorted_entries(&map.borrow())),
+        Some(other) => Err(format!("{}() expects a map, got {}", name, other.type_name())),
+        None => Err(format!("{}() requires 1 argument (map)", name)),
+    }
+}
+
+/// Look up a key, with an optional default (nil if not given) for missing keys
+fn builtin_map_get(args: Vec<Value>) -> Result<Value, String> {
+    let (_, entries, key) = map_and_key("map-get", &args)?;
+    let found = entries.borrow().get(&key).cloned();
+    Ok(found.unwrap_or_else(|| args.get(2).cloned().unwrap_or(Value::Null)))
+}
+
+/// Add or replace an entry (mutates the map)
+fn builtin_map_set(args: Vec<Value>) -> Result<Value, String> {
+    if args.len() != 3 {
+        return Err(format!("map-set() requires 3 arguments (map, key, value), got {}", args.len()));
+    }
+    let (map, entries, key) = map_and_key("map-set", &args)?;
+    map.ensure_mutable().map_err(|e| format!("map-set() can't {}", e))?;
+    entries.borrow_mut().insert(key, args[2].clone());
+    Ok(Value::Null)
+}
+
+/// Remove an entry (mutates the map), returning its value or nil if it wasn't there
+fn builtin_map_remove(args: Vec<Value>) -> Result<Value, String> {
+    let (map, entries, key) = map_and_key("map-remove", &args)?;
+    map.ensure_mutable().map_err(|e| format!("map-remove() can't {}", e))?;
+    let removed = entries.borrow_mut().remove(&key);
+    Ok(removed.unwrap_or(Value::Null))
+}
+
+fn builtin_has_key(args: Vec<Value>) -> Result<Value, String> {
+    let (_, entries, key) = map_and_key("has-key", &args)?;
+    let found = entries.borrow().contains_key(&key);
+    Ok(Value::Boolean(found))
+}
+
+/// The keys of a map, sorted
+fn builtin_map_keys(args: Vec<Value>) -> Result<Value, String> {
+    let entries = map_entries("map-keys", &args)?;
+    Ok(Value::new_list(entries.into_iter().map(|(k, _)| k).collect()))
+}
+
+/// The values of a map, in the order of their (sorted) keys
+fn builtin_map_values(args: Vec<Value>) -> Result<Value, String> {
+    let entries = map_entries("map-values", &args)?;
+    Ok(Value::new_list(entries.into_iter().map(|(_, v)| v).collect()))
+}
+
+/// The path argument of get-in/set-in: a list of keys, indexes and field names
+fn path_arg(name: &str, args: &[Value]) -> Result<Vec<Value>, String> {
+    match args.get(1) {
+        Some(Value::List(path)) => Ok(path.borrow().clone()),
+        Some(other) => Err(format!("{}() expects the path as a list, like list(\"users\", 0, \"name\"), got {}", name, other.type_name())),
+        None => Err(format!("{}() requires a value and a path", name)),
+    }
+}
+
+/// One step down a path: a list index, a map key or a struct field.
+/// None if there's nothing there.
+fn step_into(container: &Value, step: &Value) -> Option<Value> {
+    match (container, step) {
+        (Value::List(items), Value::Number(n)) if *n >= 0.0 && n.fract() == 0.0 => {
+            items.borrow().get(*n as usize).cloned()
+        }
+        (Value::Map(entries), key) => MapKey::from_value(key).ok().and_then(|key| entries.borrow().get(&key).cloned()),
+        (Value::Struct { fields, .. }, Value::String(field)) => fields.borrow().get(&**field).cloned(),
+        _ => None,
+    }
+}
+
+/// get-in(value, path, default) - reach into nested lists, maps and structs,
+/// giving back the default (nil if not given) as soon as a step is missing
+fn builtin_get_in(args: Vec<Value>) -> Result<Value, String> {
+    let path = path_arg("get-in", &args)?;
+    let mut current = args[0].clone();
+    for step in &path {
+        match step_into(&current, step) {
+            Some(next) => current = next,
+            None => return Ok(args.get(2).cloned().unwrap_or(Value::Null)),
+        }
+    }
+    Ok(current)
+}
+
+/// set-in(value, path, new) - replace whatever is at the end of the path
+/// (mutates in place). Every step but the last has to exist already; a
+/// map gets a new key if the last one is missing.
+fn builtin_set_in(args: Vec<Value>) -> Result<Value, String> {
+    if args.len() != 3 {
+        return Err(format!("set-in() requires 3 arguments (value, path, new), got {}", args.len()));
+    }
+    let path = path_arg("set-in", &args)?;
+    let Some((last, steps)) = path.split_last() else {
+        return Err("set-in() needs a path with at least one step".to_string());
+    };
+    let mut container = args[0].clone();
+    for (i, step) in steps.iter().enumerate() {
+        container = step_into(&container, step)
+            .ok_or_else(|| format!("set-in() couldn't find {} (step {} of the path)", step, i))?;
+    }
+    container.ensure_mutable().map_err(|e| format!("set-in() can't {}", e))?;
+    let new = args[2].clone();
+    match (&container, last) {
+        (Value::List(items), Value::Number(n)) if *n >= 0.0 && n.fract() == 0.0 => {
+            let mut items = items.borrow_mut();
+            let len = items.len();
+            let slot = items
+                .get_mut(*n as usize)
+                .ok_or_else(|| format!("set-in() index {} is out of bounds for a list of {}", n, len))?;
+            *slot = new;
+        }
+        (Value::Map(entries), key) => {
+            let key = MapKey::from_value(key).map_err(|e| format!("set-in(): {}", e))?;
+            entries.borrow_mut().insert(key, new);
+        }
+        (Value::Struct { name, fields }, Value::String(field)) => {
+            let mut fields = fields.borrow_mut();
+            let slot = fields
+                .get_mut(&**field)
+                .ok_or_else(|| format!("set-in() found a {} with no field called '{}'", name, field))?;
+            *slot = new;
+        }
+        (other, step) => {
+            return Err(format!("set-in() can't use {} as a step into a {}", step, other.type_name()));
+        }
+    }
+    Ok(args[0].clone())
+}
+
+/// validate(value, schema) - a list of everything wrong with value (empty if it's fine)
+fn builtin_validate(args: Vec<Value>) -> Result<Value, String> {
+    let [value, schema] = args.as_slice() else {
+        return Err(format!("validate() requires 2 arguments (value, schema), got {}", args.len()));
+    };
+    let problems = schema::validate(value, schema)?;
+    Ok(Value::new_list(problems.into_iter().map(|s| Value::String(s.into())).collect()))
+}
+
+// =============================================================================
+// Regular Expressions
+// =============================================================================
+
+/// How many compiled patterns to keep, so a regex used in a loop is only compiled once
+const REGEX_CACHE_SIZE: usize = 64;
+
+thread_local! {
+    static REGEX_CACHE: RefCell<HashMap<String, Regex>> = RefCell::new(HashMap::new());
+}
+
+/// The pattern and text every regex builtin starts with, the pattern compiled
+fn regex_args(args: &[Value], name: &str, usage: &str) -> Result<(Regex, String), String> {
+    let count = usage.split(", ").count();
+    if args.len() != count {
+        return Err(format!("{}() requires {} arguments ({}), got {}", name, count, usage, args.len()));
+    }
+    let (Value::String(pattern), Value::String(text)) = (&args[0], &args[1]) else {
+        return Err(format!("{}() expects strings ({})", name, usage));
+    };
+    let regex = REGEX_CACHE.with(|cache| {
+        if let Some(regex) = cache.borrow().get(&**pattern) {
+            return Ok(regex.clone());
+        }
+        let regex = Regex::new(pattern).map_err(|e| {
+            // The last line says what's wrong; the ones above draw a picture of where
+            let message = e.to_string();
+            let reason = message.lines().last().unwrap_or_default().trim_start_matches("error: ").to_string();
+            format!("'{}' isn't a pattern the goose understands ({})", pattern, reason)
+        })?;
+        let mut cache = cache.borrow_mut();
+        if cache.len() >= REGEX_CACHE_SIZE {
+            cache.clear();
+        }
+        cache.insert(pattern.to_string(), regex.clone());
+        Ok::<Regex, String>(regex)
+    })?;
+    Ok((regex, text.to_string()))
+}
+
+/// A match as a list: the whole match, then each capture group (nil if it didn't take part)
+fn match_list(captures: &Captures) -> Value {
+    let groups = captures.iter().map(|group| group.map_or(Value::Null, |g| Value::String(g.as_str().into())));
+    Value::new_list(groups.collect())
+}
+
+/// regex-match(pattern, text) - the first match and its groups as a list, or nil
+fn builtin_regex_match(args: Vec<Value>) -> Result<Value, String> {
+    let (regex, text) = regex_args(&args, "regex-match", "pattern, text")?;
+    Ok(regex.captures(&text).map_or(Value::Null, |captures| match_list(&captures)))
+}
+
+/// regex-find-all(pattern, text) - every match: just the text if the pattern
+/// has no groups, otherwise a list like regex-match gives
+fn builtin_regex_find_all(args: Vec<Value>) -> Result<Value, String> {
+    let (regex, text) = regex_args(&args, "regex-find-all", "pattern, text")?;
+    let matches = if regex.captures_len() == 1 {
+        regex.find_iter(&text).map(|found| Value::String(found.as_str().into())).collect()
+    } else {
+        regex.captures_iter(&text).map(|captures| match_list(&captures)).collect()
+    };
+    Ok(Value::new_list(matches))
+}
+
+/// regex-replace(pattern, text, replacement) - replace every match; $1 or
+/// ${name} in the replacement stands for a group
+fn builtin_regex_replace(args: Vec<Value>) -> Result<Value, String> {
+    let usage = "pattern, text, replacement";
+    let (regex, text) = regex_args(&args, "regex-replace", usage)?;
+    match &args[2] {
+        Value::String(replacement) => Ok(Value::String(regex.replace_all(&text, &**replacement).into_owned().into())),
+        other => Err(format!("regex-replace() expects a replacement string, got {}", other.type_name())),
+    }
+}
+
 // =============================================================================
 // Phase 2: File I/O (with security validation)
 // =============================================================================
 
 /// Validate a file path for security
 /// Prevents directory traversal attacks
-fn validate_path(path: &str) -> Result<(), String> {
+pub(crate) fn validate_path(path: &str) -> Result<(), String> {
     let path = Path::new(path);
 
     // Prevent directory traversal
@@ -636,7 +1516,7 @@ fn builtin_read_file(args: Vec<Value>) -> Result<Value, String> {
     match args.first() {
         Some(Value::String(path)) => {
             validate_path(path)?;
-            fs::read_to_string(path).map(Value::String).map_err(|e| {
+            fs::read_to_string(&**path).map(|s| Value::String(s.into())).map_err(|e| {
                 if e.kind() == io::ErrorKind::NotFound {
                     format!("The goose searched everywhere but couldn't find '{}'", path)
                 } else if e.kind() == io::ErrorKind::PermissionDenied {
@@ -663,7 +1543,7 @@ fn builtin_write_file(args: Vec<Value>) -> Result<Value, String> {
     match (&args[0], &args[1]) {
         (Value::String(path), Value::String(content)) => {
             validate_path(path)?;
-            fs::write(path, content).map(|_| Value::Null).map_err(|e| {
+            fs::write(&**path, &**content).map(|_| Value::Null).map_err(|e| {
                 if e.kind() == io::ErrorKind::PermissionDenied {
                     format!("The goose is not allowed to write to '{}'", path)
                 } else {
@@ -695,7 +1575,7 @@ fn builtin_append_file(args: Vec<Value>) -> Result<Value, String> {
             let file = OpenOptions::new()
                 .create(true)
                 .append(true)
-                .open(path);
+                .open(&**path);
 
             match file {
                 Ok(mut f) => {
@@ -727,7 +1607,7 @@ fn builtin_append_file(args: Vec<Value>) -> Result<Value, String> {
 /// Check if a file exists
 fn builtin_file_exists(args: Vec<Value>) -> Result<Value, String> {
     match args.first() {
-        Some(Value::String(path)) => Ok(Value::Boolean(Path::new(path).exists())),
+        Some(Value::String(path)) => Ok(Value::Boolean(Path::new(&**path).exists())),
         Some(other) => Err(format!(
             "file-exists() expects a string path, got {}",
             other.type_name()
@@ -736,6 +1616,98 @@ fn builtin_file_exists(args: Vec<Value>) -> Result<Value, String> {
     }
 }
 
+/// Look up a file's metadata, with the same friendly errors as read-file
+fn file_metadata(name: &str, args: &[Value]) -> Result<(String, fs::Metadata), String> {
+    let path = match args.first() {
+        Some(Value::String(path)) => path,
+        Some(other) => return Err(format!("{}() expects a string path, got {}", name, other.type_name())),
+        None => return Err(format!("{}() requires 1 argument (path)", name)),
+    };
+    validate_path(path)?;
+    let metadata = fs::metadata(&**path).map_err(|e| {
+        if e.kind() == io::ErrorKind::NotFound {
+            format!("The goose searched everywhere but couldn't find '{}'", path)
+        } else {
+            format!("Failed to look at '{}': {}", path, e)
+        }
+    })?;
+    Ok((path.to_string(), metadata))
+}
+
+/// Size of a file in bytes
+fn builtin_file_size(args: Vec<Value>) -> Result<Value, String> {
+    let (_, metadata) = file_metadata("file-size", &args)?;
+    Ok(Value::Number(metadata.len() as f64))
+}
+
+/// When a file last changed, in milliseconds since 1970 (the same scale as now())
+fn builtin_file_modified(args: Vec<Value>) -> Result<Value, String> {
+    let (path, metadata) = file_metadata("file-modified", &args)?;
+    let modified = metadata
+        .modified()
+        .map_err(|_| format!("This system doesn't record when '{}' changed", path))?;
+    let ms = match modified.duration_since(std::time::UNIX_EPOCH) {
+        Ok(since) => since.as_millis() as f64,
+        Err(before) => -(before.duration().as_millis() as f64),
+    };
+    Ok(Value::Number(ms))
+}
+
+/// Hex checksum of a file's contents: file-hash(path) for sha256, or pick
+/// "sha256", "sha512", "sha1" or "md5"
+fn builtin_file_hash(args: Vec<Value>) -> Result<Value, String> {
+    use sha2::Digest;
+
+    let (path, metadata) = file_metadata("file-hash", &args)?;
+    if metadata.is_dir() {
+        return Err(format!("'{}' is a folder - the goose can only hash files", path));
+    }
+    let algorithm = match args.get(1) {
+        Some(Value::String(algorithm)) => algorithm.to_lowercase(),
+        Some(other) => return Err(format!("file-hash() expects the algorithm as a string, got {}", other.type_name())),
+        None => "sha256".to_string(),
+    };
+    let bytes = fs::read(&path).map_err(|e| format!("Failed to read '{}': {}", path, e))?;
+    let digest = match algorithm.as_str() {
+        "sha256" => sha2::Sha256::digest(&bytes).to_vec(),
+        "sha512" => sha2::Sha512::digest(&bytes).to_vec(),
+        "sha1" => sha1::Sha1::digest(&bytes).to_vec(),
+        "md5" => md5::Md5::digest(&bytes).to_vec(),
+        other => {
+            return Err(format!(
+                "file-hash() doesn't know the algorithm '{}' (try sha256, sha512, sha1 or md5)",
+                other
+            ))
+        }
+    };
+    Ok(Value::String(digest.iter().map(|b| format!("{:02x}", b)).collect::<String>().into()))
+}
+
+/// Every file and folder matching a pattern like "data/*.csv" or "**/*.duck",
+/// sorted, as relative paths
+fn builtin_glob(args: Vec<Value>) -> Result<Value, String> {
+    match args.first() {
+        Some(Value::String(pattern)) => {
+            validate_path(pattern)?;
+            let paths = glob::find(pattern)?;
+            Ok(Value::new_list(paths.into_iter().map(|s| Value::String(s.into())).collect()))
+        }
+        Some(other) => Err(format!("glob() expects a pattern string, got {}", other.type_name())),
+        None => Err("glob() requires 1 argument (pattern)".to_string()),
+    }
+}
+
+/// Does a path match a glob pattern? Only looks at the text, not the disk
+fn builtin_path_matches(args: Vec<Value>) -> Result<Value, String> {
+    match (args.first(), args.get(1)) {
+        (Some(Value::String(path)), Some(Value::String(pattern))) => {
+            Ok(Value::Boolean(glob::matches(path, pattern)))
+        }
+        (Some(_), Some(_)) => Err("path-matches() expects two strings (path, pattern)".to_string()),
+        _ => Err("path-matches() requires 2 arguments (path, pattern)".to_string()),
+    }
+}
+
 // =============================================================================
 // Environment Variables
 // =============================================================================
@@ -745,8 +1717,8 @@ fn builtin_env(args: Vec<Value>) -> Result<Value, String> {
     match args.first() {
         Some(Value::String(key)) => {
             // Security: Don't expose sensitive variable names in errors
-            match std::env::var(key) {
-                Ok(val) => Ok(Value::String(val)),
+            match std::env::var(&**key) {
+                Ok(val) => Ok(Value::String(val.into())),
                 Err(_) => Ok(Value::Null),
             }
         }
@@ -755,6 +1727,69 @@ fn builtin_env(args: Vec<Value>) -> Result<Value, String> {
     }
 }
 
+/// The directory the program started in; set-cwd can't leave it
+fn start_dir() -> Result<&'static PathBuf, String> {
+    static START_DIR: OnceLock<PathBuf> = OnceLock::new();
+    if let Some(dir) = START_DIR.get() {
+        return Ok(dir);
+    }
+    let dir = std::env::current_dir().map_err(|e| format!("The goose lost track of where it is: {}", e))?;
+    Ok(START_DIR.get_or_init(|| dir))
+}
+
+/// The current working directory
+fn builtin_cwd(_args: Vec<Value>) -> Result<Value, String> {
+    start_dir()?;
+    std::env::current_dir()
+        .map(|dir| Value::String(dir.to_string_lossy().into_owned().into()))
+        .map_err(|e| format!("The goose lost track of where it is: {}", e))
+}
+
+/// Change the working directory - into a folder below where the program
+/// started, or back to somewhere inside it (e.g. a path saved from cwd())
+fn builtin_set_cwd(args: Vec<Value>) -> Result<Value, String> {
+    let path = match args.first() {
+        Some(Value::String(path)) => path,
+        Some(other) => return Err(format!("set-cwd() expects a path string, got {}", other.type_name())),
+        None => return Err("set-cwd() requires 1 argument (path)".to_string()),
+    };
+    let start = start_dir()?;
+    let target = if Path::new(&**path).is_absolute() {
+        PathBuf::from(&**path)
+    } else {
+        validate_path(path)?;
+        std::env::current_dir().map_err(|e| e.to_string())?.join(&**path)
+    };
+    let target = target
+        .canonicalize()
+        .map_err(|_| format!("The goose searched everywhere but couldn't find the folder '{}'", path))?;
+    if !target.starts_with(start.canonicalize().map_err(|e| e.to_string())?) {
+        return Err("set-cwd() can't leave the folder the program started in - the goose stays near its pond".to_string());
+    }
+    std::env::set_current_dir(&target).map_err(|e| format!("Failed to change into '{}': {}", path, e))?;
+    Ok(Value::Null)
+}
+
+/// Join path parts with the platform's separator: path-join("data", "a.csv")
+/// or path-join(list("data", "a.csv"))
+fn builtin_path_join(args: Vec<Value>) -> Result<Value, String> {
+    let parts = match args.as_slice() {
+        [Value::List(items)] => items.borrow().clone(),
+        _ => args,
+    };
+    if parts.is_empty() {
+        return Err("path-join() needs at least one part".to_string());
+    }
+    let mut joined = PathBuf::new();
+    for part in &parts {
+        match part {
+            Value::String(part) => joined.push(&**part),
+            other => return Err(format!("path-join() expects strings, got {}", other.type_name())),
+        }
+    }
+    Ok(Value::String(joined.to_string_lossy().into_owned().into()))
+}
+
 // =============================================================================
 // JSON Support
 // =============================================================================
@@ -767,7 +1802,7 @@ fn json_to_value(json: serde_json::Value) -> Result<Value, String> {
         serde_json::Value::Number(n) => {
             Ok(Value::Number(n.as_f64().unwrap_or(0.0)))
         }
-        serde_json::Value::String(s) => Ok(Value::String(s)),
+        serde_json::Value::String(s) => Ok(Value::String(s.into())),
         serde_json::Value::Array(arr) => {
             let items: Result<Vec<_>, _> = arr.into_iter().map(json_to_value).collect();
             Ok(Value::new_list(items?))
@@ -792,7 +1827,10 @@ fn value_to_json(value: &Value) -> Result<serde_json::Value, String> {
                 .map(serde_json::Value::Number)
                 .ok_or_else(|| "Cannot convert number to JSON".to_string())
         }
-        Value::String(s) => Ok(serde_json::Value::String(s.clone())),
+        // JSON numbers are floats to most readers, so that's what a decimal becomes
+        Value::Decimal(d) => value_to_json(&Value::Number(d.to_f64())),
+        Value::Fraction(f) => value_to_json(&Value::Number(f.to_f64())),
+        Value::String(s) => Ok(serde_json::Value::String(s.to_string())),
         Value::List(items) => {
             let arr: Result<Vec<_>, _> = items.borrow().iter().map(value_to_json).collect();
             Ok(serde_json::Value::Array(arr?))
@@ -804,6 +1842,14 @@ fn value_to_json(value: &Value) -> Result<serde_json::Value, String> {
             }
             Ok(serde_json::Value::Object(obj))
         }
+        // JSON object keys are always strings, so 1 and "1" end up the same
+        Value::Map(map) => {
+            let mut obj = serde_json::Map::new();
+            for (k, v) in sorted_entries(&map.borrow()) {
+                obj.insert(k.to_string(), value_to_json(&v)?);
+            }
+            Ok(serde_json::Value::Object(obj))
+        }
         other => Err(format!("Cannot convert {} to JSON", other.type_name())),
     }
 }
@@ -828,18 +1874,84 @@ fn builtin_json_stringify(args: Vec<Value>) -> Result<Value, String> {
             let json = value_to_json(value)?;
             let s = serde_json::to_string(&json)
                 .map_err(|e| format!("JSON stringify error: {}", e))?;
-            Ok(Value::String(s))
+            Ok(Value::String(s.into()))
         }
         None => Err("json-stringify() requires 1 argument".to_string()),
     }
 }
 
+// =============================================================================
+// Config Formats (TOML and YAML)
+// =============================================================================
+
+/// Build the struct a table or mapping becomes, the same kind json-parse makes
+fn object(entries: impl IntoIterator<Item = (String, Value)>) -> Value {
+    Value::new_struct("object".to_string(), entries.into_iter().collect())
+}
+
+/// Convert a TOML value to a Duck value. Dates and times become strings.
+fn toml_to_value(toml: toml::Value) -> Value {
+    match toml {
+        toml::Value::String(s) => Value::String(s.into()),
+        toml::Value::Integer(n) => Value::Number(n as f64),
+        toml::Value::Float(n) => Value::Number(n),
+        toml::Value::Boolean(b) => Value::Boolean(b),
+        toml::Value::Datetime(when) => Value::String(when.to_string().into()),
+        toml::Value::Array(items) => Value::new_list(items.into_iter().map(toml_to_value).collect()),
+        toml::Value::Table(table) => object(table.into_iter().map(|(k, v)| (k, toml_to_value(v)))),
+    }
+}
+
+/// Convert a YAML value to a Duck value. Keys that aren't strings (like `1:`
+/// or `true:`) become strings, since they name struct fields; tags are dropped.
+fn yaml_to_value(yaml: serde_yaml::Value) -> Value {
+    match yaml {
+        serde_yaml::Value::Null => Value::Null,
+        serde_yaml::Value::Bool(b) => Value::Boolean(b),
+        serde_yaml::Value::Number(n) => Value::Number(n.as_f64().unwrap_or(0.0)),
+        serde_yaml::Value::String(s) => Value::String(s.into()),
+        serde_yaml::Value::Sequence(items) => Value::new_list(items.into_iter().map(yaml_to_value).collect()),
+        serde_yaml::Value::Mapping(mapping) => object(mapping.into_iter().map(|(k, v)| {
+            let key = match yaml_to_value(k) {
+                Value::String(s) => s.to_string(),
+                other => other.to_string(),
+            };
+            (key, yaml_to_value(v))
+        })),
+        serde_yaml::Value::Tagged(tagged) => yaml_to_value(tagged.value),
+    }
+}
+
+/// Parse a TOML document (like Cargo.toml or a settings file) into a struct
+fn builtin_toml_parse(args: Vec<Value>) -> Result<Value, String> {
+    match args.first() {
+        Some(Value::String(s)) => {
+            let table: toml::Table = s.parse().map_err(|e: toml::de::Error| format!("TOML parse error: {}", e.message()))?;
+            Ok(toml_to_value(toml::Value::Table(table)))
+        }
+        Some(other) => Err(format!("toml-parse() expects a string, got {}", other.type_name())),
+        None => Err("toml-parse() requires 1 argument".to_string()),
+    }
+}
+
+/// Parse a YAML document into Duck values
+fn builtin_yaml_parse(args: Vec<Value>) -> Result<Value, String> {
+    match args.first() {
+        Some(Value::String(s)) => {
+            let parsed: serde_yaml::Value = serde_yaml::from_str(s).map_err(|e| format!("YAML parse error: {}", e))?;
+            Ok(yaml_to_value(parsed))
+        }
+        Some(other) => Err(format!("yaml-parse() expects a string, got {}", other.type_name())),
+        None => Err("yaml-parse() requires 1 argument".to_string()),
+    }
+}
+
 // =============================================================================
 // HTTP Client
 // =============================================================================
 
 /// Parse headers from a list of key-value pairs
-fn parse_headers(header_list: &Value) -> Result<Vec<(String, String)>, String> {
+pub(crate) fn parse_headers(header_list: &Value) -> Result<Vec<(String, String)>, String> {
     match header_list {
         Value::List(items) => {
             let borrowed = items.borrow();
@@ -851,7 +1963,7 @@ fn parse_headers(header_list: &Value) -> Result<Vec<(String, String)>, String> {
                     Value::String(k) => {
                         if let Some(val) = iter.next() {
                             match val {
-                                Value::String(v) => headers.push((k.clone(), v.clone())),
+                                Value::String(v) => headers.push((k.to_string(), v.to_string())),
                                 other => return Err(format!(
                                     "Header value must be string, got {}",
                                     other.type_name()
@@ -874,15 +1986,16 @@ fn parse_headers(header_list: &Value) -> Result<Vec<(String, String)>, String> {
 }
 
 /// Build HTTP response struct
-fn build_http_response(status: u16, body: String, headers: Vec<(String, String)>) -> Value {
+fn build_http_response(response: http::Response) -> Value {
     let mut fields = HashMap::new();
-    fields.insert("status".to_string(), Value::Number(status as f64));
-    fields.insert("body".to_string(), Value::String(body));
+    fields.insert("status".to_string(), Value::Number(response.status as f64));
+    fields.insert("body".to_string(), Value::String(response.body.into()));
 
     // Convert headers to list of key-value pairs
-    let header_values: Vec<Value> = headers
+    let header_values: Vec<Value> = response
+        .headers
         .into_iter()
-        .flat_map(|(k, v)| vec![Value::String(k), Value::String(v)])
+        .flat_map(|(k, v)| vec![Value::String(k.into()), Value::String(v.into())])
         .collect();
     fields.insert("headers".to_string(), Value::new_list(header_values));
 
@@ -907,26 +2020,8 @@ fn builtin_http_get(args: Vec<Value>) -> Result<Value, String> {
         Vec::new()
     };
 
-    let client = reqwest::blocking::Client::new();
-    let mut request = client.get(&url);
-
-    for (key, value) in &headers {
-        request = request.header(key.as_str(), value.as_str());
-    }
-
-    let response = request
-        .send()
-        .map_err(|e| format!("HTTP GET error: {}", e))?;
-
-    let status = response.status().as_u16();
-    let resp_headers: Vec<(String, String)> = response
-        .headers()
-        .iter()
-        .map(|(k, v)| (k.to_string(), v.to_str().unwrap_or("").to_string()))
-        .collect();
-    let body = response.text().map_err(|e| format!("Failed to read response: {}", e))?;
-
-    Ok(build_http_response(status, body, resp_headers))
+    let response = http::get(&url, &headers)?;
+    Ok(build_http_response(response))
 }
 
 /// HTTP POST request
@@ -952,26 +2047,155 @@ fn builtin_http_post(args: Vec<Value>) -> Result<Value, String> {
         Vec::new()
     };
 
-    let client = reqwest::blocking::Client::new();
-    let mut request = client.post(&url).body(body);
+    let response = http::post(&url, &body, &headers)?;
+    Ok(build_http_response(response))
+}
+
+// =============================================================================
+// Terminal Styling
+// =============================================================================
+
+/// ANSI codes for each style word `style()` understands
+const STYLE_CODES: &[(&str, &str)] = &[
+    ("bold", "1"),
+    ("dim", "2"),
+    ("italic", "3"),
+    ("underline", "4"),
+    ("black", "30"),
+    ("red", "31"),
+    ("green", "32"),
+    ("yellow", "33"),
+    ("blue", "34"),
+    ("magenta", "35"),
+    ("cyan", "36"),
+    ("white", "37"),
+    ("gray", "90"),
+    ("on-black", "40"),
+    ("on-red", "41"),
+    ("on-green", "42"),
+    ("on-yellow", "43"),
+    ("on-blue", "44"),
+    ("on-magenta", "45"),
+    ("on-cyan", "46"),
+    ("on-white", "47"),
+];
+
+/// Whether printed text should carry colors: not when NO_COLOR is set or
+/// output is going to a file or pipe (unless FORCE_COLOR insists)
+fn color_enabled() -> bool {
+    use std::io::IsTerminal;
+    if std::env::var_os("NO_COLOR").is_some_and(|v| !v.is_empty()) {
+        return false;
+    }
+    if std::env::var_os("FORCE_COLOR").is_some_and(|v| !v.is_empty()) {
+        return true;
+    }
+    io::stdout().is_terminal()
+}
+
+/// Wrap text in terminal styles: style("Done!", "bold green") or
+/// style("Oops", list("red", "underline"))
+fn builtin_style(args: Vec<Value>) -> Result<Value, String> {
+    style_text(args, color_enabled())
+}
+
+/// style() with the color decision made by the caller
+fn style_text(args: Vec<Value>, color: bool) -> Result<Value, String> {
+    let text = match args.first() {
+        Some(Value::String(text)) => text.to_string(),
+        Some(other) => other.to_string(),
+        None => return Err("style() requires 2 arguments (text, styles)".to_string()),
+    };
+    let words: Vec<String> = match args.get(1) {
+        Some(Value::String(styles)) => styles
+            .split(|c: char| c.is_whitespace() || c == ',')
+            .filter(|word| !word.is_empty())
+            .map(str::to_lowercase)
+            .collect(),
+        Some(Value::List(styles)) => styles.borrow().iter().map(|style| style.to_string().to_lowercase()).collect(),
+        Some(other) => return Err(format!("style() expects styles like \"bold red\", got {}", other.type_name())),
+        None => return Err("style() requires 2 arguments (text, styles)".to_string()),
+    };
+    let mut codes = Vec::new();
+    for word in &words {
+        match STYLE_CODES.iter().find(|(name, _)| name == word) {
+            Some((_, code)) => codes.push(*code),
+            None => {
+                let known: Vec<&str> = STYLE_CODES.iter().map(|(name, _)| *name).collect();
+                return Err(format!("style() doesn't know '{}' (try one of: {})", word, known.join(", ")));
+            }
+        }
+    }
+    Ok(Value::String(apply_style(&text, &codes, color).into()))
+}
 
-    for (key, value) in &headers {
-        request = request.header(key.as_str(), value.as_str());
+fn apply_style(text: &str, codes: &[&str], color: bool) -> String {
+    if !color || codes.is_empty() {
+        return text.to_string();
     }
+    format!("\x1b[{}m{}\x1b[0m", codes.join(";"), text)
+}
 
-    let response = request
-        .send()
-        .map_err(|e| format!("HTTP POST error: {}", e))?;
+// =============================================================================
+// Terminal Charts
+// =============================================================================
 
-    let status = response.status().as_u16();
-    let resp_headers: Vec<(String, String)> = response
-        .headers()
+/// The numbers in a list, for a chart
+fn chart_numbers(name: &str, items: &[Value]) -> Result<Vec<f64>, String> {
+    items
         .iter()
-        .map(|(k, v)| (k.to_string(), v.to_str().unwrap_or("").to_string()))
-        .collect();
-    let resp_body = response.text().map_err(|e| format!("Failed to read response: {}", e))?;
+        .map(|item| match item {
+            Value::Number(n) => Ok(*n),
+            other => Err(format!("{}() can only chart numbers, got {}", name, other.type_name())),
+        })
+        .collect()
+}
 
-    Ok(build_http_response(status, resp_body, resp_headers))
+/// A bar chart as text: plot-bars(list(3, 5)), plot-bars(values, labels),
+/// or plot-bars(map("ducks" -> 3, "geese" -> 5))
+fn builtin_plot_bars(args: Vec<Value>) -> Result<Value, String> {
+    let (labels, values) = match (args.first(), args.get(1)) {
+        (Some(Value::Map(entries)), None) => {
+            let entries = sorted_entries(&entries.borrow());
+            let labels = entries.iter().map(|(label, _)| match label {
+                Value::String(s) => s.to_string(),
+                other => other.to_string(),
+            });
+            let values: Vec<Value> = entries.iter().map(|(_, value)| value.clone()).collect();
+            (labels.collect(), chart_numbers("plot-bars", &values)?)
+        }
+        (Some(Value::List(items)), labels) => {
+            let values = chart_numbers("plot-bars", &items.borrow())?;
+            let labels: Vec<String> = match labels {
+                Some(Value::List(labels)) if labels.borrow().len() == values.len() => {
+                    labels.borrow().iter().map(|label| match label {
+                        Value::String(s) => s.to_string(),
+                        other => other.to_string(),
+                    }).collect()
+                }
+                Some(Value::List(_)) => return Err("plot-bars() needs one label for each value".to_string()),
+                Some(other) => return Err(format!("plot-bars() expects labels as a list, got {}", other.type_name())),
+                None => (1..=values.len()).map(|i| i.to_string()).collect(),
+            };
+            (labels, values)
+        }
+        (Some(other), _) => {
+            return Err(format!("plot-bars() expects a list or map of numbers, got {}", other.type_name()))
+        }
+        (None, _) => return Err("plot-bars() requires 1 argument (values)".to_string()),
+    };
+    plot::bars(&labels, &values).map(|s| Value::String(s.into()))
+}
+
+/// A line chart as text: plot-line(values) or plot-line(values, height)
+fn builtin_plot_line(args: Vec<Value>) -> Result<Value, String> {
+    let values = match args.first() {
+        Some(Value::List(items)) => chart_numbers("plot-line", &items.borrow())?,
+        Some(other) => return Err(format!("plot-line() expects a list of numbers, got {}", other.type_name())),
+        None => return Err("plot-line() requires 1 argument (values)".to_string()),
+    };
+    let height = size_arg(&args, 1, plot::LINE_HEIGHT, "plot-line()")?;
+    plot::line(&values, height).map(|s| Value::String(s.into()))
 }
 
 // =============================================================================
@@ -984,7 +2208,7 @@ fn builtin_base64_encode(args: Vec<Value>) -> Result<Value, String> {
     match args.first() {
         Some(Value::String(s)) => {
             let encoded = base64::engine::general_purpose::STANDARD.encode(s.as_bytes());
-            Ok(Value::String(encoded))
+            Ok(Value::String(encoded.into()))
         }
         Some(other) => Err(format!("base64-encode() expects a string, got {}", other.type_name())),
         None => Err("base64-encode() requires 1 argument".to_string()),
@@ -997,17 +2221,62 @@ fn builtin_base64_decode(args: Vec<Value>) -> Result<Value, String> {
     match args.first() {
         Some(Value::String(s)) => {
             let decoded = base64::engine::general_purpose::STANDARD
-                .decode(s)
+                .decode(&**s)
                 .map_err(|e| format!("Base64 decode error: {}", e))?;
             let text = String::from_utf8(decoded)
                 .map_err(|e| format!("Invalid UTF-8 after decode: {}", e))?;
-            Ok(Value::String(text))
+            Ok(Value::String(text.into()))
         }
         Some(other) => Err(format!("base64-decode() expects a string, got {}", other.type_name())),
         None => Err("base64-decode() requires 1 argument".to_string()),
     }
 }
 
+// ============================================================================
+// Property testing generators
+// ============================================================================
+
+/// Read an optional non-negative whole-number argument
+fn size_arg(args: &[Value], index: usize, default: usize, usage: &str) -> Result<usize, String> {
+    match args.get(index) {
+        None => Ok(default),
+        Some(Value::Number(n)) if *n > MAX_BUILT_ITEMS as f64 => {
+            Err(format!("{} can't go past {} - the goose stops there", usage, MAX_BUILT_ITEMS))
+        }
+        Some(Value::Number(n)) if *n >= 0.0 && n.fract() == 0.0 => Ok(*n as usize),
+        Some(other) => Err(format!("{} expects a whole, non-negative length, got {}", usage, other)),
+    }
+}
+
+/// gen-int(min, max) - generator of whole numbers, -100 to 100 by default
+fn builtin_gen_int(args: Vec<Value>) -> Result<Value, String> {
+    let (min, max) = match args.as_slice() {
+        [] => (-100, 100),
+        [Value::Number(min), Value::Number(max)] if min <= max => (*min as i64, *max as i64),
+        [Value::Number(_), Value::Number(_)] => {
+            return Err("gen-int(min, max) needs min to be no bigger than max".to_string())
+        }
+        _ => return Err("gen-int() takes no arguments, or two numbers: gen-int(min, max)".to_string()),
+    };
+    Ok(Generator::Int { min, max }.to_value())
+}
+
+/// gen-string(max-length) - generator of short strings, up to 10 characters by default
+fn builtin_gen_string(args: Vec<Value>) -> Result<Value, String> {
+    let max_len = size_arg(&args, 0, 10, "gen-string(max-length)")?;
+    Ok(Generator::Str { max_len }.to_value())
+}
+
+/// gen-list(element-generator, max-length) - generator of lists, up to 10 elements by default
+fn builtin_gen_list(args: Vec<Value>) -> Result<Value, String> {
+    let element = match args.first() {
+        Some(value) => Generator::from_value(value).map_err(|e| format!("gen-list() {}", e))?,
+        None => return Err("gen-list() requires a generator for its elements".to_string()),
+    };
+    let max_len = size_arg(&args, 1, 10, "gen-list(generator, max-length)")?;
+    Ok(Generator::List { element: Box::new(element), max_len }.to_value())
+}
+
 #[cfg(test)]
 mod tests {
     use super::*;
@@ -1025,6 +2294,18 @@ mod tests {
         assert!(!is_builtin("unknown"));
     }
 
+    #[test]
+    fn test_every_builtin_resolves_to_itself() {
+        for (name, _) in BUILTINS {
+            let builtin = Builtin::lookup(name).unwrap();
+            assert_eq!(builtin.name(), *name, "'{}' is listed twice", name);
+        }
+        assert_eq!(Builtin::lookup("read-file").unwrap().capability(), Some(Capability::FsRead));
+        assert_eq!(Builtin::lookup("map").unwrap().hosted(), Some(Hosted::Map));
+        assert!(call_builtin("map", vec![]).unwrap_err().contains("running program"));
+        assert!(matches!(call_builtin("len", vec![Value::String("duck".into())]), Ok(Value::Number(n)) if n == 4.0));
+    }
+
     #[test]
     fn test_floor() {
         let result = builtin_floor(vec![Value::Number(3.7)]);
@@ -1056,19 +2337,19 @@ mod tests {
     fn test_type_of() {
         assert!(matches!(
             builtin_type_of(vec![Value::Number(1.0)]),
-            Ok(Value::String(s)) if s == "number"
+            Ok(Value::String(s)) if &*s == "number"
         ));
         assert!(matches!(
-            builtin_type_of(vec![Value::String("hi".to_string())]),
-            Ok(Value::String(s)) if s == "string"
+            builtin_type_of(vec![Value::String("hi".into())]),
+            Ok(Value::String(s)) if &*s == "string"
         ));
         assert!(matches!(
             builtin_type_of(vec![Value::Boolean(true)]),
-            Ok(Value::String(s)) if s == "boolean"
+            Ok(Value::String(s)) if &*s == "boolean"
         ));
         assert!(matches!(
             builtin_type_of(vec![Value::Null]),
-            Ok(Value::String(s)) if s == "null"
+            Ok(Value::String(s)) if &*s == "null"
         ));
     }
 
@@ -1078,7 +2359,7 @@ mod tests {
         let result = builtin_len(vec![list]);
         assert!(matches!(result, Ok(Value::Number(n)) if n == 2.0));
 
-        let result = builtin_len(vec![Value::String("hello".to_string())]);
+        let result = builtin_len(vec![Value::String("hello".into())]);
         assert!(matches!(result, Ok(Value::Number(n)) if n == 5.0));
     }
 
@@ -1144,32 +2425,21 @@ mod tests {
     #[test]
     fn test_string_conversion() {
         let result = builtin_string(vec![Value::Number(42.0)]);
-        assert!(matches!(result, Ok(Value::String(s)) if s == "42"));
+        assert!(matches!(result, Ok(Value::String(s)) if &*s == "42"));
 
         let result = builtin_string(vec![Value::Boolean(true)]);
-        assert!(matches!(result, Ok(Value::String(s)) if s == "true"));
+        assert!(matches!(result, Ok(Value::String(s)) if &*s == "true"));
     }
 
     #[test]
     fn test_number_conversion() {
-        let result = builtin_number(vec![Value::String("42".to_string())]);
+        let result = builtin_number(vec![Value::String("42".into())]);
         assert!(matches!(result, Ok(Value::Number(n)) if n == 42.0));
 
         let result = builtin_number(vec![Value::Boolean(true)]);
         assert!(matches!(result, Ok(Value::Number(n)) if n == 1.0));
     }
 
-    #[test]
-    fn test_random() {
-        let result = builtin_random(vec![]);
-        match result {
-            Ok(Value::Number(n)) => {
-                assert!(n >= 0.0 && n < 1.0);
-            }
-            _ => panic!("Expected number"),
-        }
-    }
-
     // Phase 1 tests
 
     #[test]
@@ -1190,8 +2460,8 @@ mod tests {
         }
 
         // Reverse string
-        let result = builtin_reverse(vec![Value::String("hello".to_string())]).unwrap();
-        assert!(matches!(result, Value::String(s) if s == "olleh"));
+        let result = builtin_reverse(vec![Value::String("hello".into())]).unwrap();
+        assert!(matches!(result, Value::String(s) if &*s == "olleh"));
     }
 
     #[test]
@@ -1214,45 +2484,136 @@ mod tests {
 
         // Sort strings
         let list = Value::new_list(vec![
-            Value::String("c".to_string()),
-            Value::String("a".to_string()),
-            Value::String("b".to_string()),
+            Value::String("c".into()),
+            Value::String("a".into()),
+            Value::String("b".into()),
         ]);
         let result = builtin_sort(vec![list]).unwrap();
         if let Value::List(items) = result {
             let borrowed = items.borrow();
-            assert!(matches!(&borrowed[0], Value::String(s) if s == "a"));
-            assert!(matches!(&borrowed[1], Value::String(s) if s == "b"));
-            assert!(matches!(&borrowed[2], Value::String(s) if s == "c"));
+            assert!(matches!(&borrowed[0], Value::String(s) if &**s == "a"));
+            assert!(matches!(&borrowed[1], Value::String(s) if &**s == "b"));
+            assert!(matches!(&borrowed[2], Value::String(s) if &**s == "c"));
         } else {
             panic!("Expected list");
         }
     }
 
+    #[test]
+    fn test_sort_mixed_list_falls_back_to_compare() {
+        let original = Value::new_list(vec![
+            Value::Null,
+            Value::String("b".into()),
+            Value::Number(2.0),
+            Value::new_list(vec![]),
+            Value::Number(1.0),
+        ]);
+        let result = builtin_sort(vec![original.clone()]).unwrap();
+        assert_eq!(result.to_string(), "[1, 2, \"b\", [], null]");
+        // The input list is left alone
+        assert_eq!(original.to_string(), "[null, \"b\", 2, [], 1]");
+
+        let order = builtin_compare(vec![Value::Number(5.0), Value::String("a".into())]).unwrap();
+        assert_eq!(order, Value::Number(-1.0));
+    }
+
+    #[test]
+    fn test_fill_repeat_list_and_concat() {
+        let board = builtin_fill(vec![Value::Number(2.0), Value::new_list(vec![Value::Number(0.0)])]).unwrap();
+        assert_eq!(board.to_string(), "[[0], [0]]");
+        // Rows are separate lists, not the same one twice
+        if let Value::List(rows) = &board {
+            builtin_push(vec![rows.borrow()[0].clone(), Value::Number(1.0)]).unwrap();
+        }
+        assert_eq!(board.to_string(), "[[0, 1], [0]]");
+
+        let pattern = Value::new_list(vec![Value::Number(1.0), Value::Number(2.0)]);
+        let repeated = builtin_repeat_list(vec![pattern.clone(), Value::Number(3.0)]).unwrap();
+        assert_eq!(repeated.to_string(), "[1, 2, 1, 2, 1, 2]");
+        assert!(builtin_repeat_list(vec![pattern.clone(), Value::Number(-1.0)]).is_err());
+        let huge = Value::Number(10f64.powi(300));
+        assert!(builtin_repeat_list(vec![pattern.clone(), huge.clone()]).unwrap_err().contains("the goose stops there"));
+        assert!(builtin_fill(vec![huge.clone(), Value::Number(0.0)]).unwrap_err().contains("the goose stops there"));
+        assert_eq!(builtin_repeat_list(vec![Value::new_list(vec![]), huge]).unwrap().to_string(), "[]");
+
+        let joined = builtin_concat(vec![pattern.clone(), Value::new_list(vec![]), pattern]).unwrap();
+        assert_eq!(joined.to_string(), "[1, 2, 1, 2]");
+        assert_eq!(builtin_concat(vec![]).unwrap().to_string(), "[]");
+        assert!(builtin_concat(vec![Value::Number(1.0)]).is_err());
+    }
+
+    #[test]
+    fn test_is_nan_and_is_finite() {
+        assert_eq!(builtin_is_nan(vec![Value::Number(f64::NAN)]).unwrap(), Value::Boolean(true));
+        assert_eq!(builtin_is_nan(vec![Value::Number(1.0)]).unwrap(), Value::Boolean(false));
+        assert_eq!(builtin_is_finite(vec![Value::Number(f64::INFINITY)]).unwrap(), Value::Boolean(false));
+        assert_eq!(builtin_is_finite(vec![Value::Number(-3.5)]).unwrap(), Value::Boolean(true));
+        assert!(builtin_is_finite(vec![Value::String("1".into())]).is_err());
+    }
+
+    #[test]
+    fn test_div_and_divmod_round_down() {
+        let num = |n: f64| Value::Number(n);
+        assert_eq!(builtin_div(vec![num(7.0), num(2.0)]).unwrap(), num(3.0));
+        assert_eq!(builtin_div(vec![num(-7.0), num(2.0)]).unwrap(), num(-4.0));
+        assert_eq!(builtin_divmod(vec![num(-7.0), num(3.0)]).unwrap().to_string(), "[-3, 2]");
+        assert_eq!(builtin_divmod(vec![num(7.0), num(-3.0)]).unwrap().to_string(), "[-3, -2]");
+        assert!(builtin_div(vec![num(1.0), num(0.0)]).is_err());
+
+        // a == div(a, b) * b + a % b for every sign combination
+        for (a, b) in [(7.0_f64, 3.0), (-7.0, 3.0), (7.0, -3.0), (-7.0, -3.0), (5.5, 2.0)] {
+            assert_eq!((a / b).floor() * b + floored_mod(a, b), a);
+        }
+    }
+
+    #[test]
+    fn test_string_order_options() {
+        let plain = StringOrder::default();
+        let ignore_case = StringOrder { ignore_case: true, natural: false };
+        let natural = StringOrder { ignore_case: false, natural: true };
+        assert_eq!(compare_strings("apple", "Banana", plain), Ordering::Greater);
+        assert_eq!(compare_strings("apple", "Banana", ignore_case), Ordering::Less);
+        assert_eq!(compare_strings("file10", "file2", plain), Ordering::Less);
+        assert_eq!(compare_strings("file10", "file2", natural), Ordering::Greater);
+        assert_eq!(compare_strings("v007", "v7", natural), Ordering::Less);
+        // Case-insensitive ties still have a fixed order
+        assert_eq!(compare_strings("Duck", "duck", ignore_case), Ordering::Less);
+
+        let files = Value::new_list(vec![
+            Value::String("b10".into()),
+            Value::String("B2".into()),
+            Value::String("a1".into()),
+        ]);
+        let options = Value::new_list(vec![Value::String("natural".into()), Value::String("ignore-case".into())]);
+        let sorted = builtin_sort(vec![files, options]).unwrap();
+        assert_eq!(sorted.to_string(), "[\"a1\", \"B2\", \"b10\"]");
+        assert!(builtin_sort(vec![Value::new_list(vec![]), Value::String("backwards".into())]).is_err());
+    }
+
     #[test]
     fn test_join() {
         let list = Value::new_list(vec![
-            Value::String("a".to_string()),
-            Value::String("b".to_string()),
-            Value::String("c".to_string()),
+            Value::String("a".into()),
+            Value::String("b".into()),
+            Value::String("c".into()),
         ]);
-        let result = builtin_join(vec![list, Value::String(",".to_string())]).unwrap();
-        assert!(matches!(result, Value::String(s) if s == "a,b,c"));
+        let result = builtin_join(vec![list, Value::String(",".into())]).unwrap();
+        assert!(matches!(result, Value::String(s) if &*s == "a,b,c"));
     }
 
     #[test]
     fn test_split() {
         let result = builtin_split(vec![
-            Value::String("a,b,c".to_string()),
-            Value::String(",".to_string()),
+            Value::String("a,b,c".into()),
+            Value::String(",".into()),
         ])
         .unwrap();
         if let Value::List(items) = result {
             let borrowed = items.borrow();
             assert_eq!(borrowed.len(), 3);
-            assert!(matches!(&borrowed[0], Value::String(s) if s == "a"));
-            assert!(matches!(&borrowed[1], Value::String(s) if s == "b"));
-            assert!(matches!(&borrowed[2], Value::String(s) if s == "c"));
+            assert!(matches!(&borrowed[0], Value::String(s) if &**s == "a"));
+            assert!(matches!(&borrowed[1], Value::String(s) if &**s == "b"));
+            assert!(matches!(&borrowed[2], Value::String(s) if &**s == "c"));
         } else {
             panic!("Expected list");
         }
@@ -1260,17 +2621,17 @@ mod tests {
 
     #[test]
     fn test_trim() {
-        let result = builtin_trim(vec![Value::String("  hello  ".to_string())]).unwrap();
-        assert!(matches!(result, Value::String(s) if s == "hello"));
+        let result = builtin_trim(vec![Value::String("  hello  ".into())]).unwrap();
+        assert!(matches!(result, Value::String(s) if &*s == "hello"));
     }
 
     #[test]
     fn test_uppercase_lowercase() {
-        let result = builtin_uppercase(vec![Value::String("hello".to_string())]).unwrap();
-        assert!(matches!(result, Value::String(s) if s == "HELLO"));
+        let result = builtin_uppercase(vec![Value::String("hello".into())]).unwrap();
+        assert!(matches!(result, Value::String(s) if &*s == "HELLO"));
 
-        let result = builtin_lowercase(vec![Value::String("HELLO".to_string())]).unwrap();
-        assert!(matches!(result, Value::String(s) if s == "hello"));
+        let result = builtin_lowercase(vec![Value::String("HELLO".into())]).unwrap();
+        assert!(matches!(result, Value::String(s) if &*s == "hello"));
     }
 
     #[test]
@@ -1289,15 +2650,15 @@ mod tests {
 
         // String contains
         let result = builtin_contains(vec![
-            Value::String("hello world".to_string()),
-            Value::String("world".to_string()),
+            Value::String("hello world".into()),
+            Value::String("world".into()),
         ])
         .unwrap();
         assert!(matches!(result, Value::Boolean(true)));
 
         let result = builtin_contains(vec![
-            Value::String("hello".to_string()),
-            Value::String("xyz".to_string()),
+            Value::String("hello".into()),
+            Value::String("xyz".into()),
         ])
         .unwrap();
         assert!(matches!(result, Value::Boolean(false)));
@@ -1306,13 +2667,272 @@ mod tests {
     #[test]
     fn test_file_exists() {
         // Test with a file that definitely exists
-        let result = builtin_file_exists(vec![Value::String("Cargo.toml".to_string())]).unwrap();
+        let result = builtin_file_exists(vec![Value::String("Cargo.toml".into())]).unwrap();
         assert!(matches!(result, Value::Boolean(true)));
 
         // Test with a file that doesn't exist
         let result =
-            builtin_file_exists(vec![Value::String("nonexistent_file_12345.txt".to_string())])
+            builtin_file_exists(vec![Value::String("nonexistent_file_12345.txt".into())])
                 .unwrap();
         assert!(matches!(result, Value::Boolean(false)));
     }
+
+    #[test]
+    fn test_path_join_and_platform() {
+        let joined = builtin_path_join(vec![Value::String("data".into()), Value::String("a.csv".into())]).unwrap();
+        let expected = Path::new("data").join("a.csv").to_string_lossy().into_owned();
+        assert_eq!(joined, Value::String(expected.clone().into()));
+        let from_list = builtin_path_join(vec![Value::new_list(vec![
+            Value::String("data".into()),
+            Value::String("a.csv".into()),
+        ])]);
+        assert_eq!(from_list.unwrap(), Value::String(expected.into()));
+        assert!(builtin_path_join(vec![Value::Number(1.0)]).is_err());
+        assert_eq!(call_builtin("platform", vec![]).unwrap(), Value::String(std::env::consts::OS.into()));
+    }
+
+    #[test]
+    fn test_set_cwd_stays_inside_start_dir() {
+        assert!(builtin_set_cwd(vec![Value::String("..".into())]).is_err());
+        let outside = std::env::temp_dir().to_string_lossy().into_owned();
+        assert!(builtin_set_cwd(vec![Value::String(outside.into())]).unwrap_err().contains("can't leave"));
+    }
+
+    #[test]
+    fn test_file_metadata_and_hash() {
+        let path = format!("goose-hash-test-{}.txt", std::process::id());
+        fs::write(&path, "honk").unwrap();
+        let arg = || Value::String(path.clone().into());
+        assert_eq!(call_builtin("file-size", vec![arg()]).unwrap(), Value::Number(4.0));
+        assert!(matches!(call_builtin("file-modified", vec![arg()]).unwrap(), Value::Number(ms) if ms > 0.0));
+        let sha256 = call_builtin("file-hash", vec![arg()]).unwrap();
+        assert_eq!(
+            sha256,
+            Value::String("4de18cc93efe15c1d1cc2407cfc9f054b4d9217975538ac005dba541acee1954".into())
+        );
+        let md5 = call_builtin("file-hash", vec![arg(), Value::String("md5".into())]).unwrap();
+        assert!(matches!(md5, Value::String(hex) if hex.len() == 32));
+        assert!(call_builtin("file-hash", vec![arg(), Value::String("crc".into())]).is_err());
+        fs::remove_file(&path).unwrap();
+        assert!(call_builtin("file-size", vec![arg()]).unwrap_err().contains("couldn't find"));
+    }
+
+    #[test]
+    fn test_style() {
+        assert_eq!(apply_style("hi", &["1", "32"], true), "\x1b[1;32mhi\x1b[0m");
+        assert_eq!(apply_style("hi", &["1", "32"], false), "hi");
+        let args = || vec![Value::String("hi".into()), Value::String("bold, green".into())];
+        assert_eq!(style_text(args(), true).unwrap(), Value::String("\x1b[1;32mhi\x1b[0m".into()));
+        assert_eq!(style_text(args(), false).unwrap(), Value::String("hi".into()));
+        assert!(call_builtin("style", vec![Value::String("hi".into()), Value::String("sparkly".into())])
+            .unwrap_err()
+            .contains("sparkly"));
+    }
+
+    #[test]
+    fn test_decimal_and_to_fixed() {
+        let price = call_builtin("decimal", vec![Value::String("19.99".into())]).unwrap();
+        assert_eq!(price.to_string(), "19.99");
+        assert_eq!(price.type_name(), "decimal");
+        let fixed = |value: Value, places: f64| call_builtin("to-fixed", vec![value, Value::Number(places)]).unwrap();
+        assert_eq!(fixed(Value::Number(2.675), 2.0), Value::String("2.68".into()));
+        assert_eq!(fixed(price, 0.0), Value::String("20".into()));
+        assert_eq!(fixed(Value::Number(3.0), 2.0), Value::String("3.00".into()));
+        assert!(call_builtin("decimal", vec![Value::String("1.2.3".into())]).is_err());
+    }
+
+    #[test]
+    fn test_get_in_and_set_in() {
+        let data = call_builtin("json-parse", vec![Value::String(r#"{"users": [{"name": "Gerald"}]}"#.into())]).unwrap();
+        let path = |steps: Vec<Value>| Value::new_list(steps);
+        let name_path = path(vec![Value::String("users".into()), Value::Number(0.0), Value::String("name".into())]);
+        assert_eq!(
+            call_builtin("get-in", vec![data.clone(), name_path.clone()]).unwrap(),
+            Value::String("Gerald".into())
+        );
+        let missing = path(vec![Value::String("users".into()), Value::Number(3.0)]);
+        assert_eq!(call_builtin("get-in", vec![data.clone(), missing.clone(), Value::Number(0.0)]).unwrap(), Value::Number(0.0));
+
+        call_builtin("set-in", vec![data.clone(), name_path.clone(), Value::String("Mabel".into())]).unwrap();
+        assert_eq!(call_builtin("get-in", vec![data.clone(), name_path]).unwrap(), Value::String("Mabel".into()));
+        assert!(call_builtin("set-in", vec![data.clone(), missing, Value::Null]).is_err());
+        assert!(call_builtin("set-in", vec![data, path(vec![]), Value::Null]).is_err());
+    }
+
+    #[test]
+    fn test_slice_lists_and_strings() {
+        let numbers = Value::new_list((0..5).map(|n| Value::Number(n as f64)).collect());
+        let slice = |args: Vec<Value>| call_builtin("slice", args).map(|value| value.to_string());
+        assert_eq!(slice(vec![numbers.clone(), Value::Number(1.0), Value::Number(3.0)]).unwrap(), "[1, 2]");
+        assert_eq!(slice(vec![numbers.clone(), Value::Number(-2.0)]).unwrap(), "[3, 4]");
+        assert_eq!(slice(vec![numbers.clone(), Value::Number(3.0), Value::Number(99.0)]).unwrap(), "[3, 4]");
+        assert_eq!(slice(vec![numbers.clone(), Value::Number(4.0), Value::Number(1.0)]).unwrap(), "[]");
+        let word = Value::String("héllo".into());
+        assert_eq!(slice(vec![word.clone(), Value::Number(1.0), Value::Number(-1.0)]).unwrap(), "éll");
+        assert!(slice(vec![word, Value::Number(0.5)]).is_err());
+        assert!(slice(vec![Value::Number(1.0), Value::Number(0.0)]).is_err());
+    }
+
+    #[test]
+    fn test_index_of_finds_the_first_match_or_nil() {
+        let letters = Value::new_list(vec![Value::String("a".into()), Value::String("b".into()), Value::String("b".into())]);
+        let index_of = |haystack: &Value, needle: &str| call_builtin("index-of", vec![haystack.clone(), Value::String(needle.into())]);
+        assert_eq!(index_of(&letters, "b").unwrap(), Value::Number(1.0));
+        assert_eq!(index_of(&letters, "z").unwrap(), Value::Null);
+        assert_eq!(index_of(&Value::String("héllo".into()), "llo").unwrap(), Value::Number(2.0));
+        assert!(call_builtin("index-of", vec![Value::Number(1.0), Value::Number(1.0)]).is_err());
+    }
+
+    #[test]
+    fn test_padding_and_repeat_string() {
+        let text = |s: &str| Value::String(s.into());
+        assert_eq!(call_builtin("pad-left", vec![Value::Number(42.0), Value::Number(5.0)]).unwrap(), text("   42"));
+        assert_eq!(call_builtin("pad-right", vec![text("pip"), Value::Number(5.0), text(".")]).unwrap(), text("pip.."));
+        assert_eq!(call_builtin("pad-left", vec![text("gerald"), Value::Number(3.0)]).unwrap(), text("gerald"));
+        assert_eq!(call_builtin("pad-left", vec![text("é"), Value::Number(3.0), text("0")]).unwrap(), text("00é"));
+        assert!(call_builtin("pad-left", vec![text("x"), Value::Number(3.0), text("ab")]).is_err());
+        assert!(call_builtin("pad-right", vec![text("x"), Value::Number(-1.0)]).is_err());
+        assert_eq!(call_builtin("repeat-string", vec![text("ab"), Value::Number(3.0)]).unwrap(), text("ababab"));
+        assert_eq!(call_builtin("repeat-string", vec![text("ab"), Value::Number(0.0)]).unwrap(), text(""));
+
+        // The same limit as a format() width, however the numbers multiply out
+        let huge = Value::Number(10f64.powi(300));
+        assert_eq!(call_builtin("pad-left", vec![text("x"), Value::Number(1000.0)]).unwrap().to_string().len(), 1000);
+        assert!(call_builtin("pad-left", vec![text("x"), Value::Number(1001.0)]).unwrap_err().contains("stops at 1000"));
+        assert!(call_builtin("pad-right", vec![text("x"), huge.clone()]).unwrap_err().contains("stops at 1000"));
+        assert!(call_builtin("repeat-string", vec![text("ab"), Value::Number(501.0)]).unwrap_err().contains("stops at 1000"));
+        assert!(call_builtin("repeat-string", vec![text("ab"), huge.clone()]).unwrap_err().contains("stops at 1000"));
+        assert_eq!(call_builtin("repeat-string", vec![text(""), huge]).unwrap(), text(""));
+    }
+
+    #[test]
+    fn test_starts_with_and_ends_with() {
+        let text = |s: &str| Value::String(s.into());
+        assert_eq!(call_builtin("starts-with", vec![text("quack!"), text("qu")]).unwrap(), Value::Boolean(true));
+        assert_eq!(call_builtin("ends-with", vec![text("quack!"), text("qu")]).unwrap(), Value::Boolean(false));
+        assert_eq!(call_builtin("ends-with", vec![text("quack!"), text("")]).unwrap(), Value::Boolean(true));
+        let error = call_builtin("starts-with", vec![text("quack"), Value::Number(1.0)]).unwrap_err();
+        assert_eq!(error, "starts-with() expects a string, got number");
+        assert!(call_builtin("ends-with", vec![Value::Null, text("k")]).is_err());
+    }
+
+    #[test]
+    fn test_validate_returns_readable_problems() {
+        let data = call_builtin("json-parse", vec![Value::String(r#"{"name": 5}"#.into())]).unwrap();
+        let schema = call_builtin("json-parse", vec![Value::String(r#"{"name": "string", "age": {"required": false}}"#.into())]).unwrap();
+        let problems = call_builtin("validate", vec![data, schema.clone()]).unwrap();
+        assert_eq!(problems, Value::new_list(vec![Value::String("'name' should be a string, got number".into())]));
+        assert!(call_builtin("validate", vec![schema]).is_err());
+    }
+
+    #[test]
+    fn test_fraction_builtins() {
+        let three_quarters = call_builtin("fraction", vec![Value::Number(6.0), Value::Number(8.0)]).unwrap();
+        assert_eq!(three_quarters.to_string(), "3/4");
+        assert_eq!(call_builtin("fraction", vec![Value::String("3/4".into())]).unwrap(), three_quarters);
+        assert_eq!(call_builtin("fraction", vec![Value::Number(0.75)]).unwrap(), three_quarters);
+        assert_eq!(call_builtin("denominator", vec![three_quarters.clone()]).unwrap(), Value::Number(4.0));
+        assert_eq!(
+            call_builtin("to-fixed", vec![three_quarters, Value::Number(1.0)]).unwrap(),
+            Value::String("0.8".into())
+        );
+        assert!(call_builtin("fraction", vec![Value::Number(1.0), Value::Number(0.0)]).is_err());
+        assert!(call_builtin("fraction", vec![Value::Number(1.5), Value::Number(2.0)]).is_err());
+    }
+
+    #[test]
+    fn test_matrix_builtins() {
+        let identity = Value::new_list(vec![
+            Value::new_list(vec![Value::Number(1.0), Value::Number(0.0)]),
+            Value::new_list(vec![Value::Number(0.0), Value::Number(1.0)]),
+        ]);
+        let twos = call_builtin("matrix", vec![Value::Number(2.0), Value::Number(2.0), Value::Number(2.0)]).unwrap();
+        assert_eq!(call_builtin("mat-mul", vec![identity.clone(), twos.clone()]).unwrap(), twos);
+        assert_eq!(
+            call_builtin("mat-add", vec![identity.clone(), identity.clone()]).unwrap(),
+            call_builtin("mat-scale", vec![identity.clone(), Value::Number(2.0)]).unwrap()
+        );
+        assert_eq!(call_builtin("transpose", vec![identity.clone()]).unwrap(), identity);
+        let row = call_builtin("matrix", vec![Value::Number(1.0), Value::Number(3.0)]).unwrap();
+        assert!(call_builtin("mat-sub", vec![identity, row]).unwrap_err().contains("same size"));
+
+        let huge = Value::Number(10f64.powi(300));
+        assert!(call_builtin("matrix", vec![huge.clone(), Value::Number(1.0)]).unwrap_err().contains("the goose stops there"));
+        assert!(call_builtin("matrix", vec![huge, Value::Number(0.0)]).unwrap_err().contains("the goose stops there"));
+        let square = vec![Value::Number(10_000.0), Value::Number(10_000.0)];
+        assert!(call_builtin("matrix", square).unwrap_err().contains("the goose stops there"));
+    }
+
+    #[test]
+    fn test_plot_builtins() {
+        let counts = Value::new_list(vec![Value::Number(1.0), Value::Number(2.0)]);
+        let Value::String(chart) = call_builtin("plot-bars", vec![counts.clone()]).unwrap() else {
+            panic!("plot-bars should give back text");
+        };
+        assert!(chart.starts_with("1 | ####################  "));
+        let Value::String(chart) = call_builtin("plot-line", vec![counts.clone(), Value::Number(2.0)]).unwrap() else {
+            panic!("plot-line should give back text");
+        };
+        assert_eq!(&*chart, "2 | *\n1 |*\n  +--");
+        for height in [1001.0, 10f64.powi(300)] {
+            assert!(call_builtin("plot-line", vec![counts.clone(), Value::Number(height)]).is_err());
+        }
+        assert!(call_builtin("plot-bars", vec![Value::new_list(vec![Value::String("x".into())])]).is_err());
+    }
+
+    #[test]
+    fn test_regex_match_find_all_and_replace() {
+        let text = |s: &str| Value::String(s.into());
+        let list = |items: Vec<Value>| Value::new_list(items);
+        let found = call_builtin("regex-match", vec![text(r"(\w+)@(\w+)?\.com"), text("mail pip@.com now")]).unwrap();
+        assert_eq!(found, list(vec![text("pip@.com"), text("pip"), Value::Null]));
+        assert_eq!(call_builtin("regex-match", vec![text("z+"), text("duck")]).unwrap(), Value::Null);
+
+        let all = call_builtin("regex-find-all", vec![text(r"\d+"), text("3 ducks, 12 geese")]).unwrap();
+        assert_eq!(all, list(vec![text("3"), text("12")]));
+        let pairs = call_builtin("regex-find-all", vec![text(r"(\d+) (\w+)"), text("3 ducks, 12 geese")]).unwrap();
+        assert_eq!(pairs, list(vec![list(vec![text("3 ducks"), text("3"), text("ducks")]), list(vec![text("12 geese"), text("12"), text("geese")])]));
+
+        let swapped = call_builtin("regex-replace", vec![text(r"(\w+) (\w+)"), text("honk loudly"), text("$2 $1")]).unwrap();
+        assert_eq!(swapped, text("loudly honk"));
+        let error = call_builtin("regex-match", vec![text("(unclosed"), text("x")]).unwrap_err();
+        assert!(error.starts_with("'(unclosed' isn't a pattern the goose understands"), "{}", error);
+        assert!(call_builtin("regex-replace", vec![text("a"), text("a")]).unwrap_err().contains("requires 3 arguments"));
+    }
+
+    #[test]
+    fn test_toml_and_yaml_parse() {
+        let field = |value: &Value, name: &str| match value {
+            Value::Struct { fields, .. } => fields.borrow().get(name).cloned().unwrap(),
+            other => panic!("expected a struct, got {}", other),
+        };
+        let toml = "title = \"pond\"\n[server]\nport = 8080\nhosts = [\"a\", \"b\"]\nopened = 2024-05-01\n";
+        let config = call_builtin("toml-parse", vec![Value::String(toml.into())]).unwrap();
+        assert_eq!(field(&config, "title"), Value::String("pond".into()));
+        let server = field(&config, "server");
+        assert_eq!(field(&server, "port"), Value::Number(8080.0));
+        assert_eq!(field(&server, "opened"), Value::String("2024-05-01".into()));
+        assert_eq!(field(&server, "hosts").to_string(), "[\"a\", \"b\"]");
+        assert!(call_builtin("toml-parse", vec![Value::String("port = ".into())]).unwrap_err().starts_with("TOML parse error"));
+
+        let yaml = "name: Gerald\nfriends:\n  - pip\n  - tern\n1: one\nnothing: ~\n";
+        let duck = call_builtin("yaml-parse", vec![Value::String(yaml.into())]).unwrap();
+        assert_eq!(field(&duck, "name"), Value::String("Gerald".into()));
+        assert_eq!(field(&duck, "friends").to_string(), "[\"pip\", \"tern\"]");
+        assert_eq!(field(&duck, "1"), Value::String("one".into()));
+        assert_eq!(field(&duck, "nothing"), Value::Null);
+        assert!(call_builtin("yaml-parse", vec![Value::String("a: [".into())]).unwrap_err().starts_with("YAML parse error"));
+    }
+
+    #[test]
+    fn test_glob_and_path_matches() {
+        assert!(call_builtin("glob", vec![Value::String("../*".into())]).is_err());
+        let found = call_builtin("glob", vec![Value::String("docs/*.md".into())]).unwrap();
+        assert!(matches!(found, Value::List(items) if !items.borrow().is_empty()));
+        let matched = call_builtin(
+            "path-matches",
+            vec![Value::String("data/sales.csv".into()), Value::String("data/*.csv".into())],
+        );
+        assert_eq!(matched.unwrap(), Value::Boolean(true));
+    }
 }
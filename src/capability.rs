@@ -0,0 +1,184 @@
+// Capabilities - what a program is allowed to reach outside the interpreter
+//
+// Every builtin that touches the outside world declares the capability it needs
+// (see `builtins::capability`), and a `Policy` says which capabilities a run has.
+// Everything is allowed by default; `goose run --allow`/`--deny` and
+// `Interpreter::set_policy` narrow it down for scripts you don't fully trust.
+// Two switches sit on top of the proc capability: `shell` can do anything the
+// user can, so it stays off until asked for, and the desktop builtins
+// (clipboard and notifications) can be turned off on their own.
+
+use std::collections::BTreeSet;
+use std::fmt;
+use std::str::FromStr;
+
+/// A kind of access to the world outside the program
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub enum Capability {
+    /// Reading files and folders (and migrating other files)
+    FsRead,
+    /// Creating and changing files
+    FsWrite,
+    /// HTTP, WebSockets, and serving
+    Net,
+    /// Changing the goose's own process, like its working folder, and running other programs
+    Proc,
+    /// Environment variables and where the user's folders are
+    Env,
+    /// The real clock: `now` and `sleep`
+    Time,
+}
+
+impl Capability {
+    pub const ALL: [Capability; 6] = [
+        Capability::FsRead,
+        Capability::FsWrite,
+        Capability::Net,
+        Capability::Proc,
+        Capability::Env,
+        Capability::Time,
+    ];
+
+    /// The name used on the command line, like `fs-read`
+    pub fn name(self) -> &'static str {
+        match self {
+            Capability::FsRead => "fs-read",
+            Capability::FsWrite => "fs-write",
+            Capability::Net => "net",
+            Capability::Proc => "proc",
+            Capability::Env => "env",
+            Capability::Time => "time",
+        }
+    }
+}
+
+impl fmt::Display for Capability {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.write_str(self.name())
+    }
+}
+
+impl FromStr for Capability {
+    type Err = String;
+
+    fn from_str(name: &str) -> Result<Self, String> {
+        Capability::ALL.into_iter().find(|capability| capability.name() == name).ok_or_else(|| {
+            let names: Vec<&str> = Capability::ALL.iter().map(|capability| capability.name()).collect();
+            format!("The goose has never heard of '{}'. Try one of: {}", name, names.join(", "))
+        })
+    }
+}
+
+/// Which capabilities a run has
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Policy {
+    allowed: BTreeSet<Capability>,
+    /// Whether `shell` may run commands (with proc access)
+    shell: bool,
+    /// Whether clipboard and notification builtins may touch the desktop (with proc access)
+    desktop: bool,
+}
+
+impl Policy {
+    /// Every capability allowed - the default. `shell` is still off until
+    /// `with_shell` turns it on.
+    pub fn all() -> Self {
+        Policy { allowed: Capability::ALL.into_iter().collect(), shell: false, desktop: true }
+    }
+
+    /// Nothing allowed: pure computation only
+    pub fn none() -> Self {
+        Policy { allowed: BTreeSet::new(), shell: false, desktop: false }
+    }
+
+    /// The policy for `--allow` and `--deny`: only the allowed capabilities if
+    /// any are listed (otherwise all of them), minus the denied ones
+    pub fn from_flags(allow: &[Capability], deny: &[Capability]) -> Self {
+        let mut policy = if allow.is_empty() { Policy::all() } else { Policy::none() };
+        for &capability in allow {
+            policy = policy.allow(capability);
+        }
+        for &capability in deny {
+            policy = policy.deny(capability);
+        }
+        policy
+    }
+
+    pub fn allow(mut self, capability: Capability) -> Self {
+        self.allowed.insert(capability);
+        self
+    }
+
+    pub fn deny(mut self, capability: Capability) -> Self {
+        self.allowed.remove(&capability);
+        self
+    }
+
+    /// Allow or refuse `shell` (`goose run --allow-shell`)
+    pub fn with_shell(mut self, allowed: bool) -> Self {
+        self.shell = allowed;
+        self
+    }
+
+    /// Allow or refuse the clipboard and notification builtins (`goose test`
+    /// turns them off so tests can't scribble on your clipboard)
+    pub fn with_desktop(mut self, allowed: bool) -> Self {
+        self.desktop = allowed;
+        self
+    }
+
+    pub fn allows(&self, capability: Capability) -> bool {
+        self.allowed.contains(&capability)
+    }
+
+    pub fn allows_shell(&self) -> bool {
+        self.shell && self.allows(Capability::Proc)
+    }
+
+    pub fn allows_desktop(&self) -> bool {
+        self.desktop && self.allows(Capability::Proc)
+    }
+
+    /// The error for using something this policy doesn't allow
+    pub fn check(&self, capability: Capability, what: &str) -> Result<(), String> {
+        if self.allows(capability) {
+            Ok(())
+        } else {
+            Err(format!("{} needs {} access, and the goose has been told not to allow that here", what, capability))
+        }
+    }
+}
+
+impl Default for Policy {
+    fn default() -> Self {
+        Policy::all()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_allow_narrows_and_deny_removes() {
+        let everything = Policy::from_flags(&[], &[]);
+        assert!(Capability::ALL.iter().all(|&capability| everything.allows(capability)));
+
+        let policy = Policy::from_flags(&[Capability::FsRead, Capability::Net], &[Capability::Net]);
+        assert!(policy.allows(Capability::FsRead));
+        assert!(!policy.allows(Capability::Net) && !policy.allows(Capability::FsWrite));
+        assert!(policy.check(Capability::Net, "http-get()").unwrap_err().contains("needs net access"));
+
+        assert_eq!("fs-write".parse::<Capability>(), Ok(Capability::FsWrite));
+        assert!("disk".parse::<Capability>().unwrap_err().contains("fs-read, fs-write, net, proc, env, time"));
+    }
+
+    #[test]
+    fn test_shell_and_desktop_sit_on_top_of_proc() {
+        assert!(!Policy::all().allows_shell() && Policy::all().allows_desktop());
+        assert!(Policy::all().with_shell(true).allows_shell());
+        assert!(!Policy::all().with_desktop(false).allows_desktop());
+        let no_proc = Policy::all().with_shell(true).deny(Capability::Proc);
+        assert!(!no_proc.allows_shell() && !no_proc.allows_desktop());
+    }
+}
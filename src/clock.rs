@@ -0,0 +1,160 @@
+// Clocks - where `now` and `sleep` get their time from
+// The real clock waits; the virtual one just moves its hands, so tests finish instantly
+
+use std::cell::Cell;
+use std::rc::Rc;
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::Arc;
+use std::thread;
+use std::time::{Duration, SystemTime, UNIX_EPOCH};
+
+/// How long a real sleep waits between checks for cancellation
+const SLEEP_SLICE: Duration = Duration::from_millis(20);
+
+/// The longest goose will wait in one go, for a sleep or a time limit
+pub const MAX_WAIT: Duration = Duration::from_secs(7 * 24 * 60 * 60);
+
+/// `ms` milliseconds as a Duration, or None if that's negative, not a number
+/// or longer than MAX_WAIT
+pub fn wait_duration(ms: f64) -> Option<Duration> {
+    Duration::try_from_secs_f64(ms / 1000.0).ok().filter(|wait| *wait <= MAX_WAIT)
+}
+
+/// A source of time for the interpreter
+pub trait Clock {
+    /// Milliseconds since the Unix epoch
+    fn now_ms(&self) -> f64;
+
+    /// Wait for `ms` milliseconds. `interrupted` returns an error message once
+    /// the program has been cancelled or run out of time; the sleep should stop
+    /// early and return that error.
+    fn sleep_ms(&mut self, ms: f64, interrupted: &dyn Fn() -> Option<String>) -> Result<(), String>;
+}
+
+/// The real clock: actual time, actual waiting
+#[derive(Debug, Default)]
+pub struct SystemClock;
+
+impl Clock for SystemClock {
+    fn now_ms(&self) -> f64 {
+        SystemTime::now()
+            .duration_since(UNIX_EPOCH)
+            .unwrap_or_default()
+            .as_secs_f64()
+            * 1000.0
+    }
+
+    fn sleep_ms(&mut self, ms: f64, interrupted: &dyn Fn() -> Option<String>) -> Result<(), String> {
+        let mut remaining = wait_duration(ms)
+            .ok_or_else(|| format!("Can't sleep for {} ms - the goose waits a week at most", ms))?;
+        loop {
+            if let Some(reason) = interrupted() {
+                return Err(reason);
+            }
+            if remaining.is_zero() {
+                return Ok(());
+            }
+            let slice = remaining.min(SLEEP_SLICE);
+            thread::sleep(slice);
+            remaining -= slice;
+        }
+    }
+}
+
+/// A pretend clock that starts at 0 and only moves when something sleeps
+/// (or when `advance` is called). Clones share the same time, so an embedder
+/// can keep one to read or move the clock while the interpreter uses another.
+#[derive(Debug, Clone, Default)]
+pub struct VirtualClock {
+    now: Rc<Cell<f64>>,
+}
+
+impl VirtualClock {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Move the clock forward without sleeping
+    pub fn advance(&self, ms: f64) {
+        self.now.set(self.now.get() + ms);
+    }
+}
+
+impl Clock for VirtualClock {
+    fn now_ms(&self) -> f64 {
+        self.now.get()
+    }
+
+    fn sleep_ms(&mut self, ms: f64, interrupted: &dyn Fn() -> Option<String>) -> Result<(), String> {
+        if let Some(reason) = interrupted() {
+            return Err(reason);
+        }
+        self.advance(ms);
+        Ok(())
+    }
+}
+
+/// Stops a running interpreter from another thread (see `Interpreter::cancel_handle`)
+#[derive(Debug, Clone, Default)]
+pub struct CancelHandle {
+    cancelled: Arc<AtomicBool>,
+}
+
+impl CancelHandle {
+    /// Ask the program to stop at its next statement (or during its current sleep)
+    pub fn cancel(&self) {
+        self.cancelled.store(true, Ordering::SeqCst);
+    }
+
+    pub fn is_cancelled(&self) -> bool {
+        self.cancelled.load(Ordering::SeqCst)
+    }
+
+    /// Clear a cancellation so the interpreter can run again
+    pub fn reset(&self) {
+        self.cancelled.store(false, Ordering::SeqCst);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::time::Instant;
+
+    #[test]
+    fn test_virtual_clock_sleeps_instantly_and_shares_time() {
+        let mut clock = VirtualClock::new();
+        let observer = clock.clone();
+        let started = Instant::now();
+        clock.sleep_ms(60_000.0, &|| None).unwrap();
+        assert!(started.elapsed() < Duration::from_secs(1));
+        assert_eq!(observer.now_ms(), 60_000.0);
+        observer.advance(5.0);
+        assert_eq!(clock.now_ms(), 60_005.0);
+    }
+
+    #[test]
+    fn test_real_sleep_stops_when_interrupted() {
+        let handle = CancelHandle::default();
+        let remote = handle.clone();
+        let started = Instant::now();
+        let waiter = thread::spawn(move || {
+            thread::sleep(Duration::from_millis(30));
+            remote.cancel();
+        });
+        let result = SystemClock.sleep_ms(10_000.0, &|| handle.is_cancelled().then(|| "cancelled".to_string()));
+        waiter.join().unwrap();
+        assert_eq!(result, Err("cancelled".to_string()));
+        assert!(started.elapsed() < Duration::from_secs(5));
+    }
+
+    #[test]
+    fn test_waits_are_capped_at_a_week() {
+        assert_eq!(wait_duration(1500.0), Some(Duration::from_millis(1500)));
+        assert_eq!(wait_duration(MAX_WAIT.as_millis() as f64), Some(MAX_WAIT));
+        for ms in [10f64.powi(300), f64::INFINITY, f64::NAN, -1.0] {
+            assert_eq!(wait_duration(ms), None);
+        }
+        assert!(SystemClock.sleep_ms(10f64.powi(300), &|| None).unwrap_err().contains("a week at most"));
+    }
+}
@@ -0,0 +1,289 @@
+// Exact decimal numbers behind `decimal(...)` and `to-fixed`
+// Ordinary numbers are binary floats, so 0.1 + 0.2 isn't quite 0.3. A decimal
+// keeps whole units of 10^-scale instead, which is what money needs.
+
+use std::cmp::Ordering;
+use std::fmt;
+
+/// Most digits a decimal keeps after the point; longer results are rounded
+pub const MAX_SCALE: u32 = 28;
+
+/// Extra digits a division keeps beyond its inputs (1 / 3 is 0.3333333333)
+const DIVISION_PLACES: u32 = 10;
+
+const TOO_BIG: &str = "that's too big for a decimal";
+
+/// A decimal number: `units` lots of 10^-`scale`, so 12.50 is (1250, 2).
+/// The scale is kept, which is why 1.10 prints as 1.10.
+#[derive(Debug, Clone, Copy)]
+pub struct Decimal {
+    units: i128,
+    scale: u32,
+}
+
+fn pow10(exponent: u32) -> Option<i128> {
+    10i128.checked_pow(exponent)
+}
+
+/// Divide, rounding halves away from zero (2.5 -> 3, -2.5 -> -3)
+fn div_round(n: i128, d: i128) -> i128 {
+    let (quotient, remainder) = (n / d, n % d);
+    if remainder.unsigned_abs() * 2 >= d.unsigned_abs() {
+        quotient + if (n < 0) == (d < 0) { 1 } else { -1 }
+    } else {
+        quotient
+    }
+}
+
+impl Decimal {
+    /// Read text like "12.50", "-3" or "+0.125"
+    pub fn parse(text: &str) -> Result<Decimal, String> {
+        let trimmed = text.trim();
+        let (negative, digits) = match trimmed.strip_prefix('-') {
+            Some(rest) => (true, rest),
+            None => (false, trimmed.strip_prefix('+').unwrap_or(trimmed)),
+        };
+        let (whole, fraction) = digits.split_once('.').unwrap_or((digits, ""));
+        let is_digits = |s: &str| s.chars().all(|c| c.is_ascii_digit());
+        if (whole.is_empty() && fraction.is_empty()) || !is_digits(whole) || !is_digits(fraction) {
+            return Err(format!("\"{}\" isn't a decimal number", text));
+        }
+        let scale = fraction.len() as u32;
+        if scale > MAX_SCALE {
+            return Err(format!("decimals can have at most {} digits after the point", MAX_SCALE));
+        }
+        let mut units: i128 = 0;
+        for digit in whole.chars().chain(fraction.chars()) {
+            units = units
+                .checked_mul(10)
+                .and_then(|u| u.checked_add(digit.to_digit(10).unwrap_or(0) as i128))
+                .ok_or(TOO_BIG)?;
+        }
+        Ok(Decimal { units: if negative { -units } else { units }, scale })
+    }
+
+    /// The decimal a number looks like when printed, so 0.1 becomes exactly 0.1
+    pub fn from_f64(n: f64) -> Result<Decimal, String> {
+        if !n.is_finite() {
+            return Err(format!("{} can't be a decimal", n));
+        }
+        let text = n.to_string();
+        match text.split_once('.') {
+            Some((_, fraction)) if fraction.len() as u32 > MAX_SCALE => {
+                Ok(Decimal::parse(&format!("{:.*}", MAX_SCALE as usize, n))?.normalize(0))
+            }
+            _ => Decimal::parse(&text),
+        }
+    }
+
+    /// A decimal of `units` lots of 10^-`scale`
+    pub fn from_parts(units: i128, scale: u32) -> Result<Decimal, String> {
+        if scale > MAX_SCALE {
+            return Err(format!("decimals can have at most {} digits after the point", MAX_SCALE));
+        }
+        Ok(Decimal { units, scale })
+    }
+
+    /// (units, scale), so 12.50 is (1250, 2)
+    pub fn parts(self) -> (i128, u32) {
+        (self.units, self.scale)
+    }
+
+    /// The nearest ordinary number
+    pub fn to_f64(self) -> f64 {
+        self.to_string().parse().unwrap_or(f64::NAN)
+    }
+
+    pub fn is_zero(self) -> bool {
+        self.units == 0
+    }
+
+    pub fn is_negative(self) -> bool {
+        self.units < 0
+    }
+
+    /// The same value with a bigger scale (more zeros after the point)
+    fn rescale(self, scale: u32) -> Result<Decimal, String> {
+        let factor = pow10(scale - self.scale).ok_or(TOO_BIG)?;
+        let units = self.units.checked_mul(factor).ok_or(TOO_BIG)?;
+        Ok(Decimal { units, scale })
+    }
+
+    /// Both values as units of the same scale
+    fn align(self, other: Decimal) -> Result<(i128, i128, u32), String> {
+        let scale = self.scale.max(other.scale);
+        Ok((self.rescale(scale)?.units, other.rescale(scale)?.units, scale))
+    }
+
+    /// Drop trailing zeros after the point, keeping at least `min_scale` digits
+    fn normalize(mut self, min_scale: u32) -> Decimal {
+        while self.scale > min_scale && self.units % 10 == 0 {
+            self.units /= 10;
+            self.scale -= 1;
+        }
+        self
+    }
+
+    /// Round (halves away from zero) or pad to exactly `places` digits after the point
+    pub fn round(self, places: u32) -> Result<Decimal, String> {
+        if places >= self.scale {
+            return self.rescale(places.min(MAX_SCALE));
+        }
+        let factor = pow10(self.scale - places).ok_or(TOO_BIG)?;
+        Ok(Decimal { units: div_round(self.units, factor), scale: places })
+    }
+
+    pub fn checked_add(self, other: Decimal) -> Result<Decimal, String> {
+        let (a, b, scale) = self.align(other)?;
+        Ok(Decimal { units: a.checked_add(b).ok_or(TOO_BIG)?, scale })
+    }
+
+    /// -self; only fails for units of i128::MIN, which has no positive twin
+    pub fn checked_neg(self) -> Result<Decimal, String> {
+        Ok(Decimal { units: self.units.checked_neg().ok_or(TOO_BIG)?, scale: self.scale })
+    }
+
+    pub fn checked_sub(self, other: Decimal) -> Result<Decimal, String> {
+        let (a, b, scale) = self.align(other)?;
+        Ok(Decimal { units: a.checked_sub(b).ok_or(TOO_BIG)?, scale })
+    }
+
+    pub fn checked_mul(self, other: Decimal) -> Result<Decimal, String> {
+        let units = self.units.checked_mul(other.units).ok_or(TOO_BIG)?;
+        let product = Decimal { units, scale: self.scale + other.scale };
+        if product.scale > MAX_SCALE {
+            product.round(MAX_SCALE)
+        } else {
+            Ok(product)
+        }
+    }
+
+    /// Divide, keeping ten more digits than the inputs had and then dropping
+    /// any trailing zeros (so 10.00 / 4 is 2.50)
+    pub fn checked_div(self, other: Decimal) -> Result<Decimal, String> {
+        if other.is_zero() {
+            return Err("can't divide by zero".to_string());
+        }
+        let kept = self.scale.max(other.scale);
+        let scale = (kept + DIVISION_PLACES).min(MAX_SCALE);
+        // self.units * 10^shift / other.units has exactly `scale` digits after the point
+        let shift = scale + other.scale - self.scale;
+        let numerator = self.units.checked_mul(pow10(shift).ok_or(TOO_BIG)?).ok_or(TOO_BIG)?;
+        Ok(Decimal { units: div_round(numerator, other.units), scale }.normalize(kept))
+    }
+
+    /// Remainder with the sign of the divisor, like `%` on numbers
+    pub fn checked_rem(self, other: Decimal) -> Result<Decimal, String> {
+        if other.is_zero() {
+            return Err("can't divide by zero".to_string());
+        }
+        let (a, b, scale) = self.align(other)?;
+        let mut units = a % b;
+        if units != 0 && (units < 0) != (b < 0) {
+            units += b;
+        }
+        Ok(Decimal { units, scale })
+    }
+
+    /// Raise to a whole-number power
+    pub fn pow(self, exponent: f64) -> Result<Decimal, String> {
+        if exponent.fract() != 0.0 || exponent.abs() > u32::MAX as f64 {
+            return Err(format!("decimals can only be raised to whole-number powers, not {}", exponent));
+        }
+        // Square and multiply, so big powers of 1 don't take forever
+        let (mut result, mut base, mut remaining) = (Decimal { units: 1, scale: 0 }, self, exponent.abs() as u32);
+        while remaining > 0 {
+            if remaining & 1 == 1 {
+                result = result.checked_mul(base)?;
+            }
+            remaining >>= 1;
+            if remaining > 0 {
+                base = base.checked_mul(base)?;
+            }
+        }
+        if exponent < 0.0 {
+            Decimal { units: 1, scale: 0 }.checked_div(result)
+        } else {
+            Ok(result)
+        }
+    }
+}
+
+impl PartialEq for Decimal {
+    fn eq(&self, other: &Self) -> bool {
+        self.cmp(other) == Ordering::Equal
+    }
+}
+
+impl Eq for Decimal {}
+
+impl PartialOrd for Decimal {
+    fn partial_cmp(&self, other: &Self) -> Option<Ordering> {
+        Some(self.cmp(other))
+    }
+}
+
+impl Ord for Decimal {
+    /// By value, whatever the scale: 1.10 and 1.1 are equal
+    fn cmp(&self, other: &Self) -> Ordering {
+        match self.align(*other) {
+            Ok((a, b, _)) => a.cmp(&b),
+            Err(_) => self.to_f64().total_cmp(&other.to_f64()),
+        }
+    }
+}
+
+impl fmt::Display for Decimal {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        let digits = self.units.unsigned_abs().to_string();
+        let sign = if self.units < 0 { "-" } else { "" };
+        let scale = self.scale as usize;
+        if scale == 0 {
+            return write!(f, "{}{}", sign, digits);
+        }
+        let digits = format!("{:0>width$}", digits, width = scale + 1);
+        let (whole, fraction) = digits.split_at(digits.len() - scale);
+        write!(f, "{}{}.{}", sign, whole, fraction)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn d(text: &str) -> Decimal {
+        Decimal::parse(text).unwrap()
+    }
+
+    #[test]
+    fn test_arithmetic_is_exact() {
+        assert_eq!(d("0.1").checked_add(d("0.2")).unwrap().to_string(), "0.3");
+        assert_eq!(d("19.99").checked_mul(d("3")).unwrap().to_string(), "59.97");
+        assert_eq!(d("1.10").checked_sub(d("0.1")).unwrap().to_string(), "1.00");
+        assert_eq!(d("10.00").checked_div(d("4")).unwrap().to_string(), "2.50");
+        assert_eq!(d("1").checked_div(d("3")).unwrap().to_string(), "0.3333333333");
+        assert_eq!(d("-7").checked_rem(d("3")).unwrap().to_string(), "2");
+        assert_eq!(d("1.5").pow(2.0).unwrap().to_string(), "2.25");
+        assert!(d("1").checked_div(d("0")).is_err());
+    }
+
+    #[test]
+    fn test_parse_round_and_compare() {
+        assert_eq!(d("-0.05").to_string(), "-0.05");
+        assert_eq!(d("2.675").round(2).unwrap().to_string(), "2.68");
+        assert_eq!(d("-2.5").round(0).unwrap().to_string(), "-3");
+        assert_eq!(d("3").round(2).unwrap().to_string(), "3.00");
+        assert_eq!(d("1.10"), d("1.1"));
+        assert!(d("0.09") < d("0.1"));
+        assert_eq!(Decimal::from_f64(0.1).unwrap().to_string(), "0.1");
+        assert!(Decimal::parse("12.3.4").is_err());
+        assert!(Decimal::parse("lots").is_err());
+    }
+
+    #[test]
+    fn test_negating_the_smallest_units_is_an_error() {
+        let smallest = Decimal::from_parts(i128::MIN, 2).unwrap();
+        assert!(smallest.checked_neg().is_err());
+        assert_eq!(d("1.25").checked_neg().unwrap().to_string(), "-1.25");
+    }
+}
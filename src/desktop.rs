@@ -0,0 +1,125 @@
+// Desktop helpers - clipboard and notifications, for little automation scripts
+// Only built with `--features desktop`; works by asking the platform's own tools
+
+/// Put text on the system clipboard
+pub fn clipboard_set(text: &str) -> Result<(), String> {
+    imp::clipboard_set(text)
+}
+
+/// Read text from the system clipboard
+pub fn clipboard_get() -> Result<String, String> {
+    imp::clipboard_get()
+}
+
+/// Pop up a desktop notification
+pub fn notify(title: &str, message: &str) -> Result<(), String> {
+    imp::notify(title, message)
+}
+
+#[cfg(feature = "desktop")]
+mod imp {
+    use std::io::Write;
+    use std::process::{Command, Stdio};
+
+    /// Run a tool, feeding it `input` if given, and return what it printed
+    fn run(program: &str, args: &[&str], input: Option<&str>) -> Result<String, String> {
+        let mut child = Command::new(program)
+            .args(args)
+            .stdin(if input.is_some() { Stdio::piped() } else { Stdio::null() })
+            .stdout(Stdio::piped())
+            .stderr(Stdio::null())
+            .spawn()
+            .map_err(|e| format!("couldn't run {}: {}", program, e))?;
+        if let (Some(text), Some(mut stdin)) = (input, child.stdin.take()) {
+            stdin.write_all(text.as_bytes()).map_err(|e| e.to_string())?;
+        }
+        let output = child.wait_with_output().map_err(|e| e.to_string())?;
+        if !output.status.success() {
+            return Err(format!("{} gave up ({})", program, output.status));
+        }
+        Ok(String::from_utf8_lossy(&output.stdout).into_owned())
+    }
+
+    /// A program and its arguments
+    #[cfg(all(unix, not(target_os = "macos")))]
+    type Tool = (&'static str, &'static [&'static str]);
+
+    /// The clipboard tools for this desktop: (copy, paste)
+    #[cfg(all(unix, not(target_os = "macos")))]
+    fn clipboard_tools() -> Result<(Tool, Tool), String> {
+        if std::env::var_os("WAYLAND_DISPLAY").is_some() {
+            Ok((("wl-copy", &[]), ("wl-paste", &["--no-newline"])))
+        } else if std::env::var_os("DISPLAY").is_some() {
+            Ok((("xclip", &["-selection", "clipboard"]), ("xclip", &["-selection", "clipboard", "-o"])))
+        } else {
+            Err("there's no desktop here, so there's no clipboard either".to_string())
+        }
+    }
+
+    #[cfg(all(unix, not(target_os = "macos")))]
+    pub fn clipboard_set(text: &str) -> Result<(), String> {
+        let ((program, args), _) = clipboard_tools()?;
+        run(program, args, Some(text)).map(|_| ())
+    }
+
+    #[cfg(all(unix, not(target_os = "macos")))]
+    pub fn clipboard_get() -> Result<String, String> {
+        let (_, (program, args)) = clipboard_tools()?;
+        run(program, args, None)
+    }
+
+    #[cfg(all(unix, not(target_os = "macos")))]
+    pub fn notify(title: &str, message: &str) -> Result<(), String> {
+        run("notify-send", &[title, message], None).map(|_| ())
+    }
+
+    #[cfg(target_os = "macos")]
+    pub fn clipboard_set(text: &str) -> Result<(), String> {
+        run("pbcopy", &[], Some(text)).map(|_| ())
+    }
+
+    #[cfg(target_os = "macos")]
+    pub fn clipboard_get() -> Result<String, String> {
+        run("pbpaste", &[], None)
+    }
+
+    #[cfg(target_os = "macos")]
+    pub fn notify(title: &str, message: &str) -> Result<(), String> {
+        let quote = |s: &str| s.replace('\\', "\\\\").replace('"', "\\\"");
+        let script = format!("display notification \"{}\" with title \"{}\"", quote(message), quote(title));
+        run("osascript", &["-e", &script], None).map(|_| ())
+    }
+
+    #[cfg(windows)]
+    pub fn clipboard_set(text: &str) -> Result<(), String> {
+        run("clip", &[], Some(text)).map(|_| ())
+    }
+
+    #[cfg(windows)]
+    pub fn clipboard_get() -> Result<String, String> {
+        run("powershell", &["-NoProfile", "-Command", "Get-Clipboard"], None)
+            .map(|text| text.trim_end_matches(['\r', '\n']).to_string())
+    }
+
+    #[cfg(windows)]
+    pub fn notify(_title: &str, _message: &str) -> Result<(), String> {
+        Err("notifications aren't supported on Windows yet".to_string())
+    }
+}
+
+#[cfg(not(feature = "desktop"))]
+mod imp {
+    const NOT_BUILT: &str = "this goose was built without desktop support (rebuild with `cargo build --features desktop`)";
+
+    pub fn clipboard_set(_text: &str) -> Result<(), String> {
+        Err(NOT_BUILT.to_string())
+    }
+
+    pub fn clipboard_get() -> Result<String, String> {
+        Err(NOT_BUILT.to_string())
+    }
+
+    pub fn notify(_title: &str, _message: &str) -> Result<(), String> {
+        Err(NOT_BUILT.to_string())
+    }
+}
@@ -0,0 +1,122 @@
+// Structural diffs behind assert-eq and assert-ne
+// Walks lists, maps and structs side by side and names each place they differ
+
+use crate::property::describe;
+use crate::values::{sorted_entries, Value};
+
+/// Most differences listed before the rest are summarised as "and N more"
+const MAX_DIFFERENCES: usize = 10;
+
+/// Every place `actual` differs from `expected`, one line each, like
+/// `at [1].name: expected "pip", got "tern"`. Empty if they're equal.
+pub fn diff(expected: &Value, actual: &Value) -> Vec<String> {
+    let mut differences = Vec::new();
+    walk("", expected, actual, &mut differences);
+    if differences.len() > MAX_DIFFERENCES {
+        let hidden = differences.len() - MAX_DIFFERENCES;
+        differences.truncate(MAX_DIFFERENCES);
+        differences.push(format!("... and {} more", hidden));
+    }
+    differences
+}
+
+fn walk(path: &str, expected: &Value, actual: &Value, out: &mut Vec<String>) {
+    if expected == actual {
+        return;
+    }
+    let here = if path.is_empty() { "top level" } else { path };
+    match (expected, actual) {
+        (Value::List(a), Value::List(b)) => {
+            let (a, b) = (a.borrow(), b.borrow());
+            for (i, (x, y)) in a.iter().zip(b.iter()).enumerate() {
+                walk(&format!("{}[{}]", path, i), x, y, out);
+            }
+            for (i, missing) in a.iter().enumerate().skip(b.len()) {
+                out.push(format!("at {}[{}]: missing {}", path, i, describe(missing)));
+            }
+            for (i, extra) in b.iter().enumerate().skip(a.len()) {
+                out.push(format!("at {}[{}]: unexpected {}", path, i, describe(extra)));
+            }
+        }
+        (Value::Map(a), Value::Map(b)) => {
+            let (a, b) = (a.borrow(), b.borrow());
+            let (a_entries, b_entries) = (sorted_entries(&a), sorted_entries(&b));
+            for (key, x) in &a_entries {
+                let at = format!("{}[{}]", path, describe(key));
+                match b_entries.iter().find(|(k, _)| k == key) {
+                    Some((_, y)) => walk(&at, x, y, out),
+                    None => out.push(format!("at {}: missing {}", at, describe(x))),
+                }
+            }
+            for (key, y) in &b_entries {
+                if !a_entries.iter().any(|(k, _)| k == key) {
+                    out.push(format!("at {}[{}]: unexpected {}", path, describe(key), describe(y)));
+                }
+            }
+        }
+        (Value::Struct { name: n1, fields: f1 }, Value::Struct { name: n2, fields: f2 }) if n1 == n2 => {
+            let (f1, f2) = (f1.borrow(), f2.borrow());
+            let mut names: Vec<&String> = f1.keys().chain(f2.keys().filter(|k| !f1.contains_key(*k))).collect();
+            names.sort();
+            for name in names {
+                let at = format!("{}.{}", path, name);
+                match (f1.get(name), f2.get(name)) {
+                    (Some(x), Some(y)) => walk(&at, x, y, out),
+                    (Some(x), None) => out.push(format!("at {}: missing {}", at, describe(x))),
+                    (None, Some(y)) => out.push(format!("at {}: unexpected {}", at, describe(y))),
+                    (None, None) => {}
+                }
+            }
+        }
+        _ => out.push(format!(
+            "at {}: expected {}, got {}",
+            here,
+            describe(expected),
+            describe(actual)
+        )),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::collections::HashMap;
+
+    fn list(items: Vec<Value>) -> Value {
+        Value::new_list(items)
+    }
+
+    #[test]
+    fn test_equal_values_have_no_diff() {
+        let xs = list(vec![Value::Number(1.0), Value::String("a".into())]);
+        assert!(diff(&xs, &xs.deep_clone()).is_empty());
+    }
+
+    #[test]
+    fn test_list_and_struct_differences_are_located() {
+        let duck = |name: &str| {
+            let mut fields = HashMap::new();
+            fields.insert("name".to_string(), Value::String(name.into()));
+            Value::new_struct("Duck".into(), fields)
+        };
+        let expected = list(vec![Value::Number(1.0), duck("pip"), Value::Number(3.0)]);
+        let actual = list(vec![Value::Number(1.0), duck("tern")]);
+        assert_eq!(
+            diff(&expected, &actual),
+            vec!["at [1].name: expected \"pip\", got \"tern\"", "at [2]: missing 3"]
+        );
+        assert_eq!(
+            diff(&Value::Number(2.0), &Value::String("2".into())),
+            vec!["at top level: expected 2, got \"2\""]
+        );
+    }
+
+    #[test]
+    fn test_long_diffs_are_truncated() {
+        let expected = list((0..15).map(|n| Value::Number(n as f64)).collect());
+        let actual = list(vec![]);
+        let lines = diff(&expected, &actual);
+        assert_eq!(lines.len(), MAX_DIFFERENCES + 1);
+        assert_eq!(lines.last().unwrap(), "... and 5 more");
+    }
+}
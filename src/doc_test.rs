@@ -0,0 +1,169 @@
+// Doc tests - `goose test --doc` checks `-- expect:` comments against what
+// the code really does
+//
+// An expectation belongs to the top-level block it's written on or below:
+//
+//     quack [print 6 * 7]  -- expect: 42
+//     quack [list(1, 2)]
+//     -- expect: [1, 2]
+//
+// The file runs top to bottom, and each block's printed output (or, if it
+// printed nothing, the value of its expression) is compared with its
+// expectations, one `-- expect:` line per line of output. Time is virtual and
+// the desktop is off limits, as in `goose test`.
+
+use crate::capability::Policy;
+use crate::clock::VirtualClock;
+use crate::interpreter::Interpreter;
+use crate::lexer::{Token, TriviaKind};
+use crate::parser::Parser;
+use crate::test_runner::{never_ran, TestOutcome};
+use crate::values::Value;
+
+/// What starts an expectation comment
+pub const EXPECT: &str = "-- expect:";
+
+/// One block's expectations and how they went
+#[derive(Debug, Clone)]
+pub struct DocResult {
+    /// The line of the first `-- expect:` comment
+    pub line: usize,
+    /// The line of the block it belongs to
+    pub block_line: usize,
+    /// What the comments say, one line per comment
+    pub expected: String,
+    pub outcome: TestOutcome,
+}
+
+/// Every `-- expect:` comment in a file, with its line and the text after the colon
+pub fn expectations(tokens: &[Token]) -> Vec<(usize, String)> {
+    let mut found = Vec::new();
+    for token in tokens {
+        // The token's line comes after all of its trivia, so count back the line breaks
+        let mut breaks_after = token.leading_trivia.iter().filter(|t| t.kind == TriviaKind::Newline).count();
+        for trivia in &token.leading_trivia {
+            match trivia.kind {
+                TriviaKind::Newline => breaks_after -= 1,
+                TriviaKind::Comment => {
+                    if let Some(text) = trivia.text.strip_prefix(EXPECT) {
+                        found.push((token.line - breaks_after, text.trim().to_string()));
+                    }
+                }
+                TriviaKind::Whitespace => {}
+            }
+        }
+    }
+    found
+}
+
+/// Run a file and check its expectations, in order. Errors if the file
+/// doesn't parse or a block with no expectations fails, since the rest of
+/// the file can't be trusted after that.
+pub fn run_doc_tests(tokens: Vec<Token>, args: Vec<String>) -> Result<Vec<DocResult>, String> {
+    let expected = expectations(&tokens);
+    let blocks = Parser::new(tokens).parse().map_err(|errors| errors.join("\n"))?;
+    if let Some((line, _)) = expected.iter().find(|(line, _)| blocks.first().is_none_or(|block| *line < block.line)) {
+        return Err(format!("Line {}: This expectation comes before any code, so there's nothing to check", line));
+    }
+
+    let mut interpreter = Interpreter::with_args(args);
+    interpreter.set_clock(Box::new(VirtualClock::new()));
+    interpreter.set_policy(Policy::all().with_desktop(false));
+    interpreter.capture_output(true);
+
+    let mut results = Vec::new();
+    for (i, block) in blocks.iter().enumerate() {
+        let next_line = blocks.get(i + 1).map_or(usize::MAX, |next| next.line);
+        let mine: Vec<&(usize, String)> = expected.iter().filter(|(line, _)| (block.line..next_line).contains(line)).collect();
+        // Unquacked blocks don't run, and don't get a refusal either - the goose is just reading
+        let skipped_before = interpreter.stats().skipped_blocks.len();
+        let ran = if block.was_quacked { Some(interpreter.run_block(block.clone())) } else { None };
+        let refused = never_ran(&interpreter.stats().skipped_blocks[skipped_before..]);
+        let output = interpreter.take_output();
+        let Some(&&(line, _)) = mine.first() else {
+            if let Some(Err(e)) = ran {
+                return Err(format!("The doc tests stopped at line {}: {}", block.line, e));
+            }
+            continue;
+        };
+
+        let expected = mine.iter().map(|(_, text)| text.as_str()).collect::<Vec<_>>().join("\n");
+        let outcome = match (ran, refused) {
+            (None, _) => TestOutcome::Skipped,
+            (Some(Err(e)), _) => TestOutcome::Failed(e),
+            // A nested block missing its quack didn't run, so its output can't count
+            (Some(Ok(_)), Err(refusal)) => TestOutcome::Failed(refusal),
+            (Some(Ok(value)), Ok(())) => {
+                let actual = match value {
+                    _ if !output.is_empty() => output.lines().map(str::trim_end).collect::<Vec<_>>().join("\n"),
+                    Some(Value::Null) | None => String::new(),
+                    Some(value) => value.to_string(),
+                };
+                if actual == expected {
+                    TestOutcome::Passed
+                } else {
+                    TestOutcome::Failed(format!("expected {}, got {}", show(&expected), show(&actual)))
+                }
+            }
+        };
+        results.push(DocResult { line, block_line: block.line, expected, outcome });
+    }
+    Ok(results)
+}
+
+/// Output for a failure message: one line as is, several quoted with their line breaks shown
+fn show(text: &str) -> String {
+    if text.is_empty() {
+        "nothing".to_string()
+    } else if text.contains('\n') {
+        format!("{:?}", text)
+    } else {
+        text.to_string()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::lexer::lex;
+
+    fn run(source: &str) -> Result<Vec<DocResult>, String> {
+        run_doc_tests(lex(source).unwrap(), vec![])
+    }
+
+    #[test]
+    fn test_expectations_check_output_and_values() {
+        let results = run(concat!(
+            "-- A worked example\n",
+            "quack [let ducks be list(\"pip\", \"tern\")]\n",
+            "quack [print len(ducks)]  -- expect: 2\n",
+            "quack [ducks]\n",
+            "-- expect: [\"pip\", \"tern\"]\n",
+            "quack [for each [d] in ducks do\n",
+            "    quack [print d]\n",
+            "]\n",
+            "-- expect: pip\n",
+            "-- expect: tern\n",
+            "quack [print 1 + 1]  -- expect: 3\n",
+            "[print \"skipped\"]  -- expect: skipped\n",
+        ))
+        .unwrap();
+        let lines: Vec<usize> = results.iter().map(|result| result.line).collect();
+        assert_eq!(lines, [3, 5, 9, 11, 12]);
+        assert_eq!(results[2].expected, "pip\ntern");
+        let outcomes: Vec<&TestOutcome> = results.iter().map(|result| &result.outcome).collect();
+        assert_eq!(outcomes[..3], [&TestOutcome::Passed, &TestOutcome::Passed, &TestOutcome::Passed]);
+        assert_eq!(outcomes[3], &TestOutcome::Failed("expected 3, got 2".to_string()));
+        assert_eq!(outcomes[4], &TestOutcome::Skipped);
+
+        let refused = run("quack [if true then\n    [print 1]\n]  -- expect: 1").unwrap();
+        assert_eq!(refused[0].outcome, TestOutcome::Failed("Never ran (no quack): line(s) 2".to_string()));
+    }
+
+    #[test]
+    fn test_errors_without_expectations_stop_the_run() {
+        let error = run("quack [print 1]\nquack [honk false]\nquack [print 2]  -- expect: 2").unwrap_err();
+        assert!(error.starts_with("The doc tests stopped at line 2"), "{}", error);
+        assert!(run("-- expect: 1\nquack [print 1]").unwrap_err().contains("before any code"));
+    }
+}
@@ -0,0 +1,109 @@
+// Format specs behind format() and `{x:.2}` in f-strings
+//
+// A spec is an optional `0` (pad numbers with zeros), a minimum width, and an
+// optional `.` with a number of decimal places: `.2`, `8`, `08.2`. Numbers are
+// right-aligned in their width and everything else left-aligned, like a table.
+
+use crate::builtins;
+use crate::decimal::MAX_SCALE;
+use crate::values::Value;
+
+/// The widest a spec can ask for, so a typo can't fill memory with spaces
+pub const MAX_WIDTH: usize = 1000;
+
+/// `width` if it's within MAX_WIDTH. Specs, padding and repeat-string all
+/// stop at the same place.
+pub fn check_width(width: usize) -> Result<usize, String> {
+    if width > MAX_WIDTH {
+        return Err(format!("A width of {} is a bit much - the goose stops at {}", width, MAX_WIDTH));
+    }
+    Ok(width)
+}
+
+/// How to show a value: zero-padding, minimum width, and decimal places
+#[derive(Debug, Clone, Copy, PartialEq, Default)]
+pub struct FormatSpec {
+    pub zero_pad: bool,
+    pub width: usize,
+    pub precision: Option<usize>,
+}
+
+impl FormatSpec {
+    pub fn parse(spec: &str) -> Result<FormatSpec, String> {
+        let invalid = || format!("'{}' isn't a format the goose understands - try something like \".2\" or \"08.2\"", spec);
+        let (width, precision) = match spec.split_once('.') {
+            Some((width, places)) => (width, Some(places)),
+            None => (spec, None),
+        };
+        let number = |digits: &str| {
+            if digits.is_empty() || !digits.bytes().all(|b| b.is_ascii_digit()) {
+                Err(invalid())
+            } else {
+                digits.parse::<usize>().map_err(|_| invalid())
+            }
+        };
+
+        let zero_pad = width.len() > 1 && width.starts_with('0');
+        let width = if width.is_empty() { 0 } else { check_width(number(width)?)? };
+        let precision = precision.map(number).transpose()?;
+        if precision.is_some_and(|places| places > MAX_SCALE as usize) {
+            return Err(format!("The goose can show at most {} decimal places", MAX_SCALE));
+        }
+        Ok(FormatSpec { zero_pad, width, precision })
+    }
+
+    pub fn apply(&self, value: &Value) -> Result<String, String> {
+        let numeric = matches!(value, Value::Number(_) | Value::Decimal(_) | Value::Fraction(_));
+        let text = match (self.precision, value) {
+            (Some(places), _) if numeric => {
+                match builtins::call_builtin("to-fixed", vec![value.clone(), Value::Number(places as f64)])? {
+                    Value::String(text) => text.to_string(),
+                    other => other.to_string(),
+                }
+            }
+            (Some(_), other) => return Err(format!("Only numbers have decimal places to round, not {}", other.type_name())),
+            (None, Value::String(s)) => s.to_string(),
+            (None, other) => other.to_string(),
+        };
+
+        let padding = self.width.saturating_sub(text.chars().count());
+        Ok(if !numeric {
+            text + &" ".repeat(padding)
+        } else if self.zero_pad {
+            // Zeros go after the sign: -0042, not 00-42
+            let (sign, digits) = text.split_at(if text.starts_with('-') { 1 } else { 0 });
+            format!("{}{}{}", sign, "0".repeat(padding), digits)
+        } else {
+            " ".repeat(padding) + &text
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn format(value: Value, spec: &str) -> Result<String, String> {
+        FormatSpec::parse(spec)?.apply(&value)
+    }
+
+    #[test]
+    fn test_places_width_and_zeros() {
+        assert_eq!(format(Value::Number(10.0 / 3.0), ".2").unwrap(), "3.33");
+        assert_eq!(format(Value::Number(2.5), "8.3").unwrap(), "   2.500");
+        assert_eq!(format(Value::Number(-4.2), "06.1").unwrap(), "-004.2");
+        assert_eq!(format(Value::Number(7.0), "03").unwrap(), "007");
+        assert_eq!(format(Value::String("pip".into()), "5").unwrap(), "pip  ");
+        assert_eq!(format(Value::Number(12345.0), "3").unwrap(), "12345");
+        assert_eq!(FormatSpec::parse("").unwrap(), FormatSpec::default());
+    }
+
+    #[test]
+    fn test_bad_specs_are_errors() {
+        assert!(FormatSpec::parse("x").is_err());
+        assert!(FormatSpec::parse(".").is_err());
+        assert!(FormatSpec::parse("2.2.2").is_err());
+        assert!(FormatSpec::parse("99999").unwrap_err().contains("stops at 1000"));
+        assert!(format(Value::String("pip".into()), ".2").unwrap_err().contains("not string"));
+    }
+}
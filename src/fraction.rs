@@ -0,0 +1,217 @@
+// Exact fractions behind `fraction(...)`
+// 1/3 stays 1/3 instead of 0.333..., and results are always in lowest terms
+
+use std::cmp::Ordering;
+use std::fmt;
+
+use crate::decimal::Decimal;
+
+const TOO_BIG: &str = "that's too big for a fraction";
+
+/// A fraction in lowest terms, with the sign on the numerator
+#[derive(Debug, Clone, Copy)]
+pub struct Fraction {
+    numerator: i128,
+    denominator: i128,
+}
+
+fn gcd(mut a: i128, mut b: i128) -> i128 {
+    while b != 0 {
+        (a, b) = (b, a % b);
+    }
+    // Only gcd(MIN, MIN) doesn't fit; leaving that unsimplified is harmless
+    a.checked_abs().unwrap_or(1)
+}
+
+impl Fraction {
+    /// numerator / denominator, simplified: new(2, -4) is -1/2
+    pub fn new(numerator: i128, denominator: i128) -> Result<Fraction, String> {
+        if denominator == 0 {
+            return Err("a fraction can't have 0 on the bottom".to_string());
+        }
+        let divisor = gcd(numerator, denominator);
+        let (mut numerator, mut denominator) = (numerator / divisor, denominator / divisor);
+        if denominator < 0 {
+            numerator = numerator.checked_neg().ok_or(TOO_BIG)?;
+            denominator = denominator.checked_neg().ok_or(TOO_BIG)?;
+        }
+        Ok(Fraction { numerator, denominator })
+    }
+
+    /// The exact fraction a decimal stands for: 0.75 is 3/4
+    pub fn from_decimal(d: Decimal) -> Result<Fraction, String> {
+        let (units, scale) = d.parts();
+        Fraction::new(units, 10i128.checked_pow(scale).ok_or(TOO_BIG)?)
+    }
+
+    /// The fraction a number looks like when printed: 0.1 is 1/10
+    pub fn from_f64(n: f64) -> Result<Fraction, String> {
+        Fraction::from_decimal(Decimal::from_f64(n)?)
+    }
+
+    pub fn numerator(self) -> i128 {
+        self.numerator
+    }
+
+    pub fn denominator(self) -> i128 {
+        self.denominator
+    }
+
+    /// The nearest ordinary number
+    pub fn to_f64(self) -> f64 {
+        self.numerator as f64 / self.denominator as f64
+    }
+
+    /// Rounded (halves away from zero) to a decimal with `places` digits after the point
+    pub fn to_decimal(self, places: u32) -> Result<Decimal, String> {
+        let scaled = 10i128
+            .checked_pow(places)
+            .and_then(|factor| self.numerator.checked_mul(factor))
+            .ok_or(TOO_BIG)?;
+        let (quotient, remainder) = (scaled / self.denominator, scaled % self.denominator);
+        let units = if remainder.unsigned_abs() * 2 >= self.denominator.unsigned_abs() {
+            quotient + scaled.signum()
+        } else {
+            quotient
+        };
+        Decimal::from_parts(units, places)
+    }
+
+    pub fn is_zero(self) -> bool {
+        self.numerator == 0
+    }
+
+    pub fn checked_add(self, other: Fraction) -> Result<Fraction, String> {
+        let numerator = self
+            .numerator
+            .checked_mul(other.denominator)
+            .zip(other.numerator.checked_mul(self.denominator))
+            .and_then(|(a, b)| a.checked_add(b))
+            .ok_or(TOO_BIG)?;
+        Fraction::new(numerator, self.denominator.checked_mul(other.denominator).ok_or(TOO_BIG)?)
+    }
+
+    /// -self; only fails for a numerator of i128::MIN, which has no positive twin
+    pub fn checked_neg(self) -> Result<Fraction, String> {
+        let numerator = self.numerator.checked_neg().ok_or(TOO_BIG)?;
+        Ok(Fraction { numerator, denominator: self.denominator })
+    }
+
+    pub fn checked_sub(self, other: Fraction) -> Result<Fraction, String> {
+        self.checked_add(other.checked_neg()?)
+    }
+
+    pub fn checked_mul(self, other: Fraction) -> Result<Fraction, String> {
+        // Cancel across first so the products stay small
+        let (a, b) = (gcd(self.numerator, other.denominator).max(1), gcd(other.numerator, self.denominator).max(1));
+        let numerator = (self.numerator / a).checked_mul(other.numerator / b).ok_or(TOO_BIG)?;
+        let denominator = (self.denominator / b).checked_mul(other.denominator / a).ok_or(TOO_BIG)?;
+        Fraction::new(numerator, denominator)
+    }
+
+    pub fn checked_div(self, other: Fraction) -> Result<Fraction, String> {
+        if other.is_zero() {
+            return Err("can't divide by zero".to_string());
+        }
+        self.checked_mul(Fraction { numerator: other.denominator, denominator: other.numerator })
+    }
+
+    /// Remainder with the sign of the divisor, like `%` on numbers
+    pub fn checked_rem(self, other: Fraction) -> Result<Fraction, String> {
+        let quotient = self.checked_div(other)?;
+        let floor = quotient.numerator.div_euclid(quotient.denominator);
+        self.checked_sub(other.checked_mul(Fraction { numerator: floor, denominator: 1 })?)
+    }
+
+    /// Raise to a whole-number power
+    pub fn pow(self, exponent: f64) -> Result<Fraction, String> {
+        if exponent.fract() != 0.0 || exponent.abs() > u32::MAX as f64 {
+            return Err(format!("fractions can only be raised to whole-number powers, not {}", exponent));
+        }
+        let power = exponent.abs() as u32;
+        let numerator = self.numerator.checked_pow(power).ok_or(TOO_BIG)?;
+        let denominator = self.denominator.checked_pow(power).ok_or(TOO_BIG)?;
+        if exponent < 0.0 {
+            Fraction::new(denominator, numerator)
+        } else {
+            Fraction::new(numerator, denominator)
+        }
+    }
+}
+
+impl PartialEq for Fraction {
+    fn eq(&self, other: &Self) -> bool {
+        self.cmp(other) == Ordering::Equal
+    }
+}
+
+impl Eq for Fraction {}
+
+impl PartialOrd for Fraction {
+    fn partial_cmp(&self, other: &Self) -> Option<Ordering> {
+        Some(self.cmp(other))
+    }
+}
+
+impl Ord for Fraction {
+    fn cmp(&self, other: &Self) -> Ordering {
+        // Denominators are positive, so cross-multiplying keeps the order
+        match (
+            self.numerator.checked_mul(other.denominator),
+            other.numerator.checked_mul(self.denominator),
+        ) {
+            (Some(a), Some(b)) => a.cmp(&b),
+            _ => self.to_f64().total_cmp(&other.to_f64()),
+        }
+    }
+}
+
+impl fmt::Display for Fraction {
+    /// 1/3, -5/2, or just 2 for a whole number
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        if self.denominator == 1 {
+            write!(f, "{}", self.numerator)
+        } else {
+            write!(f, "{}/{}", self.numerator, self.denominator)
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn f(numerator: i128, denominator: i128) -> Fraction {
+        Fraction::new(numerator, denominator).unwrap()
+    }
+
+    #[test]
+    fn test_simplifies_and_prints() {
+        assert_eq!(f(2, 4).to_string(), "1/2");
+        assert_eq!(f(3, -6).to_string(), "-1/2");
+        assert_eq!(f(4, 2).to_string(), "2");
+        assert!(Fraction::new(1, 0).is_err());
+        assert_eq!(Fraction::from_f64(0.75).unwrap(), f(3, 4));
+    }
+
+    #[test]
+    fn test_arithmetic_is_exact() {
+        assert_eq!(f(1, 3).checked_add(f(1, 6)).unwrap(), f(1, 2));
+        assert_eq!(f(1, 2).checked_sub(f(3, 4)).unwrap(), f(-1, 4));
+        assert_eq!(f(2, 3).checked_mul(f(9, 4)).unwrap(), f(3, 2));
+        assert_eq!(f(1, 2).checked_div(f(1, 4)).unwrap(), f(2, 1));
+        assert_eq!(f(-7, 1).checked_rem(f(3, 1)).unwrap(), f(2, 1));
+        assert_eq!(f(2, 3).pow(-2.0).unwrap(), f(9, 4));
+        assert!(f(1, 2).checked_div(f(0, 1)).is_err());
+        assert!(f(1, 3) < f(1, 2));
+        assert_eq!(f(2, 3).to_decimal(3).unwrap().to_string(), "0.667");
+    }
+
+    #[test]
+    fn test_negating_the_smallest_numerator_is_an_error() {
+        let smallest = f(i128::MIN, 1);
+        assert!(smallest.checked_neg().is_err());
+        assert!(f(0, 1).checked_sub(smallest).is_err());
+        assert_eq!(f(1, 2).checked_neg().unwrap(), f(-1, 2));
+    }
+}
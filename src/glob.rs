@@ -0,0 +1,169 @@
+// Glob patterns behind `glob` and `path-matches`
+// `*` and `?` stay inside one folder, `**` crosses any number, `[abc]` picks a character
+
+use std::fs;
+use std::path::Path;
+
+/// Characters that make a path segment a pattern rather than a plain name
+fn is_wild(segment: &str) -> bool {
+    segment.contains(['*', '?', '['])
+}
+
+/// Split a path or pattern into its segments, accepting either separator
+fn segments(path: &str) -> Vec<&str> {
+    path.split(['/', '\\']).filter(|s| !s.is_empty() && *s != ".").collect()
+}
+
+/// Does the whole path match the pattern?
+pub fn matches(path: &str, pattern: &str) -> bool {
+    match_segments(&segments(path), &segments(pattern))
+}
+
+fn match_segments(path: &[&str], pattern: &[&str]) -> bool {
+    match pattern.split_first() {
+        None => path.is_empty(),
+        Some((&"**", rest)) => {
+            // Zero folders, or swallow one and try again
+            match_segments(path, rest)
+                || path.split_first().is_some_and(|(first, remaining)| {
+                    !first.starts_with('.') && match_segments(remaining, pattern)
+                })
+        }
+        Some((segment, rest)) => path
+            .split_first()
+            .is_some_and(|(first, remaining)| match_segment(first, segment) && match_segments(remaining, rest)),
+    }
+}
+
+/// Match one file or folder name. Wildcards don't match a leading `.`, so
+/// hidden files only turn up when the pattern asks for them.
+fn match_segment(name: &str, pattern: &str) -> bool {
+    if name.starts_with('.') && !pattern.starts_with('.') {
+        return false;
+    }
+    let name: Vec<char> = name.chars().collect();
+    let pattern: Vec<char> = pattern.chars().collect();
+    match_chars(&name, &pattern)
+}
+
+fn match_chars(name: &[char], pattern: &[char]) -> bool {
+    match pattern.first() {
+        None => name.is_empty(),
+        Some('*') => (0..=name.len()).any(|skip| match_chars(&name[skip..], &pattern[1..])),
+        Some('?') => !name.is_empty() && match_chars(&name[1..], &pattern[1..]),
+        Some('[') => match (name.first(), parse_class(&pattern[1..])) {
+            (Some(c), Some((matches_class, used))) => {
+                matches_class(*c) && match_chars(&name[1..], &pattern[1 + used..])
+            }
+            // An unclosed `[` is just a bracket
+            (Some('['), None) => match_chars(&name[1..], &pattern[1..]),
+            _ => false,
+        },
+        Some(c) => name.first() == Some(c) && match_chars(&name[1..], &pattern[1..]),
+    }
+}
+
+/// Parse a `[...]` class (after the `[`), returning a matcher and how many
+/// pattern characters it used, including the closing `]`
+fn parse_class(pattern: &[char]) -> Option<(impl Fn(char) -> bool, usize)> {
+    let negated = matches!(pattern.first(), Some('!') | Some('^'));
+    let start = usize::from(negated);
+    // A `]` right at the start is a literal
+    let close = pattern.iter().skip(start + 1).position(|c| *c == ']')? + start + 1;
+    let members = &pattern[start..close];
+
+    let mut ranges = Vec::new();
+    let mut i = 0;
+    while i < members.len() {
+        if i + 2 < members.len() && members[i + 1] == '-' {
+            ranges.push((members[i], members[i + 2]));
+            i += 3;
+        } else {
+            ranges.push((members[i], members[i]));
+            i += 1;
+        }
+    }
+    let matcher = move |c: char| ranges.iter().any(|(lo, hi)| (*lo..=*hi).contains(&c)) != negated;
+    Some((matcher, close + 1))
+}
+
+/// Every file and folder under the current directory matching the pattern,
+/// as relative paths using `/`, sorted
+pub fn find(pattern: &str) -> Result<Vec<String>, String> {
+    let pattern_segments = segments(pattern);
+    // Start from the folder named by the pattern's plain (wildcard-free) prefix
+    let fixed = pattern_segments.iter().take_while(|s| !is_wild(s)).count();
+    let base: Vec<&str> = pattern_segments[..fixed].to_vec();
+    let max_depth = if pattern_segments.contains(&"**") {
+        usize::MAX
+    } else {
+        pattern_segments.len() - fixed
+    };
+
+    let mut found = Vec::new();
+    let base_path = if base.is_empty() { ".".to_string() } else { base.join("/") };
+    if fixed == pattern_segments.len() {
+        // No wildcards at all: it either exists or it doesn't
+        if Path::new(&base_path).exists() && !pattern_segments.is_empty() {
+            found.push(base_path);
+        }
+        return Ok(found);
+    }
+    walk(Path::new(&base_path), &base, max_depth, &pattern_segments, &mut found);
+    found.sort();
+    Ok(found)
+}
+
+fn walk(dir: &Path, prefix: &[&str], depth_left: usize, pattern: &[&str], found: &mut Vec<String>) {
+    if depth_left == 0 {
+        return;
+    }
+    let Ok(entries) = fs::read_dir(dir) else { return };
+    for entry in entries.flatten() {
+        let name = entry.file_name().to_string_lossy().into_owned();
+        let mut path: Vec<&str> = prefix.to_vec();
+        path.push(&name);
+        if match_segments(&path, pattern) {
+            found.push(path.join("/"));
+        }
+        // Don't follow symlinked folders, so a link loop can't trap the goose
+        let is_dir = entry.file_type().map(|t| t.is_dir()).unwrap_or(false);
+        if is_dir {
+            walk(&entry.path(), &path, depth_left - 1, pattern, found);
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_wildcards_within_a_folder() {
+        assert!(matches("data/sales.csv", "data/*.csv"));
+        assert!(!matches("data/2024/sales.csv", "data/*.csv"));
+        assert!(matches("notes1.txt", "notes?.txt"));
+        assert!(matches("b.txt", "[abc].txt"));
+        assert!(!matches("d.txt", "[a-c].txt"));
+        assert!(matches("d.txt", "[!a-c].txt"));
+        assert!(!matches(".hidden.csv", "*.csv"));
+        assert!(matches(".hidden.csv", ".*.csv"));
+    }
+
+    #[test]
+    fn test_double_star_crosses_folders() {
+        assert!(matches("data/2024/q1/sales.csv", "data/**/*.csv"));
+        assert!(matches("data/sales.csv", "data/**/*.csv"));
+        assert!(matches("a\\b\\c.txt", "**/c.txt"));
+        assert!(!matches("data/sales.txt", "data/**/*.csv"));
+    }
+
+    #[test]
+    fn test_find_walks_the_filesystem() {
+        let found = find("src/*.rs").unwrap();
+        assert!(found.contains(&"src/glob.rs".to_string()));
+        assert!(found.windows(2).all(|w| w[0] <= w[1]));
+        assert_eq!(find("Cargo.toml").unwrap(), vec!["Cargo.toml".to_string()]);
+        assert!(find("no-such-folder/*.duck").unwrap().is_empty());
+    }
+}
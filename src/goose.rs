@@ -1,5 +1,7 @@
 // Goose personality module - snarky interpreter messages
 
+use serde::Serialize;
+use std::fmt::Display;
 use std::time::{SystemTime, UNIX_EPOCH};
 
 /// Simple pseudo-random number generator using time-based seed
@@ -19,7 +21,40 @@ fn choose<T>(items: &[T]) -> &T {
     &items[idx]
 }
 
-#[derive(Debug, Clone, Default)]
+/// Fill in a message's `{name}` placeholders. The message tables are static
+/// and only the one message chosen gets formatted, so a busy run doesn't build
+/// twenty strings for every skipped block or error.
+fn fill(template: &str, values: &[(&str, &dyn Display)]) -> String {
+    let mut message = String::with_capacity(template.len() + 16);
+    let mut rest = template;
+    // One pass, so a value that happens to contain `{line}` stays as it is
+    while let Some(start) = rest.find('{') {
+        message.push_str(&rest[..start]);
+        let after = &rest[start + 1..];
+        let value = after
+            .find('}')
+            .and_then(|end| values.iter().find(|(name, _)| *name == &after[..end]).map(|found| (end, found.1)));
+        match value {
+            Some((end, value)) => {
+                message.push_str(&value.to_string());
+                rest = &after[end + 1..];
+            }
+            None => {
+                message.push('{');
+                rest = after;
+            }
+        }
+    }
+    message.push_str(rest);
+    message
+}
+
+/// Choose a message template and fill it in
+fn pick(templates: &[&str], values: &[(&str, &dyn Display)]) -> String {
+    fill(choose::<&str>(templates), values)
+}
+
+#[derive(Debug, Clone, Default, Serialize)]
 pub struct ExecutionStats {
     pub total_blocks: usize,
     pub quacked_blocks: usize,
@@ -27,6 +62,19 @@ pub struct ExecutionStats {
     pub functions_defined: usize,
     pub structs_defined: usize,
     pub loops_executed: usize,
+    /// Source lines of blocks the goose refused to run (no quack)
+    pub skipped_blocks: Vec<usize>,
+    /// Bonuses and penalties the program awarded itself (goose-bonus / goose-penalty)
+    pub rating_adjustments: Vec<RatingAdjustment>,
+}
+
+/// A script-requested change to the final rating
+#[derive(Debug, Clone, PartialEq, Serialize)]
+pub struct RatingAdjustment {
+    /// Positive for a bonus, negative for a penalty
+    pub points: f64,
+    pub reason: String,
+    pub line: usize,
 }
 
 #[derive(Debug, Clone)]
@@ -40,173 +88,212 @@ pub enum ErrorKind {
     ArgumentMismatch { expected: usize, got: usize },
     SyntaxError(String),
     InvalidOperation(String),
+    /// Strict math caught a calculation producing infinity or NaN
+    BadNumber { operation: String, result: String },
+    /// `becomes` (or another `let`) on a name declared with `always`
+    ConstantReassignment(String),
+    /// `let` or `define` of a name that already means a builtin
+    BuiltinName(String),
 }
 
 /// Generate a refusal message for unquacked blocks
 pub fn refusal(line: usize, _block_preview: &str) -> String {
-    let messages = [
-        format!("I see a block on line {}, but I didn't hear a quack. I'm not doing that.", line),
-        format!("Line {}: No quack? No work. I'm a goose, not a volunteer.", line),
-        format!("Quackless block detected on line {}. I'm going to pretend I didn't see that.", line),
-        format!("Line {}: *stares at unquacked block* *walks away*", line),
-        format!("Did you forget something on line {}? Rhymes with 'wack'. Starts with 'qu'.", line),
-        format!("Line {}: I require the ancient ritual of the quack. This block has not been blessed.", line),
-        format!("Error on line {}: QUACK_NOT_FOUND. Please insert quack and try again.", line),
-        format!("Line {}: I'm a goose of principle. No quack, no execution.", line),
-        format!("Skipping line {}. The council of geese has not approved this block.", line),
-        format!("Line {}: The audacity of an unquacked block. Truly remarkable.", line),
-        format!("I could execute line {}... but I won't. You know why.", line),
-        format!("Line {}: Quack status: missing. Execution status: denied.", line),
-        format!("Line {} tried to sneak by without a quack. Nice try.", line),
-        format!("Honk honk! Line {} is missing something important. Think about it.", line),
-        format!("Line {}: This block is naked without its quack. I can't look at it.", line),
-        format!("Line {}: *taps webbed foot impatiently* Where. Is. The. Quack?", line),
-        format!("Line {}: You expect me to execute this? Without a quack? The NERVE.", line),
-        format!("Line {}: In the great book of geese, it is written: no quack, no stack.", line),
-        format!("Line {}: I'm not angry about the missing quack. Just disappointed.", line),
-        format!("Line {}: This block whispered 'execute me' but I only listen to quacks.", line),
+    static MESSAGES: [&str; 20] = [
+        "I see a block on line {line}, but I didn't hear a quack. I'm not doing that.",
+        "Line {line}: No quack? No work. I'm a goose, not a volunteer.",
+        "Quackless block detected on line {line}. I'm going to pretend I didn't see that.",
+        "Line {line}: *stares at unquacked block* *walks away*",
+        "Did you forget something on line {line}? Rhymes with 'wack'. Starts with 'qu'.",
+        "Line {line}: I require the ancient ritual of the quack. This block has not been blessed.",
+        "Error on line {line}: QUACK_NOT_FOUND. Please insert quack and try again.",
+        "Line {line}: I'm a goose of principle. No quack, no execution.",
+        "Skipping line {line}. The council of geese has not approved this block.",
+        "Line {line}: The audacity of an unquacked block. Truly remarkable.",
+        "I could execute line {line}... but I won't. You know why.",
+        "Line {line}: Quack status: missing. Execution status: denied.",
+        "Line {line} tried to sneak by without a quack. Nice try.",
+        "Honk honk! Line {line} is missing something important. Think about it.",
+        "Line {line}: This block is naked without its quack. I can't look at it.",
+        "Line {line}: *taps webbed foot impatiently* Where. Is. The. Quack?",
+        "Line {line}: You expect me to execute this? Without a quack? The NERVE.",
+        "Line {line}: In the great book of geese, it is written: no quack, no stack.",
+        "Line {line}: I'm not angry about the missing quack. Just disappointed.",
+        "Line {line}: This block whispered 'execute me' but I only listen to quacks.",
     ];
 
-    choose(&messages).clone()
+    pick(&MESSAGES, &[("line", &line)])
 }
 
 /// Generate an error message based on error kind
 pub fn error(kind: ErrorKind, line: usize, details: &str) -> String {
     match kind {
         ErrorKind::TypeError { expected, got } => {
-            let messages = [
-                format!("Line {}: You gave me a {} but I wanted a {}. I'm a goose, not a wizard.", line, got, expected),
-                format!("Line {}: A {}? I asked for a {}! We're not playing 'guess the type' here.", line, got, expected),
-                format!("Type error on line {}: Expected {}, got {}. This isn't a duck, it's a type error.", line, expected, got),
-                format!("Line {}: I ordered a {}, but the kitchen sent out a {}. Unacceptable.", line, expected, got),
-                format!("Line {}: {} and {} are not the same thing. I learned that. Why haven't you?", line, expected, got),
-                format!("Line {}: *honks in disappointment* That's a {}, not a {}.", line, got, expected),
-                format!("Line {}: You're trying to fit a {} into a {}-shaped hole. Classic human error.", line, got, expected),
-                format!("Line {}: The prophecy spoke of a {}, but you brought a {}. The ritual is ruined.", line, expected, got),
-                format!("Line {}: {} !== {}. This is not a philosophical debate.", line, got, expected),
-                format!("Line {}: Wanted: {}. Got: {}. My disappointment is immeasurable.", line, expected, got),
+            static MESSAGES: [&str; 10] = [
+                "Line {line}: You gave me a {got} but I wanted a {expected}. I'm a goose, not a wizard.",
+                "Line {line}: A {got}? I asked for a {expected}! We're not playing 'guess the type' here.",
+                "Type error on line {line}: Expected {expected}, got {got}. This isn't a duck, it's a type error.",
+                "Line {line}: I ordered a {expected}, but the kitchen sent out a {got}. Unacceptable.",
+                "Line {line}: {expected} and {got} are not the same thing. I learned that. Why haven't you?",
+                "Line {line}: *honks in disappointment* That's a {got}, not a {expected}.",
+                "Line {line}: You're trying to fit a {got} into a {expected}-shaped hole. Classic human error.",
+                "Line {line}: The prophecy spoke of a {expected}, but you brought a {got}. The ritual is ruined.",
+                "Line {line}: {got} !== {expected}. This is not a philosophical debate.",
+                "Line {line}: Wanted: {expected}. Got: {got}. My disappointment is immeasurable.",
             ];
-            choose(&messages).clone()
+            pick(&MESSAGES, &[("line", &line), ("got", &got), ("expected", &expected)])
         }
 
         ErrorKind::UnknownVariable(name) => {
-            let messages = [
-                format!("Line {}: What is '{}'? I've never heard of it. Did you make that up?", line, name),
-                format!("Line {}: '{}' doesn't exist. I checked everywhere. Even behind the pond.", line, name),
-                format!("Unknown variable '{}' on line {}. Is this a test? Did you think I wouldn't notice?", name, line),
-                format!("Line {}: '{}' is not a thing. Stop trying to make '{}' happen.", line, name, name),
-                format!("Line {}: I searched my entire memory for '{}'. Nothing. Nada. Just cobwebs.", line, name),
-                format!("Line {}: '{}' sounds made up. Because it is. Because you never defined it.", line, name),
-                format!("Line {}: Who is '{}'? I don't know her.", line, name),
-                format!("Line {}: *squints at '{}'* ...is this some kind of inside joke I'm not part of?", line, name),
-                format!("Line {}: The variable '{}' is like my patience: nonexistent.", line, name),
-                format!("Line {}: '{}' has gone missing. Or was it ever here? An existential crisis.", line, name),
+            static MESSAGES: [&str; 10] = [
+                "Line {line}: What is '{name}'? I've never heard of it. Did you make that up?",
+                "Line {line}: '{name}' doesn't exist. I checked everywhere. Even behind the pond.",
+                "Unknown variable '{name}' on line {line}. Is this a test? Did you think I wouldn't notice?",
+                "Line {line}: '{name}' is not a thing. Stop trying to make '{name}' happen.",
+                "Line {line}: I searched my entire memory for '{name}'. Nothing. Nada. Just cobwebs.",
+                "Line {line}: '{name}' sounds made up. Because it is. Because you never defined it.",
+                "Line {line}: Who is '{name}'? I don't know her.",
+                "Line {line}: *squints at '{name}'* ...is this some kind of inside joke I'm not part of?",
+                "Line {line}: The variable '{name}' is like my patience: nonexistent.",
+                "Line {line}: '{name}' has gone missing. Or was it ever here? An existential crisis.",
             ];
-            choose(&messages).clone()
+            pick(&MESSAGES, &[("line", &line), ("name", &name)])
         }
 
         ErrorKind::UnknownFunction(name) => {
-            let messages = [
-                format!("Line {}: Function '{}' not found. Did you forget to define it, or is this wishful thinking?", line, name),
-                format!("Line {}: '{}'? Never heard of her. This function doesn't exist.", line, name),
-                format!("Line {}: Calling '{}' is like calling a number that's been disconnected.", line, name),
-                format!("Line {}: The function '{}' is a myth. A legend. And definitely not defined.", line, name),
-                format!("Line {}: I would love to call '{}', but it's not picking up. Because it doesn't exist.", line, name),
-                format!("Line {}: '{}' is not a function. It's a cry for help.", line, name),
-                format!("Line {}: *looks for '{}'* *finds nothing* *honks sadly*", line, name),
-                format!("Line {}: You called '{}' but nobody answered. Define your functions, friend.", line, name),
-                format!("Line {}: 404 Function Not Found: '{}'. Please check your imagination.", line, name),
-                format!("Line {}: '{}' would be a great function. If only someone would write it.", line, name),
+            static MESSAGES: [&str; 10] = [
+                "Line {line}: Function '{name}' not found. Did you forget to define it, or is this wishful thinking?",
+                "Line {line}: '{name}'? Never heard of her. This function doesn't exist.",
+                "Line {line}: Calling '{name}' is like calling a number that's been disconnected.",
+                "Line {line}: The function '{name}' is a myth. A legend. And definitely not defined.",
+                "Line {line}: I would love to call '{name}', but it's not picking up. Because it doesn't exist.",
+                "Line {line}: '{name}' is not a function. It's a cry for help.",
+                "Line {line}: *looks for '{name}'* *finds nothing* *honks sadly*",
+                "Line {line}: You called '{name}' but nobody answered. Define your functions, friend.",
+                "Line {line}: 404 Function Not Found: '{name}'. Please check your imagination.",
+                "Line {line}: '{name}' would be a great function. If only someone would write it.",
             ];
-            choose(&messages).clone()
+            pick(&MESSAGES, &[("line", &line), ("name", &name)])
         }
 
         ErrorKind::DivisionByZero => {
-            let messages = [
-                format!("Line {}: You want me to divide by zero? I'm not falling for that.", line),
-                format!("Line {}: Division by zero detected. I may be a goose, but I'm not stupid.", line),
-                format!("Line {}: Ah, the classic divide by zero trick. The answer is HONK.", line),
-                format!("Line {}: Dividing by zero opens a portal to the void. I'm not doing that.", line),
-                format!("Line {}: Zero goes into nothing an infinite number of times. I don't have that kind of time.", line),
-                format!("Line {}: You want infinity? Go stare at the stars. Don't divide by zero.", line),
-                format!("Line {}: *attempts to divide by zero* *reality trembles* *goose refuses*", line),
-                format!("Line {}: Divide by zero? What is this, amateur hour?", line),
-                format!("Line {}: The last goose who divided by zero was never seen again. I'm not risking it.", line),
-                format!("Line {}: Division by zero is not a math operation, it's a cry for help.", line),
+            static MESSAGES: [&str; 10] = [
+                "Line {line}: You want me to divide by zero? I'm not falling for that.",
+                "Line {line}: Division by zero detected. I may be a goose, but I'm not stupid.",
+                "Line {line}: Ah, the classic divide by zero trick. The answer is HONK.",
+                "Line {line}: Dividing by zero opens a portal to the void. I'm not doing that.",
+                "Line {line}: Zero goes into nothing an infinite number of times. I don't have that kind of time.",
+                "Line {line}: You want infinity? Go stare at the stars. Don't divide by zero.",
+                "Line {line}: *attempts to divide by zero* *reality trembles* *goose refuses*",
+                "Line {line}: Divide by zero? What is this, amateur hour?",
+                "Line {line}: The last goose who divided by zero was never seen again. I'm not risking it.",
+                "Line {line}: Division by zero is not a math operation, it's a cry for help.",
             ];
-            choose(&messages).clone()
+            pick(&MESSAGES, &[("line", &line)])
         }
 
         ErrorKind::IndexOutOfBounds { index, len } => {
-            let messages = [
-                format!("Line {}: Index {} is out of bounds. The array only has {} elements. Count better.", line, index, len),
-                format!("Line {}: Trying to access index {} of an array with {} elements. Bold strategy.", line, index, len),
-                format!("Line {}: There is no index {}. There are only {} spots. This isn't Narnia.", line, index, len),
-                format!("Line {}: Index {} doesn't exist. The array is {} long. Do the math.", line, index, len),
-                format!("Line {}: *goose looks at index {}* *goose looks at length {}* *goose judges you*", line, index, len),
-                format!("Line {}: You reached for index {} but the array stopped at {}. Awkward.", line, index, len.saturating_sub(1)),
-                format!("Line {}: Array has {} elements but you wanted #{}. Off-by-a-lot error.", line, len, index),
-                format!("Line {}: Index {} is in the shadow realm. Array only goes to {}.", line, index, len.saturating_sub(1)),
+            static MESSAGES: [&str; 8] = [
+                "Line {line}: Index {index} is out of bounds. The array only has {len} elements. Count better.",
+                "Line {line}: Trying to access index {index} of an array with {len} elements. Bold strategy.",
+                "Line {line}: There is no index {index}. There are only {len} spots. This isn't Narnia.",
+                "Line {line}: Index {index} doesn't exist. The array is {len} long. Do the math.",
+                "Line {line}: *goose looks at index {index}* *goose looks at length {len}* *goose judges you*",
+                "Line {line}: You reached for index {index} but the array stopped at {last}. Awkward.",
+                "Line {line}: Array has {len} elements but you wanted #{index}. Off-by-a-lot error.",
+                "Line {line}: Index {index} is in the shadow realm. Array only goes to {last}.",
             ];
-            choose(&messages).clone()
+            pick(&MESSAGES, &[("line", &line), ("index", &index), ("len", &len), ("last", &len.saturating_sub(1))])
         }
 
         ErrorKind::InvalidFieldAccess { type_name, field } => {
-            let messages = [
-                format!("Line {}: Type '{}' doesn't have a field called '{}'. Nice try though.", line, type_name, field),
-                format!("Line {}: '{}' on a '{}'? That's not a thing. That's never been a thing.", line, field, type_name),
-                format!("Line {}: A {} with a {} field? What fantasy world are you coding in?", line, type_name, field),
-                format!("Line {}: *checks {}* *no {} found* *honks in confusion*", line, type_name, field),
-                format!("Line {}: The {} type looked everywhere for '{}'. It's just not there.", line, type_name, field),
-                format!("Line {}: You're asking {} for '{}'. It doesn't have that. It never did.", line, type_name, field),
-                format!("Line {}: Field '{}' on type '{}'? In this economy?", line, field, type_name),
-                format!("Line {}: {} says: \"I don't know what '{}' is and at this point I'm afraid to ask.\"", line, type_name, field),
+            static MESSAGES: [&str; 8] = [
+                "Line {line}: Type '{type_name}' doesn't have a field called '{field}'. Nice try though.",
+                "Line {line}: '{field}' on a '{type_name}'? That's not a thing. That's never been a thing.",
+                "Line {line}: A {type_name} with a {field} field? What fantasy world are you coding in?",
+                "Line {line}: *checks {type_name}* *no {field} found* *honks in confusion*",
+                "Line {line}: The {type_name} type looked everywhere for '{field}'. It's just not there.",
+                "Line {line}: You're asking {type_name} for '{field}'. It doesn't have that. It never did.",
+                "Line {line}: Field '{field}' on type '{type_name}'? In this economy?",
+                "Line {line}: {type_name} says: \"I don't know what '{field}' is and at this point I'm afraid to ask.\"",
             ];
-            choose(&messages).clone()
+            pick(&MESSAGES, &[("line", &line), ("type_name", &type_name), ("field", &field)])
         }
 
         ErrorKind::ArgumentMismatch { expected, got } => {
-            let messages = [
-                format!("Line {}: Expected {} arguments, got {}. Counting is fundamental.", line, expected, got),
-                format!("Line {}: {} arguments? I need exactly {}. No more, no less.", line, got, expected),
-                format!("Line {}: You gave me {} args but I wanted {}. This isn't a buffet.", line, got, expected),
-                format!("Line {}: Argument count: expected {}, received {}. We need to talk.", line, expected, got),
-                format!("Line {}: *counts arguments* {} ... *counts parameters* {} ... *visible frustration*", line, got, expected),
-                format!("Line {}: {} arguments provided, {} required. The math isn't mathing.", line, got, expected),
-                format!("Line {}: I specifically asked for {} arguments. You gave me {}. Why?", line, expected, got),
-                format!("Line {}: Arguments: wanted {}, got {}. Close only counts in horseshoes and hand grenades.", line, expected, got),
+            static MESSAGES: [&str; 8] = [
+                "Line {line}: Expected {expected} arguments, got {got}. Counting is fundamental.",
+                "Line {line}: {got} arguments? I need exactly {expected}. No more, no less.",
+                "Line {line}: You gave me {got} args but I wanted {expected}. This isn't a buffet.",
+                "Line {line}: Argument count: expected {expected}, received {got}. We need to talk.",
+                "Line {line}: *counts arguments* {got} ... *counts parameters* {expected} ... *visible frustration*",
+                "Line {line}: {got} arguments provided, {expected} required. The math isn't mathing.",
+                "Line {line}: I specifically asked for {expected} arguments. You gave me {got}. Why?",
+                "Line {line}: Arguments: wanted {expected}, got {got}. Close only counts in horseshoes and hand grenades.",
             ];
-            choose(&messages).clone()
+            pick(&MESSAGES, &[("line", &line), ("expected", &expected), ("got", &got)])
         }
 
         ErrorKind::SyntaxError(msg) => {
-            let messages = [
-                format!("Line {}: Syntax error - {}. Did you let a cat walk on your keyboard?", line, msg),
-                format!("Line {}: {}. That's not valid syntax. That's not valid anything.", line, msg),
-                format!("Line {}: Parse error: {}. I'm fluent in code, but this is gibberish.", line, msg),
-                format!("Line {}: {}. The syntax... it burns my eyes.", line, msg),
-                format!("Line {}: Syntax error: {}. Were you trying to summon a demon?", line, msg),
-                format!("Line {}: {}. I've seen some things, but this syntax is new.", line, msg),
-                format!("Line {}: {}. *honks in syntactical horror*", line, msg),
-                format!("Line {}: Invalid syntax: {}. Let's pretend this never happened.", line, msg),
-                format!("Line {}: {}. Did you mean to write actual code?", line, msg),
-                format!("Line {}: Syntax error ({}). Even I can't parse this, and I'm very smart.", line, msg),
+            static MESSAGES: [&str; 10] = [
+                "Line {line}: Syntax error - {msg}. Did you let a cat walk on your keyboard?",
+                "Line {line}: {msg}. That's not valid syntax. That's not valid anything.",
+                "Line {line}: Parse error: {msg}. I'm fluent in code, but this is gibberish.",
+                "Line {line}: {msg}. The syntax... it burns my eyes.",
+                "Line {line}: Syntax error: {msg}. Were you trying to summon a demon?",
+                "Line {line}: {msg}. I've seen some things, but this syntax is new.",
+                "Line {line}: {msg}. *honks in syntactical horror*",
+                "Line {line}: Invalid syntax: {msg}. Let's pretend this never happened.",
+                "Line {line}: {msg}. Did you mean to write actual code?",
+                "Line {line}: Syntax error ({msg}). Even I can't parse this, and I'm very smart.",
             ];
-            choose(&messages).clone()
+            pick(&MESSAGES, &[("line", &line), ("msg", &msg)])
+        }
+
+        ErrorKind::BadNumber { operation, result } => {
+            static MESSAGES: [&str; 5] = [
+                "Line {line}: {operation} came out as {result}. Strict math does not accept that as a number.",
+                "Line {line}: {operation} = {result}? The goose counted on its feathers and refuses.",
+                "Line {line}: {operation} produced {result}. In strict mode, that's a HONK.",
+                "Line {line}: *stares at {operation}* {result}. That's not a number, that's a cry for help.",
+                "Line {line}: {operation} went off the edge of the number line ({result}).",
+            ];
+            pick(&MESSAGES, &[("line", &line), ("operation", &operation), ("result", &result)])
+        }
+
+        ErrorKind::ConstantReassignment(name) => {
+            static MESSAGES: [&str; 5] = [
+                "Line {line}: '{name}' was declared 'always'. The goose does not rewrite history.",
+                "Line {line}: You said '{name}' would ALWAYS be that. The goose remembers. The goose does not rewrite history.",
+                "Line {line}: '{name}' is set in stone. The goose does not rewrite history.",
+                "Line {line}: *guards '{name}'* It's 'always', not 'until I change my mind'. The goose does not rewrite history.",
+                "Line {line}: Changing '{name}'? It was 'always'. The goose does not rewrite history.",
+            ];
+            pick(&MESSAGES, &[("line", &line), ("name", &name)])
+        }
+
+        ErrorKind::BuiltinName(name) => {
+            static MESSAGES: [&str; 5] = [
+                "Line {line}: '{name}' is a builtin. Pick another name - the goose won't share.",
+                "Line {line}: '{name}' already belongs to the goose, it's a builtin. Pick another name.",
+                "Line {line}: *clutches '{name}'* That's a builtin. Pick another name.",
+                "Line {line}: You can't have '{name}', it's a builtin. Pick another name - 'my-{name}', maybe?",
+                "Line {line}: '{name}' is a builtin, and builtins aren't up for grabs. Pick another name.",
+            ];
+            pick(&MESSAGES, &[("line", &line), ("name", &name)])
         }
 
         ErrorKind::InvalidOperation(op) => {
-            let base_messages = [
-                format!("Line {}: Invalid operation '{}'. What were you even trying to do?", line, op),
-                format!("Line {}: '{}' is not a valid operation. I checked. Twice.", line, op),
-                format!("Line {}: Operation '{}' failed. Some things just aren't meant to be.", line, op),
-                format!("Line {}: You can't just '{}' and expect it to work.", line, op),
-                format!("Line {}: '{}' - that's not how this works. That's not how any of this works.", line, op),
-                format!("Line {}: Invalid operation: {}. The goose council has rejected this.", line, op),
-                format!("Line {}: '{}' is illegal in 47 states and all ponds.", line, op),
-                format!("Line {}: *attempts {}* *nothing happens* *confused honking*", line, op),
-                format!("Line {}: Operation '{}' is about as valid as a three-dollar bill.", line, op),
-                format!("Line {}: {}? In THIS language? Absolutely not.", line, op),
+            static BASE_MESSAGES: [&str; 10] = [
+                "Line {line}: Invalid operation '{op}'. What were you even trying to do?",
+                "Line {line}: '{op}' is not a valid operation. I checked. Twice.",
+                "Line {line}: Operation '{op}' failed. Some things just aren't meant to be.",
+                "Line {line}: You can't just '{op}' and expect it to work.",
+                "Line {line}: '{op}' - that's not how this works. That's not how any of this works.",
+                "Line {line}: Invalid operation: {op}. The goose council has rejected this.",
+                "Line {line}: '{op}' is illegal in 47 states and all ponds.",
+                "Line {line}: *attempts {op}* *nothing happens* *confused honking*",
+                "Line {line}: Operation '{op}' is about as valid as a three-dollar bill.",
+                "Line {line}: {op}? In THIS language? Absolutely not.",
             ];
 
             let detail_suffix = if !details.is_empty() {
@@ -215,7 +302,7 @@ pub fn error(kind: ErrorKind, line: usize, details: &str) -> String {
                 String::new()
             };
 
-            format!("{}{}", choose(&base_messages), detail_suffix)
+            format!("{}{}", pick(&BASE_MESSAGES, &[("line", &line), ("op", &op)]), detail_suffix)
         }
     }
 }
@@ -254,109 +341,112 @@ pub fn rate_code(stats: &ExecutionStats) -> (u8, String) {
     let unquacked_penalty = (stats.unquacked_blocks as f64 * 0.5).min(3.0);
     score -= unquacked_penalty;
 
+    // Rubric adjustments requested by the program itself
+    score += stats.rating_adjustments.iter().map(|adj| adj.points).sum::<f64>();
+
     // Clamp score to 1-10
     let final_score = (score.round() as u8).clamp(1, 10);
 
     let message = match final_score {
         10 => {
-            let messages = [
+            static MESSAGES: [&str; 5] = [
                 "Perfect quacking. I'm... I'm actually proud of you.",
                 "10/10. Flawless. I have nothing sarcastic to say. This is unprecedented.",
                 "A perfect score. The geese sing songs of this code.",
                 "Immaculate. *single tear rolls down beak*",
                 "This code... it's beautiful. I'm not crying, you're crying.",
             ];
-            choose(&messages).to_string()
+            choose(&MESSAGES).to_string()
         }
         9 => {
-            let messages = [
+            static MESSAGES: [&str; 5] = [
                 "Excellent. Almost suspicious how good this is.",
                 "9/10. Near perfection. I'm watching you.",
                 "Outstanding work. Did you have help from a goose?",
                 "Impressive. Very impressive. *narrows eyes*",
                 "9 out of 10. The 1 missing point is for humility.",
             ];
-            choose(&messages).to_string()
+            choose(&MESSAGES).to_string()
         }
         8 => {
-            let messages = [
+            static MESSAGES: [&str; 5] = [
                 "Pretty good! You clearly respect the quack.",
                 "8/10. Solid quacking. Room for improvement, but I'm not mad.",
                 "Good code! The pond approves.",
                 "Well done. You may pet the goose. Once.",
                 "8 out of 10. You're getting the hang of this.",
             ];
-            choose(&messages).to_string()
+            choose(&MESSAGES).to_string()
         }
         7 => {
-            let messages = [
+            static MESSAGES: [&str; 5] = [
                 "Not bad. Not great. But not bad.",
                 "7/10. Above average quacking. Keep at it.",
                 "Decent work. I've seen worse. I've seen much worse.",
                 "Seven out of ten. The quack is adequate.",
                 "Acceptable. The council of geese gives a slight nod.",
             ];
-            choose(&messages).to_string()
+            choose(&MESSAGES).to_string()
         }
         6 => {
-            let messages = [
+            static MESSAGES: [&str; 5] = [
                 "Mediocre. I've seen better from actual ducks.",
                 "6/10. It works, I guess. Barely.",
                 "Passable. Like a C- in goose school.",
                 "Meh. The code runs but it doesn't spark joy.",
                 "Six out of ten. The minimum for not being embarrassing.",
             ];
-            choose(&messages).to_string()
+            choose(&MESSAGES).to_string()
         }
         5 => {
-            let messages = [
+            static MESSAGES: [&str; 5] = [
                 "Average. Thoroughly, painfully average.",
                 "5/10. Right in the middle. Maximum mediocrity achieved.",
                 "It's... fine. Just fine. Not good, not terrible. Fine.",
                 "Half-baked, half-quacked. Fitting.",
                 "Five out of ten. The participation trophy of scores.",
             ];
-            choose(&messages).to_string()
+            choose(&MESSAGES).to_string()
         }
         4 => {
-            let messages = [
+            static MESSAGES: [&str; 5] = [
                 "This code is a mess. I ran it, but I wasn't happy about it.",
                 "4/10. Below average. I expected nothing and I'm still disappointed.",
                 "Poor showing. The geese are shaking their heads.",
                 "Four out of ten. Were you even trying?",
                 "Subpar. Like watching someone try to fly without wings.",
             ];
-            choose(&messages).to_string()
+            choose(&MESSAGES).to_string()
         }
         3 => {
-            let messages = [
+            static MESSAGES: [&str; 5] = [
                 "This is rough. Really rough. Like sandpaper made of sadness.",
                 "3/10. I've seen better code from a random number generator.",
                 "Three out of ten. The quacking was barely audible.",
                 "Yikes. And I mean that in the most professional way.",
                 "This code needs help. Professional help.",
             ];
-            choose(&messages).to_string()
+            choose(&MESSAGES).to_string()
         }
         2 => {
-            let messages = [
+            static MESSAGES: [&str; 5] = [
                 "I'm embarrassed for both of us.",
                 "2/10. The code equivalent of a sad honk.",
                 "Two out of ten. At least you tried. Did you try?",
                 "This is almost impressively bad. Almost.",
                 "Oof. Double oof. Triple oof with a side of yikes.",
             ];
-            choose(&messages).to_string()
+            choose(&MESSAGES).to_string()
         }
         1 => {
-            let messages = [
+            static MESSAGES: [&str; 5] = [
                 "1/10. The only point is for turning on your computer.",
                 "This is the worst code I've ever seen. And I've seen a lot.",
                 "One out of ten. I'm calling the code police.",
                 "Absolutely catastrophic. How did you even do this?",
                 "One point. Because zero felt too harsh. It wasn't too harsh.",
             ];
-            choose(&messages).to_string()
+            choose(&MESSAGES).to_string()
         }
         _ => "Something went wrong with the rating. Much like your code.".to_string(),
     };
@@ -366,9 +456,9 @@ pub fn rate_code(stats: &ExecutionStats) -> (u8, String) {
 
 /// Generate a random startup message
 pub fn startup() -> String {
-    let emojis = ["\u{1fabf}", ">o)", "~(o>", "\u{1f986}", "(o_O>"];
+    static EMOJIS: [&str; 5] = ["\u{1fabf}", ">o)", "~(o>", "\u{1f986}", "(o_O>"];
 
-    let messages = [
+    static MESSAGES: [&str; 15] = [
         "Goose interpreter v0.3.1 - Ready to honk",
         "Goose is awake. Don't forget to quack.",
         "*aggressive goose noises* Let's run some code.",
@@ -386,12 +476,12 @@ pub fn startup() -> String {
         "*intimidating goose stare* Show me your code.",
     ];
 
-    format!("{} {}", choose(&emojis), choose(&messages))
+    format!("{} {}", choose(&EMOJIS), choose(&MESSAGES))
 }
 
 /// Generate a random success message
 pub fn success() -> String {
-    let messages = [
+    static MESSAGES: [&str; 18] = [
         "Execution complete. Good job, I guess.",
         "All done! The code was... acceptable.",
         "Finished. Your quacking was adequate.",
@@ -412,12 +502,12 @@ pub fn success() -> String {
         "Program complete. You may now pat yourself on the back.",
     ];
 
-    choose(&messages).to_string()
+    choose(&MESSAGES).to_string()
 }
 
 /// Generate a random REPL comment after executing a line
 pub fn repl_comment() -> String {
-    let messages = [
+    static MESSAGES: [&str; 20] = [
         "*nods approvingly*",
         "Honk.",
         "*watches silently*",
@@ -440,41 +530,54 @@ pub fn repl_comment() -> String {
         "*goose noises*",
     ];
 
-    choose(&messages).to_string()
+    choose(&MESSAGES).to_string()
 }
 
 /// Generate a random warning message
 pub fn warning(line: usize, message: &str) -> String {
-    let prefixes = [
-        format!("Line {}: Hmm, suspicious... {}", line, message),
-        format!("Line {}: *concerned honk* {}", line, message),
-        format!("Line {}: I'm not saying this is wrong, but... {}", line, message),
-        format!("Line {}: Warning: {} (I'm just saying)", line, message),
-        format!("Line {}: The goose senses something off: {}", line, message),
-        format!("Line {}: Proceed with caution - {}", line, message),
-        format!("Line {}: *squints suspiciously* {}", line, message),
-        format!("Line {}: Not an error, but maybe reconsider? {}", line, message),
+    static PREFIXES: [&str; 8] = [
+        "Line {line}: Hmm, suspicious... {message}",
+        "Line {line}: *concerned honk* {message}",
+        "Line {line}: I'm not saying this is wrong, but... {message}",
+        "Line {line}: Warning: {message} (I'm just saying)",
+        "Line {line}: The goose senses something off: {message}",
+        "Line {line}: Proceed with caution - {message}",
+        "Line {line}: *squints suspiciously* {message}",
+        "Line {line}: Not an error, but maybe reconsider? {message}",
+    ];
+
+    pick(&PREFIXES, &[("line", &line), ("message", &message)])
+}
+
+/// Generate a nag for an answer that wasn't what the program asked for
+pub fn nag(problem: &str) -> String {
+    static FORMATS: [&str; 5] = [
+        "*impatient honk* {problem} Try again.",
+        "Nope. {problem} Once more, with feeling.",
+        "The goose is not amused. {problem} Again.",
+        "{problem} The goose will wait. The goose has all day.",
+        "*taps webbed foot* {problem} Try again.",
     ];
 
-    choose(&prefixes).clone()
+    pick(&FORMATS, &[("problem", &problem)])
 }
 
 /// Generate a debug message with goose flair
 pub fn debug(line: usize, message: &str) -> String {
-    let formats = [
-        format!("[DEBUG L{}] {} (goose is watching)", line, message),
-        format!("[L{}] *takes notes* {}", line, message),
-        format!("[DEBUG] Line {}: {} - filed under 'interesting'", line, message),
-        format!("[L{}] {}", line, message),
-        format!("[GOOSE DEBUG L{}] {}", line, message),
+    static FORMATS: [&str; 5] = [
+        "[DEBUG L{line}] {message} (goose is watching)",
+        "[L{line}] *takes notes* {message}",
+        "[DEBUG] Line {line}: {message} - filed under 'interesting'",
+        "[L{line}] {message}",
+        "[GOOSE DEBUG L{line}] {message}",
     ];
 
-    choose(&formats).clone()
+    pick(&FORMATS, &[("line", &line), ("message", &message)])
 }
 
 /// Generate an encouraging message when the user is struggling
 pub fn encouragement() -> String {
-    let messages = [
+    static MESSAGES: [&str; 8] = [
         "Don't worry, even the best programmers forget to quack sometimes.",
         "Keep trying! Rome wasn't quacked in a day.",
         "Errors are just learning opportunities. Annoying learning opportunities.",
@@ -485,12 +588,12 @@ pub fn encouragement() -> String {
         "Mistakes are proof that you're trying. So... good job?",
     ];
 
-    choose(&messages).to_string()
+    choose(&MESSAGES).to_string()
 }
 
 /// Generate a sassy response for when users try something weird
 pub fn sass() -> String {
-    let messages = [
+    static MESSAGES: [&str; 8] = [
         "Was that supposed to work? Because it didn't.",
         "Interesting choice. Wrong, but interesting.",
         "I'm going to pretend I didn't see that.",
@@ -501,12 +604,12 @@ pub fn sass() -> String {
         "You know what, I'm not even going to comment. Wait, I just did.",
     ];
 
-    choose(&messages).to_string()
+    choose(&MESSAGES).to_string()
 }
 
 /// Generate a goodbye message
 pub fn goodbye() -> String {
-    let messages = [
+    static MESSAGES: [&str; 10] = [
         "Goodbye! May your future code be properly quacked.",
         "*flies away into the sunset* Until next time!",
         "Goose out. *drops mic*",
@@ -519,35 +622,103 @@ pub fn goodbye() -> String {
         "Goodbye! *aggressive goodbye honk*",
     ];
 
-    choose(&messages).to_string()
+    choose(&MESSAGES).to_string()
 }
 
 /// Generate a honk assertion failure message
 pub fn honk_failure(line: usize, custom_message: &str) -> String {
     if !custom_message.is_empty() {
-        let prefixes = [
-            format!("HONK! Line {}: {}", line, custom_message),
-            format!("HONK HONK! Assertion failed at line {}: {}", line, custom_message),
-            format!("*AGGRESSIVE HONKING* Line {}: {}", line, custom_message),
-            format!("The goose is DISPLEASED! Line {}: {}", line, custom_message),
+        static PREFIXES: [&str; 4] = [
+            "HONK! Line {line}: {custom_message}",
+            "HONK HONK! Assertion failed at line {line}: {custom_message}",
+            "*AGGRESSIVE HONKING* Line {line}: {custom_message}",
+            "The goose is DISPLEASED! Line {line}: {custom_message}",
         ];
-        return choose(&prefixes).clone();
+        return pick(&PREFIXES, &[("line", &line), ("custom_message", &custom_message)]);
     }
 
-    let messages = [
-        format!("HONK! Assertion failed at line {}. The goose is NOT happy.", line),
-        format!("HONK HONK HONK! Your assumption was wrong at line {}!", line),
-        format!("*aggressive honking* Line {}: That condition is FALSE!", line),
-        format!("The goose has inspected your assertion at line {}. It is LIES.", line),
-        format!("HONK! Line {}: The goose trusted you. The goose was betrayed.", line),
-        format!("Line {}: *slams wing on table* THIS IS FALSE!", line),
-        format!("ASSERTION FAILURE at line {}! The council of geese is outraged!", line),
-        format!("Line {}: HONK! Your boolean is broken!", line),
-        format!("*honks in disappointment* Line {}: That's not true and you know it.", line),
-        format!("Line {}: The goose has spoken. Your assertion is invalid.", line),
+    static MESSAGES: [&str; 10] = [
+        "HONK! Assertion failed at line {line}. The goose is NOT happy.",
+        "HONK HONK HONK! Your assumption was wrong at line {line}!",
+        "*aggressive honking* Line {line}: That condition is FALSE!",
+        "The goose has inspected your assertion at line {line}. It is LIES.",
+        "HONK! Line {line}: The goose trusted you. The goose was betrayed.",
+        "Line {line}: *slams wing on table* THIS IS FALSE!",
+        "ASSERTION FAILURE at line {line}! The council of geese is outraged!",
+        "Line {line}: HONK! Your boolean is broken!",
+        "*honks in disappointment* Line {line}: That's not true and you know it.",
+        "Line {line}: The goose has spoken. Your assertion is invalid.",
     ];
 
-    choose(&messages).clone()
+    pick(&MESSAGES, &[("line", &line)])
+}
+
+/// Where the goose's verdicts go. The CLI prints them; embedders (GUIs, web
+/// frontends) can install their own sink to receive them as structured events.
+pub trait OutputSink {
+    /// A block was skipped for missing its quack
+    fn refusal(&mut self, line: usize, message: &str);
+
+    /// Something suspicious happened that isn't an error (e.g. a change through an alias)
+    fn warning(&mut self, line: usize, message: &str);
+
+    /// The program finished without errors
+    fn success(&mut self, message: &str);
+
+    /// The final rating, with the stats it was based on
+    fn rating(&mut self, score: u8, quip: &str, stats: &ExecutionStats);
+
+    /// Hot reload swapped in new function definitions (or couldn't, and kept the old ones)
+    fn reloaded(&mut self, _message: &str) {}
+}
+
+/// The default sink: drops everything, so an interpreter embedded in another
+/// program doesn't print behind its back
+#[derive(Debug, Default)]
+pub struct SilentSink;
+
+impl OutputSink for SilentSink {
+    fn refusal(&mut self, _line: usize, _message: &str) {}
+    fn warning(&mut self, _line: usize, _message: &str) {}
+    fn success(&mut self, _message: &str) {}
+    fn rating(&mut self, _score: u8, _quip: &str, _stats: &ExecutionStats) {}
+}
+
+/// What `goose` uses: refusals and warnings to stderr, everything else to stdout
+#[derive(Debug, Default)]
+pub struct ConsoleSink;
+
+impl OutputSink for ConsoleSink {
+    fn refusal(&mut self, _line: usize, message: &str) {
+        eprintln!("{}", message);
+    }
+
+    fn warning(&mut self, line: usize, message: &str) {
+        eprintln!("{}", warning(line, message));
+    }
+
+    fn success(&mut self, message: &str) {
+        println!("{}", message);
+    }
+
+    fn rating(&mut self, score: u8, quip: &str, stats: &ExecutionStats) {
+        println!();
+        println!("═══════════════════════════════════════");
+        println!("  Goose rated your code: {}/10", score);
+        println!("  \"{}\"", quip);
+        for adjustment in &stats.rating_adjustments {
+            println!("  {:+} for {} (line {})", adjustment.points, adjustment.reason, adjustment.line);
+        }
+        if !stats.skipped_blocks.is_empty() {
+            let lines: Vec<String> = stats.skipped_blocks.iter().map(|line| line.to_string()).collect();
+            println!("  Never ran (no quack): line(s) {}", lines.join(", "));
+        }
+        println!("═══════════════════════════════════════");
+    }
+
+    fn reloaded(&mut self, message: &str) {
+        eprintln!("{}", message);
+    }
 }
 
 #[cfg(test)]
@@ -579,6 +750,18 @@ mod tests {
         assert!(msg.contains("5") || msg.contains("zero"));
     }
 
+    #[test]
+    fn test_error_constant_reassignment() {
+        let msg = error(ErrorKind::ConstantReassignment("pi".to_string()), 3, "");
+        assert!(msg.contains("'pi'") && msg.contains("does not rewrite history"));
+    }
+
+    #[test]
+    fn test_fill_only_replaces_placeholders_in_the_template() {
+        let filled = fill("Line {line}: '{name}' {unknown}", &[("line", &3), ("name", &"{line}")]);
+        assert_eq!(filled, "Line 3: '{line}' {unknown}");
+    }
+
     #[test]
     fn test_rate_code_perfect() {
         let stats = ExecutionStats {
@@ -588,6 +771,8 @@ mod tests {
             functions_defined: 3,
             structs_defined: 2,
             loops_executed: 5,
+            skipped_blocks: Vec::new(),
+            rating_adjustments: Vec::new(),
         };
         let (score, _msg) = rate_code(&stats);
         assert!(score >= 8);
@@ -602,11 +787,32 @@ mod tests {
             functions_defined: 0,
             structs_defined: 0,
             loops_executed: 0,
+            skipped_blocks: vec![1, 2, 3, 4, 5, 6, 7, 8],
+            rating_adjustments: Vec::new(),
         };
         let (score, _msg) = rate_code(&stats);
         assert!(score <= 4);
     }
 
+    #[test]
+    fn test_rate_code_applies_adjustments() {
+        let base = ExecutionStats {
+            total_blocks: 10,
+            quacked_blocks: 10,
+            ..Default::default()
+        };
+        let (base_score, _) = rate_code(&base);
+
+        let mut penalized = base.clone();
+        penalized.rating_adjustments.push(RatingAdjustment {
+            points: -3.0,
+            reason: "no comments".to_string(),
+            line: 1,
+        });
+        let (penalized_score, _) = rate_code(&penalized);
+        assert_eq!(penalized_score, base_score - 3);
+    }
+
     #[test]
     fn test_startup_has_content() {
         let msg = startup();
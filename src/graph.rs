@@ -0,0 +1,152 @@
+// `goose graph` - pictures of a program's structure
+//
+// The call graph shows which top-level functions use which (and what the top
+// level itself calls); the import graph shows which files migrate which. Both
+// come out as Graphviz DOT or Mermaid text, for `dot -Tsvg` or a Markdown file.
+
+use std::collections::HashSet;
+use std::fs;
+
+use crate::analysis::{SymbolKind, SymbolTable};
+use crate::ast::{Block, Statement};
+use crate::parser::parse_str;
+use crate::visit::{walk_statement, Visitor};
+
+/// What the top level of a program is called in the call graph
+pub const TOP_LEVEL: &str = "(top level)";
+
+/// Named nodes joined by arrows, in the order they were found
+#[derive(Debug, Default, PartialEq)]
+pub struct Graph {
+    nodes: Vec<String>,
+    edges: Vec<(usize, usize)>,
+}
+
+impl Graph {
+    /// Which functions call (or pass around) which. Every function gets a node,
+    /// even one nothing calls; methods appear as `Struct.method`.
+    pub fn calls(blocks: &[Block]) -> Graph {
+        let table = SymbolTable::build(blocks);
+        let mut graph = Graph::default();
+        for function in table.of_kind(SymbolKind::Function) {
+            graph.node(&function.name);
+        }
+        for call in table.calls() {
+            graph.edge(call.caller.as_deref().unwrap_or(TOP_LEVEL), &call.callee);
+        }
+        graph
+    }
+
+    /// Which files migrate which, starting from `path` and following every
+    /// import that can be read. Paths are resolved the way `migrate` does,
+    /// from the current directory; `git+` libraries are shown but not followed.
+    pub fn imports(path: &str) -> Result<Graph, String> {
+        let source = fs::read_to_string(path).map_err(|e| format!("The goose couldn't read '{}': {}", path, e))?;
+        let blocks = parse_str(&source).map_err(|errors| errors.join("\n"))?;
+        let mut graph = Graph::default();
+        let mut pending = vec![(path.to_string(), blocks)];
+        let mut seen = HashSet::from([path.to_string()]);
+        while let Some((file, blocks)) = pending.pop() {
+            graph.node(&file);
+            let mut migrates = Migrates(Vec::new());
+            for block in &blocks {
+                migrates.visit_block(block);
+            }
+            for target in migrates.0 {
+                graph.edge(&file, &target);
+                if target.starts_with("git+") || !seen.insert(target.clone()) {
+                    continue;
+                }
+                let parsed = fs::read_to_string(&target).ok().and_then(|source| parse_str(&source).ok());
+                if let Some(blocks) = parsed {
+                    pending.push((target, blocks));
+                }
+            }
+        }
+        Ok(graph)
+    }
+
+    fn node(&mut self, name: &str) -> usize {
+        match self.nodes.iter().position(|node| node == name) {
+            Some(index) => index,
+            None => {
+                self.nodes.push(name.to_string());
+                self.nodes.len() - 1
+            }
+        }
+    }
+
+    fn edge(&mut self, from: &str, to: &str) {
+        let edge = (self.node(from), self.node(to));
+        if !self.edges.contains(&edge) {
+            self.edges.push(edge);
+        }
+    }
+
+    /// Graphviz DOT, for `goose graph file.duck | dot -Tsvg > graph.svg`
+    pub fn to_dot(&self) -> String {
+        let quote = |name: &str| format!("\"{}\"", name.replace('\\', "\\\\").replace('"', "\\\""));
+        let mut out = String::from("digraph duck {\n");
+        for node in &self.nodes {
+            out.push_str(&format!("    {};\n", quote(node)));
+        }
+        for &(from, to) in &self.edges {
+            out.push_str(&format!("    {} -> {};\n", quote(&self.nodes[from]), quote(&self.nodes[to])));
+        }
+        out.push_str("}\n");
+        out
+    }
+
+    /// A Mermaid flowchart, for pasting into Markdown. Nodes get plain ids
+    /// (n0, n1, ...) since names like `find-max` would read as arrows.
+    pub fn to_mermaid(&self) -> String {
+        let mut out = String::from("flowchart LR\n");
+        for (i, node) in self.nodes.iter().enumerate() {
+            out.push_str(&format!("    n{}[\"{}\"]\n", i, node.replace('"', "#quot;")));
+        }
+        for &(from, to) in &self.edges {
+            out.push_str(&format!("    n{} --> n{}\n", from, to));
+        }
+        out
+    }
+}
+
+/// Collects the path of every migrate in a program, in order
+struct Migrates(Vec<String>);
+
+impl Visitor for Migrates {
+    fn visit_statement(&mut self, stmt: &Statement) {
+        if let Statement::Migrate { path, .. } = stmt {
+            self.0.push(path.clone());
+        }
+        walk_statement(self, stmt);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_call_graph_as_dot_and_mermaid() {
+        let source = concat!(
+            "quack [define double taking [n] as quack [return n * 2]]\n",
+            "quack [define quadruple taking [n] as quack [return double(double(n))]]\n",
+            "quack [define unused taking [] as quack [return nil]]\n",
+            "quack [print quadruple(1)]",
+        );
+        let graph = Graph::calls(&parse_str(source).unwrap());
+        assert_eq!(
+            graph.to_dot(),
+            concat!(
+                "digraph duck {\n",
+                "    \"double\";\n    \"quadruple\";\n    \"unused\";\n    \"(top level)\";\n",
+                "    \"quadruple\" -> \"double\";\n",
+                "    \"(top level)\" -> \"quadruple\";\n",
+                "}\n",
+            )
+        );
+        assert!(graph.to_mermaid().starts_with("flowchart LR\n    n0[\"double\"]\n"));
+        assert!(graph.to_mermaid().ends_with("    n1 --> n0\n    n3 --> n1\n"));
+    }
+}
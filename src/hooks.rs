@@ -0,0 +1,54 @@
+// Execution hooks - let tracers, profilers, debuggers and coverage watch a run
+// Register with `Interpreter::add_hooks`; every method has a do-nothing default
+
+use std::cell::RefCell;
+use std::rc::Rc;
+
+use crate::ast::Statement;
+use crate::values::Value;
+
+/// Callbacks the interpreter makes as it runs. Implement only the ones you need.
+pub trait ExecutionHooks {
+    /// A quacked statement is about to run
+    fn on_statement(&mut self, _line: usize, _statement: &Statement) {}
+
+    /// A function, lambda (named "<lambda>") or builtin is about to be called
+    fn on_call(&mut self, _name: &str, _args: &[Value], _line: usize) {}
+
+    /// A statement failed. Reported once, at the innermost statement, even if
+    /// the error is later rescued by `attempt`
+    fn on_error(&mut self, _line: usize, _error: &str) {}
+
+    /// A block was skipped for missing its quack
+    fn on_block_skipped(&mut self, _line: usize) {}
+}
+
+/// Identifies registered hooks, for `Interpreter::remove_hooks`
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct HookId(pub(crate) usize);
+
+/// Which quacked blocks and honk assertions ran (see `Interpreter::start_coverage`)
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct Coverage {
+    /// Lines of quacked blocks that executed, in first-run order
+    pub quacks: Vec<usize>,
+    /// Lines of honk assertions that were checked, in first-run order
+    pub honks: Vec<usize>,
+    /// for-all property checks that passed, as (line, cases checked)
+    pub properties: Vec<(usize, usize)>,
+}
+
+/// Records coverage into a shared `Coverage` as statements run
+pub(crate) struct CoverageHooks(pub(crate) Rc<RefCell<Coverage>>);
+
+impl ExecutionHooks for CoverageHooks {
+    fn on_statement(&mut self, line: usize, statement: &Statement) {
+        let mut coverage = self.0.borrow_mut();
+        if !coverage.quacks.contains(&line) {
+            coverage.quacks.push(line);
+        }
+        if matches!(statement, Statement::Honk { .. }) && !coverage.honks.contains(&line) {
+            coverage.honks.push(line);
+        }
+    }
+}
@@ -0,0 +1,83 @@
+// HTTP client - what http-get, http-post and `goose update` use to talk to the web
+// Only built with the `http` feature (on by default). Without it every request
+// is an error, so the goose still builds where there's no TLS or network stack.
+
+/// What came back from a request
+#[derive(Debug, Clone, PartialEq)]
+pub struct Response {
+    pub status: u16,
+    pub headers: Vec<(String, String)>,
+    pub body: String,
+}
+
+/// GET a URL with extra request headers
+pub fn get(url: &str, headers: &[(String, String)]) -> Result<Response, String> {
+    imp::send("GET", url, None, headers)
+}
+
+/// POST a body to a URL with extra request headers
+pub fn post(url: &str, body: &str, headers: &[(String, String)]) -> Result<Response, String> {
+    imp::send("POST", url, Some(body), headers)
+}
+
+/// Download a file's bytes, failing on anything but a success status
+/// (the goose's own updates and library installs)
+pub fn download(url: &str) -> Result<Vec<u8>, String> {
+    imp::download(url)
+}
+
+#[cfg(feature = "http")]
+mod imp {
+    use super::Response;
+
+    /// Who the goose says it is when it downloads things for itself
+    const USER_AGENT: &str = "goose-updater";
+
+    pub fn send(method: &str, url: &str, body: Option<&str>, headers: &[(String, String)]) -> Result<Response, String> {
+        let client = reqwest::blocking::Client::new();
+        let mut request = match body {
+            Some(body) => client.post(url).body(body.to_string()),
+            None => client.get(url),
+        };
+        for (key, value) in headers {
+            request = request.header(key.as_str(), value.as_str());
+        }
+
+        let response = request.send().map_err(|e| format!("HTTP {} error: {}", method, e))?;
+        let status = response.status().as_u16();
+        let headers = response
+            .headers()
+            .iter()
+            .map(|(k, v)| (k.to_string(), v.to_str().unwrap_or("").to_string()))
+            .collect();
+        let body = response.text().map_err(|e| format!("Failed to read response: {}", e))?;
+        Ok(Response { status, headers, body })
+    }
+
+    pub fn download(url: &str) -> Result<Vec<u8>, String> {
+        let client = reqwest::blocking::Client::builder()
+            .user_agent(USER_AGENT)
+            .build()
+            .map_err(|e| e.to_string())?;
+        let response = client.get(url).send().map_err(|e| e.to_string())?;
+        if !response.status().is_success() {
+            return Err(format!("HTTP {}", response.status()));
+        }
+        response.bytes().map(|b| b.to_vec()).map_err(|e| e.to_string())
+    }
+}
+
+#[cfg(not(feature = "http"))]
+mod imp {
+    use super::Response;
+
+    const NOT_BUILT: &str = "this goose was built without HTTP support (rebuild with `cargo build --features http`)";
+
+    pub fn send(_method: &str, _url: &str, _body: Option<&str>, _headers: &[(String, String)]) -> Result<Response, String> {
+        Err(NOT_BUILT.to_string())
+    }
+
+    pub fn download(_url: &str) -> Result<Vec<u8>, String> {
+        Err(NOT_BUILT.to_string())
+    }
+}
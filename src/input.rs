@@ -0,0 +1,217 @@
+// Input sources - where `input()` gets its answers
+// Real stdin, stdin with every answer recorded, or a recorded session played back
+
+use std::cell::RefCell;
+use std::collections::VecDeque;
+use std::fs;
+use std::io::{self, IsTerminal, Write};
+use std::path::Path;
+use std::rc::Rc;
+
+use serde::{Deserialize, Serialize};
+
+/// Somewhere `input()` can read lines from
+pub trait InputSource {
+    /// Show the prompt (if any) and read one line, without its line ending.
+    /// `None` means there's nothing left to read (stdin was closed)
+    fn read_line(&mut self, prompt: Option<&str>) -> Result<Option<String>, String>;
+
+    /// Like `read_line`, but the answer isn't shown as it's typed
+    fn read_secret(&mut self, prompt: Option<&str>) -> Result<Option<String>, String> {
+        self.read_line(prompt)
+    }
+}
+
+/// Reads from the terminal, like you'd expect
+#[derive(Debug, Default)]
+pub struct StdinInput;
+
+impl InputSource for StdinInput {
+    fn read_line(&mut self, prompt: Option<&str>) -> Result<Option<String>, String> {
+        if let Some(prompt) = prompt {
+            print!("{}", prompt);
+            io::stdout().flush().ok();
+        }
+
+        let mut input = String::new();
+        match io::stdin().read_line(&mut input) {
+            Ok(0) => Ok(None),
+            Ok(_) => Ok(Some(input.trim_end_matches('\n').trim_end_matches('\r').to_string())),
+            Err(e) => Err(format!("Failed to read input: {}", e)),
+        }
+    }
+
+    fn read_secret(&mut self, prompt: Option<&str>) -> Result<Option<String>, String> {
+        // Piped input has nothing to hide from
+        if !io::stdin().is_terminal() {
+            return self.read_line(prompt);
+        }
+        rpassword::prompt_password(prompt.unwrap_or(""))
+            .map(Some)
+            .map_err(|e| format!("Failed to read input: {}", e))
+    }
+}
+
+/// Every answer given during a run, as saved by `--record` and read by `--replay`
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+pub struct Session {
+    /// Answers in the order they were given. Secrets aren't written down:
+    /// they're `None` (null in the file), and a replay asks for them again.
+    pub inputs: Vec<Option<String>>,
+}
+
+impl Session {
+    pub fn load(path: &Path) -> Result<Session, String> {
+        let text = fs::read_to_string(path)
+            .map_err(|e| format!("Couldn't read session file '{}': {}", path.display(), e))?;
+        serde_json::from_str(&text)
+            .map_err(|e| format!("Session file '{}' isn't valid: {}", path.display(), e))
+    }
+
+    pub fn save(&self, path: &Path) -> Result<(), String> {
+        let json = serde_json::to_string_pretty(self).map_err(|e| e.to_string())?;
+        fs::write(path, json).map_err(|e| format!("Couldn't write session file '{}': {}", path.display(), e))
+    }
+}
+
+/// Passes reads through to another source, remembering every answer
+pub struct RecordingInput {
+    inner: Box<dyn InputSource>,
+    session: Rc<RefCell<Session>>,
+}
+
+impl RecordingInput {
+    pub fn new(inner: Box<dyn InputSource>) -> Self {
+        RecordingInput {
+            inner,
+            session: Rc::new(RefCell::new(Session::default())),
+        }
+    }
+
+    /// A handle to the answers recorded so far (keep it to save them after the run)
+    pub fn session(&self) -> Rc<RefCell<Session>> {
+        Rc::clone(&self.session)
+    }
+}
+
+impl InputSource for RecordingInput {
+    fn read_line(&mut self, prompt: Option<&str>) -> Result<Option<String>, String> {
+        let answer = self.inner.read_line(prompt)?;
+        if let Some(answer) = &answer {
+            self.session.borrow_mut().inputs.push(Some(answer.clone()));
+        }
+        Ok(answer)
+    }
+
+    fn read_secret(&mut self, prompt: Option<&str>) -> Result<Option<String>, String> {
+        let answer = self.inner.read_secret(prompt)?;
+        if answer.is_some() {
+            self.session.borrow_mut().inputs.push(None);
+        }
+        Ok(answer)
+    }
+}
+
+/// Answers each `input()` from a recorded session instead of the keyboard
+pub struct ReplayInput {
+    answers: VecDeque<Option<String>>,
+    /// Print prompts and answers as if someone typed them
+    echo: bool,
+    /// Where secrets the session didn't write down are asked for again
+    secrets: Box<dyn InputSource>,
+}
+
+impl ReplayInput {
+    pub fn new(session: Session, echo: bool) -> Self {
+        ReplayInput {
+            answers: session.inputs.into(),
+            echo,
+            secrets: Box::new(StdinInput),
+        }
+    }
+
+    /// Ask `source` for secrets instead of the terminal
+    pub fn with_secrets(mut self, source: Box<dyn InputSource>) -> Self {
+        self.secrets = source;
+        self
+    }
+
+    fn next_answer(&mut self) -> Result<Option<String>, String> {
+        self.answers.pop_front().ok_or_else(|| {
+            "The replay ran out of answers - the program asked for more input than was recorded".to_string()
+        })
+    }
+}
+
+impl InputSource for ReplayInput {
+    fn read_line(&mut self, prompt: Option<&str>) -> Result<Option<String>, String> {
+        let answer = self.next_answer()?.ok_or_else(|| {
+            "The replay has a secret here, but the program asked for ordinary input - was it changed since recording?"
+                .to_string()
+        })?;
+        if self.echo {
+            println!("{}{}", prompt.unwrap_or(""), answer);
+        }
+        Ok(Some(answer))
+    }
+
+    fn read_secret(&mut self, prompt: Option<&str>) -> Result<Option<String>, String> {
+        let Some(answer) = self.next_answer()? else {
+            return self.secrets.read_secret(prompt);
+        };
+        if self.echo {
+            println!("{}{}", prompt.unwrap_or(""), "*".repeat(answer.chars().count()));
+        }
+        Ok(Some(answer))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_recorded_answers_replay_in_order() {
+        let mut recorder = RecordingInput::new(Box::new(ReplayInput::new(
+            Session { inputs: vec![Some("Gerald".to_string()), Some("5".to_string())] },
+            false,
+        )));
+        let session = recorder.session();
+        assert_eq!(recorder.read_line(Some("Name? ")).unwrap().as_deref(), Some("Gerald"));
+        assert_eq!(recorder.read_line(None).unwrap().as_deref(), Some("5"));
+
+        let mut replay = ReplayInput::new(session.borrow().clone(), false);
+        assert_eq!(replay.read_line(None).unwrap().as_deref(), Some("Gerald"));
+        assert_eq!(replay.read_line(None).unwrap().as_deref(), Some("5"));
+        assert!(replay.read_line(None).unwrap_err().contains("ran out"));
+    }
+
+    #[test]
+    fn test_secrets_are_not_recorded_and_asked_for_again() {
+        let typed = Session { inputs: vec![Some("Gerald".to_string()), Some("hunter2".to_string())] };
+        let mut recorder = RecordingInput::new(Box::new(ReplayInput::new(typed, false)));
+        let session = recorder.session();
+        recorder.read_line(None).unwrap();
+        assert_eq!(recorder.read_secret(Some("Password: ")).unwrap().as_deref(), Some("hunter2"));
+        assert_eq!(*session.borrow(), Session { inputs: vec![Some("Gerald".to_string()), None] });
+        assert!(!serde_json::to_string(&*session.borrow()).unwrap().contains("hunter2"));
+
+        let typed_again = ReplayInput::new(Session { inputs: vec![Some("swordfish".to_string())] }, false);
+        let mut replay = ReplayInput::new(session.borrow().clone(), false).with_secrets(Box::new(typed_again));
+        assert_eq!(replay.read_line(None).unwrap().as_deref(), Some("Gerald"));
+        assert_eq!(replay.read_secret(None).unwrap().as_deref(), Some("swordfish"));
+
+        let mut mismatched = ReplayInput::new(Session { inputs: vec![None] }, false);
+        assert!(mismatched.read_line(None).unwrap_err().contains("secret"));
+    }
+
+    #[test]
+    fn test_session_round_trips_through_file() {
+        let path = std::env::temp_dir().join(format!("goose-session-{}.json", std::process::id()));
+        let session = Session { inputs: vec![Some("quack".to_string()), None] };
+        session.save(&path).unwrap();
+        assert_eq!(Session::load(&path).unwrap(), session);
+        fs::remove_file(&path).ok();
+        assert!(Session::load(&path).is_err());
+    }
+}
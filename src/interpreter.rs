@@ -1,17 +1,36 @@
 // Interpreter - executes Duck programs
 // Only executes blocks that were properly "quacked"
 
+use std::io::{self, Write};
 use std::cell::RefCell;
 use std::collections::{HashMap, HashSet};
 use std::path::PathBuf;
+use std::time::{Duration, Instant};
 use std::rc::Rc;
-
-use crate::ast::{AssignTarget, BinaryOp, Block, Expr, Literal, Pattern, Statement, StringPart, UnaryOp};
-use crate::lexer;
-use crate::parser;
-use crate::builtins;
-use crate::goose::{self, ErrorKind, ExecutionStats};
-use crate::values::{Closure, Value};
+use std::sync::Arc;
+
+use crate::ast::{AssignTarget, BinaryOp, Block, Expr, Literal, MatchArm, Pattern, Statement, StringPart, UnaryOp};
+use crate::ast_cache;
+use crate::builtins::{self, Builtin, Hosted};
+use crate::capability::{Capability, Policy};
+use crate::clock::{self, CancelHandle, Clock, SystemClock};
+use crate::decimal::Decimal;
+use crate::diff;
+pub use crate::hooks::Coverage;
+use crate::input::{InputSource, StdinInput};
+use crate::random::{RandomSource, SeededRandom};
+use crate::hooks::{CoverageHooks, ExecutionHooks, HookId};
+use crate::goose::{self, ErrorKind, ExecutionStats, OutputSink, RatingAdjustment, SilentSink};
+use crate::script_args;
+use crate::server::{HttpResponse, Server};
+use crate::watch::Watcher;
+use crate::websocket;
+use crate::desktop;
+use crate::shell;
+use crate::property::{self, shrink_failure, Generator, Rng};
+use crate::values::{self, Closure, MapKey, Shared, Value};
+
+type ListCell = Shared<Vec<Value>>;
 
 /// Control flow signals for statements
 #[derive(Debug)]
@@ -31,6 +50,8 @@ pub enum ControlFlow {
 pub struct Environment {
     /// Variables in this scope
     values: HashMap<String, Value>,
+    /// Names in this scope declared with `always`, which can't be reassigned
+    constants: HashSet<String>,
     /// Parent scope (if any)
     parent: Option<Rc<RefCell<Environment>>>,
 }
@@ -40,6 +61,7 @@ impl Environment {
     pub fn new() -> Self {
         Environment {
             values: HashMap::new(),
+            constants: HashSet::new(),
             parent: None,
         }
     }
@@ -48,6 +70,7 @@ impl Environment {
     pub fn with_parent(parent: Rc<RefCell<Environment>>) -> Self {
         Environment {
             values: HashMap::new(),
+            constants: HashSet::new(),
             parent: Some(parent),
         }
     }
@@ -57,6 +80,29 @@ impl Environment {
         self.values.insert(name, value);
     }
 
+    /// Define a constant in this scope
+    pub fn define_constant(&mut self, name: String, value: Value) {
+        self.constants.insert(name.clone());
+        self.values.insert(name, value);
+    }
+
+    /// Whether this scope has a variable by this name, constant or not
+    pub fn defines(&self, name: &str) -> bool {
+        self.values.contains_key(name)
+    }
+
+    /// Whether the variable this name refers to (the nearest one up the
+    /// scope chain) is a constant
+    pub fn is_constant(&self, name: &str) -> bool {
+        if self.values.contains_key(name) {
+            self.constants.contains(name)
+        } else if let Some(ref parent) = self.parent {
+            parent.borrow().is_constant(name)
+        } else {
+            false
+        }
+    }
+
     /// Get a variable, searching up the scope chain
     pub fn get(&self, name: &str) -> Option<Value> {
         if let Some(value) = self.values.get(name) {
@@ -68,6 +114,18 @@ impl Environment {
         }
     }
 
+    /// Names (in this scope and its parents) bound to the list or struct with this identity
+    fn names_holding(&self, identity: usize, names: &mut Vec<String>) {
+        for (name, value) in &self.values {
+            if value.identity() == Some(identity) && !names.contains(name) {
+                names.push(name.clone());
+            }
+        }
+        if let Some(ref parent) = self.parent {
+            parent.borrow().names_holding(identity, names);
+        }
+    }
+
     /// Assign to an existing variable in any scope
     pub fn assign(&mut self, name: &str, value: Value) -> bool {
         if self.values.contains_key(name) {
@@ -87,9 +145,28 @@ impl Default for Environment {
     }
 }
 
+/// A saved copy of an interpreter's globals and stats (see `Interpreter::snapshot`)
+#[derive(Debug, Clone)]
+pub struct Snapshot {
+    globals: HashMap<String, Value>,
+    constants: HashSet<String>,
+    stats: ExecutionStats,
+    imported_files: HashSet<PathBuf>,
+}
+
 /// Default instruction limit (10 million instructions)
 const DEFAULT_INSTRUCTION_LIMIT: usize = 10_000_000;
 
+/// How often hot reload looks at the watched file while the program runs
+const RELOAD_INTERVAL: Duration = Duration::from_millis(250);
+
+/// The file hot reload takes new function definitions from
+struct HotReload {
+    path: String,
+    watcher: Watcher,
+    next_look: Instant,
+}
+
 /// The interpreter
 pub struct Interpreter {
     /// Global environment
@@ -102,6 +179,46 @@ pub struct Interpreter {
     max_instructions: Option<usize>,
     /// Files already imported (to prevent circular imports)
     imported_files: HashSet<PathBuf>,
+    /// Receives refusals, success messages, and the final rating
+    sink: Box<dyn OutputSink>,
+    /// Registered execution hooks, in the order they were added
+    hooks: Vec<(HookId, Box<dyn ExecutionHooks>)>,
+    /// Id for the next `add_hooks`
+    next_hook_id: usize,
+    /// Whether the error now propagating has been passed to `on_error` yet
+    error_reported: bool,
+    /// Executed quack/honk lines, while coverage is being recorded
+    coverage: Option<(HookId, Rc<RefCell<Coverage>>)>,
+    /// Lines already warned about, while aliasing detection is on
+    alias_warnings: Option<HashSet<usize>>,
+    /// The lines in `stats.skipped_blocks`, so a refusal can be checked for without a scan
+    refused_lines: HashSet<usize>,
+    /// Error instead of quietly producing infinity or NaN
+    strict_math: bool,
+    /// Where `now` and `sleep` get their time
+    clock: Box<dyn Clock>,
+    /// Lets another thread stop the program
+    cancel: CancelHandle,
+    /// Real time after which the program is stopped, if there's a time limit
+    deadline: Option<(Instant, Duration)>,
+    /// The code the program asked to leave with, once it has called `exit`
+    exit_code: Option<i32>,
+    /// Where `input` gets its answers
+    input: Box<dyn InputSource>,
+    /// Where `random` gets its numbers
+    random: Box<dyn RandomSource>,
+    /// The command-line arguments, as `parse-args` reads them
+    script_args: Vec<String>,
+    /// Print the value of each top-level expression statement, like the REPL
+    echo_expressions: bool,
+    /// Where to pick up changed function definitions, while hot reload is on
+    hot_reload: Option<HotReload>,
+    /// Which capabilities builtins and migrate may use
+    policy: Policy,
+    /// What `print` wrote, while output is being captured instead of printed
+    captured_output: Option<String>,
+    /// WebSocket connections the program has open, closed when the interpreter goes
+    sockets: websocket::Sockets,
 }
 
 impl Interpreter {
@@ -120,7 +237,7 @@ impl Interpreter {
         env.borrow_mut().define("TAU".to_string(), Value::Number(std::f64::consts::TAU));
 
         // Pre-define command-line arguments as quack-args
-        let args_values: Vec<Value> = args.into_iter().map(Value::String).collect();
+        let args_values: Vec<Value> = args.iter().cloned().map(|s| Value::String(s.into())).collect();
         env.borrow_mut().define("quack-args".to_string(), Value::new_list(args_values));
 
         Interpreter {
@@ -129,16 +246,273 @@ impl Interpreter {
             instruction_count: 0,
             max_instructions: Some(DEFAULT_INSTRUCTION_LIMIT),
             imported_files: HashSet::new(),
+            sink: Box::new(SilentSink),
+            hooks: Vec::new(),
+            next_hook_id: 0,
+            error_reported: false,
+            coverage: None,
+            alias_warnings: None,
+            refused_lines: HashSet::new(),
+            strict_math: false,
+            clock: Box::new(SystemClock),
+            cancel: CancelHandle::default(),
+            deadline: None,
+            exit_code: None,
+            input: Box::new(StdinInput),
+            random: Box::new(SeededRandom::from_time()),
+            script_args: args,
+            echo_expressions: false,
+            hot_reload: None,
+            policy: Policy::all(),
+            captured_output: None,
+            sockets: websocket::Sockets::new(),
+        }
+    }
+
+    /// Keep what the program prints instead of writing it to stdout, until
+    /// turned off again. Collect it with `take_output`.
+    pub fn capture_output(&mut self, capture: bool) {
+        self.captured_output = capture.then(String::new);
+    }
+
+    /// Everything printed since the last call, while output is being captured
+    pub fn take_output(&mut self) -> String {
+        self.captured_output.as_mut().map(std::mem::take).unwrap_or_default()
+    }
+
+    /// Print a line of program output (or keep it, while capturing)
+    fn print_line(&mut self, text: &str) {
+        match &mut self.captured_output {
+            Some(output) => {
+                output.push_str(text);
+                output.push('\n');
+            }
+            None => {
+                println!("{}", text);
+                io::stdout().flush().ok();
+            }
+        }
+    }
+
+    /// Send refusals, warnings, success messages and the rating somewhere. By
+    /// default they go nowhere; `goose::ConsoleSink` prints them as `goose` does.
+    pub fn set_output_sink(&mut self, sink: Box<dyn OutputSink>) {
+        self.sink = sink;
+    }
+
+    /// Print the value of every top-level expression statement `run` executes
+    /// (as `=> value`, skipping nil), so a file of expressions reads like a worksheet
+    pub fn set_echo_expressions(&mut self, echo: bool) {
+        self.echo_expressions = echo;
+    }
+
+    /// Turn strict math on or off. When on, arithmetic or a builtin that turns
+    /// finite numbers into infinity or NaN is an error instead of a result.
+    pub fn set_strict_math(&mut self, strict: bool) {
+        self.strict_math = strict;
+    }
+
+    /// Show an operand in a strict math error, using e-notation for huge numbers
+    fn describe_operand(value: &Value) -> String {
+        match value {
+            Value::Number(n) if n.abs() >= 1e15 => format!("{:e}", n),
+            other => other.to_string(),
+        }
+    }
+
+    /// Under strict math, reject a non-finite result computed from finite inputs
+    fn check_strict_math(&self, result: Value, inputs: &[Value], operation: impl FnOnce() -> String, line: usize) -> Result<Value, String> {
+        let finite_inputs = !inputs.iter().any(|input| matches!(input, Value::Number(n) if !n.is_finite()));
+        match result {
+            Value::Number(n) if self.strict_math && !n.is_finite() && finite_inputs => {
+                let result = if n.is_nan() { "NaN" } else if n > 0.0 { "infinity" } else { "-infinity" };
+                Err(goose::error(
+                    ErrorKind::BadNumber { operation: operation(), result: result.to_string() },
+                    line,
+                    "",
+                ))
+            }
+            result => Ok(result),
         }
     }
 
+    /// Define a global before the program runs, as if it started with
+    /// `let name be value` - e.g. settings an embedder hands to its scripts
+    pub fn define_global(&mut self, name: &str, value: Value) {
+        self.env.borrow_mut().define(name.to_string(), value);
+    }
+
     /// Set the maximum instruction limit (None for unlimited)
     pub fn set_instruction_limit(&mut self, limit: Option<usize>) {
         self.max_instructions = limit;
     }
 
+    /// Use a different clock for `now` and `sleep` - e.g. a `VirtualClock`, so
+    /// tests of timing code finish instantly and give the same answer every run
+    pub fn set_clock(&mut self, clock: Box<dyn Clock>) {
+        self.clock = clock;
+    }
+
+    /// Answer `input()` from somewhere other than the keyboard - e.g. a
+    /// `ReplayInput`, so interactive programs can be tested with canned answers
+    pub fn set_input_source(&mut self, input: Box<dyn InputSource>) {
+        self.input = input;
+    }
+
+    /// Draw `random()` numbers from somewhere else - e.g. a `SeededRandom`
+    /// or a `ReplayRandom`, so a run can be reproduced number for number
+    pub fn set_random_source(&mut self, random: Box<dyn RandomSource>) {
+        self.random = random;
+    }
+
+    /// Limit what the program can reach: files, network, environment, `shell`
+    /// and the desktop. A builtin needing something the policy doesn't allow is an error.
+    pub fn set_policy(&mut self, policy: Policy) {
+        self.policy = policy;
+    }
+
+    pub fn policy(&self) -> &Policy {
+        &self.policy
+    }
+
+    /// A handle that stops this interpreter (at its next statement, or in the
+    /// middle of a sleep) when cancelled, from any thread
+    pub fn cancel_handle(&self) -> CancelHandle {
+        self.cancel.clone()
+    }
+
+    /// Stop the program once `limit` of real time has passed, counting from now
+    /// (None, or a limit too far off for the system clock, for no limit).
+    /// Sleeps are cut short rather than overrunning it.
+    pub fn set_time_limit(&mut self, limit: Option<Duration>) {
+        self.deadline = limit.and_then(|limit| Some((Instant::now().checked_add(limit)?, limit)));
+    }
+
+    /// Keep the functions and methods up to date with `path` while the program
+    /// runs (None to stop). Every so often between statements the file is looked
+    /// at, and if it changed, its definitions are swapped in with
+    /// `reload_functions`. A version that doesn't parse or load is skipped with a note.
+    pub fn set_hot_reload(&mut self, path: Option<&str>) -> Result<(), String> {
+        self.hot_reload = match path {
+            Some(path) => Some(HotReload {
+                path: path.to_string(),
+                watcher: Watcher::new(path)?,
+                next_look: Instant::now() + RELOAD_INTERVAL,
+            }),
+            None => None,
+        };
+        Ok(())
+    }
+
+    /// Replace the functions and methods a program defines with the ones in
+    /// `blocks` (a newer version of it), leaving every other global - and so
+    /// all the program's data - as it is. Functions already stored in a
+    /// variable or list keep their old body. Returns the names redefined.
+    /// If any definition fails, none of them are kept.
+    pub fn reload_functions(&mut self, blocks: &[Block]) -> Result<Vec<String>, String> {
+        let mut globals = Rc::clone(&self.env);
+        loop {
+            let parent = globals.borrow().parent.clone();
+            match parent {
+                Some(parent) => globals = parent,
+                None => break,
+            }
+        }
+        // What the definitions can change, to put back if one of them fails
+        let saved_globals = globals.borrow().values.clone();
+        let saved_methods: Vec<_> = saved_globals
+            .values()
+            .filter_map(|value| match value {
+                Value::StructType { methods, .. } => Some((Rc::clone(methods), methods.borrow().clone())),
+                _ => None,
+            })
+            .collect();
+        let saved_stats = self.stats.clone();
+
+        let old_env = std::mem::replace(&mut self.env, Rc::clone(&globals));
+        let mut reloaded = Vec::new();
+        let mut result = Ok(());
+        for block in blocks.iter().filter(|block| block.was_quacked) {
+            let name = match &block.statement {
+                Statement::FunctionDef { name, .. } => name.clone(),
+                Statement::MethodDef { struct_name, name, .. } => format!("{}.{}", struct_name, name),
+                _ => continue,
+            };
+            result = self.execute_statement(&block.statement, block.line).map(|_| ());
+            if result.is_err() {
+                break;
+            }
+            reloaded.push(name);
+        }
+        self.env = old_env;
+        if result.is_err() {
+            globals.borrow_mut().values = saved_globals;
+            for (methods, saved) in saved_methods {
+                *methods.borrow_mut() = saved;
+            }
+            self.stats = saved_stats;
+            self.refused_lines = self.stats.skipped_blocks.iter().copied().collect();
+        }
+        result.map(|_| reloaded)
+    }
+
+    /// Swap in new definitions if hot reload is on, it's time to look, and the file changed
+    fn check_hot_reload(&mut self) {
+        let Some(hot) = self.hot_reload.as_mut() else { return };
+        if Instant::now() < hot.next_look {
+            return;
+        }
+        hot.next_look = Instant::now() + RELOAD_INTERVAL;
+        if hot.watcher.poll().is_empty() {
+            return;
+        }
+        let path = hot.path.clone();
+        let blocks = std::fs::read_to_string(&path)
+            .map_err(|e| e.to_string())
+            .and_then(|source| ast_cache::parse_cached(&source, &path));
+        let message = match blocks.and_then(|blocks| self.reload_functions(&blocks)) {
+            Ok(names) if names.is_empty() => return,
+            Ok(names) => format!("*goose swaps in the new* {} (from {})", names.join(", "), path),
+            Err(e) => format!("*goose keeps the old definitions* {} changed but didn't load:\n{}", path, e),
+        };
+        self.sink.reloaded(&message);
+    }
+
+    /// Why the program has to stop right now, if it does
+    fn interruption(&self) -> Option<String> {
+        if let Some(code) = self.exit_code {
+            return Some(format!("exit({}) was called - the goose is leaving early", code));
+        }
+        Self::interruption_for(&self.cancel, self.deadline)
+    }
+
+    /// The code the program passed to `exit`, if it called it. `run` stops
+    /// cleanly at that point; turning the code into the process's exit
+    /// status is up to the embedder.
+    pub fn exit_code(&self) -> Option<i32> {
+        self.exit_code
+    }
+
+    /// `interruption` without borrowing the interpreter, for use while the clock is sleeping
+    fn interruption_for(cancel: &CancelHandle, deadline: Option<(Instant, Duration)>) -> Option<String> {
+        if cancel.is_cancelled() {
+            return Some("Execution cancelled - the goose was told to stop, and the goose stopped".to_string());
+        }
+        match deadline {
+            Some((deadline, limit)) if Instant::now() >= deadline => Some(format!(
+                "Time limit exceeded ({:.1}s) - the goose has places to be",
+                limit.as_secs_f64()
+            )),
+            _ => None,
+        }
+    }
+
     /// Check and increment instruction counter
     fn check_instruction_limit(&mut self) -> Result<(), String> {
+        if let Some(reason) = self.interruption() {
+            return Err(reason);
+        }
+        self.check_hot_reload();
         self.instruction_count += 1;
         if let Some(max) = self.max_instructions {
             if self.instruction_count > max {
@@ -158,15 +532,25 @@ impl Interpreter {
         for block in blocks {
             if block.was_quacked {
                 self.stats.quacked_blocks += 1;
-                self.execute_block(&block)?;
+                let result = if !self.echo_expressions {
+                    self.execute_block(&block).map(|_| None)
+                } else {
+                    self.execute_top_level(&block)
+                };
+                match result {
+                    Ok(Some(value)) if !matches!(value, Value::Null) => println!("=> {}", value),
+                    Ok(_) => {}
+                    // Leaving through exit() is the program's choice, not a failure
+                    Err(_) if self.exit_code.is_some() => return Ok(()),
+                    Err(e) => return Err(e),
+                }
             } else {
-                self.stats.unquacked_blocks += 1;
                 // Report the skipped block with a sarcastic message
-                let msg = goose::refusal(block.line, "");
-                eprintln!("{}", msg);
+                self.skip_block(block.line);
             }
         }
 
+        self.sink.success(&goose::success());
         Ok(())
     }
 
@@ -177,23 +561,31 @@ impl Interpreter {
 
         if block.was_quacked {
             self.stats.quacked_blocks += 1;
+            self.execute_top_level(&block)
+        } else {
+            self.skip_block(block.line);
+            Ok(None)
+        }
+    }
 
-            // For expression statements, we want to return the value
-            match &block.statement {
-                Statement::Expression(expr) => {
-                    let value = self.evaluate(expr, block.line)?;
-                    Ok(Some(value))
-                }
-                _ => {
-                    self.execute_block(&block)?;
-                    Ok(None)
+    /// Run a quacked top-level block, giving back the value if it was an expression statement
+    fn execute_top_level(&mut self, block: &Block) -> Result<Option<Value>, String> {
+        match &block.statement {
+            Statement::Expression(expr) => {
+                self.error_reported = false;
+                self.fire_hooks(|hooks| hooks.on_statement(block.line, &block.statement));
+                match self.evaluate(expr, block.line) {
+                    Ok(value) => Ok(Some(value)),
+                    Err(error) => {
+                        self.report_error(block.line, &error);
+                        Err(error)
+                    }
                 }
             }
-        } else {
-            self.stats.unquacked_blocks += 1;
-            let msg = goose::refusal(block.line, "");
-            eprintln!("{}", msg);
-            Ok(None)
+            _ => {
+                self.execute_block(block)?;
+                Ok(None)
+            }
         }
     }
 
@@ -202,9 +594,278 @@ impl Interpreter {
         &self.stats
     }
 
+    /// Run a body of blocks in the global scope, skipping unquacked ones
+    pub fn run_body(&mut self, blocks: &[Block]) -> Result<(), String> {
+        self.execute_blocks(blocks).map(|_| ())
+    }
+
+    /// Parse and run a program's top level once, defining its functions and
+    /// globals (without the closing rating). Pair with `call` to use a script
+    /// as, say, a request handler without parsing it again for every request.
+    pub fn load(&mut self, source: &str) -> Result<(), String> {
+        let blocks = ast_cache::parse_cached(source, "<loaded script>")?;
+        self.run_body(&blocks)
+    }
+
+    /// Call a function by name - one the loaded program defined, or a builtin.
+    /// Each call starts a fresh instruction budget, so a long-lived interpreter
+    /// doesn't hit the infinite-loop limit after enough calls. Globals the
+    /// function changes stay changed for the next call.
+    pub fn call(&mut self, name: &str, args: Vec<Value>) -> Result<Value, String> {
+        let func = if let Some(builtin) = Builtin::lookup(name) {
+            Value::BuiltinFunction(builtin)
+        } else {
+            self.env
+                .borrow()
+                .get(name)
+                .ok_or_else(|| goose::error(ErrorKind::UnknownVariable(name.to_string()), 0, ""))?
+        };
+        self.instruction_count = 0;
+        self.error_reported = false;
+        self.call_function(func, args, 0)
+    }
+
+    /// A new interpreter that starts from this one's globals: functions are
+    /// shared as they are, while lists, maps, structs and struct methods are
+    /// copied, so whatever the child changes or defines stays in the child.
+    /// Load a prelude once, then give each request or user expression its own
+    /// child. Values aren't thread-safe, so use a child on the thread that made it.
+    pub fn child(&self) -> Interpreter {
+        let mut child = Interpreter::with_args(self.script_args.clone());
+        let mut copied = HashMap::new();
+        let mut env = Environment::new();
+        for (name, value) in &self.env.borrow().values {
+            let value = match value {
+                Value::StructType { name, fields, methods } => Value::StructType {
+                    name: name.clone(),
+                    fields: fields.clone(),
+                    methods: Rc::new(RefCell::new(methods.borrow().clone())),
+                },
+                other => other.deep_clone_with(&mut copied),
+            };
+            env.define(name.clone(), value);
+        }
+        env.constants = self.env.borrow().constants.clone();
+        child.env = Rc::new(RefCell::new(env));
+        child.imported_files = self.imported_files.clone();
+        child.max_instructions = self.max_instructions;
+        child.strict_math = self.strict_math;
+        child.policy = self.policy.clone();
+        child.echo_expressions = self.echo_expressions;
+        child
+    }
+
+    /// Register hooks to be called as the program runs
+    pub fn add_hooks(&mut self, hooks: Box<dyn ExecutionHooks>) -> HookId {
+        let id = HookId(self.next_hook_id);
+        self.next_hook_id += 1;
+        self.hooks.push((id, hooks));
+        id
+    }
+
+    /// Unregister hooks added with `add_hooks`, handing them back
+    pub fn remove_hooks(&mut self, id: HookId) -> Option<Box<dyn ExecutionHooks>> {
+        let index = self.hooks.iter().position(|(hook_id, _)| *hook_id == id)?;
+        Some(self.hooks.remove(index).1)
+    }
+
+    /// Call every registered hook
+    fn fire_hooks(&mut self, mut event: impl FnMut(&mut dyn ExecutionHooks)) {
+        for (_, hooks) in &mut self.hooks {
+            event(hooks.as_mut());
+        }
+    }
+
+    /// Start recording which quacked blocks and honks execute
+    pub fn start_coverage(&mut self) {
+        self.take_coverage();
+        let coverage = Rc::new(RefCell::new(Coverage::default()));
+        let id = self.add_hooks(Box::new(CoverageHooks(Rc::clone(&coverage))));
+        self.coverage = Some((id, coverage));
+    }
+
+    /// Stop recording and return what ran since `start_coverage`
+    pub fn take_coverage(&mut self) -> Coverage {
+        match self.coverage.take() {
+            Some((id, coverage)) => {
+                self.remove_hooks(id);
+                coverage.take()
+            }
+            None => Coverage::default(),
+        }
+    }
+
+    /// Warn (through the output sink) whenever a list or struct is changed
+    /// through one variable while other variables hold the very same value
+    pub fn detect_aliasing(&mut self) {
+        self.alias_warnings = Some(HashSet::new());
+    }
+
+    /// If aliasing detection is on and `target` is a variable whose list or
+    /// struct is also held by other variables, warn that they all see the change
+    fn check_aliasing(&mut self, target: &Expr, value: &Value, line: usize) {
+        let (Some(warned), Expr::Identifier(name), Some(identity)) =
+            (&mut self.alias_warnings, target, value.identity())
+        else {
+            return;
+        };
+        if warned.contains(&line) {
+            return;
+        }
+
+        let mut names = Vec::new();
+        self.env.borrow().names_holding(identity, &mut names);
+        names.retain(|other| other != name);
+        if names.is_empty() {
+            return;
+        }
+        names.sort();
+        warned.insert(line);
+
+        let others: Vec<String> = names.iter().map(|other| format!("'{}'", other)).collect();
+        let message = format!(
+            "changing '{}' also changes {} - they're the same {}. Use copy({}) if you wanted a separate one.",
+            name,
+            others.join(" and "),
+            value.type_name(),
+            names[0]
+        );
+        self.sink.warning(line, &message);
+    }
+
+    /// Capture the global environment and stats. Values are deep-cloned, so
+    /// later mutations to lists and structs don't leak into the snapshot.
+    pub fn snapshot(&self) -> Snapshot {
+        let mut copied = HashMap::new();
+        let globals = self
+            .env
+            .borrow()
+            .values
+            .iter()
+            .map(|(name, value)| (name.clone(), value.deep_clone_with(&mut copied)))
+            .collect();
+        Snapshot {
+            globals,
+            constants: self.env.borrow().constants.clone(),
+            stats: self.stats.clone(),
+            imported_files: self.imported_files.clone(),
+        }
+    }
+
+    /// Roll the globals and stats back to a snapshot. The snapshot itself is
+    /// left untouched, so it can be restored again later. What runs next gets
+    /// a fresh instruction budget, and the time limit (if any) starts again.
+    pub fn restore(&mut self, snapshot: &Snapshot) {
+        self.instruction_count = 0;
+        self.error_reported = false;
+        if let Some((_, limit)) = self.deadline {
+            self.set_time_limit(Some(limit));
+        }
+        let mut copied = HashMap::new();
+        let mut env = Environment::new();
+        for (name, value) in &snapshot.globals {
+            env.define(name.clone(), value.deep_clone_with(&mut copied));
+        }
+        env.constants = snapshot.constants.clone();
+        self.env = Rc::new(RefCell::new(env));
+        self.stats = snapshot.stats.clone();
+        self.refused_lines = self.stats.skipped_blocks.iter().copied().collect();
+        self.imported_files = snapshot.imported_files.clone();
+    }
+
+    /// Rate the run so far and hand the verdict to the output sink
+    pub fn rate(&mut self) -> (u8, String) {
+        let (score, quip) = goose::rate_code(&self.stats);
+        self.sink.rating(score, &quip, &self.stats);
+        (score, quip)
+    }
+
     /// Execute a single block
     fn execute_block(&mut self, block: &Block) -> Result<ControlFlow, String> {
-        self.execute_statement(&block.statement, block.line)
+        // A new statement starting means any earlier error was dealt with
+        self.error_reported = false;
+        self.fire_hooks(|hooks| hooks.on_statement(block.line, &block.statement));
+        let result = self.execute_statement(&block.statement, block.line);
+        if let Err(error) = &result {
+            self.report_error(block.line, error);
+        }
+        result
+    }
+
+    /// Pass an error to `on_error`, unless an inner statement already did
+    fn report_error(&mut self, line: usize, error: &str) {
+        if !self.error_reported && self.exit_code.is_none() {
+            self.error_reported = true;
+            self.fire_hooks(|hooks| hooks.on_error(line, error));
+        }
+    }
+
+    /// Record and announce a block skipped for missing its quack
+    fn skip_block(&mut self, line: usize) {
+        self.stats.unquacked_blocks += 1;
+        self.stats.skipped_blocks.push(line);
+        self.refused_lines.insert(line);
+        let msg = goose::refusal(line, "");
+        self.sink.refusal(line, &msg);
+        self.fire_hooks(|hooks| hooks.on_block_skipped(line));
+    }
+
+    /// Execute an if. `otherwise if` parses as a nested if in the otherwise
+    /// block, so a chain is followed here in a loop rather than one
+    /// `execute_block` deeper per branch, doing what that would for each.
+    fn execute_if(&mut self, mut stmt: &Statement, mut line: usize) -> Result<ControlFlow, String> {
+        let mut nested = false;
+        loop {
+            let Statement::If { condition, then_block, otherwise_block } = stmt else {
+                unreachable!("execute_if is only called with an if");
+            };
+            let checked = match nested {
+                false => self.evaluate(condition, line),
+                true => self.check_instruction_limit().and_then(|_| self.evaluate(condition, line)),
+            };
+            let cond_value = match checked {
+                Ok(value) => value,
+                Err(error) => {
+                    if nested {
+                        self.report_error(line, &error);
+                    }
+                    return Err(error);
+                }
+            };
+            if cond_value.is_truthy() {
+                return self.execute_blocks(then_block);
+            }
+            match otherwise_block.as_deref() {
+                Some([block]) if block.was_quacked && matches!(block.statement, Statement::If { .. }) => {
+                    self.error_reported = false;
+                    self.fire_hooks(|hooks| hooks.on_statement(block.line, &block.statement));
+                    stmt = &block.statement;
+                    line = block.line;
+                    nested = true;
+                }
+                Some(blocks) => return self.execute_blocks(blocks),
+                None => return Ok(ControlFlow::None),
+            }
+        }
+    }
+
+    /// A constant can be shadowed in an inner scope, but not redefined in its own,
+    /// whether by `let` or by `define`
+    fn refuse_redefining_constant(&self, name: &str, line: usize) -> Result<(), String> {
+        let env = self.env.borrow();
+        if env.defines(name) && env.is_constant(name) {
+            return Err(goose::error(ErrorKind::ConstantReassignment(name.to_string()), line, ""));
+        }
+        Ok(())
+    }
+
+    /// Builtins are resolved when the program is parsed, so a variable or
+    /// function sharing a builtin's name could never be reached
+    fn refuse_builtin_name(name: &str, line: usize) -> Result<(), String> {
+        if Builtin::lookup(name).is_some() {
+            return Err(goose::error(ErrorKind::BuiltinName(name.to_string()), line, ""));
+        }
+        Ok(())
     }
 
     /// Execute a statement
@@ -213,9 +874,38 @@ impl Interpreter {
         self.check_instruction_limit()?;
 
         match stmt {
-            Statement::Let { name, value } => {
+            Statement::Let { name, value, constant } => {
+                Self::refuse_builtin_name(name, line)?;
+                let val = self.evaluate(value, line)?;
+                self.refuse_redefining_constant(name, line)?;
+                let mut env = self.env.borrow_mut();
+                if *constant {
+                    env.define_constant(name.clone(), val);
+                } else {
+                    env.define(name.clone(), val);
+                }
+                Ok(ControlFlow::None)
+            }
+
+            Statement::LetPattern { pattern, value, constant } => {
                 let val = self.evaluate(value, line)?;
-                self.env.borrow_mut().define(name.clone(), val);
+                let bindings = self
+                    .match_pattern(pattern, &val)
+                    .ok_or_else(|| Self::unpack_mismatch("let", pattern, &val, line))?;
+                for name in bindings.keys() {
+                    Self::refuse_builtin_name(name, line)?;
+                }
+                let mut env = self.env.borrow_mut();
+                if let Some(name) = bindings.keys().find(|name| env.defines(name) && env.is_constant(name)) {
+                    return Err(goose::error(ErrorKind::ConstantReassignment(name.clone()), line, ""));
+                }
+                for (name, val) in bindings {
+                    if *constant {
+                        env.define_constant(name, val);
+                    } else {
+                        env.define(name, val);
+                    }
+                }
                 Ok(ControlFlow::None)
             }
 
@@ -225,6 +915,11 @@ impl Interpreter {
                 Ok(ControlFlow::None)
             }
 
+            Statement::CompoundAssign { target, operator, amount } => {
+                self.compound_assign(target, operator, amount, line)?;
+                Ok(ControlFlow::None)
+            }
+
             Statement::Expression(expr) => {
                 self.evaluate(expr, line)?;
                 Ok(ControlFlow::None)
@@ -232,7 +927,7 @@ impl Interpreter {
 
             Statement::Print(expr) => {
                 let value = self.evaluate(expr, line)?;
-                println!("{}", value);
+                self.print_line(&value.to_string());
                 Ok(ControlFlow::None)
             }
 
@@ -240,49 +935,77 @@ impl Interpreter {
                 let child_env = Environment::with_parent(Rc::clone(&self.env));
                 let old_env = std::mem::replace(&mut self.env, Rc::new(RefCell::new(child_env)));
 
-                let result = self.execute_statements(stmts, line);
+                let result = self.execute_blocks(stmts);
 
                 self.env = old_env;
                 result
             }
 
-            Statement::FunctionDef { name, params, body } => {
+            Statement::FunctionDef { name, params, rest, body } => {
+                for name in std::iter::once(name).chain(params).chain(rest) {
+                    Self::refuse_builtin_name(name, line)?;
+                }
+                self.refuse_redefining_constant(name, line)?;
                 self.stats.functions_defined += 1;
                 let closure = self.create_closure();
                 let func = Value::Function {
                     name: name.clone(),
                     params: params.clone(),
-                    body: self.statements_to_blocks(body, line),
+                    rest: rest.clone(),
+                    body: Arc::clone(body),
                     closure,
                 };
                 self.env.borrow_mut().define(name.clone(), func);
                 Ok(ControlFlow::None)
             }
 
-            Statement::If { condition, then_block, otherwise_block } => {
-                let cond_value = self.evaluate(condition, line)?;
-                if cond_value.is_truthy() {
-                    self.execute_statements(then_block, line)
-                } else if let Some(else_stmts) = otherwise_block {
-                    self.execute_statements(else_stmts, line)
-                } else {
-                    Ok(ControlFlow::None)
-                }
+            Statement::MethodDef { struct_name, name, params, rest, body } => {
+                let methods = match self.env.borrow().get(struct_name) {
+                    Some(Value::StructType { fields, .. }) if fields.contains(name) => {
+                        return Err(format!(
+                            "Line {}: '{}' already has a field called '{}', so a method can't use that name too",
+                            line, struct_name, name
+                        ));
+                    }
+                    // A constant struct can gain methods, but not have its own ones replaced
+                    Some(Value::StructType { methods, .. })
+                        if methods.borrow().contains_key(name) && self.env.borrow().is_constant(struct_name) =>
+                    {
+                        let method = format!("{}.{}", struct_name, name);
+                        return Err(goose::error(ErrorKind::ConstantReassignment(method), line, ""));
+                    }
+                    Some(Value::StructType { methods, .. }) => methods,
+                    _ => {
+                        return Err(goose::error(
+                            ErrorKind::UnknownVariable(struct_name.clone()),
+                            line,
+                            "define the struct before its methods",
+                        ));
+                    }
+                };
+                self.stats.functions_defined += 1;
+                let closure = self.create_closure();
+                let method = Value::Function {
+                    name: format!("{}.{}", struct_name, name),
+                    params: std::iter::once("self".to_string()).chain(params.iter().cloned()).collect(),
+                    rest: rest.clone(),
+                    body: Arc::clone(body),
+                    closure,
+                };
+                methods.borrow_mut().insert(name.clone(), method);
+                Ok(ControlFlow::None)
             }
 
+            Statement::If { .. } => self.execute_if(stmt, line),
+
             Statement::Match { value, arms } => {
                 let val = self.evaluate(value, line)?;
                 for arm in arms {
-                    if let Some(bindings) = self.match_pattern(&arm.pattern, &val) {
-                        // Create new scope with pattern bindings
-                        let child_env = Rc::new(RefCell::new(Environment::with_parent(Rc::clone(&self.env))));
-                        for (name, binding_value) in bindings {
-                            child_env.borrow_mut().define(name, binding_value);
-                        }
+                    if let Some(child_env) = self.enter_match_arm(arm, &val, line)? {
                         let old_env = std::mem::replace(&mut self.env, child_env);
 
                         let result = if let Some(ref body) = arm.body {
-                            self.execute_statements(body, line)
+                            self.execute_blocks(body)
                         } else {
                             Ok(ControlFlow::None)
                         };
@@ -313,7 +1036,7 @@ impl Interpreter {
                 };
 
                 for _ in 0..n {
-                    match self.execute_statements(body, line)? {
+                    match self.execute_blocks(body)? {
                         ControlFlow::Break => break,
                         ControlFlow::Continue => continue,
                         ControlFlow::Return(v) => return Ok(ControlFlow::Return(v)),
@@ -327,7 +1050,7 @@ impl Interpreter {
             Statement::While { condition, body } => {
                 self.stats.loops_executed += 1;
                 while self.evaluate(condition, line)?.is_truthy() {
-                    match self.execute_statements(body, line)? {
+                    match self.execute_blocks(body)? {
                         ControlFlow::Break => break,
                         ControlFlow::Continue => continue,
                         ControlFlow::Return(v) => return Ok(ControlFlow::Return(v)),
@@ -337,62 +1060,13 @@ impl Interpreter {
                 Ok(ControlFlow::None)
             }
 
-            Statement::ForEach { variable, iterable, body } => {
+            Statement::ForEach { pattern, iterable, body } => {
                 self.stats.loops_executed += 1;
                 let collection = self.evaluate(iterable, line)?;
 
-                match collection {
-                    Value::List(items) => {
-                        let items_borrowed = items.borrow().clone();
-                        for item in items_borrowed {
-                            let child_env = Rc::new(RefCell::new(Environment::with_parent(Rc::clone(&self.env))));
-                            child_env.borrow_mut().define(variable.clone(), item);
-                            let old_env = std::mem::replace(&mut self.env, child_env);
-
-                            match self.execute_statements(body, line)? {
-                                ControlFlow::Break => {
-                                    self.env = old_env;
-                                    break;
-                                }
-                                ControlFlow::Continue => {
-                                    self.env = old_env;
-                                    continue;
-                                }
-                                ControlFlow::Return(v) => {
-                                    self.env = old_env;
-                                    return Ok(ControlFlow::Return(v));
-                                }
-                                ControlFlow::None => {}
-                            }
-
-                            self.env = old_env;
-                        }
-                    }
-                    Value::String(s) => {
-                        for c in s.chars() {
-                            let child_env = Rc::new(RefCell::new(Environment::with_parent(Rc::clone(&self.env))));
-                            child_env.borrow_mut().define(variable.clone(), Value::String(c.to_string()));
-                            let old_env = std::mem::replace(&mut self.env, child_env);
-
-                            match self.execute_statements(body, line)? {
-                                ControlFlow::Break => {
-                                    self.env = old_env;
-                                    break;
-                                }
-                                ControlFlow::Continue => {
-                                    self.env = old_env;
-                                    continue;
-                                }
-                                ControlFlow::Return(v) => {
-                                    self.env = old_env;
-                                    return Ok(ControlFlow::Return(v));
-                                }
-                                ControlFlow::None => {}
-                            }
-
-                            self.env = old_env;
-                        }
-                    }
+                let items: Vec<Value> = match collection {
+                    Value::List(items) => items.borrow().clone(),
+                    Value::String(s) => s.chars().map(|c| Value::String(c.to_string().into())).collect(),
                     _ => {
                         return Err(goose::error(
                             ErrorKind::TypeError {
@@ -403,16 +1077,42 @@ impl Interpreter {
                             "in for-each iterable",
                         ));
                     }
+                };
+
+                for item in items {
+                    let child_env = Rc::new(RefCell::new(Environment::with_parent(Rc::clone(&self.env))));
+                    self.bind_loop_item(&mut child_env.borrow_mut(), pattern, item, line)?;
+                    let old_env = std::mem::replace(&mut self.env, child_env);
+
+                    match self.execute_blocks(body)? {
+                        ControlFlow::Break => {
+                            self.env = old_env;
+                            break;
+                        }
+                        ControlFlow::Continue => {
+                            self.env = old_env;
+                            continue;
+                        }
+                        ControlFlow::Return(v) => {
+                            self.env = old_env;
+                            return Ok(ControlFlow::Return(v));
+                        }
+                        ControlFlow::None => {}
+                    }
+
+                    self.env = old_env;
                 }
 
                 Ok(ControlFlow::None)
             }
 
             Statement::StructDef { name, fields } => {
+                self.refuse_redefining_constant(name, line)?;
                 self.stats.structs_defined += 1;
                 let struct_type = Value::StructType {
                     name: name.clone(),
                     fields: fields.clone(),
+                    methods: Rc::new(RefCell::new(HashMap::new())),
                 };
                 self.env.borrow_mut().define(name.clone(), struct_type);
                 Ok(ControlFlow::None)
@@ -449,8 +1149,11 @@ impl Interpreter {
                 let list_val = self.evaluate(list, line)?;
                 let item = self.evaluate(value, line)?;
 
-                match list_val {
+                match &list_val {
                     Value::List(items) => {
+                        list_val.ensure_mutable()
+                            .map_err(|e| goose::error(ErrorKind::InvalidOperation(e), line, "in push statement"))?;
+                        self.check_aliasing(list, &list_val, line);
                         items.borrow_mut().push(item);
                         Ok(ControlFlow::None)
                     }
@@ -467,17 +1170,19 @@ impl Interpreter {
 
             Statement::Attempt { try_block, rescue_var, rescue_block } => {
                 // Try to execute the try_block
-                let result = self.execute_statements(try_block, line);
+                let result = self.execute_blocks(try_block);
 
                 match result {
                     Ok(flow) => Ok(flow),
+                    // Being cancelled or running out of time isn't the program's error to rescue
+                    Err(error_msg) if self.interruption().is_some() => Err(error_msg),
                     Err(error_msg) => {
                         // Error occurred, execute rescue block with error bound to rescue_var
                         let child_env = Rc::new(RefCell::new(Environment::with_parent(Rc::clone(&self.env))));
-                        child_env.borrow_mut().define(rescue_var.clone(), Value::String(error_msg));
+                        child_env.borrow_mut().define(rescue_var.clone(), Value::String(error_msg.into()));
                         let old_env = std::mem::replace(&mut self.env, child_env);
 
-                        let rescue_result = self.execute_statements(rescue_block, line);
+                        let rescue_result = self.execute_blocks(rescue_block);
 
                         self.env = old_env;
                         rescue_result
@@ -489,11 +1194,20 @@ impl Interpreter {
                 self.execute_migrate(path, alias.as_ref(), line)?;
                 Ok(ControlFlow::None)
             }
+
+            // Tests and their fixtures only run under `goose test`
+            Statement::Test { .. } | Statement::BeforeEach(_) | Statement::AfterEach(_) => {
+                Ok(ControlFlow::None)
+            }
         }
     }
 
     /// Execute a migrate statement - import code from another Duck file
-    fn execute_migrate(&mut self, path: &str, alias: Option<&String>, _line: usize) -> Result<(), String> {
+    fn execute_migrate(&mut self, path: &str, alias: Option<&String>, line: usize) -> Result<(), String> {
+        self.policy
+            .check(Capability::FsRead, "migrate")
+            .map_err(|e| goose::error(ErrorKind::InvalidOperation(e), line, ""))?;
+
         // Check if this is a git library reference (git+user/repo)
         let file_path = if path.starts_with("git+") {
             self.resolve_git_library(path)?
@@ -520,14 +1234,8 @@ impl Interpreter {
             format!("The goose couldn't read '{}': {}", path, e)
         })?;
 
-        // Lex and parse
-        let tokens = lexer::lex(&source).map_err(|e| {
-            format!("Syntax error in '{}': {}", path, e)
-        })?;
-        let mut parser = parser::Parser::new(tokens);
-        let blocks = parser.parse().map_err(|errors| {
-            format!("Parse error in '{}': {}", path, errors.join(", "))
-        })?;
+        // Lex and parse (reusing the parse if this exact source was seen before)
+        let blocks = ast_cache::parse_cached(&source, path)?;
 
         // Execute the blocks and collect definitions
         if let Some(namespace) = alias {
@@ -536,7 +1244,7 @@ impl Interpreter {
             let old_env = std::mem::replace(&mut self.env, child_env);
 
             // Execute all blocks
-            for block in &blocks {
+            for block in blocks.iter() {
                 if block.was_quacked {
                     if let Err(e) = self.execute_block(block) {
                         self.env = old_env;
@@ -556,7 +1264,7 @@ impl Interpreter {
             println!("The flock has arrived from '{}' as {}!", path, namespace);
         } else {
             // Without alias: execute directly in current scope (definitions become globals)
-            for block in &blocks {
+            for block in blocks.iter() {
                 if block.was_quacked {
                     self.execute_block(block)?;
                 }
@@ -650,10 +1358,14 @@ impl Interpreter {
         Ok(full_path)
     }
 
-    /// Execute multiple statements
-    fn execute_statements(&mut self, stmts: &[Statement], line: usize) -> Result<ControlFlow, String> {
-        for stmt in stmts {
-            match self.execute_statement(stmt, line)? {
+    /// Execute the blocks of a body, skipping any that weren't quacked
+    fn execute_blocks(&mut self, blocks: &[Block]) -> Result<ControlFlow, String> {
+        for block in blocks {
+            if !block.was_quacked {
+                self.refuse_nested_block(block);
+                continue;
+            }
+            match self.execute_block(block)? {
                 ControlFlow::None => {}
                 other => return Ok(other),
             }
@@ -661,16 +1373,12 @@ impl Interpreter {
         Ok(ControlFlow::None)
     }
 
-    /// Convert statements to blocks (for function body storage)
-    fn statements_to_blocks(&self, stmts: &[Statement], line: usize) -> Vec<Block> {
-        stmts
-            .iter()
-            .map(|s| Block {
-                statement: s.clone(),
-                was_quacked: true,
-                line,
-            })
-            .collect()
+    /// Refuse an unquacked block inside a body. Bodies can run many times
+    /// (loops, repeated calls), so each line is only complained about once.
+    fn refuse_nested_block(&mut self, block: &Block) {
+        if !self.refused_lines.contains(&block.line) {
+            self.skip_block(block.line);
+        }
     }
 
     /// Create a closure capturing the current environment
@@ -684,6 +1392,9 @@ impl Interpreter {
     fn assign_to_target(&mut self, target: &AssignTarget, value: Value, line: usize) -> Result<(), String> {
         match target {
             AssignTarget::Variable(name) => {
+                if self.env.borrow().is_constant(name) {
+                    return Err(goose::error(ErrorKind::ConstantReassignment(name.clone()), line, ""));
+                }
                 if !self.env.borrow_mut().assign(name, value.clone()) {
                     // Variable doesn't exist yet, define it
                     self.env.borrow_mut().define(name.clone(), value);
@@ -692,66 +1403,181 @@ impl Interpreter {
             }
             AssignTarget::Field { object, field } => {
                 let obj_val = self.evaluate(object, line)?;
-                match obj_val {
-                    Value::Struct { fields, .. } => {
-                        fields.borrow_mut().insert(field.clone(), value);
-                        Ok(())
-                    }
-                    _ => Err(goose::error(
-                        ErrorKind::InvalidFieldAccess {
-                            type_name: obj_val.type_name().to_string(),
-                            field: field.clone(),
-                        },
-                        line,
-                        "",
-                    )),
-                }
+                self.set_field(object, obj_val, field, value, line)
             }
             AssignTarget::Index { object, index } => {
                 let obj_val = self.evaluate(object, line)?;
                 let idx_val = self.evaluate(index, line)?;
+                self.set_index(object, obj_val, idx_val, value, line)
+            }
+        }
+    }
 
-                match (&obj_val, &idx_val) {
-                    (Value::List(items), Value::Number(n)) => {
-                        let idx = *n as i64;
-                        let mut items_mut = items.borrow_mut();
-                        let len = items_mut.len();
-                        let actual_idx = if idx < 0 {
-                            (len as i64 + idx) as usize
-                        } else {
-                            idx as usize
-                        };
-
-                        if actual_idx >= len {
-                            return Err(goose::error(
-                                ErrorKind::IndexOutOfBounds { index: idx, len },
-                                line,
-                                "",
-                            ));
-                        }
+    /// `x grows by n`: the target's object and index are evaluated once,
+    /// read, combined with the amount and written back
+    fn compound_assign(&mut self, target: &AssignTarget, operator: &BinaryOp, amount: &Expr, line: usize) -> Result<(), String> {
+        match target {
+            AssignTarget::Variable(name) => {
+                let current = self.evaluate(&Expr::Identifier(name.clone()), line)?;
+                let amount = self.evaluate(amount, line)?;
+                let value = self.apply_binary_op(operator, current, amount, line)?;
+                self.assign_to_target(target, value, line)
+            }
+            AssignTarget::Field { object, field } => {
+                let obj_val = self.evaluate(object, line)?;
+                let current = self.get_field(obj_val.clone(), field, line)?;
+                let amount = self.evaluate(amount, line)?;
+                let value = self.apply_binary_op(operator, current, amount, line)?;
+                self.set_field(object, obj_val, field, value, line)
+            }
+            AssignTarget::Index { object, index } => {
+                let obj_val = self.evaluate(object, line)?;
+                let idx_val = self.evaluate(index, line)?;
+                let current = self.index_into(obj_val.clone(), idx_val.clone(), line)?;
+                let amount = self.evaluate(amount, line)?;
+                let value = self.apply_binary_op(operator, current, amount, line)?;
+                self.set_index(object, obj_val, idx_val, value, line)
+            }
+        }
+    }
 
-                        items_mut[actual_idx] = value;
-                        Ok(())
-                    }
-                    (Value::List(_), _) => Err(goose::error(
-                        ErrorKind::TypeError {
-                            expected: "number".to_string(),
-                            got: idx_val.type_name().to_string(),
-                        },
-                        line,
-                        "in index",
-                    )),
-                    _ => Err(goose::error(
-                        ErrorKind::TypeError {
-                            expected: "list".to_string(),
-                            got: obj_val.type_name().to_string(),
-                        },
+    /// Write a struct field; `object` is the expression `obj_val` came from,
+    /// used only for the aliasing warning
+    fn set_field(&mut self, object: &Expr, obj_val: Value, field: &str, value: Value, line: usize) -> Result<(), String> {
+        match &obj_val {
+            Value::Struct { fields, .. } => {
+                obj_val.ensure_mutable()
+                    .map_err(|e| goose::error(ErrorKind::InvalidOperation(e), line, &format!("setting field '{}'", field)))?;
+                self.check_aliasing(object, &obj_val, line);
+                fields.borrow_mut().insert(field.to_string(), value);
+                Ok(())
+            }
+            _ => Err(goose::error(
+                ErrorKind::InvalidFieldAccess {
+                    type_name: obj_val.type_name().to_string(),
+                    field: field.to_string(),
+                },
+                line,
+                "",
+            )),
+        }
+    }
+
+    /// Write a list element or map entry; `object` is the expression
+    /// `obj_val` came from, used only for the aliasing warning
+    fn set_index(&mut self, object: &Expr, obj_val: Value, idx_val: Value, value: Value, line: usize) -> Result<(), String> {
+        match (&obj_val, &idx_val) {
+            (Value::List(items), Value::Number(n)) => {
+                obj_val.ensure_mutable()
+                    .map_err(|e| goose::error(ErrorKind::InvalidOperation(e), line, "in index assignment"))?;
+                self.check_aliasing(object, &obj_val, line);
+                let idx = *n as i64;
+                let mut items_mut = items.borrow_mut();
+                let len = items_mut.len();
+                let actual_idx = if idx < 0 {
+                    (len as i64 + idx) as usize
+                } else {
+                    idx as usize
+                };
+
+                if actual_idx >= len {
+                    return Err(goose::error(
+                        ErrorKind::IndexOutOfBounds { index: idx, len },
                         line,
-                        "for indexing",
-                    )),
+                        "",
+                    ));
+                }
+
+                items_mut[actual_idx] = value;
+                Ok(())
+            }
+            (Value::Map(map), key) => {
+                obj_val.ensure_mutable()
+                    .map_err(|e| goose::error(ErrorKind::InvalidOperation(e), line, "in index assignment"))?;
+                self.check_aliasing(object, &obj_val, line);
+                let key = MapKey::from_value(key)
+                    .map_err(|e| goose::error(ErrorKind::InvalidOperation(e), line, "in index assignment"))?;
+                map.borrow_mut().insert(key, value);
+                Ok(())
+            }
+            (Value::List(_), _) => Err(goose::error(
+                ErrorKind::TypeError {
+                    expected: "number".to_string(),
+                    got: idx_val.type_name().to_string(),
+                },
+                line,
+                "in index",
+            )),
+            _ => Err(goose::error(
+                ErrorKind::TypeError {
+                    expected: "list or map".to_string(),
+                    got: obj_val.type_name().to_string(),
+                },
+                line,
+                "for indexing",
+            )),
+        }
+    }
+
+    /// Check one match arm against a value: if the pattern fits and the guard
+    /// (if any) holds, return the scope holding the pattern's bindings
+    fn enter_match_arm(&mut self, arm: &MatchArm, value: &Value, line: usize) -> Result<Option<Rc<RefCell<Environment>>>, String> {
+        let Some(bindings) = self.match_pattern(&arm.pattern, value) else {
+            return Ok(None);
+        };
+        let child_env = Rc::new(RefCell::new(Environment::with_parent(Rc::clone(&self.env))));
+        for (name, binding_value) in bindings {
+            child_env.borrow_mut().define(name, binding_value);
+        }
+
+        if let Some(guard) = &arm.guard {
+            let old_env = std::mem::replace(&mut self.env, Rc::clone(&child_env));
+            let passed = self.evaluate(guard, line);
+            self.env = old_env;
+            if !passed?.is_truthy() {
+                return Ok(None);
+            }
+        }
+        Ok(Some(child_env))
+    }
+
+    /// Match a value against a pattern, returning bindings if successful
+    /// Why `let [...] be value` (or `for each [...]`, named by `statement`) couldn't unpack the value
+    fn unpack_mismatch(statement: &str, pattern: &Pattern, value: &Value, line: usize) -> String {
+        match (pattern, value) {
+            (Pattern::List { items, rest }, Value::List(list)) => {
+                let len = list.borrow().len();
+                let fits = if rest.is_some() { len >= items.len() } else { len == items.len() };
+                if fits {
+                    return format!("Line {}: the items in that list don't fit the pattern in '{}'", line, statement);
                 }
+                format!(
+                    "Line {}: '{}' needs {} {} item{} to unpack, but the list has {}",
+                    line,
+                    statement,
+                    if rest.is_some() { "at least" } else { "exactly" },
+                    items.len(),
+                    if items.len() == 1 { "" } else { "s" },
+                    len
+                )
             }
+            (_, other) => format!("Line {}: '{}' can only unpack a list, got {}", line, statement, other.type_name()),
+        }
+    }
+
+    /// Bind one item of a for-each loop, unpacking it if the loop has a list pattern
+    fn bind_loop_item(&self, env: &mut Environment, pattern: &Pattern, item: Value, line: usize) -> Result<(), String> {
+        if let Pattern::Variable(name) = pattern {
+            env.define(name.clone(), item);
+            return Ok(());
+        }
+        let bindings = self
+            .match_pattern(pattern, &item)
+            .ok_or_else(|| Self::unpack_mismatch("for each", pattern, &item, line))?;
+        for (name, value) in bindings {
+            env.define(name, value);
         }
+        Ok(())
     }
 
     /// Match a value against a pattern, returning bindings if successful
@@ -777,10 +1603,14 @@ impl Interpreter {
                 if matches { Some(HashMap::new()) } else { None }
             }
 
-            Pattern::List(patterns) => {
+            Pattern::List { items: patterns, rest } => {
                 if let Value::List(items) = value {
                     let items_borrowed = items.borrow();
-                    if patterns.len() != items_borrowed.len() {
+                    let fits = match rest {
+                        Some(_) => items_borrowed.len() >= patterns.len(),
+                        None => items_borrowed.len() == patterns.len(),
+                    };
+                    if !fits {
                         return None;
                     }
                     let mut all_bindings = HashMap::new();
@@ -788,6 +1618,10 @@ impl Interpreter {
                         let bindings = self.match_pattern(pat, val)?;
                         all_bindings.extend(bindings);
                     }
+                    if let Some(rest) = rest {
+                        let remaining = Value::new_list(items_borrowed[patterns.len()..].to_vec());
+                        all_bindings.extend(self.match_pattern(rest, &remaining)?);
+                    }
                     Some(all_bindings)
                 } else {
                     None
@@ -822,123 +1656,86 @@ impl Interpreter {
         match expr {
             Expr::Literal(lit) => Ok(self.literal_to_value(lit)),
 
-            Expr::Identifier(name) => {
-                // Check for builtin first
-                if builtins::is_builtin(name) {
-                    return Ok(Value::BuiltinFunction(name.clone()));
-                }
-
-                self.env.borrow().get(name).ok_or_else(|| {
-                    goose::error(ErrorKind::UnknownVariable(name.clone()), line, "")
-                })
-            }
+            // Builtin names were resolved by the parser; an AST built by hand
+            // might still name one here
+            Expr::Identifier(name) => self
+                .env
+                .borrow()
+                .get(name)
+                .or_else(|| Builtin::lookup(name).map(Value::BuiltinFunction))
+                .ok_or_else(|| goose::error(ErrorKind::UnknownVariable(name.clone()), line, "")),
 
-            Expr::Binary { left, operator, right } => {
-                let lhs = self.evaluate(left, line)?;
-                let rhs = self.evaluate(right, line)?;
-                self.apply_binary_op(operator, lhs, rhs, line)
-            }
+            Expr::Builtin(builtin) => Ok(Value::BuiltinFunction(*builtin)),
 
-            Expr::Unary { operator, operand } => {
-                let val = self.evaluate(operand, line)?;
-                self.apply_unary_op(operator, val, line)
-            }
+            Expr::Binary { .. } | Expr::Unary { .. } => self.evaluate_operators(expr, line),
 
             Expr::Call { callee, arguments } => {
-                let func = self.evaluate(callee, line)?;
+                // instance.name(args) calls a method unless the struct has a field by that name
                 let mut args = Vec::new();
+                let func = match callee.as_ref() {
+                    Expr::FieldAccess { object, field } | Expr::OptionalFieldAccess { object, field } => {
+                        let obj = self.evaluate(object, line)?;
+                        // person?.greet() is nil, without evaluating the arguments
+                        if matches!(obj, Value::Null) && matches!(callee.as_ref(), Expr::OptionalFieldAccess { .. }) {
+                            return Ok(Value::Null);
+                        }
+                        match self.find_method(&obj, field) {
+                            Some(method) => {
+                                args.push(obj);
+                                method
+                            }
+                            None => self.get_field(obj, field, line)?,
+                        }
+                    }
+                    _ => self.evaluate(callee, line)?,
+                };
                 for arg in arguments {
                     args.push(self.evaluate(arg, line)?);
                 }
+                if let (Expr::Builtin(builtin), Some(target), Some(value)) = (callee.as_ref(), arguments.first(), args.first()) {
+                    if builtin.mutates_first_argument() {
+                        self.check_aliasing(target, value, line);
+                    }
+                }
                 self.call_function(func, args, line)
             }
 
             Expr::FieldAccess { object, field } => {
                 let obj = self.evaluate(object, line)?;
-                match obj {
-                    Value::Struct { fields, name } => {
-                        fields.borrow().get(field).cloned().ok_or_else(|| {
-                            goose::error(
-                                ErrorKind::InvalidFieldAccess {
-                                    type_name: name,
-                                    field: field.clone(),
-                                },
-                                line,
-                                "",
-                            )
-                        })
-                    }
-                    _ => Err(goose::error(
-                        ErrorKind::InvalidFieldAccess {
-                            type_name: obj.type_name().to_string(),
-                            field: field.clone(),
-                        },
-                        line,
-                        "",
-                    )),
-                }
+                self.get_field(obj, field, line)
             }
 
             Expr::Index { object, index } => {
                 let obj = self.evaluate(object, line)?;
                 let idx = self.evaluate(index, line)?;
+                self.index_into(obj, idx, line)
+            }
 
-                match (&obj, &idx) {
-                    (Value::List(items), Value::Number(n)) => {
-                        let i = *n as i64;
-                        let items_borrowed = items.borrow();
-                        let len = items_borrowed.len();
-                        let actual_idx = if i < 0 {
-                            (len as i64 + i) as usize
-                        } else {
-                            i as usize
-                        };
+            Expr::Slice { object, start, end, inclusive } => {
+                let obj = self.evaluate(object, line)?;
+                let start = self.evaluate(start, line)?;
+                let end = match self.evaluate(end, line)? {
+                    // `through -1` goes right to the end
+                    Value::Number(n) if *inclusive && n == -1.0 => Value::Null,
+                    Value::Number(n) if *inclusive => Value::Number(n + 1.0),
+                    end => end,
+                };
+                builtins::call_builtin("slice", vec![obj, start, end])
+                    .map_err(|e| goose::error(ErrorKind::InvalidOperation(e), line, ""))
+            }
 
-                        items_borrowed.get(actual_idx).cloned().ok_or_else(|| {
-                            goose::error(
-                                ErrorKind::IndexOutOfBounds { index: i, len },
-                                line,
-                                "",
-                            )
-                        })
-                    }
-                    (Value::String(s), Value::Number(n)) => {
-                        let i = *n as i64;
-                        let len = s.chars().count();
-                        let actual_idx = if i < 0 {
-                            (len as i64 + i) as usize
-                        } else {
-                            i as usize
-                        };
+            Expr::OptionalFieldAccess { object, field } => match self.evaluate(object, line)? {
+                Value::Null => Ok(Value::Null),
+                obj => self.get_field(obj, field, line),
+            },
 
-                        s.chars().nth(actual_idx)
-                            .map(|c| Value::String(c.to_string()))
-                            .ok_or_else(|| {
-                                goose::error(
-                                    ErrorKind::IndexOutOfBounds { index: i, len },
-                                    line,
-                                    "",
-                                )
-                            })
-                    }
-                    (Value::List(_), _) => Err(goose::error(
-                        ErrorKind::TypeError {
-                            expected: "number".to_string(),
-                            got: idx.type_name().to_string(),
-                        },
-                        line,
-                        "in index",
-                    )),
-                    _ => Err(goose::error(
-                        ErrorKind::TypeError {
-                            expected: "list or string".to_string(),
-                            got: obj.type_name().to_string(),
-                        },
-                        line,
-                        "for indexing",
-                    )),
+            Expr::OptionalIndex { object, index } => match self.evaluate(object, line)? {
+                Value::Null => Ok(Value::Null),
+                obj => {
+                    let idx = self.evaluate(index, line)?;
+                    self.index_into(obj, idx, line)
                 }
-            }
+            },
 
             Expr::List(elements) => {
                 let mut items = Vec::new();
@@ -948,16 +1745,27 @@ impl Interpreter {
                 Ok(Value::new_list(items))
             }
 
+            Expr::MapLiteral(entries) => {
+                let mut map = HashMap::new();
+                for (key_expr, value_expr) in entries {
+                    let key = self.evaluate(key_expr, line)?;
+                    let key = MapKey::from_value(&key)
+                        .map_err(|e| goose::error(ErrorKind::InvalidOperation(e), line, "in map literal"))?;
+                    map.insert(key, self.evaluate(value_expr, line)?);
+                }
+                Ok(Value::new_map(map))
+            }
+
             Expr::Lambda { params, body } => {
                 let closure = self.create_closure();
-                Ok(Value::new_lambda(params.clone(), (**body).clone(), closure))
+                Ok(Value::new_lambda(params.clone(), Arc::clone(body), closure))
             }
 
             Expr::BlockLambda { params, body } => {
                 let closure = self.create_closure();
                 Ok(Value::BlockLambda {
                     params: params.clone(),
-                    body: body.clone(),
+                    body: Arc::clone(body),
                     closure,
                 })
             }
@@ -979,6 +1787,14 @@ impl Interpreter {
                 // Evaluate field values
                 let mut field_values = HashMap::new();
                 for (field_name, field_expr) in fields {
+                    if !expected_fields.contains(field_name) {
+                        let mut known: Vec<&str> = expected_fields.iter().map(String::as_str).collect();
+                        known.sort_unstable();
+                        return Err(format!(
+                            "Unknown field '{}' in struct '{}' at line {} (it has {})",
+                            field_name, name, line, known.join(", ")
+                        ));
+                    }
                     let value = self.evaluate(field_expr, line)?;
                     field_values.insert(field_name.clone(), value);
                 }
@@ -1040,20 +1856,22 @@ impl Interpreter {
                             let val = self.evaluate(e, line)?;
                             result.push_str(&format!("{}", val));
                         }
+                        StringPart::Formatted(e, spec) => {
+                            let val = self.evaluate(e, line)?;
+                            let shown = spec
+                                .apply(&val)
+                                .map_err(|e| goose::error(ErrorKind::InvalidOperation(e), line, "in f-string format"))?;
+                            result.push_str(&shown);
+                        }
                     }
                 }
-                Ok(Value::String(result))
+                Ok(Value::String(result.into()))
             }
 
             Expr::Match { value, arms } => {
                 let val = self.evaluate(value, line)?;
                 for arm in arms {
-                    if let Some(bindings) = self.match_pattern(&arm.pattern, &val) {
-                        // Create scope with bindings
-                        let child_env = Rc::new(RefCell::new(Environment::with_parent(Rc::clone(&self.env))));
-                        for (name, binding_val) in bindings {
-                            child_env.borrow_mut().define(name, binding_val);
-                        }
+                    if let Some(child_env) = self.enter_match_arm(arm, &val, line)? {
                         let old_env = std::mem::replace(&mut self.env, child_env);
 
                         let result = if let Some(ref expr) = arm.expression {
@@ -1076,18 +1894,164 @@ impl Interpreter {
         match lit {
             Literal::Int(n) => Value::Number(*n as f64),
             Literal::Float(n) => Value::Number(*n),
-            Literal::String(s) => Value::String(s.clone()),
+            Literal::String(s) => Value::String(Arc::clone(s)),
             Literal::Bool(b) => Value::Boolean(*b),
             Literal::Nil => Value::Null,
         }
     }
 
+    /// Index a list, string or map: `obj at idx`
+    fn index_into(&self, obj: Value, idx: Value, line: usize) -> Result<Value, String> {
+        match (&obj, &idx) {
+            (Value::List(items), Value::Number(n)) => {
+                let i = *n as i64;
+                let items_borrowed = items.borrow();
+                let len = items_borrowed.len();
+                let actual_idx = if i < 0 {
+                    (len as i64 + i) as usize
+                } else {
+                    i as usize
+                };
+
+                items_borrowed.get(actual_idx).cloned().ok_or_else(|| {
+                    goose::error(
+                        ErrorKind::IndexOutOfBounds { index: i, len },
+                        line,
+                        "",
+                    )
+                })
+            }
+            (Value::String(s), Value::Number(n)) => {
+                let i = *n as i64;
+                let len = s.chars().count();
+                let actual_idx = if i < 0 {
+                    (len as i64 + i) as usize
+                } else {
+                    i as usize
+                };
+
+                s.chars().nth(actual_idx)
+                    .map(|c| Value::String(c.to_string().into()))
+                    .ok_or_else(|| {
+                        goose::error(
+                            ErrorKind::IndexOutOfBounds { index: i, len },
+                            line,
+                            "",
+                        )
+                    })
+            }
+            (Value::Map(map), key) => {
+                let key = MapKey::from_value(key)
+                    .map_err(|e| goose::error(ErrorKind::InvalidOperation(e), line, "in index"))?;
+                map.borrow().get(&key).cloned().ok_or_else(|| goose::error(
+                    ErrorKind::InvalidOperation(format!("the map has no key {}", property::describe(&idx))),
+                    line,
+                    "use map-get for a default",
+                ))
+            }
+            (Value::List(_), _) => Err(goose::error(
+                ErrorKind::TypeError {
+                    expected: "number".to_string(),
+                    got: idx.type_name().to_string(),
+                },
+                line,
+                "in index",
+            )),
+            _ => Err(goose::error(
+                ErrorKind::TypeError {
+                    expected: "list, string or map".to_string(),
+                    got: obj.type_name().to_string(),
+                },
+                line,
+                "for indexing",
+            )),
+        }
+    }
+
+    /// Evaluate a tree of operators with a work list rather than by recursion.
+    /// `1 + 2 + ... + n` parses as one long left spine, `2 ** 2 ** ...` and
+    /// `- - ... 1` as long right ones, and generated code can make any of them
+    /// tens of thousands of operators long. Only operands that aren't operators
+    /// themselves go back through `evaluate`.
+    fn evaluate_operators(&mut self, expr: &Expr, line: usize) -> Result<Value, String> {
+        enum Work<'a> {
+            Evaluate(&'a Expr),
+            /// Combine the top two values
+            Binary(&'a BinaryOp),
+            /// Decide from the top value whether `right` runs at all
+            ShortCircuit(&'a BinaryOp, &'a Expr),
+            Unary(&'a UnaryOp),
+        }
+
+        let mut work = vec![Work::Evaluate(expr)];
+        let mut values: Vec<Value> = Vec::new();
+        while let Some(next) = work.pop() {
+            match next {
+                Work::Evaluate(Expr::Binary { left, operator, right }) => {
+                    if matches!(operator, BinaryOp::Coalesce | BinaryOp::And | BinaryOp::Or) {
+                        work.push(Work::ShortCircuit(operator, right));
+                    } else {
+                        work.push(Work::Binary(operator));
+                        work.push(Work::Evaluate(right));
+                    }
+                    work.push(Work::Evaluate(left));
+                }
+                Work::Evaluate(Expr::Unary { operator, operand }) => {
+                    work.push(Work::Unary(operator));
+                    work.push(Work::Evaluate(operand));
+                }
+                Work::Evaluate(operand) => values.push(self.evaluate(operand, line)?),
+                // The right side only runs if the left doesn't already decide the
+                // answer, and whichever side decided it is the result
+                Work::ShortCircuit(operator, right) => {
+                    let lhs = values.pop().expect("the left operand was evaluated first");
+                    let decided = match operator {
+                        BinaryOp::Coalesce => !matches!(lhs, Value::Null),
+                        _ => lhs.is_truthy() == matches!(operator, BinaryOp::Or),
+                    };
+                    if decided {
+                        values.push(lhs);
+                    } else {
+                        work.push(Work::Evaluate(right));
+                    }
+                }
+                Work::Binary(operator) => {
+                    let rhs = values.pop().expect("the right operand was evaluated");
+                    let lhs = values.pop().expect("the left operand was evaluated");
+                    let result = if !self.strict_math {
+                        self.apply_binary_op(operator, lhs, rhs, line)?
+                    } else {
+                        let inputs = [lhs.clone(), rhs.clone()];
+                        let result = self.apply_binary_op(operator, lhs, rhs, line)?;
+                        self.check_strict_math(result, &inputs, || {
+                            format!("{} {} {}", Self::describe_operand(&inputs[0]), operator, Self::describe_operand(&inputs[1]))
+                        }, line)?
+                    };
+                    values.push(result);
+                }
+                Work::Unary(operator) => {
+                    let operand = values.pop().expect("the operand was evaluated");
+                    values.push(self.apply_unary_op(operator, operand, line)?);
+                }
+            }
+        }
+        Ok(values.pop().expect("an operator tree has a value"))
+    }
+
     /// Apply a binary operator
     fn apply_binary_op(&self, op: &BinaryOp, lhs: Value, rhs: Value, line: usize) -> Result<Value, String> {
+        let exact = Self::apply_fraction_op(op, &lhs, &rhs).or_else(|| Self::apply_decimal_op(op, &lhs, &rhs));
+        if let Some(result) = exact {
+            return result.map_err(|e| match e.as_str() {
+                "can't divide by zero" => goose::error(ErrorKind::DivisionByZero, line, ""),
+                _ => goose::error(ErrorKind::InvalidOperation(e), line, ""),
+            });
+        }
+
         match op {
             BinaryOp::Add => match (&lhs, &rhs) {
                 (Value::Number(a), Value::Number(b)) => Ok(Value::Number(a + b)),
-                (Value::String(a), Value::String(b)) => Ok(Value::String(format!("{}{}", a, b))),
+                (Value::String(a), Value::String(b)) => Ok(Value::String(format!("{}{}", a, b).into())),
                 _ => Err(goose::error(
                     ErrorKind::InvalidOperation(format!("{} + {}", lhs.type_name(), rhs.type_name())),
                     line,
@@ -1107,7 +2071,7 @@ impl Interpreter {
             BinaryOp::Mul => match (&lhs, &rhs) {
                 (Value::Number(a), Value::Number(b)) => Ok(Value::Number(a * b)),
                 (Value::String(s), Value::Number(n)) | (Value::Number(n), Value::String(s)) => {
-                    Ok(Value::String(s.repeat(*n as usize)))
+                    Ok(Value::String(s.repeat(*n as usize).into()))
                 }
                 _ => Err(goose::error(
                     ErrorKind::InvalidOperation(format!("{} * {}", lhs.type_name(), rhs.type_name())),
@@ -1136,7 +2100,7 @@ impl Interpreter {
                     if *b == 0.0 {
                         Err(goose::error(ErrorKind::DivisionByZero, line, ""))
                     } else {
-                        Ok(Value::Number(a % b))
+                        Ok(Value::Number(builtins::floored_mod(*a, *b)))
                     }
                 }
                 _ => Err(goose::error(
@@ -1198,11 +2162,13 @@ impl Interpreter {
                 )),
             },
 
-            BinaryOp::And => Ok(Value::Boolean(lhs.is_truthy() && rhs.is_truthy())),
-            BinaryOp::Or => Ok(Value::Boolean(lhs.is_truthy() || rhs.is_truthy())),
+            // These three are evaluated lazily in `evaluate`; this is for already-computed sides
+            BinaryOp::And => Ok(if lhs.is_truthy() { rhs } else { lhs }),
+            BinaryOp::Or => Ok(if lhs.is_truthy() { lhs } else { rhs }),
+            BinaryOp::Coalesce => Ok(if matches!(lhs, Value::Null) { rhs } else { lhs }),
 
             BinaryOp::Concat => match (&lhs, &rhs) {
-                (Value::String(a), Value::String(b)) => Ok(Value::String(format!("{}{}", a, b))),
+                (Value::String(a), Value::String(b)) => Ok(Value::String(format!("{}{}", a, b).into())),
                 (Value::List(a), Value::List(b)) => {
                     let mut new_list = a.borrow().clone();
                     new_list.extend(b.borrow().iter().cloned());
@@ -1217,6 +2183,67 @@ impl Interpreter {
         }
     }
 
+    /// Arithmetic and comparisons with a decimal on either side, or None if
+    /// neither side is one. An ordinary number on the other side is read the
+    /// way it prints, so decimal("0.3") - 0.1 is exactly 0.2.
+    fn apply_decimal_op(op: &BinaryOp, lhs: &Value, rhs: &Value) -> Option<Result<Value, String>> {
+        let as_decimal = |value: &Value| match value {
+            Value::Decimal(d) => Some(Ok(*d)),
+            Value::Number(n) => Some(Decimal::from_f64(*n)),
+            _ => None,
+        };
+        if !matches!(lhs, Value::Decimal(_)) && !matches!(rhs, Value::Decimal(_)) {
+            return None;
+        }
+        let (a, b) = (as_decimal(lhs)?, as_decimal(rhs)?);
+        let result = match op {
+            // Equality compares by value without converting, like `compare`
+            BinaryOp::Eq | BinaryOp::NotEq | BinaryOp::And | BinaryOp::Or | BinaryOp::Concat | BinaryOp::Coalesce => return None,
+            BinaryOp::Lt => return Some(Ok(Value::Boolean(lhs.compare(rhs).is_lt()))),
+            BinaryOp::LtEq => return Some(Ok(Value::Boolean(lhs.compare(rhs).is_le()))),
+            BinaryOp::Gt => return Some(Ok(Value::Boolean(lhs.compare(rhs).is_gt()))),
+            BinaryOp::GtEq => return Some(Ok(Value::Boolean(lhs.compare(rhs).is_ge()))),
+            BinaryOp::Pow => match rhs {
+                Value::Number(n) => a.and_then(|a| a.pow(*n)),
+                _ => a.and_then(|a| b.and_then(|b| a.pow(b.to_f64()))),
+            },
+            BinaryOp::Add => a.and_then(|a| b.and_then(|b| a.checked_add(b))),
+            BinaryOp::Sub => a.and_then(|a| b.and_then(|b| a.checked_sub(b))),
+            BinaryOp::Mul => a.and_then(|a| b.and_then(|b| a.checked_mul(b))),
+            BinaryOp::Div => a.and_then(|a| b.and_then(|b| a.checked_div(b))),
+            BinaryOp::Mod => a.and_then(|a| b.and_then(|b| a.checked_rem(b))),
+        };
+        Some(result.map(Value::Decimal))
+    }
+
+    /// Arithmetic with a fraction on either side, or None if neither side is
+    /// one. Numbers and decimals on the other side become exact fractions
+    /// (0.25 is 1/4), so the answer is a fraction too.
+    fn apply_fraction_op(op: &BinaryOp, lhs: &Value, rhs: &Value) -> Option<Result<Value, String>> {
+        if !matches!(lhs, Value::Fraction(_)) && !matches!(rhs, Value::Fraction(_)) {
+            return None;
+        }
+        let (a, b) = (values::exact_fraction(lhs)?, values::exact_fraction(rhs)?);
+        let result = match op {
+            // Equality compares by value without converting, like `compare`
+            BinaryOp::Eq | BinaryOp::NotEq | BinaryOp::And | BinaryOp::Or | BinaryOp::Concat | BinaryOp::Coalesce => return None,
+            BinaryOp::Lt => return Some(Ok(Value::Boolean(lhs.compare(rhs).is_lt()))),
+            BinaryOp::LtEq => return Some(Ok(Value::Boolean(lhs.compare(rhs).is_le()))),
+            BinaryOp::Gt => return Some(Ok(Value::Boolean(lhs.compare(rhs).is_gt()))),
+            BinaryOp::GtEq => return Some(Ok(Value::Boolean(lhs.compare(rhs).is_ge()))),
+            BinaryOp::Pow => match rhs {
+                Value::Number(n) => a.and_then(|a| a.pow(*n)),
+                _ => a.and_then(|a| b.and_then(|b| a.pow(b.to_f64()))),
+            },
+            BinaryOp::Add => a.and_then(|a| b.and_then(|b| a.checked_add(b))),
+            BinaryOp::Sub => a.and_then(|a| b.and_then(|b| a.checked_sub(b))),
+            BinaryOp::Mul => a.and_then(|a| b.and_then(|b| a.checked_mul(b))),
+            BinaryOp::Div => a.and_then(|a| b.and_then(|b| a.checked_div(b))),
+            BinaryOp::Mod => a.and_then(|a| b.and_then(|b| a.checked_rem(b))),
+        };
+        Some(result.map(Value::Fraction))
+    }
+
     /// Check if two values are equal
     fn values_equal(&self, a: &Value, b: &Value) -> bool {
         a == b
@@ -1227,6 +2254,14 @@ impl Interpreter {
         match op {
             UnaryOp::Neg => match val {
                 Value::Number(n) => Ok(Value::Number(-n)),
+                Value::Decimal(d) => d
+                    .checked_neg()
+                    .map(Value::Decimal)
+                    .map_err(|e| goose::error(ErrorKind::InvalidOperation(e), line, "")),
+                Value::Fraction(f) => f
+                    .checked_neg()
+                    .map(Value::Fraction)
+                    .map_err(|e| goose::error(ErrorKind::InvalidOperation(e), line, "")),
                 _ => Err(goose::error(
                     ErrorKind::InvalidOperation(format!("-{}", val.type_name())),
                     line,
@@ -1237,25 +2272,141 @@ impl Interpreter {
         }
     }
 
+    /// Read a struct field
+    fn get_field(&self, obj: Value, field: &str, line: usize) -> Result<Value, String> {
+        match obj {
+            Value::Struct { fields, name } => fields.borrow().get(field).cloned().ok_or_else(|| {
+                goose::error(
+                    ErrorKind::InvalidFieldAccess {
+                        type_name: name,
+                        field: field.to_string(),
+                    },
+                    line,
+                    "",
+                )
+            }),
+            _ => Err(goose::error(
+                ErrorKind::InvalidFieldAccess {
+                    type_name: obj.type_name().to_string(),
+                    field: field.to_string(),
+                },
+                line,
+                "",
+            )),
+        }
+    }
+
+    /// The method `name` for a struct instance, if its type has one. Fields
+    /// win over methods, so a struct holding a lambda still calls the lambda.
+    fn find_method(&self, obj: &Value, name: &str) -> Option<Value> {
+        let Value::Struct { name: type_name, fields } = obj else {
+            return None;
+        };
+        if fields.borrow().contains_key(name) {
+            return None;
+        }
+        match self.env.borrow().get(type_name) {
+            Some(Value::StructType { methods, .. }) => methods.borrow().get(name).cloned(),
+            _ => None,
+        }
+    }
+
     /// Call a function or builtin
     fn call_function(&mut self, func: Value, args: Vec<Value>, line: usize) -> Result<Value, String> {
+        if !self.hooks.is_empty() {
+            let name = match &func {
+                Value::BuiltinFunction(builtin) => builtin.name().to_string(),
+                Value::Function { name, .. } => name.clone(),
+                Value::Lambda { .. } | Value::BlockLambda { .. } => "<lambda>".to_string(),
+                other => other.type_name().to_string(),
+            };
+            self.fire_hooks(|hooks| hooks.on_call(&name, &args, line));
+        }
         match func {
-            Value::BuiltinFunction(name) => {
+            Value::BuiltinFunction(builtin) => {
+                if let Some(capability) = builtin.capability() {
+                    self.policy
+                        .check(capability, &format!("{}()", builtin.name()))
+                        .map_err(|e| goose::error(ErrorKind::InvalidOperation(e), line, ""))?;
+                }
+                let Some(hosted) = builtin.hosted() else {
+                    let inputs = if self.strict_math { args.clone() } else { Vec::new() };
+                    let result = builtin
+                        .call(args)
+                        .map_err(|e| goose::error(ErrorKind::InvalidOperation(e), line, ""))?;
+                    return self.check_strict_math(result, &inputs, || {
+                        let shown: Vec<String> = inputs.iter().map(Self::describe_operand).collect();
+                        format!("{}({})", builtin.name(), shown.join(", "))
+                    }, line);
+                };
                 // Handle higher-order functions that need interpreter access
-                match name.as_str() {
-                    "map" => self.builtin_map(args, line),
-                    "filter" => self.builtin_filter(args, line),
-                    "fold" => self.builtin_fold(args, line),
-                    "find" => self.builtin_find(args, line),
-                    "any" => self.builtin_any(args, line),
-                    "all" => self.builtin_all(args, line),
-                    _ => builtins::call_builtin(&name, args)
-                        .map_err(|e| goose::error(ErrorKind::InvalidOperation(e), line, ""))
+                match hosted {
+                    Hosted::Print => {
+                        let text: Vec<String> = args.iter().map(|arg| arg.to_string()).collect();
+                        self.print_line(&text.join(" "));
+                        Ok(Value::Null)
+                    }
+                    Hosted::Map => self.builtin_map(args, line),
+                    Hosted::Filter => self.builtin_filter(args, line),
+                    Hosted::Fold | Hosted::Reduce => self.builtin_fold(args, line),
+                    Hosted::Find => self.builtin_find(args, line),
+                    Hosted::InsertAt => self.builtin_insert_at(args, line),
+                    Hosted::RemoveAt => self.builtin_remove_at(args, line),
+                    Hosted::Any => self.builtin_any(args, line),
+                    Hosted::All => self.builtin_all(args, line),
+                    Hosted::GooseBonus => self.builtin_rating_adjustment(args, line, 1.0),
+                    Hosted::GoosePenalty => self.builtin_rating_adjustment(args, line, -1.0),
+                    Hosted::ForAll => self.builtin_for_all(args, line),
+                    Hosted::AssertEq | Hosted::AssertNe | Hosted::AssertClose => self.builtin_assert(builtin, args, line),
+                    Hosted::Sleep => self.builtin_sleep(args, line),
+                    Hosted::Now => Ok(Value::Number(self.clock.now_ms())),
+                    Hosted::Random => self
+                        .random
+                        .next()
+                        .map(Value::Number)
+                        .map_err(|e| goose::error(ErrorKind::InvalidOperation(e), line, "")),
+                    Hosted::Input => {
+                        let prompt = args.first().map(|prompt| prompt.to_string());
+                        self.input
+                            .read_line(prompt.as_deref())
+                            .map(|answer| Value::String(answer.unwrap_or_default().into()))
+                            .map_err(|e| goose::error(ErrorKind::InvalidOperation(e), line, ""))
+                    }
+                    Hosted::InputNumber => self.builtin_input_number(args, line),
+                    Hosted::InputChoice => self.builtin_input_choice(args, line),
+                    Hosted::InputYesNo => self.builtin_input_yes_no(args, line),
+                    Hosted::Confirm => self.builtin_confirm(args, line),
+                    Hosted::Serve => self.builtin_serve(args, line),
+                    Hosted::WatchPath => self.builtin_watch_path(args, line),
+                    Hosted::ParseArgs => self.builtin_parse_args(args, line),
+                    Hosted::Args => Ok(Value::new_list(self.script_args.iter().cloned().map(|s| Value::String(s.into())).collect())),
+                    Hosted::Exit => self.builtin_exit(args, line),
+                    Hosted::Shell => self.builtin_shell(args, line),
+                    Hosted::WsReceive => self.builtin_ws_receive(args, line),
+                    Hosted::WsConnect | Hosted::WsSend | Hosted::WsClose => self.builtin_websocket(hosted, args, line),
+                    Hosted::ClipboardGet | Hosted::ClipboardSet | Hosted::Notify => self.builtin_desktop(builtin, args, line),
+                    Hosted::SecretInput => {
+                        let prompt = args.first().map(|prompt| prompt.to_string());
+                        self.input
+                            .read_secret(prompt.as_deref())
+                            .map(|answer| Value::String(answer.unwrap_or_default().into()))
+                            .map_err(|e| goose::error(ErrorKind::InvalidOperation(e), line, ""))
+                    }
                 }
             }
 
-            Value::Function { name, params, body, closure } => {
-                if args.len() != params.len() {
+            Value::Function { name, params, rest, body, closure } => {
+                if rest.is_some() && args.len() < params.len() {
+                    let message = format!(
+                        "'{}' needs at least {} argument{}, got {}",
+                        name,
+                        params.len(),
+                        if params.len() == 1 { "" } else { "s" },
+                        args.len()
+                    );
+                    return Err(goose::error(ErrorKind::InvalidOperation(message), line, ""));
+                }
+                if rest.is_none() && args.len() != params.len() {
                     return Err(goose::error(
                         ErrorKind::ArgumentMismatch {
                             expected: params.len(),
@@ -1269,10 +2420,14 @@ impl Interpreter {
                 // Create new environment for function call
                 let func_env = Rc::new(RefCell::new(Environment::with_parent(Rc::clone(&self.env))));
 
-                // Bind parameters
-                for (param, arg) in params.iter().zip(args) {
+                // Bind parameters; anything past them goes into the rest list
+                let mut args = args.into_iter();
+                for (param, arg) in params.iter().zip(args.by_ref()) {
                     func_env.borrow_mut().define(param.clone(), arg);
                 }
+                if let Some(rest) = rest {
+                    func_env.borrow_mut().define(rest, Value::new_list(args.collect()));
+                }
 
                 // Bind closure variables
                 for (name, value) in closure.captured.borrow().iter() {
@@ -1285,7 +2440,11 @@ impl Interpreter {
 
                 // Execute function body
                 let mut result = Value::Null;
-                for block in &body {
+                for block in body.iter() {
+                    if !block.was_quacked {
+                        self.refuse_nested_block(block);
+                        continue;
+                    }
                     match self.execute_block(block)? {
                         ControlFlow::Return(v) => {
                             result = v;
@@ -1372,8 +2531,12 @@ impl Interpreter {
 
                 // Execute block lambda body statements
                 let mut result = Value::Null;
-                for stmt in &body {
-                    match self.execute_statement(stmt, line)? {
+                for block in body.iter() {
+                    if !block.was_quacked {
+                        self.refuse_nested_block(block);
+                        continue;
+                    }
+                    match self.execute_block(block)? {
                         ControlFlow::Return(v) => {
                             result = v;
                             break;
@@ -1393,7 +2556,7 @@ impl Interpreter {
                 Ok(result)
             }
 
-            Value::StructType { name, fields } => {
+            Value::StructType { name, fields, .. } => {
                 // Struct instantiation via function call syntax
                 if args.len() != fields.len() {
                     return Err(goose::error(
@@ -1427,26 +2590,41 @@ impl Interpreter {
         self.call_function(callable, args, line)
     }
 
+    /// Put the function first. map, filter and reduce take their arguments
+    /// either way round - map(fn, list) or map(list, fn), reduce(fn, initial, list)
+    /// or fold(list, initial, fn) - so a call that doesn't start with a function
+    /// is the list-first order, reversed.
+    fn function_first(mut args: Vec<Value>) -> Vec<Value> {
+        if !matches!(
+            args.first(),
+            Some(Value::Function { .. } | Value::Lambda { .. } | Value::BlockLambda { .. } | Value::BuiltinFunction(_))
+        ) {
+            args.reverse();
+        }
+        args
+    }
+
     /// Built-in map: apply function to each element
     fn builtin_map(&mut self, args: Vec<Value>, line: usize) -> Result<Value, String> {
         if args.len() != 2 {
             return Err(goose::error(
                 ErrorKind::ArgumentMismatch { expected: 2, got: args.len() },
                 line,
-                "map(list, function)",
+                "map(function, list)",
             ));
         }
 
-        let list = match &args[0] {
+        let args = Self::function_first(args);
+        let list = match &args[1] {
             Value::List(items) => items.borrow().clone(),
             other => return Err(goose::error(
                 ErrorKind::TypeError { expected: "list".to_string(), got: other.type_name().to_string() },
                 line,
-                "in map() first argument",
+                "in map()",
             )),
         };
 
-        let func = args[1].clone();
+        let func = args[0].clone();
         let mut results = Vec::new();
 
         for item in list {
@@ -1463,20 +2641,21 @@ impl Interpreter {
             return Err(goose::error(
                 ErrorKind::ArgumentMismatch { expected: 2, got: args.len() },
                 line,
-                "filter(list, predicate)",
+                "filter(predicate, list)",
             ));
         }
 
-        let list = match &args[0] {
+        let args = Self::function_first(args);
+        let list = match &args[1] {
             Value::List(items) => items.borrow().clone(),
             other => return Err(goose::error(
                 ErrorKind::TypeError { expected: "list".to_string(), got: other.type_name().to_string() },
                 line,
-                "in filter() first argument",
+                "in filter()",
             )),
         };
 
-        let func = args[1].clone();
+        let func = args[0].clone();
         let mut results = Vec::new();
 
         for item in list {
@@ -1489,27 +2668,29 @@ impl Interpreter {
         Ok(Value::new_list(results))
     }
 
-    /// Built-in fold: reduce list to single value
+    /// Built-in fold / reduce: reduce list to single value.
+    /// fold(list, initial, function) and reduce(function, initial, list) are the same.
     fn builtin_fold(&mut self, args: Vec<Value>, line: usize) -> Result<Value, String> {
         if args.len() != 3 {
             return Err(goose::error(
                 ErrorKind::ArgumentMismatch { expected: 3, got: args.len() },
                 line,
-                "fold(list, initial, function)",
+                "reduce(function, initial, list)",
             ));
         }
 
-        let list = match &args[0] {
+        let args = Self::function_first(args);
+        let list = match &args[2] {
             Value::List(items) => items.borrow().clone(),
             other => return Err(goose::error(
                 ErrorKind::TypeError { expected: "list".to_string(), got: other.type_name().to_string() },
                 line,
-                "in fold() first argument",
+                "in reduce()",
             )),
         };
 
         let mut accumulator = args[1].clone();
-        let func = args[2].clone();
+        let func = args[0].clone();
 
         for item in list {
             accumulator = self.call_callable(func.clone(), vec![accumulator, item], line)?;
@@ -1524,20 +2705,21 @@ impl Interpreter {
             return Err(goose::error(
                 ErrorKind::ArgumentMismatch { expected: 2, got: args.len() },
                 line,
-                "find(list, predicate)",
+                "find(predicate, list)",
             ));
         }
 
-        let list = match &args[0] {
+        let args = Self::function_first(args);
+        let list = match &args[1] {
             Value::List(items) => items.borrow().clone(),
             other => return Err(goose::error(
                 ErrorKind::TypeError { expected: "list".to_string(), got: other.type_name().to_string() },
                 line,
-                "in find() first argument",
+                "in find()",
             )),
         };
 
-        let func = args[1].clone();
+        let func = args[0].clone();
 
         for item in list {
             let result = self.call_callable(func.clone(), vec![item.clone()], line)?;
@@ -1549,48 +2731,684 @@ impl Interpreter {
         Ok(Value::Null)
     }
 
-    /// Built-in any: check if any element satisfies predicate
-    fn builtin_any(&mut self, args: Vec<Value>, line: usize) -> Result<Value, String> {
-        if args.len() != 2 {
-            return Err(goose::error(
-                ErrorKind::ArgumentMismatch { expected: 2, got: args.len() },
-                line,
-                "any(list, predicate)",
-            ));
-        }
-
-        let list = match &args[0] {
-            Value::List(items) => items.borrow().clone(),
+    /// The list and position for insert-at / remove-at. Negative positions count
+    /// from the end, like `at`; `room` is how far past the last item is allowed.
+    fn list_position(args: &[Value], room: usize, usage: &str, line: usize) -> Result<(Rc<ListCell>, usize), String> {
+        let items = match &args[0] {
+            list @ Value::List(items) => {
+                list.ensure_mutable()
+                    .map_err(|e| goose::error(ErrorKind::InvalidOperation(format!("{} can't {}", usage, e)), line, ""))?;
+                items.clone()
+            }
             other => return Err(goose::error(
                 ErrorKind::TypeError { expected: "list".to_string(), got: other.type_name().to_string() },
                 line,
-                "in any() first argument",
+                &format!("in {}", usage),
             )),
         };
+        let index = match &args[1] {
+            Value::Number(n) => *n as i64,
+            other => return Err(goose::error(
+                ErrorKind::TypeError { expected: "number".to_string(), got: other.type_name().to_string() },
+                line,
+                &format!("in {} index", usage),
+            )),
+        };
+        let len = items.borrow().len();
+        let position = if index < 0 { len as i64 + index } else { index };
+        if position < 0 || position as usize >= len + room {
+            return Err(goose::error(ErrorKind::IndexOutOfBounds { index, len }, line, ""));
+        }
+        Ok((items, position as usize))
+    }
 
-        let func = args[1].clone();
-
-        for item in list {
-            let result = self.call_callable(func.clone(), vec![item], line)?;
-            if result.is_truthy() {
-                return Ok(Value::Boolean(true));
-            }
+    /// Built-in insert-at: put a value at a position, shifting the rest along
+    fn builtin_insert_at(&mut self, args: Vec<Value>, line: usize) -> Result<Value, String> {
+        if args.len() != 3 {
+            return Err(goose::error(
+                ErrorKind::ArgumentMismatch { expected: 3, got: args.len() },
+                line,
+                "insert-at(list, index, value)",
+            ));
         }
 
-        Ok(Value::Boolean(false))
+        let (items, position) = Self::list_position(&args, 1, "insert-at()", line)?;
+        items.borrow_mut().insert(position, args[2].clone());
+        Ok(Value::Null)
     }
 
-    /// Built-in all: check if all elements satisfy predicate
-    fn builtin_all(&mut self, args: Vec<Value>, line: usize) -> Result<Value, String> {
+    /// Built-in remove-at: take the item at a position out of the list and return it
+    fn builtin_remove_at(&mut self, args: Vec<Value>, line: usize) -> Result<Value, String> {
         if args.len() != 2 {
             return Err(goose::error(
                 ErrorKind::ArgumentMismatch { expected: 2, got: args.len() },
                 line,
-                "all(list, predicate)",
+                "remove-at(list, index)",
             ));
         }
 
-        let list = match &args[0] {
+        let (items, position) = Self::list_position(&args, 0, "remove-at()", line)?;
+        let removed = items.borrow_mut().remove(position);
+        Ok(removed)
+    }
+
+    /// Built-in goose-bonus / goose-penalty: record a rubric adjustment to the final rating
+    fn builtin_rating_adjustment(&mut self, args: Vec<Value>, line: usize, sign: f64) -> Result<Value, String> {
+        let usage = if sign > 0.0 { "goose-bonus(points, reason)" } else { "goose-penalty(points, reason)" };
+        if args.len() != 2 {
+            return Err(goose::error(
+                ErrorKind::ArgumentMismatch { expected: 2, got: args.len() },
+                line,
+                usage,
+            ));
+        }
+
+        let points = match &args[0] {
+            Value::Number(n) if *n >= 0.0 => *n,
+            Value::Number(_) => return Err(goose::error(
+                ErrorKind::InvalidOperation("negative points".to_string()),
+                line,
+                &format!("{} takes positive points - pick the other one to go the other way", usage),
+            )),
+            other => return Err(goose::error(
+                ErrorKind::TypeError { expected: "number".to_string(), got: other.type_name().to_string() },
+                line,
+                &format!("in {} points", usage),
+            )),
+        };
+
+        self.stats.rating_adjustments.push(RatingAdjustment {
+            points: points * sign,
+            reason: args[1].to_string(),
+            line,
+        });
+
+        Ok(Value::Null)
+    }
+
+    /// Built-in for-all: check a property against generated inputs, shrinking any failure
+    fn builtin_for_all(&mut self, args: Vec<Value>, line: usize) -> Result<Value, String> {
+        if args.len() < 2 || args.len() > 3 {
+            return Err(goose::error(
+                ErrorKind::ArgumentMismatch { expected: 3, got: args.len() },
+                line,
+                "for-all(generator, property, cases)",
+            ));
+        }
+
+        let generator = Generator::from_value(&args[0])
+            .map_err(|e| goose::error(ErrorKind::InvalidOperation(e), line, "in for-all() first argument"))?;
+        let property = args[1].clone();
+        let cases = match args.get(2) {
+            None => 100,
+            Some(Value::Number(n)) if *n >= 1.0 => *n as usize,
+            Some(other) => return Err(goose::error(
+                ErrorKind::TypeError { expected: "positive number".to_string(), got: other.to_string() },
+                line,
+                "in for-all() case count",
+            )),
+        };
+
+        let mut rng = Rng::default();
+        for case in 1..=cases {
+            let input = generator.generate(&mut rng);
+            if let Some(error) = self.property_fails(&property, &input, line)? {
+                let found = shrink_failure(&generator, input, error, |candidate| {
+                    self.property_fails(&property, candidate, line)
+                })?;
+                let mut message = format!(
+                    "property failed on case {} of {} for input {}",
+                    case,
+                    cases,
+                    property::describe(&found.shrunk)
+                );
+                if found.shrink_steps > 0 {
+                    message.push_str(&format!(
+                        " (shrunk from {} in {} step(s))",
+                        property::describe(&found.original),
+                        found.shrink_steps
+                    ));
+                }
+                if let Some(error) = found.error.filter(|e| !e.is_empty()) {
+                    message.push_str(&format!(" - it raised: {}", error));
+                }
+                return Err(goose::honk_failure(line, &message));
+            }
+        }
+
+        if let Some((_, coverage)) = &self.coverage {
+            coverage.borrow_mut().properties.push((line, cases));
+        }
+        Ok(Value::Boolean(true))
+    }
+
+    /// Built-in assert-eq, assert-ne and assert-close. A failure honks with
+    /// both values and, for lists, maps and structs, where they differ.
+    fn builtin_assert(&mut self, builtin: Builtin, args: Vec<Value>, line: usize) -> Result<Value, String> {
+        let (name, which) = (builtin.name(), builtin.hosted());
+        let (usage, max_args) = match which {
+            Some(Hosted::AssertClose) => ("assert-close(actual, expected, tolerance, message)", 4),
+            Some(Hosted::AssertNe) => ("assert-ne(actual, unexpected, message)", 3),
+            _ => ("assert-eq(actual, expected, message)", 3),
+        };
+        if args.len() < 2 || args.len() > max_args {
+            return Err(goose::error(
+                ErrorKind::ArgumentMismatch { expected: max_args, got: args.len() },
+                line,
+                usage,
+            ));
+        }
+        if let Some((_, coverage)) = &self.coverage {
+            let mut coverage = coverage.borrow_mut();
+            if !coverage.honks.contains(&line) {
+                coverage.honks.push(line);
+            }
+        }
+
+        let (actual, expected) = (&args[0], &args[1]);
+        let (failure, message) = match which {
+            Some(Hosted::AssertEq) => {
+                let failure = (!self.values_equal(actual, expected)).then(|| {
+                    let mut lines = vec![
+                        format!("expected: {}", property::describe(expected)),
+                        format!("     got: {}", property::describe(actual)),
+                    ];
+                    if matches!(expected, Value::List(_) | Value::Map(_) | Value::Struct { .. }) {
+                        lines.extend(diff::diff(expected, actual));
+                    }
+                    lines
+                });
+                (failure, args.get(2))
+            }
+            Some(Hosted::AssertNe) => {
+                let failure = self
+                    .values_equal(actual, expected)
+                    .then(|| vec![format!("both are {}", property::describe(actual))]);
+                (failure, args.get(2))
+            }
+            _ => {
+                let number = |value: &Value, what: &str| match value {
+                    Value::Number(n) => Ok(*n),
+                    Value::Decimal(d) => Ok(d.to_f64()),
+                    Value::Fraction(f) => Ok(f.to_f64()),
+                    other => Err(goose::error(
+                        ErrorKind::TypeError { expected: "number".to_string(), got: other.type_name().to_string() },
+                        line,
+                        &format!("in assert-close() {}", what),
+                    )),
+                };
+                let (a, b) = (number(actual, "actual value")?, number(expected, "expected value")?);
+                let tolerance = match args.get(2) {
+                    Some(tolerance) => number(tolerance, "tolerance")?,
+                    None => 1e-9,
+                };
+                let failure = ((a - b).abs() > tolerance || a.is_nan() || b.is_nan()).then(|| {
+                    vec![
+                        format!("expected: {} (within {})", b, tolerance),
+                        format!("     got: {} (off by {})", a, (a - b).abs()),
+                    ]
+                });
+                (failure, args.get(3))
+            }
+        };
+
+        match failure {
+            None => Ok(Value::Boolean(true)),
+            Some(lines) => {
+                let headline = match message {
+                    Some(message) => format!("{} failed: {}", name, message),
+                    None => format!("{} failed", name),
+                };
+                Err(goose::honk_failure(line, &format!("{}\n  {}", headline, lines.join("\n  "))))
+            }
+        }
+    }
+
+    /// Run a property on one input. `None` if it held, otherwise the error it raised
+    /// (empty if it just returned false). Cancellation, timeouts and `exit` aren't
+    /// the property failing, so they come back as `Err` and end the run.
+    fn property_fails(&mut self, property: &Value, input: &Value, line: usize) -> Result<Option<Option<String>>, String> {
+        // Every case gets its own copy so a mutating property can't poison the next one
+        match self.call_callable(property.clone(), vec![input.deep_clone()], line) {
+            Ok(result) if result.is_truthy() => Ok(None),
+            Ok(_) => Ok(Some(None)),
+            Err(e) if self.interruption().is_some() => Err(e),
+            Err(e) => Ok(Some(Some(e))),
+        }
+    }
+
+    /// Built-in sleep: wait on the interpreter's clock, stopping early if cancelled or out of time
+    fn builtin_sleep(&mut self, args: Vec<Value>, line: usize) -> Result<Value, String> {
+        let ms = match args.first() {
+            Some(Value::Number(ms)) if *ms >= 0.0 && ms.is_finite() => *ms,
+            Some(Value::Number(_)) => return Err(goose::error(
+                ErrorKind::InvalidOperation("sleep() requires a non-negative number".to_string()),
+                line,
+                "",
+            )),
+            Some(other) => return Err(goose::error(
+                ErrorKind::TypeError { expected: "number".to_string(), got: other.type_name().to_string() },
+                line,
+                "in sleep()",
+            )),
+            None => return Err(goose::error(
+                ErrorKind::ArgumentMismatch { expected: 1, got: 0 },
+                line,
+                "sleep(milliseconds)",
+            )),
+        };
+
+        if clock::wait_duration(ms).is_none() {
+            return Err(goose::error(
+                ErrorKind::InvalidOperation(format!("sleep() waits a week at most ({} ms)", clock::MAX_WAIT.as_millis())),
+                line,
+                "",
+            ));
+        }
+        let (cancel, deadline) = (self.cancel.clone(), self.deadline);
+        self.clock.sleep_ms(ms, &|| Self::interruption_for(&cancel, deadline))?;
+        Ok(Value::Null)
+    }
+
+    /// Built-in exit: remember the code and unwind like an interruption, so
+    /// nothing (not even `attempt`) runs after it
+    fn builtin_exit(&mut self, args: Vec<Value>, line: usize) -> Result<Value, String> {
+        let code = match args.as_slice() {
+            [] => 0,
+            [Value::Number(n)] if n.fract() == 0.0 && (0.0..=255.0).contains(n) => *n as i32,
+            [Value::Number(n)] => return Err(goose::error(
+                ErrorKind::InvalidOperation(format!("exit() needs a whole number from 0 to 255, got {}", n)),
+                line,
+                "",
+            )),
+            [other] => return Err(goose::error(
+                ErrorKind::TypeError { expected: "number".to_string(), got: other.type_name().to_string() },
+                line,
+                "in exit()",
+            )),
+            _ => return Err(goose::error(
+                ErrorKind::ArgumentMismatch { expected: 1, got: args.len() },
+                line,
+                "exit(code)",
+            )),
+        };
+        self.exit_code = Some(code);
+        Err(self.interruption().unwrap_or_default())
+    }
+
+    /// Keep asking until `parse` accepts the answer, nagging after each bad one
+    fn ask_until<F>(&mut self, prompt: Option<String>, line: usize, parse: F) -> Result<Value, String>
+    where
+        F: Fn(&str) -> Result<Value, String>,
+    {
+        loop {
+            if let Some(reason) = self.interruption() {
+                return Err(goose::error(ErrorKind::InvalidOperation(reason), line, ""));
+            }
+            let answer = self
+                .input
+                .read_line(prompt.as_deref())
+                .map_err(|e| goose::error(ErrorKind::InvalidOperation(e), line, ""))?
+                .ok_or_else(|| goose::error(
+                    ErrorKind::InvalidOperation("ran out of input while waiting for a valid answer".to_string()),
+                    line,
+                    "",
+                ))?;
+            match parse(answer.trim()) {
+                Ok(value) => return Ok(value),
+                Err(problem) => println!("{}", goose::nag(&problem)),
+            }
+        }
+    }
+
+    /// Built-in input-number: ask until the answer is a number
+    fn builtin_input_number(&mut self, args: Vec<Value>, line: usize) -> Result<Value, String> {
+        let prompt = args.first().map(|prompt| prompt.to_string());
+        self.ask_until(prompt, line, |answer| {
+            answer
+                .parse::<f64>()
+                .ok()
+                .filter(|n| n.is_finite())
+                .map(Value::Number)
+                .ok_or_else(|| format!("'{}' is not a number.", answer))
+        })
+    }
+
+    /// Built-in input-choice: ask until the answer is one of the options (ignoring case)
+    fn builtin_input_choice(&mut self, args: Vec<Value>, line: usize) -> Result<Value, String> {
+        let options = match args.get(1) {
+            Some(Value::List(items)) if !items.borrow().is_empty() => items.borrow().clone(),
+            Some(Value::List(_)) => return Err(goose::error(
+                ErrorKind::InvalidOperation("input-choice() needs at least one option".to_string()),
+                line,
+                "",
+            )),
+            Some(other) => return Err(goose::error(
+                ErrorKind::TypeError { expected: "list".to_string(), got: other.type_name().to_string() },
+                line,
+                "in input-choice()",
+            )),
+            None => return Err(goose::error(
+                ErrorKind::ArgumentMismatch { expected: 2, got: args.len() },
+                line,
+                "input-choice(prompt, options)",
+            )),
+        };
+        let prompt = Some(args[0].to_string());
+        let listed = options.iter().map(|o| o.to_string()).collect::<Vec<_>>().join(", ");
+        self.ask_until(prompt, line, |answer| {
+            options
+                .iter()
+                .find(|option| option.to_string().eq_ignore_ascii_case(answer))
+                .cloned()
+                .ok_or_else(|| format!("Pick one of: {}.", listed))
+        })
+    }
+
+    /// Built-in input-yes-no: ask until the answer is yes/y or no/n
+    fn builtin_input_yes_no(&mut self, args: Vec<Value>, line: usize) -> Result<Value, String> {
+        let prompt = args.first().map(|prompt| prompt.to_string());
+        self.ask_until(prompt, line, |answer| match answer.to_lowercase().as_str() {
+            "y" | "yes" => Ok(Value::Boolean(true)),
+            "n" | "no" => Ok(Value::Boolean(false)),
+            _ => Err("It's a yes or no question.".to_string()),
+        })
+    }
+
+    /// Built-in confirm: a yes/no question where just pressing enter (or
+    /// closed input) means no, for asking before doing something drastic
+    fn builtin_confirm(&mut self, args: Vec<Value>, line: usize) -> Result<Value, String> {
+        let prompt = match args.first() {
+            Some(prompt) => format!("{} [y/N] ", prompt.to_string().trim_end()),
+            None => "Are you sure? [y/N] ".to_string(),
+        };
+        loop {
+            if let Some(reason) = self.interruption() {
+                return Err(goose::error(ErrorKind::InvalidOperation(reason), line, ""));
+            }
+            let answer = self
+                .input
+                .read_line(Some(&prompt))
+                .map_err(|e| goose::error(ErrorKind::InvalidOperation(e), line, ""))?;
+            match answer.map(|a| a.trim().to_lowercase()).as_deref() {
+                None | Some("") | Some("n") | Some("no") => return Ok(Value::Boolean(false)),
+                Some("y") | Some("yes") => return Ok(Value::Boolean(true)),
+                Some(_) => println!("{}", goose::nag("It's a yes or no question.")),
+            }
+        }
+    }
+
+    /// Built-in serve: answer HTTP requests on localhost with a Duck handler,
+    /// until cancelled, out of time, or (with a third argument) after that many requests
+    fn builtin_serve(&mut self, args: Vec<Value>, line: usize) -> Result<Value, String> {
+        if args.len() < 2 || args.len() > 3 {
+            return Err(goose::error(
+                ErrorKind::ArgumentMismatch { expected: 2, got: args.len() },
+                line,
+                "serve(port, handler)",
+            ));
+        }
+        let port = match &args[0] {
+            Value::Number(n) if n.fract() == 0.0 && (0.0..=65535.0).contains(n) => *n as u16,
+            other => return Err(goose::error(
+                ErrorKind::InvalidOperation(format!("serve() needs a port from 0 to 65535, got {}", other)),
+                line,
+                "",
+            )),
+        };
+        let handler = args[1].clone();
+        let max_requests = match args.get(2) {
+            Some(Value::Number(n)) if *n >= 0.0 && n.fract() == 0.0 => Some(*n as usize),
+            Some(other) => return Err(goose::error(
+                ErrorKind::InvalidOperation(format!("serve() request limit must be a whole number, got {}", other)),
+                line,
+                "",
+            )),
+            None => None,
+        };
+
+        let server = Server::bind(port).map_err(|e| goose::error(ErrorKind::InvalidOperation(e), line, ""))?;
+        println!("The goose is guarding http://localhost:{} (stop with Ctrl+C)", server.port());
+
+        let mut served = 0;
+        while max_requests.is_none_or(|max| served < max) {
+            let (cancel, deadline) = (self.cancel.clone(), self.deadline);
+            let (request, stream) = server
+                .next_request(&|| Self::interruption_for(&cancel, deadline))
+                .map_err(|e| goose::error(ErrorKind::InvalidOperation(e), line, ""))?;
+            let response = match self.call_callable(handler.clone(), vec![request.to_value()], line) {
+                Ok(value) => HttpResponse::from_value(&value).unwrap_or_else(|e| {
+                    self.sink.warning(line, &format!("serve() handler returned a bad response: {}", e));
+                    HttpResponse::text(500, &e)
+                }),
+                // Being stopped (or exiting) still stops the server
+                Err(e) if self.interruption().is_some() => return Err(e),
+                // A broken handler spoils one response, not the whole server
+                Err(e) => {
+                    self.sink.warning(line, &format!("serve() handler failed: {}", e));
+                    HttpResponse::text(500, "The goose tripped over this request.")
+                }
+            };
+            server.respond(stream, &response);
+            served += 1;
+        }
+        Ok(Value::Null)
+    }
+
+    /// Built-in watch-path: call the handler with the list of changed paths
+    /// every time something under `path` changes, until interrupted (or until
+    /// an optional number of changes has been handled)
+    fn builtin_watch_path(&mut self, args: Vec<Value>, line: usize) -> Result<Value, String> {
+        if args.len() < 2 || args.len() > 3 {
+            return Err(goose::error(
+                ErrorKind::ArgumentMismatch { expected: 2, got: args.len() },
+                line,
+                "watch-path(path, handler)",
+            ));
+        }
+        let path = match &args[0] {
+            Value::String(path) => path.clone(),
+            other => return Err(goose::error(
+                ErrorKind::TypeError { expected: "string".to_string(), got: other.type_name().to_string() },
+                line,
+                "watch-path() needs a path to watch",
+            )),
+        };
+        let handler = args[1].clone();
+        let max_changes = match args.get(2) {
+            Some(Value::Number(n)) if *n >= 0.0 && n.fract() == 0.0 => Some(*n as usize),
+            Some(other) => return Err(goose::error(
+                ErrorKind::InvalidOperation(format!("watch-path() change limit must be a whole number, got {}", other)),
+                line,
+                "",
+            )),
+            None => None,
+        };
+
+        let mut watcher = builtins::validate_path(&path)
+            .and_then(|_| Watcher::new(&path))
+            .map_err(|e| goose::error(ErrorKind::InvalidOperation(e), line, ""))?;
+
+        let mut handled = 0;
+        while max_changes.is_none_or(|max| handled < max) {
+            let (cancel, deadline) = (self.cancel.clone(), self.deadline);
+            let changed = watcher
+                .next_change(&|| Self::interruption_for(&cancel, deadline))
+                .map_err(|e| goose::error(ErrorKind::InvalidOperation(e), line, ""))?;
+            let changed = Value::new_list(changed.into_iter().map(|s| Value::String(s.into())).collect());
+            // One bad rebuild shouldn't stop the watching
+            if let Err(e) = self.call_callable(handler.clone(), vec![changed], line) {
+                if self.interruption().is_some() {
+                    return Err(e);
+                }
+                self.sink.warning(line, &format!("watch-path() handler failed: {}", e));
+            }
+            handled += 1;
+        }
+        Ok(Value::Null)
+    }
+
+    /// Built-in parse-args: read the command-line arguments (or a list of
+    /// words, handy for trying a spec out) against a spec map
+    fn builtin_parse_args(&mut self, args: Vec<Value>, line: usize) -> Result<Value, String> {
+        let spec = args.first().ok_or_else(|| goose::error(
+            ErrorKind::ArgumentMismatch { expected: 1, got: 0 },
+            line,
+            "parse-args(spec)",
+        ))?;
+        let words = match args.get(1) {
+            Some(Value::List(items)) => items.borrow().iter().map(|word| match word {
+                Value::String(word) => word.to_string(),
+                other => other.to_string(),
+            }).collect(),
+            Some(other) => return Err(goose::error(
+                ErrorKind::TypeError { expected: "list".to_string(), got: other.type_name().to_string() },
+                line,
+                "parse-args() reads its words from a list",
+            )),
+            None => self.script_args.clone(),
+        };
+        script_args::parse(spec, &words).map_err(|e| goose::error(ErrorKind::InvalidOperation(e), line, ""))
+    }
+
+    /// Built-in ws-connect, ws-send and ws-close, on this interpreter's connections
+    fn builtin_websocket(&mut self, which: Hosted, args: Vec<Value>, line: usize) -> Result<Value, String> {
+        let result = match (which, args.as_slice()) {
+            (Hosted::WsConnect, [Value::String(url), ..]) => self.sockets.connect(url),
+            (Hosted::WsConnect, [other, ..]) => Err(format!("ws-connect() expects a URL string, got {}", other.type_name())),
+            (Hosted::WsConnect, []) => Err("ws-connect() requires 1 argument (url)".to_string()),
+            (Hosted::WsSend, [socket, message]) => self.sockets.send(socket, message).map(|_| Value::Null),
+            (Hosted::WsSend, _) => Err("ws-send() requires 2 arguments (socket, message)".to_string()),
+            (_, [socket, ..]) => self.sockets.close(socket).map(|_| Value::Null),
+            (_, []) => Err("ws-close() requires 1 argument (socket)".to_string()),
+        };
+        result.map_err(|e| goose::error(ErrorKind::InvalidOperation(e), line, ""))
+    }
+
+    /// Built-in ws-receive: wait for the next WebSocket message, optionally
+    /// giving up after a number of milliseconds
+    fn builtin_ws_receive(&mut self, args: Vec<Value>, line: usize) -> Result<Value, String> {
+        let fail = |e: String| goose::error(ErrorKind::InvalidOperation(e), line, "");
+        let socket = args.first().ok_or_else(|| goose::error(
+            ErrorKind::ArgumentMismatch { expected: 1, got: 0 },
+            line,
+            "ws-receive(socket)",
+        ))?;
+        self.sockets.check_open(socket).map_err(fail)?;
+        let timeout = match args.get(1) {
+            Some(Value::Number(ms)) => match clock::wait_duration(*ms) {
+                Some(timeout) => Some(timeout),
+                None => return Err(fail(format!(
+                    "ws-receive() timeout must be a number of milliseconds from 0 up to a week ({})",
+                    clock::MAX_WAIT.as_millis()
+                ))),
+            },
+            Some(other) => return Err(fail(format!(
+                "ws-receive() timeout must be a non-negative number of milliseconds, got {}",
+                other
+            ))),
+            None => None,
+        };
+        let (cancel, deadline) = (self.cancel.clone(), self.deadline);
+        self.sockets
+            .receive(socket, timeout, &|| Self::interruption_for(&cancel, deadline))
+            .map_err(fail)
+    }
+
+    /// Built-in clipboard-get, clipboard-set and notify
+    fn builtin_desktop(&mut self, builtin: Builtin, args: Vec<Value>, line: usize) -> Result<Value, String> {
+        let (name, which) = (builtin.name(), builtin.hosted());
+        let fail = |e: String| goose::error(ErrorKind::InvalidOperation(format!("{}() failed: {}", name, e)), line, "");
+        if !self.policy.allows_desktop() {
+            return Err(fail("desktop access is turned off here".to_string()));
+        }
+        let text = |i: usize| match args.get(i) {
+            Some(Value::String(s)) => Ok(s.to_string()),
+            Some(other) => Ok(other.to_string()),
+            None => Err(goose::error(
+                ErrorKind::ArgumentMismatch { expected: if which == Some(Hosted::Notify) { 2 } else { 1 }, got: args.len() },
+                line,
+                &format!("in {}()", name),
+            )),
+        };
+        match which {
+            Some(Hosted::ClipboardGet) => desktop::clipboard_get().map(|s| Value::String(s.into())).map_err(fail),
+            Some(Hosted::ClipboardSet) => desktop::clipboard_set(&text(0)?).map(|_| Value::Null).map_err(fail),
+            _ => desktop::notify(&text(0)?, &text(1)?).map(|_| Value::Null).map_err(fail),
+        }
+    }
+
+    /// Built-in shell: run a command, giving back what it printed and its exit code
+    fn builtin_shell(&mut self, args: Vec<Value>, line: usize) -> Result<Value, String> {
+        let fail = |e: String| goose::error(ErrorKind::InvalidOperation(format!("shell() failed: {}", e)), line, "");
+        if !self.policy.allows_shell() {
+            return Err(fail("running commands is turned off here (goose run --allow-shell turns it on)".to_string()));
+        }
+        let command = match args.as_slice() {
+            [Value::String(command)] => command,
+            [other] => return Err(goose::error(
+                ErrorKind::TypeError { expected: "string".to_string(), got: other.type_name().to_string() },
+                line,
+                "in shell()",
+            )),
+            _ => return Err(goose::error(
+                ErrorKind::ArgumentMismatch { expected: 1, got: args.len() },
+                line,
+                "shell(command)",
+            )),
+        };
+
+        let (cancel, deadline) = (self.cancel.clone(), self.deadline);
+        let output = shell::run(command, &|| Self::interruption_for(&cancel, deadline)).map_err(fail)?;
+        let mut fields = HashMap::new();
+        fields.insert("stdout".to_string(), Value::String(output.stdout.into()));
+        fields.insert("stderr".to_string(), Value::String(output.stderr.into()));
+        fields.insert("status".to_string(), output.status.map_or(Value::Null, |code| Value::Number(code as f64)));
+        Ok(Value::new_struct("output".to_string(), fields))
+    }
+
+    /// Built-in any: check if any element satisfies predicate
+    fn builtin_any(&mut self, args: Vec<Value>, line: usize) -> Result<Value, String> {
+        if args.len() != 2 {
+            return Err(goose::error(
+                ErrorKind::ArgumentMismatch { expected: 2, got: args.len() },
+                line,
+                "any(list, predicate)",
+            ));
+        }
+
+        let list = match &args[0] {
+            Value::List(items) => items.borrow().clone(),
+            other => return Err(goose::error(
+                ErrorKind::TypeError { expected: "list".to_string(), got: other.type_name().to_string() },
+                line,
+                "in any() first argument",
+            )),
+        };
+
+        let func = args[1].clone();
+
+        for item in list {
+            let result = self.call_callable(func.clone(), vec![item], line)?;
+            if result.is_truthy() {
+                return Ok(Value::Boolean(true));
+            }
+        }
+
+        Ok(Value::Boolean(false))
+    }
+
+    /// Built-in all: check if all elements satisfy predicate
+    fn builtin_all(&mut self, args: Vec<Value>, line: usize) -> Result<Value, String> {
+        if args.len() != 2 {
+            return Err(goose::error(
+                ErrorKind::ArgumentMismatch { expected: 2, got: args.len() },
+                line,
+                "all(list, predicate)",
+            ));
+        }
+
+        let list = match &args[0] {
             Value::List(items) => items.borrow().clone(),
             other => return Err(goose::error(
                 ErrorKind::TypeError { expected: "list".to_string(), got: other.type_name().to_string() },
@@ -1624,6 +3442,7 @@ mod tests {
     use crate::lexer::lex;
     use crate::parser::Parser;
 
+    #[allow(clippy::map_identity)]
     fn run_source(source: &str) -> Result<(), String> {
         let tokens = lex(source).map_err(|e| e)?;
         let mut parser = Parser::new(tokens);
@@ -1651,6 +3470,1197 @@ mod tests {
         assert!(result.is_ok());
     }
 
+    #[test]
+    fn test_skipped_blocks_recorded_with_lines() {
+        let tokens = lex("quack [let x be 1]\n[let y be 2]\nquack [let z be 3]\n[print x]").unwrap();
+        let blocks = Parser::new(tokens).parse().unwrap();
+        let mut interpreter = Interpreter::new();
+        interpreter.run(blocks).unwrap();
+        assert_eq!(interpreter.stats().skipped_blocks, vec![2, 4]);
+        assert_eq!(interpreter.stats().unquacked_blocks, 2);
+    }
+
+    #[test]
+    fn test_unquacked_statement_in_function_body_skipped() {
+        let source = "quack [let hits be [0]]\nquack [define f taking [] as\n  quack [hits push 1]\n  [hits push 2]\n]\nquack [let a be f()]\nquack [let b be f()]";
+        let tokens = lex(source).unwrap();
+        let blocks = Parser::new(tokens).parse().unwrap();
+        let mut interpreter = Interpreter::new();
+        interpreter.run(blocks).unwrap();
+        let hits = interpreter.env.borrow().get("hits").unwrap();
+        assert_eq!(format!("{}", hits), "[0, 1, 1]");
+        assert_eq!(interpreter.stats().skipped_blocks, vec![4]);
+    }
+
+    #[test]
+    fn test_error_in_function_body_reports_statement_line() {
+        let source = "quack [define f taking [a] as\n  quack [print a]\n  quack [\n    print a + nope\n  ]\n]\nquack [let r be f(1)]";
+        let err = run_source(source).unwrap_err();
+        assert!(err.to_lowercase().contains("line 4"), "unexpected error: {}", err);
+    }
+
+    /// Records sink events instead of printing them
+    struct RecordingSink(Rc<RefCell<Vec<String>>>);
+
+    impl OutputSink for RecordingSink {
+        fn refusal(&mut self, line: usize, _message: &str) {
+            self.0.borrow_mut().push(format!("refusal {}", line));
+        }
+
+        fn warning(&mut self, line: usize, message: &str) {
+            self.0.borrow_mut().push(format!("warning {} {}", line, message));
+        }
+
+        fn success(&mut self, _message: &str) {
+            self.0.borrow_mut().push("success".to_string());
+        }
+
+        fn rating(&mut self, score: u8, _quip: &str, stats: &ExecutionStats) {
+            self.0.borrow_mut().push(format!("rating {} {}", score, stats.total_blocks));
+        }
+    }
+
+    struct RecordingHooks(Rc<RefCell<Vec<String>>>);
+
+    impl ExecutionHooks for RecordingHooks {
+        fn on_statement(&mut self, line: usize, _statement: &Statement) {
+            self.0.borrow_mut().push(format!("statement {}", line));
+        }
+
+        fn on_call(&mut self, name: &str, args: &[Value], line: usize) {
+            self.0.borrow_mut().push(format!("call {} {} {}", name, args.len(), line));
+        }
+
+        fn on_error(&mut self, line: usize, _error: &str) {
+            self.0.borrow_mut().push(format!("error {}", line));
+        }
+
+        fn on_block_skipped(&mut self, line: usize) {
+            self.0.borrow_mut().push(format!("skipped {}", line));
+        }
+    }
+
+    #[test]
+    fn test_hooks_see_statements_calls_skips_and_errors() {
+        let events = Rc::new(RefCell::new(Vec::new()));
+        let source = concat!(
+            "quack [define twice taking [x] as\n",
+            "  quack [return x * 2]\n",
+            "]\n",
+            "quack [let y be twice(len(\"ab\"))]\n",
+            "[print y]\n",
+            "quack [let z be nope]",
+        );
+        let blocks = Parser::new(lex(source).unwrap()).parse().unwrap();
+        let mut interpreter = Interpreter::new();
+        interpreter.set_output_sink(Box::new(RecordingSink(Rc::new(RefCell::new(Vec::new())))));
+        let id = interpreter.add_hooks(Box::new(RecordingHooks(Rc::clone(&events))));
+        assert!(interpreter.run(blocks).is_err());
+        assert_eq!(
+            *events.borrow(),
+            vec![
+                "statement 1", "statement 4", "call len 1 4", "call twice 1 4", "statement 2",
+                "skipped 5", "statement 6", "error 6",
+            ]
+        );
+
+        // Removed hooks hear nothing more
+        assert!(interpreter.remove_hooks(id).is_some());
+        let before = events.borrow().len();
+        run_on(&mut interpreter, "quack [let w be 1]");
+        assert_eq!(events.borrow().len(), before);
+    }
+
+    #[test]
+    fn test_nested_error_is_reported_once() {
+        let events = Rc::new(RefCell::new(Vec::new()));
+        let source = "quack [define boom taking [n] as\n  quack [let x be n / 0]\n]\nquack [let y be boom(1)]";
+        let blocks = Parser::new(lex(source).unwrap()).parse().unwrap();
+        let mut interpreter = Interpreter::new();
+        interpreter.add_hooks(Box::new(RecordingHooks(Rc::clone(&events))));
+        assert!(interpreter.run(blocks).is_err());
+        let errors: Vec<_> = events.borrow().iter().filter(|e| e.starts_with("error")).cloned().collect();
+        assert_eq!(errors, vec!["error 2".to_string()]);
+    }
+
+    #[test]
+    fn test_output_sink_receives_events() {
+        let events = Rc::new(RefCell::new(Vec::new()));
+        let tokens = lex("quack [let x be 1]\n[print x]").unwrap();
+        let blocks = Parser::new(tokens).parse().unwrap();
+        let mut interpreter = Interpreter::new();
+        interpreter.set_output_sink(Box::new(RecordingSink(Rc::clone(&events))));
+        interpreter.run(blocks).unwrap();
+        let (score, _) = interpreter.rate();
+        assert_eq!(
+            *events.borrow(),
+            vec!["refusal 2".to_string(), "success".to_string(), format!("rating {} 2", score)]
+        );
+    }
+
+    #[test]
+    fn test_aliasing_detection_warns_once_per_line() {
+        let events = Rc::new(RefCell::new(Vec::new()));
+        let source = concat!(
+            "quack [let a be list(1)]\n",
+            "quack [let b be a]\n",
+            "quack [let c be copy(a)]\n",
+            "quack [c push 2]\n",
+            "quack [repeat 2 times quack [b push 3]]",
+        );
+        let blocks = Parser::new(lex(source).unwrap()).parse().unwrap();
+        let mut interpreter = Interpreter::new();
+        interpreter.set_output_sink(Box::new(RecordingSink(Rc::clone(&events))));
+        interpreter.detect_aliasing();
+        interpreter.run(blocks).unwrap();
+
+        let warnings: Vec<String> = events.borrow().iter().filter(|e| e.starts_with("warning")).cloned().collect();
+        assert_eq!(warnings.len(), 1);
+        assert!(warnings[0].starts_with("warning 5 changing 'b' also changes 'a'"));
+    }
+
+    #[test]
+    fn test_aliasing_detection_covers_mutating_builtins() {
+        let events = Rc::new(RefCell::new(Vec::new()));
+        let source = concat!(
+            "quack [let a be list(1, 2)]\n",
+            "quack [let b be a]\n",
+            "quack [insert-at(b, 0, 5)]\n",
+            "quack [let gone be remove-at(b, 0)]\n",
+            "quack [let m be map(\"k\" -> 1)]\n",
+            "quack [let n be m]\n",
+            "quack [map-set(n, \"k\", 2)]\n",
+            "quack [let old be map-remove(n, \"k\")]\n",
+            "quack [let same be set-in(b, list(0), 9)]\n",
+            "quack [let fresh be copy(a)]\n",
+            "quack [insert-at(fresh, 0, 5)]",
+        );
+        let blocks = Parser::new(lex(source).unwrap()).parse().unwrap();
+        let mut interpreter = Interpreter::new();
+        interpreter.set_output_sink(Box::new(RecordingSink(Rc::clone(&events))));
+        interpreter.detect_aliasing();
+        interpreter.run(blocks).unwrap();
+
+        let lines: Vec<String> = events
+            .borrow()
+            .iter()
+            .filter(|e| e.starts_with("warning"))
+            .map(|e| e.split(' ').nth(1).unwrap_or_default().to_string())
+            .collect();
+        assert_eq!(lines, ["3", "4", "7", "8", "9"]);
+    }
+
+    #[test]
+    fn test_goose_bonus_and_penalty_recorded() {
+        let tokens = lex("quack [goose-bonus 2 \"uses recursion\"]\nquack [goose-penalty 1 \"no tests\"]").unwrap();
+        let blocks = Parser::new(tokens).parse().unwrap();
+        let mut interpreter = Interpreter::new();
+        interpreter.run(blocks).unwrap();
+        let adjustments = &interpreter.stats().rating_adjustments;
+        assert_eq!(adjustments.len(), 2);
+        assert_eq!(adjustments[0].points, 2.0);
+        assert_eq!(adjustments[0].reason, "uses recursion");
+        assert_eq!(adjustments[1].points, -1.0);
+        assert_eq!(adjustments[1].line, 2);
+    }
+
+    #[test]
+    fn test_goose_penalty_rejects_negative_points() {
+        assert!(run_source("quack [let x be goose-penalty(-1, \"sneaky\")]").is_err());
+    }
+
+    fn run_on(interpreter: &mut Interpreter, source: &str) {
+        let blocks = Parser::new(lex(source).unwrap()).parse().unwrap();
+        interpreter.run(blocks).unwrap();
+    }
+
+    #[test]
+    fn test_snapshot_restore_rolls_back_globals_and_stats() {
+        let mut interpreter = Interpreter::new();
+        run_on(&mut interpreter, "quack [let xs be [1, 2]]");
+        let snapshot = interpreter.snapshot();
+
+        run_on(&mut interpreter, "quack [xs push 3]\nquack [let y be 1]\n[print y]");
+        assert_eq!(interpreter.stats().unquacked_blocks, 1);
+
+        interpreter.restore(&snapshot);
+        let xs = interpreter.env.borrow().get("xs").unwrap();
+        assert_eq!(format!("{}", xs), "[1, 2]");
+        assert!(interpreter.env.borrow().get("y").is_none());
+        assert_eq!(interpreter.stats().unquacked_blocks, 0);
+
+        // The snapshot survives being restored and mutated again
+        run_on(&mut interpreter, "quack [xs push 4]");
+        interpreter.restore(&snapshot);
+        let xs = interpreter.env.borrow().get("xs").unwrap();
+        assert_eq!(format!("{}", xs), "[1, 2]");
+    }
+
+    #[test]
+    fn test_restore_gives_a_fresh_instruction_budget() {
+        // As `goose test` runs a file: each test starts from the same snapshot
+        let mut interpreter = Interpreter::new();
+        interpreter.set_instruction_limit(Some(1000));
+        let snapshot = interpreter.snapshot();
+        let heavy = Parser::new(lex("quack [let i be 0]\nquack [while i < 300 do quack [i becomes i + 1]]").unwrap())
+            .parse()
+            .unwrap();
+        for _ in 0..6 {
+            interpreter.restore(&snapshot);
+            interpreter.run_body(&heavy).unwrap();
+        }
+    }
+
+    #[test]
+    fn test_reload_functions_keeps_the_data() {
+        let mut interpreter = Interpreter::new();
+        run_on(&mut interpreter, concat!(
+            "quack [let score be 5]\n",
+            "quack [struct Duck with [name]]\n",
+            "quack [define bonus taking [n] as quack [return n + 1]]\n",
+            "quack [define method greet on Duck taking [] as quack [return \"hi\"]]\n",
+            "quack [score becomes bonus(score)]",
+        ));
+
+        let newer = concat!(
+            "quack [let score be 0]\n",
+            "quack [define bonus taking [n] as quack [return n * 10]]\n",
+            "quack [define method greet on Duck taking [] as quack [return \"honk \" ++ self.name]]\n",
+            "[define unquacked taking [] as quack [return 1]]",
+        );
+        let blocks = Parser::new(lex(newer).unwrap()).parse().unwrap();
+        assert_eq!(interpreter.reload_functions(&blocks).unwrap(), ["bonus", "Duck.greet"]);
+        run_on(&mut interpreter, "quack [honk bonus(score) == 60]\nquack [honk Duck(\"pip\").greet() == \"honk pip\"]");
+        assert!(interpreter.env.borrow().get("unquacked").is_none());
+        assert!(interpreter.set_hot_reload(Some("no/such/file.duck")).is_err());
+
+        // One bad definition and none of the file's definitions are kept
+        run_on(&mut interpreter, "quack [let limit be 3 always]");
+        let broken = concat!(
+            "quack [define bonus taking [n] as quack [return n - 1]]\n",
+            "quack [define method greet on Duck taking [] as quack [return \"bye\"]]\n",
+            "quack [define fresh taking [] as quack [return 1]]\n",
+            "quack [define limit taking [] as quack [return 4]]",
+        );
+        let blocks = Parser::new(lex(broken).unwrap()).parse().unwrap();
+        assert!(interpreter.reload_functions(&blocks).is_err());
+        run_on(&mut interpreter, "quack [honk bonus(score) == 60]\nquack [honk Duck(\"pip\").greet() == \"honk pip\"]");
+        assert!(interpreter.env.borrow().get("fresh").is_none());
+    }
+
+    #[test]
+    fn test_string_literals_share_one_allocation() {
+        let mut interpreter = Interpreter::new();
+        let literal = Expr::string("quack");
+        let (Ok(Value::String(a)), Ok(Value::String(b))) = (interpreter.evaluate(&literal, 1), interpreter.evaluate(&literal, 1)) else {
+            panic!("a string literal should evaluate to a string");
+        };
+        assert!(Arc::ptr_eq(&a, &b));
+    }
+
+    #[test]
+    fn test_function_values_share_their_definition_body() {
+        let blocks = Parser::new(lex("quack [define twice taking [n] as quack [return n * 2]]").unwrap()).parse().unwrap();
+        let Statement::FunctionDef { body: defined, .. } = &blocks[0].statement else {
+            panic!("expected a function definition");
+        };
+        let defined = Arc::clone(defined);
+        let mut interpreter = Interpreter::new();
+        interpreter.run(blocks).unwrap();
+        let Some(Value::Function { body, .. }) = interpreter.env.borrow().get("twice") else {
+            panic!("twice should be defined");
+        };
+        assert!(Arc::ptr_eq(&defined, &body));
+    }
+
+    #[test]
+    fn test_args_builtin_and_seeded_globals() {
+        let blocks = Parser::new(lex(concat!(
+            "quack [honk args() == list(\"a\", \"--b\")]\n",
+            "quack [quack-args becomes nil]\n",
+            "quack [honk len(args()) == 2]\n",
+            "quack [honk greeting == \"hi\"]",
+        )).unwrap()).parse().unwrap();
+        let mut interpreter = Interpreter::with_args(vec!["a".to_string(), "--b".to_string()]);
+        interpreter.define_global("greeting", Value::String("hi".into()));
+        interpreter.run(blocks).unwrap();
+    }
+
+    #[test]
+    fn test_assertions_report_values_and_structural_diff() {
+        assert!(run_source("quack [let a be assert-eq(list(1, 2), [1, 2])]\nquack [let b be assert-ne(1, 2)]\nquack [let c be assert-close(0.1 + 0.2, 0.3)]").is_ok());
+
+        let err = run_source("quack [let a be assert-eq(list(1, 5, 3), list(1, 2, 3), \"totals\")]").unwrap_err();
+        assert!(err.contains("assert-eq failed: totals"), "{}", err);
+        assert!(err.contains("expected: [1, 2, 3]") && err.contains("got: [1, 5, 3]"), "{}", err);
+        assert!(err.contains("at [1]: expected 2, got 5"), "{}", err);
+
+        let err = run_source("quack [let b be assert-ne(\"pip\", \"pip\")]").unwrap_err();
+        assert!(err.contains("both are \"pip\""), "{}", err);
+        let err = run_source("quack [let c be assert-close(3.2, 3, 0.1)]").unwrap_err();
+        assert!(err.contains("expected: 3 (within 0.1)"), "{}", err);
+        assert!(run_source("quack [let c be assert-close(\"3\", 3)]").is_err());
+    }
+
+    #[test]
+    fn test_for_all_passes_and_reports_shrunk_failure() {
+        assert!(run_source("quack [let ok be for-all(gen-int(), [n] -> n + 0 == n, 50)]").is_ok());
+
+        let err = run_source("quack [let ok be for-all(gen-list(gen-int(0, 100)), [xs] -> len(xs) < 3)]").unwrap_err();
+        assert!(err.contains("for input [0, 0, 0]"), "unexpected error: {}", err);
+    }
+
+    #[test]
+    fn test_for_all_lets_a_timeout_through() {
+        let blocks = Parser::new(lex("quack [let ok be for-all(gen-int(50, 100), [n] -> sleep(10000))]").unwrap()).parse().unwrap();
+        let mut interpreter = Interpreter::new();
+        interpreter.set_time_limit(Some(Duration::from_millis(50)));
+        let err = interpreter.run(blocks).unwrap_err();
+        assert!(err.contains("Time limit") && !err.contains("property failed"), "unexpected error: {}", err);
+    }
+
+    #[test]
+    fn test_frozen_values_reject_mutation() {
+        let setup = "quack [let pond be freeze(list(list(1), 2))]\n";
+        assert!(run_source(&format!("{}quack [pond push 3]", setup)).unwrap_err().contains("frozen"));
+        assert!(run_source(&format!("{}quack [let x be pop(pond)]", setup)).unwrap_err().contains("frozen"));
+        assert!(run_source(&format!("{}quack [pond at 0 becomes 9]", setup)).unwrap_err().contains("frozen"));
+        assert!(run_source(&format!("{}quack [let first be pond at 0]\nquack [first push 9]", setup)).unwrap_err().contains("frozen"));
+        // Rebinding the name is still fine - it's the value that's frozen
+        assert!(run_source(&format!("{}quack [pond becomes list()]\nquack [pond push 3]", setup)).is_ok());
+    }
+
+    #[test]
+    fn test_strict_math_rejects_infinity_and_nan() {
+        let run_strict = |source: &str| {
+            let blocks = Parser::new(lex(source).unwrap()).parse().unwrap();
+            let mut interpreter = Interpreter::new();
+            interpreter.set_strict_math(true);
+            interpreter.run(blocks)
+        };
+        assert!(run_strict("quack [let big be pow(10, 300) * pow(10, 300)]").unwrap_err().contains("infinity"));
+        assert!(run_strict("quack [let bad be pow(-8, 0.5)]").unwrap_err().contains("NaN"));
+        assert!(run_strict("quack [let fine be pow(2, 10) / 3]").is_ok());
+        // Off by default
+        assert!(run_source("quack [let big be pow(10, 300) * pow(10, 300)]").is_ok());
+    }
+
+    #[test]
+    fn test_modulo_takes_sign_of_divisor() {
+        assert!(run_source("quack [honk -7 % 3 == 2]\nquack [honk 7 % -3 == -2]\nquack [honk 7 % 3 == 1]").is_ok());
+    }
+
+    #[test]
+    fn test_time_limit_and_cancel_stop_sleep() {
+        let blocks = Parser::new(lex("quack [let x be sleep(10000)]").unwrap()).parse().unwrap();
+
+        let mut interpreter = Interpreter::new();
+        interpreter.set_time_limit(Some(Duration::from_millis(50)));
+        let started = Instant::now();
+        assert!(interpreter.run(blocks.clone()).unwrap_err().contains("Time limit"));
+        assert!(started.elapsed() < Duration::from_secs(5));
+
+        let mut interpreter = Interpreter::new();
+        interpreter.cancel_handle().cancel();
+        assert!(interpreter.run(blocks).unwrap_err().contains("cancelled"));
+
+        assert!(run_source("quack [let x be sleep(10 ** 300)]").unwrap_err().contains("a week at most"));
+        let mut interpreter = Interpreter::new();
+        interpreter.set_time_limit(Some(Duration::MAX));
+        assert!(interpreter.run(Vec::new()).is_ok());
+    }
+
+    #[test]
+    fn test_input_answers_from_replayed_session() {
+        use crate::input::{ReplayInput, Session};
+        let source = "quack [let name be input(\"Name? \")]\nquack [honk name == \"Gerald\"]\nquack [let again be input()]";
+        let blocks = Parser::new(lex(source).unwrap()).parse().unwrap();
+        let mut interpreter = Interpreter::new();
+        interpreter.set_input_source(Box::new(ReplayInput::new(
+            Session { inputs: vec![Some("Gerald".to_string())] },
+            false,
+        )));
+        // The second input() has no recorded answer
+        assert!(interpreter.run(blocks).unwrap_err().contains("ran out of answers"));
+    }
+
+    #[test]
+    fn test_input_helpers_ask_again_until_valid() {
+        use crate::input::{ReplayInput, Session};
+        let source = concat!(
+            "quack [let age be input-number(\"Age? \")]\n",
+            "quack [honk age == 12.5]\n",
+            "quack [let pet be input-choice(\"Pet? \", list(\"goose\", \"duck\"))]\n",
+            "quack [honk pet == \"duck\"]\n",
+            "quack [let sure be input-yes-no(\"Sure? \")]\n",
+            "quack [honk sure == false]\n",
+            "quack [let more be input-number()]",
+        );
+        let answers = ["twelve", "12.5", "cat", "DUCK", "maybe", "n"];
+        let blocks = Parser::new(lex(source).unwrap()).parse().unwrap();
+        let mut interpreter = Interpreter::new();
+        interpreter.set_input_source(Box::new(ReplayInput::new(
+            Session { inputs: answers.iter().map(|a| Some(a.to_string())).collect() },
+            false,
+        )));
+        // Everything up to the last question is answered (after some nagging)
+        let error = interpreter.run(blocks).unwrap_err();
+        assert!(error.contains("ran out of answers"), "{}", error);
+        assert_eq!(interpreter.env.borrow().get("pet"), Some(Value::String("duck".into())));
+    }
+
+    #[test]
+    fn test_confirm_defaults_to_no_and_secret_input_reads_answer() {
+        use crate::input::{ReplayInput, Session};
+        let source = concat!(
+            "quack [honk confirm(\"Delete everything?\") == false]\n",
+            "quack [honk confirm(\"Really?\") == true]\n",
+            "quack [honk secret-input(\"Token: \") == \"hunter2\"]",
+        );
+        let answers = ["", "what", "YES", "hunter2"];
+        let blocks = Parser::new(lex(source).unwrap()).parse().unwrap();
+        let mut interpreter = Interpreter::new();
+        interpreter.set_input_source(Box::new(ReplayInput::new(
+            Session { inputs: answers.iter().map(|a| Some(a.to_string())).collect() },
+            false,
+        )));
+        assert!(interpreter.run(blocks).is_ok());
+    }
+
+    #[test]
+    fn test_serve_answers_requests_with_handler() {
+        use std::io::{Read, Write};
+        use std::net::{TcpListener, TcpStream};
+
+        let port = TcpListener::bind("127.0.0.1:0").unwrap().local_addr().unwrap().port();
+        let source = format!(
+            concat!(
+                "quack [define greet taking [request] as\n",
+                "    quack [return f\"{{request.method}} {{request.path}} {{request.body}}\"]\n",
+                "]\n",
+                "quack [let done be serve({}, greet, 2)]",
+            ),
+            port
+        );
+        let client = std::thread::spawn(move || {
+            let mut bodies = Vec::new();
+            for request in ["GET /pond?x=1 HTTP/1.1\r\n\r\n", "POST /eggs HTTP/1.1\r\nContent-Length: 4\r\n\r\nfive"] {
+                let mut stream = loop {
+                    match TcpStream::connect(("127.0.0.1", port)) {
+                        Ok(stream) => break stream,
+                        Err(_) => std::thread::sleep(Duration::from_millis(10)),
+                    }
+                };
+                stream.write_all(request.as_bytes()).unwrap();
+                let mut response = String::new();
+                stream.read_to_string(&mut response).unwrap();
+                bodies.push(response);
+            }
+            bodies
+        });
+
+        let blocks = Parser::new(lex(&source).unwrap()).parse().unwrap();
+        let mut interpreter = Interpreter::new();
+        interpreter.set_time_limit(Some(Duration::from_secs(10)));
+        interpreter.run(blocks).unwrap();
+
+        let responses = client.join().unwrap();
+        assert!(responses[0].starts_with("HTTP/1.1 200 OK"));
+        assert!(responses[0].ends_with("GET /pond "));
+        assert!(responses[1].ends_with("POST /eggs five"));
+    }
+
+    #[test]
+    fn test_watch_path_calls_handler_with_changes() {
+        let dir = format!("goose-watch-path-{}", std::process::id());
+        std::fs::create_dir_all(&dir).unwrap();
+        let source = format!(
+            concat!(
+                "quack [let seen be list()]\n",
+                "quack [define on-change taking [changed] as\n",
+                "    quack [seen push changed]\n",
+                "]\n",
+                "quack [watch-path \"{}\" on-change 1]\n",
+                "quack [honk seen == list(list(\"{}/egg.txt\"))]",
+            ),
+            dir, dir
+        );
+        let writer = {
+            let dir = dir.clone();
+            std::thread::spawn(move || {
+                std::thread::sleep(Duration::from_millis(150));
+                std::fs::write(format!("{}/egg.txt", dir), "crack").unwrap();
+            })
+        };
+        let blocks = Parser::new(lex(&source).unwrap()).parse().unwrap();
+        let mut interpreter = Interpreter::new();
+        interpreter.set_time_limit(Some(Duration::from_secs(10)));
+        let result = interpreter.run(blocks);
+        writer.join().unwrap();
+        std::fs::remove_dir_all(&dir).unwrap();
+        result.unwrap();
+    }
+
+    #[test]
+    fn test_parse_args_reads_command_line() {
+        let source = concat!(
+            "quack [let spec be map(\"positional\" -> list(\"who\"), \"flags\" -> map(\"count\" -> 1, \"loud\" -> false))]\n",
+            "quack [let options be parse-args(spec)]\n",
+            "quack [honk options.who == \"Gerald\"]\n",
+            "quack [honk options.count == 3]\n",
+            "quack [honk not options.loud]\n",
+            "quack [let tried be parse-args(spec, list(\"Mabel\", \"--loud\"))]\n",
+            "quack [honk tried.loud]",
+        );
+        let blocks = Parser::new(lex(source).unwrap()).parse().unwrap();
+        let mut interpreter = Interpreter::with_args(vec!["Gerald".to_string(), "--count".to_string(), "3".to_string()]);
+        interpreter.run(blocks).unwrap();
+    }
+
+    #[test]
+    fn test_match_guards() {
+        let source = concat!(
+            "quack [define size taking [n] as\n",
+            "    quack [match n with\n",
+            "        [when 0 then quack [return \"none\"]]\n",
+            "        [when x if x > 5 then quack [return \"lots\"]]\n",
+            "        [when x if x < 0 then quack [return \"negative\"]]\n",
+            "        [when _ then quack [return \"a few\"]]\n",
+            "    ]\n",
+            "]\n",
+            "quack [honk size(0) == \"none\"]\n",
+            "quack [honk size(9) == \"lots\"]\n",
+            "quack [honk size(-1) == \"negative\"]\n",
+            "quack [honk size(3) == \"a few\"]",
+        );
+        assert!(run_source(source).is_ok());
+    }
+
+    #[test]
+    fn test_list_patterns_with_rest() {
+        let source = concat!(
+            "quack [define total taking [xs] as\n",
+            "    quack [match xs with\n",
+            "        [when [] then quack [return 0]]\n",
+            "        [when [first, rest...] then quack [return first + total(rest)]]\n",
+            "    ]\n",
+            "]\n",
+            "quack [honk total(list(1, 2, 3, 4)) == 10]\n",
+            "quack [define shape taking [xs] as\n",
+            "    quack [match xs with\n",
+            "        [when [only] then quack [return \"one\"]]\n",
+            "        [when [a, b] then quack [return \"pair\"]]\n",
+            "        [when [1, _...] then quack [return \"starts with 1\"]]\n",
+            "        [when _ then quack [return \"other\"]]\n",
+            "    ]\n",
+            "]\n",
+            "quack [honk shape(list(9)) == \"one\"]\n",
+            "quack [honk shape(list(9, 8)) == \"pair\"]\n",
+            "quack [honk shape(list(1, 8, 7)) == \"starts with 1\"]\n",
+            "quack [honk shape(list(2, 8, 7)) == \"other\"]",
+        );
+        assert!(run_source(source).is_ok());
+    }
+
+    #[test]
+    fn test_struct_patterns() {
+        let source = concat!(
+            "quack [struct Point with [x, y]]\n",
+            "quack [struct Circle with [center, radius]]\n",
+            "quack [define describe taking [shape] as\n",
+            "    quack [match shape with\n",
+            "        [when Point with [x, y] if x == y then quack [return \"diagonal\"]]\n",
+            "        [when Point with [x, y] then quack [return f\"point {x},{y}\"]]\n",
+            "        [when Circle with [radius] then quack [return f\"circle {radius}\"]]\n",
+            "        [when _ then quack [return \"unknown\"]]\n",
+            "    ]\n",
+            "]\n",
+            "quack [honk describe(Point(2, 2)) == \"diagonal\"]\n",
+            "quack [honk describe(Point(1, 2)) == \"point 1,2\"]\n",
+            "quack [honk describe(Circle(Point(0, 0), 5)) == \"circle 5\"]\n",
+            "quack [honk describe(3) == \"unknown\"]",
+        );
+        assert!(run_source(source).is_ok());
+    }
+
+    #[test]
+    fn test_struct_literal_with_named_fields() {
+        let source = concat!(
+            "quack [struct Point with [x, y]]\n",
+            "quack [let p be Point { y: 2, x: 1 }]\n",
+            "quack [honk p.x == 1 and p.y == 2]\n",
+            "quack [honk Point { x: 1, y: 2 } == Point(1, 2)]",
+        );
+        assert!(run_source(source).is_ok());
+
+        let missing = run_source("quack [struct Point with [x, y]]\nquack [let p be Point { x: 1 }]").unwrap_err();
+        assert!(missing.contains("Missing field 'y'"));
+        let unknown = run_source("quack [struct Point with [y, x]]\nquack [let p be Point { x: 1, y: 2, z: 3 }]");
+        assert_eq!(unknown.unwrap_err(), "Unknown field 'z' in struct 'Point' at line 2 (it has x, y)");
+    }
+
+    #[test]
+    fn test_builtin_names_cannot_be_taken() {
+        for source in [
+            "quack [let format be \"csv\"]\nquack [print format]",
+            "quack [let [len, rest...] be list(1, 2)]",
+            "quack [define max taking [a, b] as\n    quack [return a]\n]",
+            "quack [define pick taking [items, len] as\n    quack [return 1]\n]",
+        ] {
+            assert!(run_source(source).unwrap_err().contains("Pick another name"), "{}", source);
+        }
+        assert!(run_source("quack [let format-name be \"csv\"]\nquack [honk format-name == \"csv\"]").is_ok());
+    }
+
+    #[test]
+    fn test_constants_cannot_be_reassigned() {
+        let source = concat!(
+            "quack [let limit be 3 always]\n",
+            "quack [define double taking [limit] as\n",
+            "    quack [limit becomes limit * 2]\n",
+            "    quack [return limit]\n",
+            "]\n",
+            "quack [honk double(5) == 10]\n",
+            "quack [let seen be list() always]\n",
+            "quack [seen push 1]\n",
+            "quack [honk len(seen) == 1]",
+        );
+        assert!(run_source(source).is_ok());
+
+        let reassigned = run_source("quack [let limit be 3 always]\nquack [limit becomes 4]").unwrap_err();
+        assert!(reassigned.contains("does not rewrite history"));
+        assert!(run_source("quack [let limit be 3 always]\nquack [let limit be 4]").is_err());
+        let from_function = "quack [let limit be 3 always]\nquack [define bump taking [] as\n    quack [limit becomes 4]\n]\nquack [let unused be bump()]";
+        assert!(run_source(from_function).unwrap_err().contains("does not rewrite history"));
+
+        // define can't replace a constant either
+        let redefined = "quack [let limit be 3 always]\nquack [define limit taking [] as\n    quack [return 4]\n]";
+        assert!(run_source(redefined).unwrap_err().contains("does not rewrite history"));
+        assert!(run_source("quack [let point be 3 always]\nquack [struct point with [x]]").unwrap_err().contains("does not rewrite history"));
+        let method = "    quack [return 1]\n]";
+        let constant_struct = format!(
+            "quack [struct point with [x]]\nquack [let fixed be point always]\n\
+             quack [define method area on fixed taking [] as\n{0}\nquack [define method area on fixed taking [] as\n{0}",
+            method
+        );
+        assert!(run_source(&constant_struct).unwrap_err().contains("'fixed.area'"));
+    }
+
+    #[test]
+    fn test_decimal_arithmetic() {
+        let source = concat!(
+            "quack [let price be decimal(\"19.99\")]\n",
+            "quack [honk string(price * 3) == \"59.97\"]\n",
+            "quack [honk decimal(\"0.1\") + 0.2 == decimal(\"0.3\")]\n",
+            "quack [honk 0.1 + 0.2 != decimal(\"0.3\")]\n",
+            "quack [honk string(decimal(\"10.00\") / 4) == \"2.50\"]\n",
+            "quack [honk decimal(\"-7\") % 3 == 2]\n",
+            "quack [honk price > 19.98 and -price < 0]",
+        );
+        assert!(run_source(source).is_ok());
+        assert!(run_source("quack [let x be decimal(\"1\") / 0]").is_err());
+    }
+
+    #[test]
+    fn test_compound_assignment() {
+        let source = concat!(
+            "quack [let x be 10]\n",
+            "quack [x grows by 5]\n",
+            "quack [x shrinks by 3]\n",
+            "quack [x multiplies by 2]\n",
+            "quack [x divides by 4]\n",
+            "quack [let scores be list(1, 2)]\n",
+            "quack [scores at 1 grows by 10]\n",
+            "quack [honk x == 6 and scores at 1 == 12]",
+        );
+        assert!(run_source(source).is_ok());
+        let error = run_source("quack [let lives be 3 always]\nquack [lives shrinks by 1]").unwrap_err();
+        assert!(error.contains("The goose does not rewrite history"));
+    }
+
+    #[test]
+    fn test_compound_assignment_evaluates_index_once() {
+        let source = concat!(
+            "quack [let calls be 0]\n",
+            "quack [define next taking [] as\n",
+            "    quack [calls grows by 1]\n",
+            "    quack [return calls]\n",
+            "]\n",
+            "quack [let scores be list(10, 20, 30)]\n",
+            "quack [scores at next() grows by 5]\n",
+            "quack [honk calls == 1 and scores at 1 == 25 and scores at 2 == 30]",
+        );
+        assert!(run_source(source).is_ok());
+    }
+
+    #[test]
+    fn test_optional_chaining_stops_at_nil() {
+        let source = concat!(
+            "quack [struct person with [name, address]]\n",
+            "quack [struct place with [city]]\n",
+            "quack [let lonely be person(\"Gerald\", nil)]\n",
+            "quack [let settled be person(\"Mabel\", place(\"Pondville\"))]\n",
+            "quack [let nothing be nil]\n",
+            "quack [honk lonely?.address?.city == nil and settled?.address?.city == \"Pondville\"]\n",
+            "quack [honk (nothing ?at (1 / 0)) == nil and (list(1, 2) ?at 1) == 2]\n",
+            "quack [honk nothing?.greet(1 / 0) == nil]",
+        );
+        assert!(run_source(source).is_ok());
+        assert!(run_source("quack [let nothing be nil]\nquack [let x be nothing.name]").is_err());
+    }
+
+    #[test]
+    fn test_load_once_then_call_by_name() {
+        let mut interpreter = Interpreter::new();
+        interpreter.set_instruction_limit(Some(1_000));
+        let source = concat!(
+            "quack [let calls be 0]\n",
+            "quack [define handle taking [path] as\n",
+            "    quack [calls becomes calls + 1]\n",
+            "    quack [return f\"{path} #{calls}\"]\n",
+            "]",
+        );
+        interpreter.load(source).unwrap();
+        for _ in 0..400 {
+            interpreter.call("handle", vec![Value::String("/".into())]).unwrap();
+        }
+        let reply = interpreter.call("handle", vec![Value::String("/home".into())]).unwrap();
+        assert_eq!(reply, Value::String("/home #401".into()));
+        assert_eq!(interpreter.call("len", vec![Value::String("abc".into())]).unwrap(), Value::Number(3.0));
+        assert!(interpreter.call("missing", vec![]).is_err());
+        assert!(interpreter.call("handle", vec![]).is_err());
+    }
+
+    #[test]
+    fn test_child_changes_stay_in_the_child() {
+        let mut prelude = Interpreter::new();
+        let source = concat!(
+            "quack [let seen be list()]\n",
+            "quack [struct duck with [name]]\n",
+            "quack [define remember taking [x] as\n",
+            "    quack [seen push x]\n",
+            "    quack [return len(seen)]\n",
+            "]",
+        );
+        prelude.load(source).unwrap();
+
+        let mut child = prelude.child();
+        assert_eq!(child.call("remember", vec![Value::Number(1.0)]).unwrap(), Value::Number(1.0));
+        child
+            .load("quack [let extra be 1]\nquack [define method greet on duck taking [] as\n    quack [return 1]\n]")
+            .unwrap();
+        assert!(child.load("quack [let d be duck(\"Gerald\")]\nquack [print d.greet()]").is_ok());
+
+        // The parent never saw any of it
+        assert_eq!(prelude.env.borrow().get("seen").unwrap().to_string(), "[]");
+        assert!(prelude.env.borrow().get("extra").is_none());
+        assert!(prelude.load("quack [let d be duck(\"Gerald\")]\nquack [print d.greet()]").is_err());
+        assert_eq!(prelude.child().call("remember", vec![Value::Number(2.0)]).unwrap(), Value::Number(1.0));
+    }
+
+    #[test]
+    fn test_and_or_short_circuit_and_return_the_deciding_side() {
+        let source = concat!(
+            "quack [honk (0 or \"none\") == \"none\" and (\"a\" or 1 / 0) == \"a\"]\n",
+            "quack [honk (nil and 1 / 0) == nil and (1 and \"b\") == \"b\"]\n",
+            "quack [let calls be 0]\n",
+            "quack [define bump taking [] as\n",
+            "    quack [calls becomes calls + 1]\n",
+            "    quack [return true]\n",
+            "]\n",
+            "quack [let x be false and bump()]\n",
+            "quack [let y be true or bump()]\n",
+            "quack [honk calls == 0]",
+        );
+        assert!(run_source(source).is_ok());
+        assert!(run_source("quack [let x be true and 1 / 0]").is_err());
+    }
+
+    #[test]
+    fn test_at_to_slices() {
+        let source = concat!(
+            "quack [let items be list(10, 20, 30, 40)]\n",
+            "quack [honk items at 1 to 3 == list(20, 30)]\n",
+            "quack [honk items at 1 through 3 == list(20, 30, 40)]\n",
+            "quack [honk items at -2 through -1 == list(30, 40)]\n",
+            "quack [honk \"goose\" at 0 to 2 == \"go\"]\n",
+            "quack [items at 0 to 2]",
+        );
+        assert!(run_source(source).is_ok());
+    }
+
+    #[test]
+    fn test_higher_order_builtins_take_the_function_first_or_last() {
+        let source = concat!(
+            "quack [let items be list(1, 2, 3)]\n",
+            "quack [honk map([x] -> x * 2, items) == list(2, 4, 6)]\n",
+            "quack [honk map(x -> x * 2, items) == list(2, 4, 6)]\n",
+            "quack [honk map(x -> x * 2, filter(y -> y > 1, items)) == list(4, 6)]\n",
+            "quack [honk map(items, [x] -> x * 2) == list(2, 4, 6)]\n",
+            "quack [honk filter([x] -> x > 1, items) == list(2, 3)]\n",
+            "quack [honk reduce([acc, x] -> acc + x, 0, items) == 6]\n",
+            "quack [honk find([x] -> x > 1, items) == 2 and find(items, [x] -> x > 5) == nil]\n",
+            "quack [honk fold(items, list(), [acc, x] -> acc ++ list(x)) == items]\n",
+            "quack [honk map(\"a\" -> 1) at \"a\" == 1]\n",
+            "quack [let a be \"a\"]\n",
+            "quack [let b be \"b\"]\n",
+            "quack [honk map(a -> 1, b -> 2) at \"b\" == 2]",
+        );
+        assert!(run_source(source).is_ok());
+    }
+
+    #[test]
+    fn test_zip_and_enumerate_unpack_in_for_each() {
+        let source = concat!(
+            "quack [let names be list(\"gerald\", \"mabel\", \"pip\")]\n",
+            "quack [honk zip(names, list(3, 5)) == list(list(\"gerald\", 3), list(\"mabel\", 5))]\n",
+            "quack [honk enumerate(\"ab\") == list(list(0, \"a\"), list(1, \"b\"))]\n",
+            "quack [let seen be list()]\n",
+            "quack [for each [i, name] in enumerate(names) do\n",
+            "    quack [seen push f\"{i}:{name}\"]\n",
+            "]\n",
+            "quack [honk seen == list(\"0:gerald\", \"1:mabel\", \"2:pip\")]\n",
+            "quack [for each [pair] in zip(names, names) do quack [honk len(pair) == 2]]",
+        );
+        assert!(run_source(source).is_ok());
+        let error = run_source("quack [for each [a, b] in list(list(1)) do quack [print a]]").unwrap_err();
+        assert!(error.contains("'for each' needs exactly 2 items"), "{}", error);
+    }
+
+    #[test]
+    fn test_format_specs_in_f_strings() {
+        let source = concat!(
+            "quack [let x be 10 / 3]\n",
+            "quack [honk f\"{x:.2}|{x * 3:06.1}|{\"pip\":5}|{len(list(1, 2)):03}\" == \"3.33|0010.0|pip  |002\"]\n",
+            "quack [honk format(x, \"6.1\") == \"   3.3\" and format(decimal(\"2.675\"), \".2\") == \"2.68\"]",
+        );
+        assert!(run_source(source).is_ok());
+        assert!(run_source("quack [print f\"{\"pip\":.2}\"]").unwrap_err().contains("decimal places"));
+        assert!(lex("quack [print f\"{1:zz}\"]").and_then(|tokens| Parser::new(tokens).parse().map_err(|e| e.join("\n"))).is_err());
+    }
+
+    #[test]
+    fn test_insert_at_and_remove_at_shift_items() {
+        let source = concat!(
+            "quack [let l be list(1, 3)]\n",
+            "quack [insert-at(l, 1, 2)]\n",
+            "quack [insert-at(l, 3, 4)]\n",
+            "quack [insert-at(l, -1, 9)]\n",
+            "quack [honk l == list(1, 2, 3, 9, 4)]\n",
+            "quack [honk remove-at(l, -2) == 9 and remove-at(l, 0) == 1]\n",
+            "quack [honk l == list(2, 3, 4)]",
+        );
+        assert!(run_source(source).is_ok());
+        let error = run_source("quack [let l be list(1)]\nquack [remove-at(l, 1)]").unwrap_err();
+        assert!(error.starts_with("Line 2:") && error.contains('1'), "{}", error);
+        assert!(run_source("quack [insert-at(list(1), -3, 0)]").is_err());
+        assert!(run_source("quack [remove-at(freeze(list(1)), 0)]").is_err());
+    }
+
+    #[test]
+    fn test_nil_coalescing_only_evaluates_what_it_needs() {
+        let source = concat!(
+            "quack [honk (nil ?? 3) == 3 and (false ?? 3) == false]\n",
+            "quack [honk (nil ?? nil ?? \"x\") == \"x\"]\n",
+            "quack [honk (1 ?? (1 / 0)) == 1]",
+        );
+        assert!(run_source(source).is_ok());
+        assert!(run_source("quack [let x be nil ?? (1 / 0)]").is_err());
+    }
+
+    #[test]
+    fn test_echo_expressions_still_runs_statements() {
+        let tokens = lex("quack [let x be 2]\nquack [x * 3]\nquack [honk x == 2]").unwrap();
+        let blocks = Parser::new(tokens).parse().unwrap();
+        let mut interpreter = Interpreter::new();
+        interpreter.set_echo_expressions(true);
+        assert!(interpreter.run(blocks).is_ok());
+        assert_eq!(interpreter.stats.quacked_blocks, 3);
+    }
+
+    #[test]
+    fn test_let_unpacks_lists() {
+        let source = concat!(
+            "quack [let [a, b] be list(1, 2)]\n",
+            "quack [let [first, rest...] be list(1, 2, 3)]\n",
+            "quack [let [[x, _], y] be list(list(5, 6), 7)]\n",
+            "quack [honk a + b == 3 and first == 1 and rest == list(2, 3) and x + y == 12]",
+        );
+        assert!(run_source(source).is_ok());
+        let error = run_source("quack [let [a, b] be list(1, 2, 3)]").unwrap_err();
+        assert!(error.contains("needs exactly 2 items to unpack, but the list has 3"));
+        assert!(run_source("quack [let [a] be \"a\"]").unwrap_err().contains("can only unpack a list"));
+        assert!(run_source("quack [let [a] be list(1) always]\nquack [let [a] be list(2)]").is_err());
+    }
+
+    #[test]
+    fn test_rest_parameter_collects_extra_arguments() {
+        let source = concat!(
+            "quack [define count-rest taking [first, others...] as\n",
+            "    quack [return length(others)]\n",
+            "]\n",
+            "quack [honk count-rest(1) == 0]\n",
+            "quack [honk count-rest(1, 2, 3) == 2]",
+        );
+        assert!(run_source(source).is_ok());
+        let error = run_source(&format!("{}\nquack [let unused be count-rest()]", source)).unwrap_err();
+        assert!(error.contains("needs at least 1 argument, got 0"));
+    }
+
+    #[test]
+    fn test_fraction_arithmetic() {
+        let source = concat!(
+            "quack [let third be fraction(1, 3)]\n",
+            "quack [honk third + fraction(1, 6) == fraction(1, 2)]\n",
+            "quack [honk third * 3 == 1]\n",
+            "quack [honk string(fraction(1, 2) + 0.25) == \"3/4\"]\n",
+            "quack [honk fraction(2, 3) ** -2 == fraction(9, 4)]\n",
+            "quack [honk third < 0.34 and -third < 0]",
+        );
+        assert!(run_source(source).is_ok());
+        assert!(run_source("quack [let x be fraction(1, 3) / 0]").is_err());
+    }
+
+    #[test]
+    fn test_struct_methods() {
+        let source = concat!(
+            "quack [struct Circle with [radius]]\n",
+            "quack [define method area on Circle taking [] as\n",
+            "    quack [return 3 * self.radius * self.radius]\n",
+            "]\n",
+            "quack [define method grow on Circle taking [by] as\n",
+            "    quack [self.radius becomes self.radius + by]\n",
+            "]\n",
+            "quack [let c be Circle(2)]\n",
+            "quack [honk c.area() == 12]\n",
+            "quack [c.grow(1)]\n",
+            "quack [honk c.area() == 27]",
+        );
+        assert!(run_source(source).is_ok());
+
+        let clash = "quack [struct Circle with [radius]]\nquack [define method radius on Circle taking [] as\n    quack [return 1]\n]";
+        assert!(run_source(clash).unwrap_err().contains("already has a field called 'radius'"));
+        assert!(run_source("quack [define method area on Nothing taking [] as\n    quack [return 1]\n]").is_err());
+    }
+
+    #[test]
+    fn test_conditional_expression() {
+        let source = concat!(
+            "quack [let n be 7]\n",
+            "quack [let parity be if n % 2 == 0 then \"even\" otherwise \"odd\"]\n",
+            "quack [honk parity == \"odd\"]\n",
+            "quack [honk len(if n > 5 then list(1, 2) otherwise list()) == 2]\n",
+            "quack [let size be if n < 3 then \"small\" otherwise if n < 10 then \"medium\" otherwise \"large\"]\n",
+            "quack [honk size == \"medium\"]\n",
+            "quack [let safe be if n > 100 then 1 / 0 otherwise 0]\n",
+            "quack [honk safe == 0]",
+        );
+        assert!(run_source(source).is_ok());
+        assert!(run_source("quack [let x be if true then 1]").is_err());
+    }
+
+    #[test]
+    fn test_attempt_rescues_runtime_errors() {
+        let source = concat!(
+            "quack [let caught be list()]\n",
+            "quack [attempt\n",
+            "    quack [let x be 1 / 0]\n",
+            "rescue [err]\n",
+            "    quack [caught push err]\n",
+            "]\n",
+            "quack [attempt\n",
+            "    quack [let y be list(1) at 5]\n",
+            "rescue err\n",
+            "    quack [caught push err]\n",
+            "]\n",
+            "quack [honk len(caught) == 2]",
+        );
+        assert!(run_source(source).is_ok());
+    }
+
+    #[test]
+    fn test_attempt_does_not_rescue_time_limit() {
+        let source = "quack [attempt\n    quack [let x be sleep(10000)]\nrescue err\n    quack [print err]\n]";
+        let blocks = Parser::new(lex(source).unwrap()).parse().unwrap();
+        let mut interpreter = Interpreter::new();
+        interpreter.set_time_limit(Some(Duration::from_millis(50)));
+        assert!(interpreter.run(blocks).unwrap_err().contains("Time limit"));
+    }
+
+    #[test]
+    fn test_long_operator_chains_do_not_overflow() {
+        let sum = vec!["1"; 50_000].join(" + ");
+        let falses = vec!["false"; 50_000].join(" or ");
+        let unreachable = vec!["1 / 0 == 1"; 50_000].join(" or ");
+        let source = format!(
+            "quack [honk {} == 50000]\nquack [honk {} or true]\nquack [honk true or {}]",
+            sum, falses, unreachable
+        );
+        assert!(run_source(&source).is_ok());
+    }
+
+    #[test]
+    fn test_long_otherwise_if_chains_do_not_overflow() {
+        let mut source = String::from("quack [let x be 4999]\nquack [let picked be -1]\nquack [if x == 0 then\n    quack [picked becomes 0]\n");
+        for i in 1..5_000 {
+            source.push_str(&format!("otherwise if x == {} then\n    quack [picked becomes {}]\n", i, i));
+        }
+        source.push_str("otherwise\n    quack [picked becomes \"none\"]\n]\nquack [honk picked == 4999]");
+        let run = move || run_source(&source);
+        let handle = std::thread::Builder::new().stack_size(crate::parser::STACK_SIZE).spawn(run).unwrap();
+        assert!(handle.join().unwrap().is_ok());
+    }
+
+    #[test]
+    fn test_ws_receive_checks_the_socket_then_the_timeout() {
+        let error = run_source("quack [let message be ws-receive(1, 10 ** 300)]").unwrap_err();
+        assert!(error.contains("expected a websocket"), "{}", error);
+
+        let listener = std::net::TcpListener::bind("127.0.0.1:0").unwrap();
+        let port = listener.local_addr().unwrap().port();
+        let server = std::thread::spawn(move || {
+            let mut socket = tungstenite::accept(listener.accept().unwrap().0).unwrap();
+            while socket.read().is_ok() {}
+        });
+        let source = format!(
+            "quack [let socket be ws-connect(\"ws://127.0.0.1:{}\")]\nquack [let message be ws-receive(socket, 10 ** 300)]",
+            port
+        );
+        let error = run_source(&source).unwrap_err();
+        assert!(error.contains("up to a week"), "{}", error);
+        // The interpreter is gone, so its socket was closed and the server hears about it
+        server.join().unwrap();
+    }
+
+    #[test]
+    fn test_exit_stops_cleanly_with_its_code() {
+        let source = concat!(
+            "quack [let reached be \"nowhere\"]\n",
+            "quack [attempt\n    quack [exit(3)]\nrescue err\n    quack [reached becomes \"rescue\"]\n]\n",
+            "quack [reached becomes \"after\"]",
+        );
+        let blocks = Parser::new(lex(source).unwrap()).parse().unwrap();
+        let mut interpreter = Interpreter::new();
+        assert!(interpreter.run(blocks).is_ok());
+        assert_eq!(interpreter.exit_code(), Some(3));
+        let reached = interpreter.env.borrow().get("reached").unwrap();
+        assert_eq!(reached.to_string(), "nowhere");
+
+        assert!(run_source("quack [exit(256)]").unwrap_err().contains("0 to 255"));
+        assert!(run_source("quack [exit(\"1\")]").is_err());
+    }
+
+    #[test]
+    fn test_shell_is_off_until_allowed() {
+        let source = "quack [let result be shell(\"echo honk\")]\nquack [honk result.stdout == \"honk\\n\" and result.status == 0]";
+        assert!(run_source(source).unwrap_err().contains("--allow-shell"));
+        if cfg!(unix) {
+            let blocks = Parser::new(lex(source).unwrap()).parse().unwrap();
+            let mut interpreter = Interpreter::new();
+            interpreter.set_policy(Policy::all().with_shell(true));
+            interpreter.run(blocks).unwrap();
+        }
+    }
+
+    #[test]
+    fn test_desktop_builtins_respect_access() {
+        let blocks = Parser::new(lex("quack [clipboard-set(\"honk\")]").unwrap()).parse().unwrap();
+        let mut interpreter = Interpreter::new();
+        interpreter.set_policy(Policy::all().with_desktop(false));
+        assert!(interpreter.run(blocks.clone()).unwrap_err().contains("desktop access is turned off"));
+        interpreter.set_policy(Policy::all().deny(Capability::Proc));
+        assert!(interpreter.run(blocks).unwrap_err().contains("clipboard-set() needs proc access"));
+    }
+
+    #[test]
+    fn test_policy_refuses_denied_capabilities() {
+        let run_with = |policy: Policy, source: &str| {
+            let mut interpreter = Interpreter::new();
+            interpreter.set_policy(policy);
+            interpreter.run(Parser::new(lex(source).unwrap()).parse().unwrap())
+        };
+        let error = run_with(Policy::all().deny(Capability::FsWrite), "quack [write-file(\"x.txt\", \"honk\")]").unwrap_err();
+        assert!(error.contains("write-file() needs fs-write access"), "{}", error);
+        assert!(run_with(Policy::none(), "quack [migrate \"helpers.duck\"]").unwrap_err().contains("migrate needs fs-read"));
+        assert!(run_with(Policy::none(), "quack [let f be now]\nquack [f()]").is_err());
+        assert!(run_with(Policy::none().allow(Capability::Time), "quack [honk now() > 0 and len(list(1)) == 1]").is_ok());
+    }
+
+    #[test]
+    fn test_maps() {
+        let source = concat!(
+            "quack [let ages be map(\"gerald\" -> 3, \"mabel\" -> 5)]\n",
+            "quack [honk ages at \"mabel\" == 5]\n",
+            "quack [ages at \"pip\" becomes 1]\n",
+            "quack [map-set ages \"gerald\" 4]\n",
+            "quack [honk map-get(ages, \"gerald\") == 4]\n",
+            "quack [honk map-get(ages, \"nobody\", 0) == 0]\n",
+            "quack [honk has-key(ages, \"pip\")]\n",
+            "quack [honk map-remove(ages, \"pip\") == 1]\n",
+            "quack [honk not has-key(ages, \"pip\")]\n",
+            "quack [honk map-keys(ages) == list(\"gerald\", \"mabel\")]\n",
+            "quack [honk map-values(ages) == list(4, 5)]\n",
+            "quack [honk len(ages) == 2]\n",
+            "quack [honk map(1 -> \"one\") == map(1 -> \"one\")]\n",
+            "quack [honk map(list(1, 2), [x] -> x * 2) == list(2, 4)]",
+        );
+        assert!(run_source(source).is_ok());
+        assert!(run_source("quack [let m be map()]\nquack [let x be m at \"missing\"]").unwrap_err().contains("no key"));
+        assert!(run_source("quack [let m be map(list() -> 1)]").unwrap_err().contains("map keys"));
+    }
+
+    #[test]
+    fn test_range_syntax() {
+        let source = concat!(
+            "quack [let total be 0]\n",
+            "quack [for each [i] in 1 through 4 do\n",
+            "    quack [total becomes total + i]\n",
+            "]\n",
+            "quack [honk total == 10]\n",
+            "quack [honk 0 to 3 == list(0, 1, 2)]\n",
+            "quack [let n be 2]\n",
+            "quack [honk len(1 to n + 1) == 2]",
+        );
+        assert!(run_source(source).is_ok());
+        assert!(run_source("quack [let r be \"a\" to 3]").is_err());
+
+        // Outside a range, to and through are ordinary names
+        let names = concat!(
+            "quack [define send taking [message, to] as\n    quack [return message ++ \" to \" ++ to]\n]\n",
+            "quack [let through be 3]\n",
+            "quack [honk send(\"hi\", \"bob\") == \"hi to bob\"]\n",
+            "quack [honk len(through to through + 2) == 2]",
+        );
+        assert!(run_source(names).is_ok());
+    }
+
+    #[test]
+    fn test_power_operator() {
+        let source = concat!(
+            "quack [honk 2 ** 10 == 1024]\n",
+            "quack [honk 2 ** 3 ** 2 == 512]\n",
+            "quack [honk -2 ** 2 == -4]\n",
+            "quack [honk (-2) ** 2 == 4]\n",
+            "quack [honk 3 * 2 ** 2 == 12]\n",
+            "quack [honk 2 ** -1 == 0.5]",
+        );
+        assert!(run_source(source).is_ok());
+    }
+
+    #[test]
+    fn test_concat_operator() {
+        let source = concat!(
+            "quack [let xs be list(1, 2)]\n",
+            "quack [let joined be xs ++ list(3) ++ list()]\n",
+            "quack [honk joined == list(1, 2, 3)]\n",
+            "quack [honk len(xs) == 2]\n",
+            "quack [honk \"quack\" ++ \"quack\" == \"quackquack\"]\n",
+            "quack [honk list(1) ++ list(1 + 1) == list(1, 2)]",
+        );
+        assert!(run_source(source).is_ok());
+        assert!(run_source("quack [let x be \"a\" ++ 1]").unwrap_err().contains("++"));
+    }
+
     #[test]
     fn test_multi_quack() {
         let result = run_source("quack quack [let x be 1] [let y be 2]");
@@ -1,5 +1,11 @@
 // Lexer - tokenization for Duck language
 
+use std::ops::Range;
+
+/// How many f-strings deep an interpolation can go (f"{f"{...}"}") before
+/// the lexer gives up instead of overflowing the stack
+pub const MAX_INTERPOLATION_DEPTH: usize = 64;
+
 /// Represents the different kinds of tokens in Duck-Lang
 #[derive(Debug, Clone, PartialEq)]
 pub enum TokenKind {
@@ -16,8 +22,10 @@ pub enum TokenKind {
 
     // Operators
     Plus,
+    PlusPlus,    // ++
     Minus,
     Star,
+    StarStar,    // **
     Slash,
     Percent,
     EqualEqual,  // ==
@@ -28,8 +36,13 @@ pub enum TokenKind {
     GreaterEqual,
     Arrow,       // ->
     FatArrow,    // =>
+    QuestionQuestion, // ??
+    QuestionDot, // ?.
+    Question,    // ? (only before `at`)
     Comma,
+    Colon,
     Dot,
+    Ellipsis,    // ...
 
     // Keywords
     Let,
@@ -87,11 +100,31 @@ pub enum TokenKind {
     StringEnd,
     InterpolationStart,
     InterpolationEnd,
+    /// The `.2` in `{x:.2}`
+    FormatSpec,
 
     // End of file
     Eof,
 }
 
+/// The kinds of source text the parser ignores but formatters need to keep
+#[derive(Debug, Clone, PartialEq)]
+pub enum TriviaKind {
+    /// Spaces, tabs, and carriage returns
+    Whitespace,
+    /// A single line break
+    Newline,
+    /// A `--` comment, without its line break
+    Comment,
+}
+
+/// A run of ignored source text, kept so tools can rewrite files losslessly
+#[derive(Debug, Clone, PartialEq)]
+pub struct Trivia {
+    pub kind: TriviaKind,
+    pub text: String,
+}
+
 /// A token with its kind, lexeme, and source location
 #[derive(Debug, Clone)]
 pub struct Token {
@@ -99,36 +132,75 @@ pub struct Token {
     pub lexeme: String,
     pub line: usize,
     pub column: usize,
+    /// Byte range of the source text this token was read from. Unlike the
+    /// lexeme it keeps a string's quotes and escapes exactly as written, so
+    /// trivia plus these slices rebuild the source
+    pub span: Range<usize>,
+    /// Whitespace, newlines, and comments between the previous token and this one
+    pub leading_trivia: Vec<Trivia>,
 }
 
 impl Token {
     pub fn new(kind: TokenKind, lexeme: String, line: usize, column: usize) -> Self {
-        Token { kind, lexeme, line, column }
+        Token { kind, lexeme, line, column, span: 0..0, leading_trivia: Vec::new() }
+    }
+
+    /// Comments written directly before this token
+    pub fn leading_comments(&self) -> impl Iterator<Item = &str> {
+        self.leading_trivia
+            .iter()
+            .filter(|t| t.kind == TriviaKind::Comment)
+            .map(|t| t.text.as_str())
+    }
+
+    /// Number of blank lines (lines holding only whitespace) directly before this token
+    pub fn blank_lines_before(&self) -> usize {
+        let mut blank_lines = 0;
+        let mut at_line_start = false;
+        for trivia in &self.leading_trivia {
+            match trivia.kind {
+                TriviaKind::Newline if at_line_start => blank_lines += 1,
+                TriviaKind::Newline => at_line_start = true,
+                TriviaKind::Comment => at_line_start = false,
+                TriviaKind::Whitespace => {}
+            }
+        }
+        blank_lines
     }
 }
 
 /// The lexer struct that maintains state during tokenization
 pub struct Lexer {
     source: Vec<char>,
+    /// Byte offset of each character in the original text, plus one past the end
+    byte_offsets: Vec<usize>,
     tokens: Vec<Token>,
     start: usize,
     current: usize,
     line: usize,
     column: usize,
     start_column: usize,
+    /// Trivia waiting to be attached to the next token
+    pending_trivia: Vec<Trivia>,
+    /// How many f-string interpolations we're currently inside
+    interpolation_depth: usize,
 }
 
 impl Lexer {
     /// Create a new lexer for the given source code
     pub fn new(source: &str) -> Self {
+        let byte_offsets = source.char_indices().map(|(i, _)| i).chain([source.len()]).collect();
         Lexer {
             source: source.chars().collect(),
+            byte_offsets,
             tokens: Vec::new(),
             start: 0,
             current: 0,
             line: 1,
             column: 1,
             start_column: 1,
+            pending_trivia: Vec::new(),
+            interpolation_depth: 0,
         }
     }
 
@@ -140,8 +212,20 @@ impl Lexer {
             self.scan_token()?;
         }
 
-        self.tokens.push(Token::new(TokenKind::Eof, String::new(), self.line, self.column));
-        Ok(self.tokens.clone())
+        let mut eof = Token::new(TokenKind::Eof, String::new(), self.line, self.column);
+        let end = self.byte_offsets[self.source.len()];
+        eof.span = end..end;
+        eof.leading_trivia = std::mem::take(&mut self.pending_trivia);
+        self.tokens.push(eof);
+        Ok(std::mem::take(&mut self.tokens))
+    }
+
+    /// Create a lexer whose line numbers start at `line` instead of 1
+    /// (used by the REPL so each entry keeps its own line number)
+    pub fn starting_at_line(source: &str, line: usize) -> Self {
+        let mut lexer = Lexer::new(source);
+        lexer.line = line;
+        lexer
     }
 
     /// Check if we've reached the end of the source
@@ -150,10 +234,13 @@ impl Lexer {
     }
 
     /// Advance to the next character and return the current one
+    /// ('\0', without moving, at the end of the source)
     fn advance(&mut self) -> char {
-        let c = self.source[self.current];
-        self.current += 1;
-        self.column += 1;
+        let c = self.peek();
+        if !self.is_at_end() {
+            self.current += 1;
+            self.column += 1;
+        }
         c
     }
 
@@ -177,7 +264,7 @@ impl Lexer {
 
     /// Match the current character and advance if it matches
     fn match_char(&mut self, expected: char) -> bool {
-        if self.is_at_end() || self.source[self.current] != expected {
+        if self.is_at_end() || self.peek() != expected {
             false
         } else {
             self.current += 1;
@@ -188,18 +275,35 @@ impl Lexer {
 
     /// Get the current lexeme
     fn current_lexeme(&self) -> String {
-        self.source[self.start..self.current].iter().collect()
+        self.source.get(self.start..self.current).unwrap_or_default().iter().collect()
     }
 
     /// Add a token to the list
     fn add_token(&mut self, kind: TokenKind) {
         let lexeme = self.current_lexeme();
-        self.tokens.push(Token::new(kind, lexeme, self.line, self.start_column));
+        self.add_token_with_lexeme(kind, lexeme);
     }
 
     /// Add a token with a specific lexeme
     fn add_token_with_lexeme(&mut self, kind: TokenKind, lexeme: String) {
-        self.tokens.push(Token::new(kind, lexeme, self.line, self.start_column));
+        let mut token = Token::new(kind, lexeme, self.line, self.start_column);
+        token.span = self.byte_offsets[self.start]..self.byte_offsets[self.current];
+        token.leading_trivia = std::mem::take(&mut self.pending_trivia);
+        self.tokens.push(token);
+    }
+
+    /// Record the current lexeme as trivia, merging runs of whitespace
+    fn add_trivia(&mut self, kind: TriviaKind) {
+        if kind == TriviaKind::Whitespace {
+            if let Some(last) = self.pending_trivia.last_mut() {
+                if last.kind == TriviaKind::Whitespace {
+                    last.text.extend(&self.source[self.start..self.current]);
+                    return;
+                }
+            }
+        }
+        let text = self.current_lexeme();
+        self.pending_trivia.push(Trivia { kind, text });
     }
 
     /// Scan a single token
@@ -208,8 +312,9 @@ impl Lexer {
 
         match c {
             // Whitespace
-            ' ' | '\r' | '\t' => {}
+            ' ' | '\r' | '\t' => self.add_trivia(TriviaKind::Whitespace),
             '\n' => {
+                self.add_trivia(TriviaKind::Newline);
                 self.line += 1;
                 self.column = 1;
             }
@@ -221,16 +326,37 @@ impl Lexer {
             ')' => self.add_token(TokenKind::RightParen),
             '{' => self.add_token(TokenKind::LeftBrace),
             '}' => self.add_token(TokenKind::RightBrace),
-            '+' => self.add_token(TokenKind::Plus),
-            '*' => self.add_token(TokenKind::Star),
+            '+' => {
+                if self.match_char('+') {
+                    self.add_token(TokenKind::PlusPlus);
+                } else {
+                    self.add_token(TokenKind::Plus);
+                }
+            }
+            '*' => {
+                if self.match_char('*') {
+                    self.add_token(TokenKind::StarStar);
+                } else {
+                    self.add_token(TokenKind::Star);
+                }
+            }
             '/' => self.add_token(TokenKind::Slash),
             '%' => self.add_token(TokenKind::Percent),
             ',' => self.add_token(TokenKind::Comma),
-            '.' => self.add_token(TokenKind::Dot),
+            ':' => self.add_token(TokenKind::Colon),
+            '.' => {
+                if self.peek() == '.' && self.peek_next() == '.' {
+                    self.advance();
+                    self.advance();
+                    self.add_token(TokenKind::Ellipsis);
+                } else {
+                    self.add_token(TokenKind::Dot);
+                }
+            }
             '_' => {
                 // Could be underscore or start of identifier
                 if self.peek().is_ascii_alphanumeric() || self.peek() == '_' {
-                    self.identifier();
+                    self.identifier()?;
                 } else {
                     self.add_token(TokenKind::Underscore);
                 }
@@ -243,6 +369,7 @@ impl Lexer {
                     while self.peek() != '\n' && !self.is_at_end() {
                         self.advance();
                     }
+                    self.add_trivia(TriviaKind::Comment);
                 } else if self.match_char('>') {
                     self.add_token(TokenKind::Arrow);
                 } else {
@@ -265,6 +392,29 @@ impl Lexer {
                     return Err(format!("Unexpected character '!' at line {}. Did you mean '!=' or 'not'?", self.line));
                 }
             }
+            '?' => {
+                if self.match_char('?') {
+                    self.add_token(TokenKind::QuestionQuestion);
+                } else if self.match_char('.') {
+                    self.add_token(TokenKind::QuestionDot);
+                } else {
+                    self.add_token(TokenKind::Question);
+                }
+            }
+            '&' => {
+                if self.match_char('&') {
+                    self.add_token(TokenKind::And);
+                } else {
+                    return Err(format!("Unexpected character '&' at line {}. Did you mean '&&' or 'and'?", self.line));
+                }
+            }
+            '|' => {
+                if self.match_char('|') {
+                    self.add_token(TokenKind::Or);
+                } else {
+                    return Err(format!("Unexpected character '|' at line {}. Did you mean '||' or 'or'?", self.line));
+                }
+            }
             '<' => {
                 if self.match_char('=') {
                     self.add_token(TokenKind::LessEqual);
@@ -287,7 +437,7 @@ impl Lexer {
             c if c.is_ascii_digit() => self.number()?,
 
             // Identifiers and keywords
-            c if c.is_ascii_alphabetic() => self.identifier(),
+            c if c.is_ascii_alphabetic() => self.identifier()?,
 
             _ => {
                 return Err(format!("Unexpected character '{}' at line {}", c, self.line));
@@ -390,13 +540,26 @@ impl Lexer {
                 }
                 value.clear();
 
+                self.start = self.current;
                 self.advance(); // consume '{'
                 self.add_token_with_lexeme(TokenKind::InterpolationStart, "{".to_string());
 
                 // Tokenize the interpolation expression until we hit '}'
-                self.scan_interpolation(start_line)?;
+                if self.interpolation_depth >= MAX_INTERPOLATION_DEPTH {
+                    return Err(format!(
+                        "F-strings nested more than {} deep in string starting at line {} - the goose lost count",
+                        MAX_INTERPOLATION_DEPTH, start_line
+                    ));
+                }
+                self.interpolation_depth += 1;
+                let scanned = self.scan_interpolation(start_line);
+                self.interpolation_depth -= 1;
+                scanned?;
 
+                // scan_interpolation stops just past the closing '}'
+                self.start = self.current - 1;
                 self.add_token_with_lexeme(TokenKind::InterpolationEnd, "}".to_string());
+                self.start = self.current;
             } else {
                 value.push(self.advance());
             }
@@ -424,10 +587,14 @@ impl Lexer {
         let mut brace_depth = 1;
 
         while brace_depth > 0 && !self.is_at_end() {
-            // Skip whitespace inside interpolation
+            // Whitespace inside the interpolation is trivia, like anywhere else
+            self.start = self.current;
             while !self.is_at_end() && (self.peek() == ' ' || self.peek() == '\t') {
                 self.advance();
             }
+            if self.current > self.start {
+                self.add_trivia(TriviaKind::Whitespace);
+            }
 
             if self.is_at_end() {
                 return Err(format!(
@@ -450,6 +617,18 @@ impl Lexer {
 
             self.start = self.current;
             self.start_column = self.column;
+
+            // A ':' that isn't inside braces starts a format spec, running to the closing '}'
+            if brace_depth == 1 && self.peek() == ':' {
+                self.advance();
+                let mut spec = String::new();
+                while !self.is_at_end() && self.peek() != '}' && self.peek() != '"' {
+                    spec.push(self.advance());
+                }
+                self.add_token_with_lexeme(TokenKind::FormatSpec, spec);
+                continue;
+            }
+
             self.scan_token()?;
         }
 
@@ -483,13 +662,12 @@ impl Lexer {
     }
 
     /// Scan an identifier or keyword
-    fn identifier(&mut self) {
+    fn identifier(&mut self) -> Result<(), String> {
         // Check for f-string: f"..."
         let first_char = self.source.get(self.start).copied().unwrap_or(' ');
         if first_char == 'f' && self.current == self.start + 1 && self.peek() == '"' {
             self.advance(); // consume the opening quote
-            self.fstring().ok(); // process as f-string (interpolated)
-            return;
+            return self.fstring(); // process as f-string (interpolated)
         }
 
         // Identifiers can contain letters, digits, underscores, and hyphens
@@ -517,6 +695,7 @@ impl Lexer {
         let text = self.current_lexeme();
         let kind = self.keyword_or_identifier(&text);
         self.add_token(kind);
+        Ok(())
     }
 
     /// Check if the identifier is a keyword, return appropriate token kind
@@ -620,6 +799,7 @@ mod tests {
         assert_eq!(tokens[11].kind, TokenKind::Arrow);
         assert_eq!(tokens[12].kind, TokenKind::Comma);
         assert_eq!(tokens[13].kind, TokenKind::Dot);
+        assert_eq!(Lexer::new("a ?? b").tokenize().unwrap()[1].kind, TokenKind::QuestionQuestion);
     }
 
     #[test]
@@ -827,6 +1007,53 @@ mod tests {
         assert_eq!(tokens[8].lexeme, "!");
     }
 
+    #[test]
+    fn test_symbolic_boolean_operators() {
+        let mut lexer = Lexer::new("a && b || c");
+        let tokens = lexer.tokenize().unwrap();
+        assert_eq!(tokens[1].kind, TokenKind::And);
+        assert_eq!(tokens[1].lexeme, "&&");
+        assert_eq!(tokens[3].kind, TokenKind::Or);
+        assert_eq!(tokens[3].lexeme, "||");
+    }
+
+    #[test]
+    fn test_error_single_ampersand() {
+        let result = lex("a & b");
+        assert!(result.unwrap_err().contains("'and'"));
+    }
+
+    #[test]
+    fn test_trivia_reconstructs_source() {
+        let source = concat!(
+            "-- setup\nquack [let x be 1]  -- one\n\n\tquack [print x]\n",
+            "quack [print \"tab\\there \\\"quoted\\\" {not interpolated}\"]\n",
+            "quack [print f\"caf\u{e9} { x + 1 } \\{literal\\} {x:.2} {f\"{x}\"}!\"]\n",
+        );
+        let tokens = lex(source).unwrap();
+        let rebuilt: String = tokens
+            .iter()
+            .flat_map(|t| t.leading_trivia.iter().map(|tr| tr.text.as_str()).chain([&source[t.span.clone()]]))
+            .collect();
+        assert_eq!(rebuilt, source);
+    }
+
+    #[test]
+    fn test_trivia_attaches_comments_and_blank_lines() {
+        let tokens = lex("quack [let x be 1]  -- one\n\n-- two\nquack [print x]").unwrap();
+        let second_quack = tokens.iter().filter(|t| t.kind == TokenKind::Quack).nth(1).unwrap();
+        let comments: Vec<&str> = second_quack.leading_comments().collect();
+        assert_eq!(comments, vec!["-- one", "-- two"]);
+        assert_eq!(second_quack.blank_lines_before(), 1);
+    }
+
+    #[test]
+    fn test_starting_at_line() {
+        let tokens = Lexer::starting_at_line("quack\n[print 1]", 7).tokenize().unwrap();
+        assert_eq!(tokens[0].line, 7);
+        assert_eq!(tokens[1].line, 8);
+    }
+
     #[test]
     fn test_list_operations() {
         let tokens = lex("list push at length [1, 2, 3]").unwrap();
@@ -0,0 +1,43 @@
+// Duck language library - the lexer, parser, AST, and interpreter behind goose
+// The goose binary is a thin CLI over these modules; tools can use them directly
+
+pub mod lexer;
+pub mod parser;
+pub mod ast;
+pub mod version;
+pub mod upgrade;
+pub mod ast_cache;
+pub mod visit;
+pub mod analysis;
+pub mod lint;
+pub mod workspace;
+pub mod graph;
+pub mod values;
+pub mod decimal;
+pub mod fraction;
+pub mod format_spec;
+pub mod interpreter;
+pub mod clock;
+pub mod input;
+pub mod random;
+pub mod server;
+pub mod script_args;
+pub mod http;
+pub mod websocket;
+pub mod watch;
+pub mod desktop;
+pub mod shell;
+pub mod glob;
+pub mod hooks;
+pub mod audit;
+pub mod capability;
+pub mod report;
+pub mod test_runner;
+pub mod doc_test;
+pub mod property;
+pub mod diff;
+pub mod plot;
+pub mod matrix;
+pub mod schema;
+pub mod builtins;
+pub mod goose;
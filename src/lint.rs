@@ -0,0 +1,201 @@
+// The checks behind `goose check` - everything that can be said about one file
+// without running it
+//
+// `FileCheck::of` lexes and parses a file and collects its problems: parse
+// errors, missing and stray quacks, and warnings (deprecated syntax and lists
+// shared by accident). Printing is left to the caller, so one file and a
+// whole workspace are reported the same way.
+
+use std::collections::HashSet;
+
+use crate::ast::{AssignTarget, Block, Expr, Statement};
+use crate::lexer::lex;
+use crate::parser::{Parser, QuackIssue, QuackIssueKind};
+use crate::visit::{walk_block, walk_blocks, walk_statement, Visitor};
+
+/// What checking one file found
+#[derive(Debug, Clone, Default)]
+pub struct FileCheck {
+    /// The parsed program, empty if it didn't parse
+    pub blocks: Vec<Block>,
+    /// Lexer and parser errors. When there are any, only deprecation warnings
+    /// are collected - the other checks need a parsed program.
+    pub errors: Vec<String>,
+    /// Deprecated syntax and suspicious list sharing. These don't count as issues.
+    pub warnings: Vec<(usize, String)>,
+    /// Lines of blocks with no quack, nested ones included
+    pub unquacked: Vec<usize>,
+    /// Quacks that authorize a different block than they seem to
+    pub stray_quacks: Vec<QuackIssue>,
+    /// Every block in the file, nested ones included
+    pub total_blocks: usize,
+}
+
+impl FileCheck {
+    pub fn of(source: &str) -> FileCheck {
+        let tokens = match lex(source) {
+            Ok(tokens) => tokens,
+            Err(e) => return FileCheck { errors: vec![e], ..FileCheck::default() },
+        };
+        let mut parser = Parser::new(tokens);
+        let parsed = parser.parse();
+        let mut check = FileCheck { warnings: parser.warnings().to_vec(), ..FileCheck::default() };
+        let blocks = match parsed {
+            Ok(blocks) => blocks,
+            Err(errors) => {
+                check.errors = errors;
+                return check;
+            }
+        };
+
+        let mut aliases = ListAliases::default();
+        walk_blocks(&mut aliases, &blocks);
+        check.warnings.extend(aliases.warnings);
+
+        let mut unquacked = UnquackedLines::default();
+        walk_blocks(&mut unquacked, &blocks);
+        check.unquacked = unquacked.lines;
+        check.total_blocks = unquacked.blocks;
+        check.stray_quacks = parser.quack_issues().to_vec();
+        check.blocks = blocks;
+        check
+    }
+
+    pub fn quacked_blocks(&self) -> usize {
+        self.total_blocks - self.unquacked.len()
+    }
+
+    /// Problems that count against the file: errors, missing quacks and stray ones
+    pub fn issue_count(&self) -> usize {
+        self.errors.len() + self.unquacked.len() + self.stray_quacks.len()
+    }
+}
+
+/// What's wrong with a stray quack, in words
+pub fn describe_quack_issue(kind: &QuackIssueKind) -> String {
+    match kind {
+        QuackIssueKind::Dangling => "Dangling quack at end of file. Nothing left to authorize.".to_string(),
+        QuackIssueKind::Excess => "More quacks than blocks. This one spills over onto a later block.".to_string(),
+        QuackIssueKind::Separated { found } => {
+            format!("Quack separated from its block by {:?}. It will authorize the next block instead.", found)
+        }
+        QuackIssueKind::Leaked { body_end_line } => format!(
+            "Excess quack leaks out of the body ending on line {} and authorizes a later block.",
+            body_end_line
+        ),
+    }
+}
+
+/// Collects the lines of unquacked blocks anywhere in the program, counting every block on the way
+#[derive(Default)]
+pub struct UnquackedLines {
+    pub lines: Vec<usize>,
+    pub blocks: usize,
+}
+
+impl Visitor for UnquackedLines {
+    fn visit_block(&mut self, block: &Block) {
+        self.blocks += 1;
+        if !block.was_quacked {
+            self.lines.push(block.line);
+        }
+        walk_block(self, block);
+    }
+}
+
+/// Finds `let b be a` (or `b becomes a`) where `a` holds a list, so `b` is
+/// the same list rather than a copy
+#[derive(Default)]
+struct ListAliases {
+    /// Variables assigned something that's obviously a new list
+    lists: HashSet<String>,
+    /// Line of the block being walked
+    line: usize,
+    warnings: Vec<(usize, String)>,
+}
+
+impl ListAliases {
+    fn makes_list(expr: &Expr) -> bool {
+        match expr {
+            Expr::List(_) => true,
+            Expr::Call { callee, .. } => matches!(
+                callee.as_ref(),
+                Expr::Builtin(builtin) if matches!(
+                    builtin.name(),
+                    "fill" | "repeat-list" | "concat" | "range" | "sort" | "reverse" | "split"
+                        | "keys" | "values" | "map" | "filter" | "copy" | "zip" | "enumerate"
+                )
+            ),
+            _ => false,
+        }
+    }
+
+    fn bind(&mut self, name: &str, value: &Expr) {
+        if let Expr::Identifier(source) = value {
+            if self.lists.contains(source) && source != name {
+                self.warnings.push((
+                    self.line,
+                    format!(
+                        "'{}' is now the same list as '{}', not a copy - changing one changes both. Use copy({}) for a separate list.",
+                        name, source, source
+                    ),
+                ));
+                self.lists.insert(name.to_string());
+                return;
+            }
+        }
+        if Self::makes_list(value) {
+            self.lists.insert(name.to_string());
+        } else {
+            self.lists.remove(name);
+        }
+    }
+}
+
+impl Visitor for ListAliases {
+    fn visit_block(&mut self, block: &Block) {
+        self.line = block.line;
+        walk_block(self, block);
+    }
+
+    fn visit_statement(&mut self, stmt: &Statement) {
+        match stmt {
+            Statement::Let { name, value, .. } => self.bind(name, value),
+            Statement::Assign { target: AssignTarget::Variable(name), value } => self.bind(name, value),
+            Statement::CompoundAssign { target: AssignTarget::Variable(name), .. } => {
+                self.lists.remove(name);
+            }
+            _ => {}
+        }
+        walk_statement(self, stmt);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_file_check_counts_blocks_and_issues() {
+        let source = concat!(
+            "quack [let ducks be list(1, 2)]\n",
+            "quack [let flock be ducks]\n",
+            "[print \"no quack\"]\n",
+            "quack [if true then\n",
+            "    [print \"nested, no quack\"]\n",
+            "]\n",
+            "quack quack",
+        );
+        let check = FileCheck::of(source);
+        assert!(check.errors.is_empty());
+        assert_eq!((check.total_blocks, check.quacked_blocks()), (5, 3));
+        assert_eq!(check.unquacked, [3, 5]);
+        assert_eq!(check.stray_quacks.len(), 2);
+        assert_eq!(check.issue_count(), 4);
+        assert_eq!(check.warnings.len(), 1);
+        assert!(check.warnings[0].1.contains("same list as 'ducks'"));
+
+        let broken = FileCheck::of("quack [print (]");
+        assert!(!broken.errors.is_empty() && broken.blocks.is_empty());
+    }
+}
@@ -1,15 +1,12 @@
-mod lexer;
-mod parser;
-mod ast;
-mod values;
-mod interpreter;
-mod builtins;
-mod goose;
+use duck_lang::{analysis, ast, audit, clock, doc_test, goose, graph, http, input, interpreter, lexer, lint, parser, random, report, test_runner, upgrade, version, workspace};
+use duck_lang::capability::{Capability, Policy};
+use duck_lang::visit::walk_block;
 
 use clap::{Parser, Subcommand};
 use std::fs;
-use std::io::{self, Write, BufRead};
-use std::path::PathBuf;
+use std::io::{self, BufRead, IsTerminal, Write};
+use std::path::{Path, PathBuf};
+use std::process::ExitCode;
 
 const VERSION: &str = env!("CARGO_PKG_VERSION");
 const REPO: &str = "konacodes/duck-lang";
@@ -19,25 +16,114 @@ const REPO: &str = "konacodes/duck-lang";
 #[command(about = "The Goose interpreter for Duck-lang", long_about = None)]
 #[command(version = VERSION)]
 struct Cli {
+    /// Leave out the startup banner and the rating, for scripts reading the output
+    /// (the default whenever output isn't going to a terminal)
+    #[arg(long, global = true)]
+    porcelain: bool,
     #[command(subcommand)]
     command: Commands,
 }
 
+// Parsed once at startup, so a roomy `Run` variant costs nothing worth boxing
+#[allow(clippy::large_enum_variant)]
 #[derive(Subcommand)]
 enum Commands {
     /// Run a Duck file
     Run {
+        /// Print execution stats as JSON after the run
+        #[arg(long)]
+        stats: bool,
+        /// Warn when a list or struct is changed through one name while other names share it
+        #[arg(long)]
+        warn_aliasing: bool,
+        /// Make calculations that produce infinity or NaN an error
+        #[arg(long)]
+        strict_math: bool,
+        /// Print the value of each top-level expression, like the REPL does
+        #[arg(long)]
+        echo_expressions: bool,
+        /// Stop the program after this many seconds
+        #[arg(long, value_name = "SECONDS")]
+        timeout: Option<f64>,
+        /// Swap in changed function definitions whenever the file is saved, keeping the program's data
+        #[arg(long)]
+        watch: bool,
+        /// Allow only these capabilities (fs-read, fs-write, net, proc, env, time); repeat for more
+        #[arg(long, value_name = "CAPABILITY")]
+        allow: Vec<Capability>,
+        /// Refuse a capability the program would otherwise have; repeat for more
+        #[arg(long, value_name = "CAPABILITY")]
+        deny: Vec<Capability>,
+        /// Let shell() run commands - only for scripts you trust, since a command can do anything you can
+        #[arg(long)]
+        allow_shell: bool,
+        /// Write a JSON log of every file, network, input, and environment access to FILE
+        #[arg(long, value_name = "FILE")]
+        audit_log: Option<PathBuf>,
+        /// Save every answer typed at an input() prompt to FILE
+        #[arg(long, value_name = "FILE", conflicts_with = "replay")]
+        record: Option<PathBuf>,
+        /// Answer input() prompts from a session saved with --record instead of the keyboard
+        #[arg(long, value_name = "FILE")]
+        replay: Option<PathBuf>,
+        /// Seed random() so every run draws the same numbers
+        #[arg(long, value_name = "N")]
+        seed: Option<u64>,
+        /// Save every number random() hands out to FILE
+        #[arg(long, value_name = "FILE", conflicts_with = "replay_rng")]
+        record_rng: Option<PathBuf>,
+        /// Hand out the numbers saved with --record-rng instead of fresh ones
+        #[arg(long, value_name = "FILE", conflicts_with = "seed")]
+        replay_rng: Option<PathBuf>,
         /// The .duck file to run
         file: String,
         /// Arguments to pass to the Duck program (accessible via quack-args)
-        #[arg(trailing_var_arg = true)]
+        #[arg(trailing_var_arg = true, allow_hyphen_values = true)]
         args: Vec<String>,
     },
     /// Check a Duck file for quack issues without running
     Check {
+        /// Print the order blocks would run in (and which are skipped) without executing
+        #[arg(long)]
+        run_order: bool,
+        /// Print the functions, structs and variables the file defines, and the
+        /// lines that use each one, as JSON
+        #[arg(long)]
+        symbols: bool,
         /// The .duck file to check
         file: String,
     },
+    /// Print which functions call which, as Graphviz DOT (or Mermaid)
+    Graph {
+        /// Show which files migrate which instead of function calls
+        #[arg(long)]
+        imports: bool,
+        /// Print a Mermaid flowchart instead of DOT
+        #[arg(long)]
+        mermaid: bool,
+        /// The .duck file to draw
+        file: String,
+    },
+    /// Rewrite deprecated syntax (like `&&` for `and`) into what's current
+    UpgradeSyntax {
+        /// Rewrite the files in place instead of only listing the changes
+        #[arg(long)]
+        write: bool,
+        /// The .duck files to upgrade
+        #[arg(required = true)]
+        files: Vec<String>,
+    },
+    /// Run the test blocks in a Duck file, each in isolation
+    Test {
+        /// Check the file's `-- expect:` comments against what each block prints, instead of running test blocks
+        #[arg(long)]
+        doc: bool,
+        /// The .duck file containing tests
+        file: String,
+        /// Arguments to pass to the Duck program (accessible via quack-args)
+        #[arg(trailing_var_arg = true, allow_hyphen_values = true)]
+        args: Vec<String>,
+    },
     /// Start the interactive REPL
     Repl,
     /// Update goose to the latest version
@@ -61,7 +147,16 @@ enum Commands {
     Libs,
 }
 
-fn main() {
+fn main() -> ExitCode {
+    // Deeply nested programs need more stack than the main thread has
+    let spawned = std::thread::Builder::new().stack_size(parser::STACK_SIZE).spawn(goose_main);
+    match spawned {
+        Ok(handle) => handle.join().unwrap_or(ExitCode::FAILURE),
+        Err(_) => goose_main(),
+    }
+}
+
+fn goose_main() -> ExitCode {
     let cli = Cli::parse();
 
     match cli.command {
@@ -70,69 +165,360 @@ fn main() {
         Commands::Versions => list_versions(),
         Commands::Install { library, version } => install_library(&library, &version),
         Commands::Libs => list_libraries(),
+        Commands::Graph { imports, mermaid, file } => graph_file(&file, imports, mermaid),
+        Commands::UpgradeSyntax { write, files } => upgrade_files(&files, write),
         _ => {
-            // Print startup message for run/check/repl commands
-            println!("{}", goose::startup());
+            // Print startup message for run/check/repl commands, unless a
+            // script is reading the output
+            let porcelain = cli.porcelain || !io::stdout().is_terminal();
+            if !porcelain {
+                println!("{}", goose::startup());
+            }
 
             match cli.command {
-                Commands::Run { file, args } => run_file(&file, args),
-                Commands::Check { file } => check_file(&file),
+                Commands::Run { stats, warn_aliasing, strict_math, echo_expressions, timeout, watch, allow, deny, allow_shell, audit_log, record, replay, seed, record_rng, replay_rng, file, args } => {
+                    let options = RunOptions {
+                        print_stats: stats,
+                        warn_aliasing,
+                        strict_math,
+                        echo_expressions,
+                        timeout,
+                        watch,
+                        policy: Policy::from_flags(&allow, &deny).with_shell(allow_shell),
+                        audit_log,
+                        record,
+                        replay,
+                        seed,
+                        record_rng,
+                        replay_rng,
+                        show_rating: !porcelain,
+                    };
+                    return run_file(&file, args, options);
+                }
+                Commands::Check { run_order, symbols, file } => check_file(&file, run_order, symbols),
+                Commands::Test { doc, file, args } => test_file(&file, doc, args),
                 Commands::Repl => run_repl(),
                 _ => unreachable!(),
             }
         }
     }
+    ExitCode::SUCCESS
+}
+
+/// Flags for `goose run`
+struct RunOptions {
+    print_stats: bool,
+    warn_aliasing: bool,
+    strict_math: bool,
+    echo_expressions: bool,
+    timeout: Option<f64>,
+    watch: bool,
+    policy: Policy,
+    audit_log: Option<PathBuf>,
+    record: Option<PathBuf>,
+    replay: Option<PathBuf>,
+    seed: Option<u64>,
+    record_rng: Option<PathBuf>,
+    replay_rng: Option<PathBuf>,
+    show_rating: bool,
 }
 
-fn run_file(path: &str, args: Vec<String>) {
+/// Run a script, giving back the code it passed to `exit`, or failure if it didn't get to run or stopped with an error
+fn run_file(path: &str, args: Vec<String>, options: RunOptions) -> ExitCode {
     let source = match fs::read_to_string(path) {
         Ok(s) => s,
         Err(_) => {
             println!("I can't find that file. Are you sure it exists?");
             println!("   Geese have excellent eyesight, you know.");
-            return;
+            return ExitCode::FAILURE;
         }
     };
 
     // Lex
-    let tokens = match lexer::lex(&source) {
+    let mut lexer = lexer::Lexer::new(&source);
+    let tokens = match lexer.tokenize() {
         Ok(t) => t,
         Err(e) => {
             println!("{}", e);
-            return;
+            return ExitCode::FAILURE;
         }
     };
-
     // Parse
     let mut parser = parser::Parser::new(tokens);
-    let blocks = match parser.parse() {
+    let parsed = parser.parse();
+    for (line, warning) in parser.warnings() {
+        println!("{}", goose::warning(*line, warning));
+    }
+    let blocks = match parsed {
         Ok(b) => b,
         Err(errors) => {
             for e in errors {
                 println!("{}", e);
             }
-            return;
+            return ExitCode::FAILURE;
         }
     };
 
     // Execute with command-line arguments
     let mut interpreter = interpreter::Interpreter::with_args(args);
-    if let Err(e) = interpreter.run(blocks) {
-        println!("{}", e);
-    } else {
-        println!("{}", goose::success());
+    interpreter.set_output_sink(Box::new(goose::ConsoleSink));
+    if options.warn_aliasing {
+        interpreter.detect_aliasing();
+    }
+    interpreter.set_strict_math(options.strict_math);
+    interpreter.set_policy(options.policy);
+    interpreter.set_echo_expressions(options.echo_expressions);
+    if let Some(seconds) = options.timeout {
+        match clock::wait_duration(seconds * 1000.0) {
+            Some(limit) => interpreter.set_time_limit(Some(limit)),
+            None => {
+                println!("The goose won't wait that long. --timeout takes a number of seconds, up to a week");
+                return ExitCode::FAILURE;
+            }
+        }
+    }
+    if options.watch {
+        if let Err(e) = interpreter.set_hot_reload(Some(path)) {
+            println!("{}", e);
+            return ExitCode::FAILURE;
+        }
+    }
+    let audit_log = options.audit_log.as_ref().map(|_| {
+        let log = audit::AuditLog::new();
+        interpreter.add_hooks(Box::new(log.clone()));
+        log
+    });
+    if let Some(path) = &options.replay {
+        match input::Session::load(path) {
+            Ok(session) => interpreter.set_input_source(Box::new(input::ReplayInput::new(session, true))),
+            Err(e) => {
+                println!("The goose can't replay that session: {}", e);
+                return ExitCode::FAILURE;
+            }
+        }
+    }
+    let recorded = options.record.as_ref().map(|_| {
+        let recorder = input::RecordingInput::new(Box::new(input::StdinInput));
+        let session = recorder.session();
+        interpreter.set_input_source(Box::new(recorder));
+        session
+    });
+    if let Some(seed) = options.seed {
+        interpreter.set_random_source(Box::new(random::SeededRandom::new(seed)));
+    }
+    if let Some(path) = &options.replay_rng {
+        match random::RandomLog::load(path) {
+            Ok(log) => interpreter.set_random_source(Box::new(random::ReplayRandom::new(log))),
+            Err(e) => {
+                println!("The goose can't replay those random numbers: {}", e);
+                return ExitCode::FAILURE;
+            }
+        }
     }
+    let recorded_rng = options.record_rng.as_ref().map(|_| {
+        let inner: Box<dyn random::RandomSource> = match options.seed {
+            Some(seed) => Box::new(random::SeededRandom::new(seed)),
+            None => Box::new(random::SeededRandom::from_time()),
+        };
+        let recorder = random::RecordingRandom::new(inner);
+        let log = recorder.log();
+        interpreter.set_random_source(Box::new(recorder));
+        log
+    });
+    let code = match interpreter.run(blocks) {
+        Ok(()) => interpreter.exit_code().map_or(ExitCode::SUCCESS, |code| ExitCode::from(code as u8)),
+        Err(e) => {
+            println!("{}", e);
+            ExitCode::FAILURE
+        }
+    };
 
-    // Always print rating at the end
-    let (score, quip) = goose::rate_code(interpreter.stats());
-    println!();
-    println!("═══════════════════════════════════════");
-    println!("  Goose rated your code: {}/10", score);
-    println!("  \"{}\"", quip);
-    println!("═══════════════════════════════════════");
+    if let (Some(path), Some(session)) = (&options.record, &recorded) {
+        if let Err(e) = session.borrow().save(path) {
+            println!("The goose couldn't save the session: {}", e);
+        }
+    }
+    if let (Some(path), Some(log)) = (&options.record_rng, &recorded_rng) {
+        if let Err(e) = log.borrow().save(path) {
+            println!("The goose couldn't save the random numbers: {}", e);
+        }
+    }
+
+    // Written even if the program failed - that's often when it matters most
+    if let (Some(path), Some(log)) = (&options.audit_log, &audit_log) {
+        let written = log.to_json().and_then(|json| fs::write(path, json).map_err(|e| e.to_string()));
+        if let Err(e) = written {
+            println!("The goose couldn't write the audit log to {}: {}", path.display(), e);
+        }
+    }
+
+    // Rate the run at the end, unless the output is for a script
+    if options.show_rating {
+        interpreter.rate();
+    }
+
+    if options.print_stats {
+        match report::to_json(interpreter.stats()) {
+            Ok(json) => println!("{}", json),
+            Err(e) => println!("The goose lost count: {}", e),
+        }
+    }
+
+    code
+}
+
+fn check_file(path: &str, run_order: bool, symbols: bool) {
+    if Path::new(path).is_dir() {
+        if run_order || symbols {
+            println!("--run-order and --symbols look at one file at a time. Pick a file, not a folder.");
+            return;
+        }
+        return check_workspace(Path::new(path));
+    }
+
+    let source = match fs::read_to_string(path) {
+        Ok(s) => s,
+        Err(_) => {
+            println!("I can't find that file. Are you sure it exists?");
+            println!("   Geese have excellent eyesight, you know.");
+            return;
+        }
+    };
+
+    if run_order || symbols {
+        let mut parser = match lexer::lex(&source) {
+            Ok(tokens) => parser::Parser::new(tokens),
+            Err(e) => {
+                println!("{}", e);
+                return;
+            }
+        };
+        let parsed = parser.parse();
+        if !symbols {
+            for (line, warning) in parser.warnings() {
+                println!("{}", goose::warning(*line, warning));
+            }
+        }
+        let blocks = match parsed {
+            Ok(blocks) => blocks,
+            Err(errors) => {
+                for e in errors {
+                    println!("{}", e);
+                }
+                return;
+            }
+        };
+        if symbols {
+            match report::to_json(&analysis::SymbolTable::build(&blocks)) {
+                Ok(json) => println!("{}", json),
+                Err(e) => eprintln!("The goose couldn't write the symbols: {}", e),
+            }
+        } else {
+            print_run_order(&blocks);
+        }
+        return;
+    }
+
+    let check = lint::FileCheck::of(&source);
+    for (line, warning) in &check.warnings {
+        println!("{}", goose::warning(*line, warning));
+    }
+    if !check.errors.is_empty() {
+        for e in &check.errors {
+            println!("{}", e);
+        }
+        return;
+    }
+
+    if check.unquacked.is_empty() && check.stray_quacks.is_empty() {
+        println!("All blocks are properly quacked! Honk!");
+        println!("   Your code passes the vibe check.");
+        return;
+    }
+
+    if !check.unquacked.is_empty() {
+        println!("QUACK ALERT! The following lines are missing quack:");
+        for line in &check.unquacked {
+            println!("   Line {}: No quack detected!", line);
+        }
+        println!();
+        println!("Remember: Every block needs a quack to be valid.");
+    }
+
+    if !check.stray_quacks.is_empty() {
+        println!("STRAY QUACKS! These quacks don't authorize the block you think they do:");
+        for issue in &check.stray_quacks {
+            println!("   Line {}, column {}: {}", issue.line, issue.column, lint::describe_quack_issue(&issue.kind));
+        }
+        println!();
+        println!("Remember: Extra quacks shift which blocks get authorized.");
+    }
+
+    println!("   {} issue(s) found.", check.issue_count());
+}
+
+/// Check every .duck file in a folder: each file's problems, then a table
+/// of quack stats and a rating for the whole workspace
+fn check_workspace(root: &Path) {
+    let workspace = match workspace::Workspace::check(root) {
+        Ok(workspace) => workspace,
+        Err(e) => {
+            println!("{}", e);
+            return;
+        }
+    };
+    if workspace.files.is_empty() {
+        println!("No .duck files in '{}'. Not a single duck. The pond is empty.", root.display());
+        return;
+    }
+
+    for file in &workspace.files {
+        let check = &file.check;
+        if check.warnings.is_empty() && file.issue_count() == 0 {
+            continue;
+        }
+        println!("{}", file.path.display());
+        for (line, warning) in &check.warnings {
+            println!("   {}", goose::warning(*line, warning));
+        }
+        for e in &check.errors {
+            println!("   {}", e);
+        }
+        for line in &check.unquacked {
+            println!("   Line {}: No quack detected!", line);
+        }
+        for issue in &check.stray_quacks {
+            println!("   Line {}, column {}: {}", issue.line, issue.column, lint::describe_quack_issue(&issue.kind));
+        }
+        for (line, message) in &file.migrate_issues {
+            println!("   Line {}: {}", line, message);
+        }
+        println!();
+    }
+
+    println!("WORKSPACE: {} file(s)", workspace.files.len());
+    let width = workspace.files.iter().map(|file| file.path.display().to_string().len()).max().unwrap_or(0);
+    for file in &workspace.files {
+        let check = &file.check;
+        let status = if !check.errors.is_empty() {
+            "doesn't parse".to_string()
+        } else {
+            format!("{}/{} blocks quacked", check.quacked_blocks(), check.total_blocks)
+        };
+        let issues = match file.issue_count() {
+            0 => String::new(),
+            count => format!(", {} issue(s)", count),
+        };
+        println!("   {:<width$}  {}{}", file.path.display().to_string(), status, issues, width = width);
+    }
+    println!("   {} issue(s) found.", workspace.issue_count());
+
+    let stats = workspace.stats();
+    let (score, quip) = goose::rate_code(&stats);
+    goose::OutputSink::rating(&mut goose::ConsoleSink, score, &quip, &stats);
 }
 
-fn check_file(path: &str) {
+fn test_file(path: &str, doc: bool, args: Vec<String>) {
     let source = match fs::read_to_string(path) {
         Ok(s) => s,
         Err(_) => {
@@ -151,6 +537,10 @@ fn check_file(path: &str) {
         }
     };
 
+    if doc {
+        return doc_test_file(path, tokens, args);
+    }
+
     // Parse
     let mut parser = parser::Parser::new(tokens);
     let blocks = match parser.parse() {
@@ -163,25 +553,215 @@ fn check_file(path: &str) {
         }
     };
 
-    // Check for quack issues (blocks where was_quacked = false)
-    let mut quack_issues = Vec::new();
-    for block in &blocks {
-        if !block.was_quacked {
-            quack_issues.push(block.line);
+    let results = match test_runner::run_tests(&blocks, args) {
+        Ok(results) => results,
+        Err(e) => {
+            println!("The tests never got started. Setup failed:");
+            println!("   {}", e);
+            std::process::exit(1);
         }
+    };
+
+    if results.is_empty() {
+        println!("No tests found. Add some with [test \"name\" do ...]. The goose is waiting.");
+        return;
     }
 
-    if quack_issues.is_empty() {
-        println!("All blocks are properly quacked! Honk!");
-        println!("   Your code passes the vibe check.");
+    println!("Running {} test(s) from {}", results.len(), path);
+    println!();
+
+    let join_lines = |lines: &[usize]| {
+        let mut lines = lines.to_vec();
+        lines.sort_unstable();
+        lines.iter().map(|line| line.to_string()).collect::<Vec<_>>().join(", ")
+    };
+
+    let (mut passed, mut failed, mut skipped) = (0, 0, 0);
+    for result in &results {
+        match &result.outcome {
+            test_runner::TestOutcome::Passed => {
+                passed += 1;
+                println!("   PASS  {} (line {})", result.name, result.line);
+            }
+            test_runner::TestOutcome::Failed(e) => {
+                failed += 1;
+                println!("   FAIL  {} (line {})", result.name, result.line);
+                println!("         {}", e.replace('\n', "\n         "));
+            }
+            test_runner::TestOutcome::Skipped => {
+                skipped += 1;
+                println!("   SKIP  {} (line {}) - no quack, no test", result.name, result.line);
+                continue;
+            }
+        }
+        println!("         quacks: {}", join_lines(&result.coverage.quacks));
+        if !result.coverage.honks.is_empty() {
+            println!("         honks: {}", join_lines(&result.coverage.honks));
+        }
+        if !result.coverage.properties.is_empty() {
+            let checks: Vec<String> = result
+                .coverage
+                .properties
+                .iter()
+                .map(|(line, cases)| format!("line {} ({} cases)", line, cases))
+                .collect();
+            println!("         properties: {}", checks.join(", "));
+        }
+    }
+
+    println!();
+    println!("{} passed, {} failed, {} skipped", passed, failed, skipped);
+    if failed > 0 {
+        println!("   The goose is disappointed, but not surprised.");
+        std::process::exit(1);
+    }
+    println!("   {}", goose::success());
+}
+
+/// Run a file and check its `-- expect:` comments (`goose test --doc`)
+fn doc_test_file(path: &str, tokens: Vec<lexer::Token>, args: Vec<String>) {
+    let results = match doc_test::run_doc_tests(tokens, args) {
+        Ok(results) => results,
+        Err(e) => {
+            println!("{}", e);
+            std::process::exit(1);
+        }
+    };
+
+    if results.is_empty() {
+        println!("No expectations found. Add some with a '-- expect: ...' comment after a print.");
+        return;
+    }
+
+    println!("Checking {} expectation(s) from {}", results.len(), path);
+    println!();
+
+    let (mut passed, mut failed, mut skipped) = (0, 0, 0);
+    for result in &results {
+        match &result.outcome {
+            test_runner::TestOutcome::Passed => {
+                passed += 1;
+                println!("   PASS  line {}: {}", result.line, result.expected.replace('\n', " / "));
+            }
+            test_runner::TestOutcome::Failed(e) => {
+                failed += 1;
+                println!("   FAIL  line {}: {}", result.line, e);
+            }
+            test_runner::TestOutcome::Skipped => {
+                skipped += 1;
+                println!("   SKIP  line {} - the block on line {} has no quack", result.line, result.block_line);
+            }
+        }
+    }
+
+    println!();
+    println!("{} passed, {} failed, {} skipped", passed, failed, skipped);
+    if failed > 0 {
+        println!("   The goose is disappointed, but not surprised.");
+        std::process::exit(1);
+    }
+    println!("   {}", goose::success());
+}
+
+/// Print a program's call graph (or import graph). No banner, since the
+/// output is meant for `dot` or a Markdown file.
+fn graph_file(path: &str, imports: bool, mermaid: bool) {
+    let graph = if imports {
+        graph::Graph::imports(path)
     } else {
-        println!("QUACK ALERT! The following lines are missing quack:");
-        for line in &quack_issues {
-            println!("   Line {}: No quack detected!", line);
+        fs::read_to_string(path)
+            .map_err(|_| "I can't find that file. Are you sure it exists?".to_string())
+            .and_then(|source| parser::parse_str(&source).map_err(|errors| errors.join("\n")))
+            .map(|blocks| graph::Graph::calls(&blocks))
+    };
+    match graph {
+        Ok(graph) if mermaid => print!("{}", graph.to_mermaid()),
+        Ok(graph) => print!("{}", graph.to_dot()),
+        Err(e) => {
+            eprintln!("{}", e);
+            std::process::exit(1);
+        }
+    }
+}
+
+/// List (or, with `write`, make) the syntax upgrades for each file
+fn upgrade_files(paths: &[String], write: bool) {
+    let mut failed = false;
+    let mut total = 0;
+    for path in paths {
+        let upgrade = fs::read_to_string(path)
+            .map_err(|e| format!("The goose couldn't read '{}': {}", path, e))
+            .and_then(|source| upgrade::upgrade_syntax(&source));
+        let upgrade = match upgrade {
+            Ok(upgrade) => upgrade,
+            Err(e) => {
+                eprintln!("{}: {}", path, e);
+                failed = true;
+                continue;
+            }
+        };
+        for change in &upgrade.changes {
+            println!("{}:{}: {} -> {}", path, change.line, change.old, change.new);
+        }
+        if write && !upgrade.changes.is_empty() {
+            if let Err(e) = fs::write(path, &upgrade.source) {
+                eprintln!("The goose couldn't write '{}': {}", path, e);
+                failed = true;
+                continue;
+            }
+        }
+        total += upgrade.changes.len();
+    }
+
+    if total > 0 && write {
+        println!("Upgraded {} piece(s) of old syntax. Honk of approval.", total);
+    } else if total > 0 {
+        println!("{} piece(s) of old syntax to upgrade. Run again with --write to rewrite them.", total);
+    } else if !failed {
+        println!("Nothing to upgrade - every file already speaks Duck {}.", version::Version::current());
+    }
+    if failed {
+        std::process::exit(1);
+    }
+}
+
+/// Print a dry-run plan: which top-level blocks run, which are skipped, and what they define
+fn print_run_order(blocks: &[ast::Block]) {
+    println!("RUN ORDER (nothing was executed):");
+
+    for (step, block) in blocks.iter().enumerate() {
+        let summary = block.statement.summary();
+        if block.was_quacked {
+            println!("   {}. Line {}: run  - {}", step + 1, block.line, summary);
+        } else {
+            println!("   {}. Line {}: skip - {} (no quack)", step + 1, block.line, summary);
         }
+    }
+
+    // Unquacked blocks nested inside bodies that will run
+    let mut nested = lint::UnquackedLines::default();
+    for block in blocks.iter().filter(|b| b.was_quacked) {
+        walk_block(&mut nested, block);
+    }
+    if !nested.lines.is_empty() {
+        let lines: Vec<String> = nested.lines.iter().map(|line| line.to_string()).collect();
         println!();
-        println!("Remember: Every block needs a quack to be valid.");
-        println!("   {} issue(s) found.", quack_issues.len());
+        println!("   Skipped inside bodies (no quack): line(s) {}", lines.join(", "));
+    }
+
+    let defined = analysis::SymbolTable::build(blocks.iter().filter(|b| b.was_quacked));
+    let names = |kind| defined.of_kind(kind).map(|symbol| symbol.name.clone()).collect::<Vec<_>>();
+    let (functions, structs) = (names(analysis::SymbolKind::Function), names(analysis::SymbolKind::Struct));
+    println!();
+    if functions.is_empty() && structs.is_empty() {
+        println!("   Defines: nothing. Bold.");
+    } else {
+        if !functions.is_empty() {
+            println!("   Defines functions: {}", functions.join(", "));
+        }
+        if !structs.is_empty() {
+            println!("   Defines structs: {}", structs.join(", "));
+        }
     }
 }
 
@@ -192,6 +772,12 @@ fn run_repl() {
 
     let stdin = io::stdin();
     let mut interpreter = interpreter::Interpreter::new();
+    interpreter.set_output_sink(Box::new(goose::ConsoleSink));
+    // Each entry gets the next line number, so errors say which entry they came from
+    let mut entry_line = 0;
+    // State before each entry, for :undo, and the entry itself if it quacked
+    // and ran without errors, for :export
+    let mut history: Vec<(interpreter::Snapshot, Option<String>)> = Vec::new();
 
     loop {
         print!("duck> ");
@@ -207,43 +793,131 @@ fn run_repl() {
             continue;
         }
 
-        // Lex the line
-        let tokens = match lexer::lex(line.trim()) {
-            Ok(t) => t,
-            Err(e) => {
-                println!("{}", e);
-                continue;
+        if line.trim() == ":undo" {
+            match history.pop() {
+                Some((snapshot, _)) => {
+                    interpreter.restore(&snapshot);
+                    println!("   Undone. The goose will pretend that never happened.");
+                }
+                None => println!("   Nothing to undo. You haven't done anything yet."),
             }
-        };
+            continue;
+        }
 
-        // Parse the line
-        let mut parser = parser::Parser::new(tokens);
-        let blocks = match parser.parse() {
-            Ok(b) => b,
-            Err(errors) => {
-                for e in errors {
-                    println!("{}", e);
-                }
-                continue;
+        if let Some(path) = line.trim().strip_prefix(":watch ") {
+            watch_in_repl(&mut interpreter, path.trim());
+            continue;
+        }
+
+        if let Some(path) = line.trim().strip_prefix(":export ") {
+            export_session(history.iter().filter_map(|(_, entry)| entry.as_deref()), path.trim());
+            continue;
+        }
+
+        entry_line += 1;
+        if !run_repl_entry(&mut interpreter, &mut history, line.trim(), entry_line) {
+            println!("Goodbye! *waddles away*");
+            return;
+        }
+    }
+}
+
+/// Run one REPL entry, adding it to `history`. An entry that doesn't run
+/// cleanly is rolled back, so what's kept always matches what `:export`
+/// writes. Returns false once the program has called `exit`.
+fn run_repl_entry(
+    interpreter: &mut interpreter::Interpreter,
+    history: &mut Vec<(interpreter::Snapshot, Option<String>)>,
+    line: &str,
+    entry_line: usize,
+) -> bool {
+    let tokens = match lexer::Lexer::starting_at_line(line, entry_line).tokenize() {
+        Ok(t) => t,
+        Err(e) => {
+            println!("{}", e);
+            return true;
+        }
+    };
+    let blocks = match parser::Parser::new(tokens).parse() {
+        Ok(b) => b,
+        Err(errors) => {
+            for e in errors {
+                println!("{}", e);
             }
-        };
+            return true;
+        }
+    };
 
-        // Execute and provide goose commentary
-        for block in blocks {
-            match interpreter.run_block(block) {
-                Ok(result) => {
-                    if let Some(value) = result {
-                        println!("=> {}", value);
-                    }
-                    // Goose comments on the line
-                    println!("   {}", goose::repl_comment());
-                }
-                Err(e) => {
-                    println!("{}", e);
+    // Execute and provide goose commentary
+    let before = interpreter.snapshot();
+    let (mut succeeded, mut ran_any) = (true, false);
+    for block in blocks {
+        let quacked = block.was_quacked;
+        succeeded &= quacked;
+        match interpreter.run_block(block) {
+            Ok(result) => {
+                ran_any |= quacked;
+                if let Some(value) = result {
+                    println!("=> {}", value);
                 }
+                // Goose comments on the line
+                println!("   {}", goose::repl_comment());
+            }
+            Err(_) if interpreter.exit_code().is_some() => return false,
+            Err(e) => {
+                succeeded = false;
+                println!("{}", e);
             }
         }
     }
+    if !succeeded && ran_any {
+        interpreter.restore(&before);
+        println!("   The goose put everything back the way it was before that line - it's all or nothing.");
+    }
+    history.push((before, succeeded.then(|| line.to_string())));
+    true
+}
+
+/// The Duck program `:export` writes: the entries that ran cleanly, in order
+fn export_source<'a>(entries: impl Iterator<Item = &'a str>) -> Option<(String, usize)> {
+    let entries: Vec<&str> = entries.collect();
+    if entries.is_empty() {
+        return None;
+    }
+    Some((format!("-- Exported from the goose REPL\n{}\n", entries.join("\n")), entries.len()))
+}
+
+/// `:export file` in the REPL: write the entries that ran cleanly as a Duck program
+fn export_session<'a>(entries: impl Iterator<Item = &'a str>, path: &str) {
+    let Some((source, count)) = export_source(entries) else {
+        println!("   Nothing to export yet. Quack something first.");
+        return;
+    };
+    match fs::write(path, source) {
+        Ok(()) => println!(
+            "   Wrote {} entr{} to {}. Run it with: goose run {}",
+            count,
+            if count == 1 { "y" } else { "ies" },
+            path,
+            path
+        ),
+        Err(e) => println!("The goose couldn't write {}: {}", path, e),
+    }
+}
+
+/// `:watch file` in the REPL: load the file's functions now, and swap in new
+/// versions of them whenever it's saved (checked as each entry runs)
+fn watch_in_repl(interpreter: &mut interpreter::Interpreter, path: &str) {
+    let loaded = fs::read_to_string(path)
+        .map_err(|e| format!("The goose couldn't read '{}': {}", path, e))
+        .and_then(|source| parser::parse_str(&source).map_err(|errors| errors.join("\n")))
+        .and_then(|blocks| interpreter.reload_functions(&blocks))
+        .and_then(|names| interpreter.set_hot_reload(Some(path)).map(|_| names));
+    match loaded {
+        Ok(names) if names.is_empty() => println!("   Watching {}, though it doesn't define any functions yet.", path),
+        Ok(names) => println!("   Loaded {} from {}. Save it and the goose will swap in the new versions.", names.join(", "), path),
+        Err(e) => println!("{}", e),
+    }
 }
 
 // =============================================================================
@@ -370,7 +1044,7 @@ fn update_goose(target_version: Option<String>) {
     animate_spinner("Downloading binary...", 800);
 
     // Download the binary
-    match download_binary(&url) {
+    match http::download(&url) {
         Ok(bytes) => {
             println!("\x1b[32m[+]\x1b[0m Download complete ({} bytes)", bytes.len());
 
@@ -494,21 +1168,8 @@ fn detect_arch() -> &'static str {
 fn fetch_latest_version() -> Result<String, String> {
     let url = format!("https://api.github.com/repos/{}/releases/latest", REPO);
 
-    let client = reqwest::blocking::Client::builder()
-        .user_agent("goose-updater")
-        .build()
-        .map_err(|e| e.to_string())?;
-
-    let response = client
-        .get(&url)
-        .send()
-        .map_err(|e| e.to_string())?;
-
-    if !response.status().is_success() {
-        return Err(format!("HTTP {}", response.status()));
-    }
-
-    let json: serde_json::Value = response.json().map_err(|e| e.to_string())?;
+    let body = http::download(&url)?;
+    let json: serde_json::Value = serde_json::from_slice(&body).map_err(|e| e.to_string())?;
 
     json["tag_name"]
         .as_str()
@@ -519,21 +1180,8 @@ fn fetch_latest_version() -> Result<String, String> {
 fn fetch_versions() -> Result<Vec<String>, String> {
     let url = format!("https://api.github.com/repos/{}/releases", REPO);
 
-    let client = reqwest::blocking::Client::builder()
-        .user_agent("goose-updater")
-        .build()
-        .map_err(|e| e.to_string())?;
-
-    let response = client
-        .get(&url)
-        .send()
-        .map_err(|e| e.to_string())?;
-
-    if !response.status().is_success() {
-        return Err(format!("HTTP {}", response.status()));
-    }
-
-    let json: serde_json::Value = response.json().map_err(|e| e.to_string())?;
+    let body = http::download(&url)?;
+    let json: serde_json::Value = serde_json::from_slice(&body).map_err(|e| e.to_string())?;
 
     let versions: Vec<String> = json
         .as_array()
@@ -545,24 +1193,6 @@ fn fetch_versions() -> Result<Vec<String>, String> {
     Ok(versions)
 }
 
-fn download_binary(url: &str) -> Result<Vec<u8>, String> {
-    let client = reqwest::blocking::Client::builder()
-        .user_agent("goose-updater")
-        .build()
-        .map_err(|e| e.to_string())?;
-
-    let response = client
-        .get(url)
-        .send()
-        .map_err(|e| e.to_string())?;
-
-    if !response.status().is_success() {
-        return Err(format!("HTTP {}", response.status()));
-    }
-
-    response.bytes().map(|b| b.to_vec()).map_err(|e| e.to_string())
-}
-
 // =============================================================================
 // Library Management
 // =============================================================================
@@ -759,3 +1389,34 @@ fn list_libraries() {
 
     println!();
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_export_only_keeps_what_the_session_kept() {
+        let mut interpreter = interpreter::Interpreter::new();
+        let mut history = Vec::new();
+        let entries = [
+            "quack [let x be 1] quack [print 1 / \"a\"]",
+            "quack [let y be 2]",
+            "quack [print x]",
+            "quack [let z be y * 2] [print z]",
+            "quack [print y]",
+        ];
+        for (i, entry) in entries.iter().enumerate() {
+            assert!(run_repl_entry(&mut interpreter, &mut history, entry, i + 1));
+        }
+        // The half-failed first line was rolled back, so x was never kept
+        let kept: Vec<bool> = history.iter().map(|(_, entry)| entry.is_some()).collect();
+        assert_eq!(kept, vec![false, true, false, false, true]);
+
+        let (source, count) = export_source(history.iter().filter_map(|(_, entry)| entry.as_deref())).unwrap();
+        assert_eq!(count, 2);
+        assert_eq!(source, "-- Exported from the goose REPL\nquack [let y be 2]\nquack [print y]\n");
+        let blocks = parser::parse_str(&source).unwrap();
+        assert!(interpreter::Interpreter::new().run(blocks).is_ok());
+        assert!(export_source(std::iter::empty()).is_none());
+    }
+}
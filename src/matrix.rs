@@ -0,0 +1,123 @@
+// Matrices behind `matrix`, `mat-mul`, `transpose` and friends
+// A matrix is a Duck list of rows, each a list of numbers; the arithmetic
+// happens here on plain f64s so linear algebra lessons don't crawl
+
+use crate::values::Value;
+
+/// Rows of numbers, all the same length
+pub type Matrix = Vec<Vec<f64>>;
+
+/// Read a list of number lists as a matrix, checking every row is the same length
+pub fn from_value(value: &Value, name: &str) -> Result<Matrix, String> {
+    let Value::List(rows) = value else {
+        return Err(format!("{}() expects a matrix (a list of rows), got {}", name, value.type_name()));
+    };
+    let rows = rows.borrow();
+    let mut matrix = Vec::with_capacity(rows.len());
+    for (r, row) in rows.iter().enumerate() {
+        let Value::List(cells) = row else {
+            return Err(format!("{}() expects every row to be a list, but row {} is {}", name, r, row.type_name()));
+        };
+        let cells = cells
+            .borrow()
+            .iter()
+            .map(|cell| match cell {
+                Value::Number(n) => Ok(*n),
+                other => Err(format!("{}() can only work with numbers, but row {} has {}", name, r, other.type_name())),
+            })
+            .collect::<Result<Vec<f64>, String>>()?;
+        matrix.push(cells);
+    }
+    if let Some(first) = matrix.first() {
+        if let Some(r) = matrix.iter().position(|row| row.len() != first.len()) {
+            return Err(format!(
+                "{}() needs rows of the same length, but row 0 has {} and row {} has {}",
+                name,
+                first.len(),
+                r,
+                matrix[r].len()
+            ));
+        }
+    }
+    Ok(matrix)
+}
+
+/// Turn a matrix back into a list of number lists
+pub fn to_value(matrix: Matrix) -> Value {
+    Value::new_list(
+        matrix
+            .into_iter()
+            .map(|row| Value::new_list(row.into_iter().map(Value::Number).collect()))
+            .collect(),
+    )
+}
+
+/// (rows, columns)
+fn shape(matrix: &Matrix) -> (usize, usize) {
+    (matrix.len(), matrix.first().map_or(0, Vec::len))
+}
+
+/// A rows x cols matrix with every cell set to `fill`
+pub fn filled(rows: usize, cols: usize, fill: f64) -> Matrix {
+    vec![vec![fill; cols]; rows]
+}
+
+/// Swap rows and columns
+pub fn transpose(matrix: &Matrix) -> Matrix {
+    let (rows, cols) = shape(matrix);
+    (0..cols).map(|c| (0..rows).map(|r| matrix[r][c]).collect()).collect()
+}
+
+/// The matrix product: each cell is a row of `a` times a column of `b`
+pub fn multiply(a: &Matrix, b: &Matrix) -> Result<Matrix, String> {
+    let ((a_rows, a_cols), (b_rows, b_cols)) = (shape(a), shape(b));
+    if a_cols != b_rows {
+        return Err(format!(
+            "mat-mul() needs the first matrix to have as many columns as the second has rows, got {}x{} and {}x{}",
+            a_rows, a_cols, b_rows, b_cols
+        ));
+    }
+    Ok(a.iter()
+        .map(|row| (0..b_cols).map(|c| row.iter().zip(b).map(|(x, b_row)| x * b_row[c]).sum()).collect())
+        .collect())
+}
+
+/// Combine two matrices of the same shape cell by cell
+pub fn elementwise(a: &Matrix, b: &Matrix, name: &str, op: impl Fn(f64, f64) -> f64) -> Result<Matrix, String> {
+    let (a_shape, b_shape) = (shape(a), shape(b));
+    if a_shape != b_shape {
+        return Err(format!(
+            "{}() needs matrices of the same size, got {}x{} and {}x{}",
+            name, a_shape.0, a_shape.1, b_shape.0, b_shape.1
+        ));
+    }
+    Ok(a.iter()
+        .zip(b)
+        .map(|(a_row, b_row)| a_row.iter().zip(b_row).map(|(x, y)| op(*x, *y)).collect())
+        .collect())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_multiply_and_transpose() {
+        let a = vec![vec![1.0, 2.0], vec![3.0, 4.0]];
+        let b = vec![vec![5.0, 6.0], vec![7.0, 8.0]];
+        assert_eq!(multiply(&a, &b).unwrap(), vec![vec![19.0, 22.0], vec![43.0, 50.0]]);
+        assert_eq!(transpose(&vec![vec![1.0, 2.0, 3.0]]), vec![vec![1.0], vec![2.0], vec![3.0]]);
+        assert!(multiply(&a, &vec![vec![1.0, 2.0]]).unwrap_err().contains("2x2 and 1x2"));
+    }
+
+    #[test]
+    fn test_from_value_rejects_ragged_rows() {
+        let ragged = Value::new_list(vec![
+            Value::new_list(vec![Value::Number(1.0), Value::Number(2.0)]),
+            Value::new_list(vec![Value::Number(3.0)]),
+        ]);
+        let error = from_value(&ragged, "transpose").unwrap_err();
+        assert!(error.contains("row 0 has 2 and row 1 has 1"));
+        assert_eq!(from_value(&to_value(filled(2, 3, 0.5)), "transpose").unwrap(), filled(2, 3, 0.5));
+    }
+}
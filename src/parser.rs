@@ -5,15 +5,84 @@ use crate::ast::{
     AssignTarget, BinaryOp, Block, Expr, Literal, MatchArm, Pattern, Statement, StringPart,
     UnaryOp,
 };
-use crate::lexer::{Token, TokenKind};
+use crate::format_spec::FormatSpec;
+use crate::lexer::{self, Token, TokenKind};
+use crate::version::{Deprecation, Version};
+use std::collections::HashSet;
+use std::ops::Range;
+use std::sync::Arc;
+
+/// How deeply expressions, bodies and patterns can nest before the parser
+/// gives up, so a file of ten thousand '(' gets an error instead of a crash
+pub const MAX_NESTING: usize = 1000;
+
+/// Stack for a thread that parses, checks or runs programs nested MAX_NESTING
+/// deep. The main thread's 8 MB runs out around 500 levels in a debug build,
+/// so goose does its work on a thread this big, and embedders taking
+/// untrusted programs should too.
+pub const STACK_SIZE: usize = 64 * 1024 * 1024;
+
+/// The ways a quack can fail to authorize the block it appears to belong to
+#[derive(Debug, Clone, PartialEq)]
+pub enum QuackIssueKind {
+    /// Quack left over at end of file with no block to authorize
+    Dangling,
+    /// More quacks than blocks before the next group of quacks
+    Excess,
+    /// Quack followed by something other than a block
+    Separated { found: TokenKind },
+    /// Quack inside a body that was never consumed there, so it authorizes a later block
+    Leaked { body_end_line: usize },
+}
+
+/// A quack that doesn't do what it looks like it does, with its exact position
+#[derive(Debug, Clone, PartialEq)]
+pub struct QuackIssue {
+    pub kind: QuackIssueKind,
+    pub line: usize,
+    pub column: usize,
+}
+
+/// An operator written in front of its operand
+enum Prefix {
+    Operator(UnaryOp),
+    /// `length of x`, which is sugar for len(x)
+    Length,
+}
+
+impl Prefix {
+    /// Wrap an operand in its prefixes, innermost (last written) first
+    fn apply_all(prefixes: Vec<Prefix>, operand: Expr) -> Expr {
+        prefixes.into_iter().rev().fold(operand, |operand, prefix| match prefix {
+            Prefix::Operator(operator) => Expr::Unary {
+                operator,
+                operand: Box::new(operand),
+            },
+            Prefix::Length => Expr::Call {
+                callee: Box::new(Expr::identifier("len")),
+                arguments: vec![operand],
+            },
+        })
+    }
+}
 
 /// Parser for Duck language
-/// Tracks quack count - when you see N quacks, the next N blocks are "authorized"
+/// Tracks pending quacks - when you see N quacks, the next N blocks are "authorized"
 pub struct Parser {
     tokens: Vec<Token>,
     pos: usize,
-    quack_count: usize, // pending quacks
+    pending_quacks: Vec<(usize, usize)>, // (line, column) of each unconsumed quack
+    quack_floor: usize,                  // pending quacks below this index belong to an outer body
     errors: Vec<String>,
+    quack_issues: Vec<QuackIssue>,
+    reported_quacks: HashSet<(usize, usize)>, // (line, column) of every quack in quack_issues
+    reported_run: (usize, usize),        // (start, length) of pending quacks known to be reported already
+    map_key_depth: Option<usize>,        // depth of a map key being parsed, where `->` is left for the map literal
+    depth: usize,                        // how many expressions, bodies and patterns we're inside
+    version: Version,                    // the Duck version the file says it's written for
+    version_span: Option<Range<usize>>,  // source bytes of the pragma's version string, quotes and all
+    warnings: Vec<(usize, String)>,      // (line, message) of each deprecated piece of syntax
+    deprecations: Vec<(Deprecation, usize, usize)>, // (line, column) of all old syntax, warned about or not
 }
 
 impl Parser {
@@ -22,45 +91,97 @@ impl Parser {
         Parser {
             tokens,
             pos: 0,
-            quack_count: 0,
+            pending_quacks: Vec::new(),
+            quack_floor: 0,
             errors: Vec::new(),
+            quack_issues: Vec::new(),
+            reported_quacks: HashSet::new(),
+            reported_run: (0, 0),
+            map_key_depth: None,
+            depth: 0,
+            version: Version::current(),
+            version_span: None,
+            warnings: Vec::new(),
+            deprecations: Vec::new(),
         }
     }
 
+    /// The Duck version from the file's `[duck version "..."]` pragma, or the
+    /// current version if it doesn't have one
+    pub fn version(&self) -> Version {
+        self.version
+    }
+
+    /// Where the `"0.4"` of the file's `[duck version "0.4"]` pragma sits in
+    /// the source, or None without a (valid) pragma
+    pub fn version_span(&self) -> Option<Range<usize>> {
+        self.version_span.clone()
+    }
+
+    /// Deprecation warnings (line, message) for old syntax the file uses
+    pub fn warnings(&self) -> &[(usize, String)] {
+        &self.warnings
+    }
+
+    /// Every piece of deprecated syntax with the line and column it starts at,
+    /// including what the file's version doesn't warn about (for `goose upgrade-syntax`)
+    pub fn deprecations(&self) -> &[(Deprecation, usize, usize)] {
+        &self.deprecations
+    }
+
+    /// Quacks that were dangling, separated from their block, or leaked out of a body
+    pub fn quack_issues(&self) -> &[QuackIssue] {
+        &self.quack_issues
+    }
+
     /// Parse the entire token stream into a list of blocks
     pub fn parse(&mut self) -> Result<Vec<Block>, Vec<String>> {
         let mut blocks = Vec::new();
+        if self.at_version_pragma() {
+            if let Err(e) = self.parse_version_pragma() {
+                self.errors.push(e);
+                self.skip_pragma();
+            }
+        }
 
         while !self.is_at_end() {
             // Count consecutive quacks
-            while self.check(TokenKind::Quack) {
-                self.advance();
-                self.quack_count += 1;
-            }
+            self.count_quacks();
 
             if self.is_at_end() {
                 break;
             }
 
             // Parse a block if we see one
-            if self.check(TokenKind::LeftBracket) {
+            if self.at_version_pragma() {
+                let line = self.peek().map_or(0, |t| t.line);
+                self.errors.push(format!(
+                    "Line {}: The duck version pragma goes at the very top of the file, with no quack - it's a label, not code",
+                    line
+                ));
+                self.skip_pragma();
+            } else if self.check(TokenKind::LeftBracket) {
                 match self.parse_block() {
                     Ok(block) => blocks.push(block),
                     Err(e) => {
                         self.errors.push(e);
+                        self.quack_floor = 0;
                         self.synchronize();
                     }
                 }
             } else if !self.is_at_end() {
                 // Unexpected token - skip it
                 let token = self.advance();
-                self.errors.push(format!(
-                    "Unexpected token {:?} at line {}",
-                    token.kind, token.line
-                ));
+                let error = format!("Unexpected token {:?} at line {}", token.kind, token.line);
+                self.errors.push(error);
             }
         }
 
+        // Anything still pending has no block left to authorize
+        for (line, column) in std::mem::take(&mut self.pending_quacks) {
+            self.report_quack_issue(QuackIssueKind::Dangling, line, column);
+        }
+
         if self.errors.is_empty() {
             Ok(blocks)
         } else {
@@ -68,21 +189,178 @@ impl Parser {
         }
     }
 
+    fn at_version_pragma(&self) -> bool {
+        let word = |offset: usize, word: &str| {
+            self.tokens.get(self.pos + offset).is_some_and(|t| t.kind == TokenKind::Identifier && t.lexeme == word)
+        };
+        self.check(TokenKind::LeftBracket) && word(1, "duck") && word(2, "version")
+    }
+
+    /// Parse: [duck version "0.4"]
+    fn parse_version_pragma(&mut self) -> Result<(), String> {
+        let line = self.expect(TokenKind::LeftBracket)?.line;
+        self.advance();
+        self.advance();
+        if !self.check(TokenKind::StringLiteral) {
+            return Err(format!("Line {}: Expected a version string like \"0.4\" after 'duck version'", line));
+        }
+        let token = self.advance();
+        let span = token.span.clone();
+        let version: Version = token.lexeme.parse().map_err(|e| format!("Line {}: {}", line, e))?;
+        self.expect(TokenKind::RightBracket)?;
+        if version > Version::current() {
+            return Err(format!(
+                "Line {}: This file was written for Duck {}, but this goose only speaks Duck {}. Try 'goose update'",
+                line,
+                version,
+                Version::current()
+            ));
+        }
+        self.version = version;
+        self.version_span = Some(span);
+        Ok(())
+    }
+
+    /// Skip past the rest of a pragma after an error in it
+    fn skip_pragma(&mut self) {
+        while !self.is_at_end() && !self.check(TokenKind::RightBracket) {
+            self.advance();
+        }
+        self.advance();
+    }
+
+    /// Warn about (or, once it's been removed, refuse) old syntax, going by the file's version
+    fn deprecated(&mut self, deprecation: Deprecation, line: usize, column: usize) -> Result<(), String> {
+        self.deprecations.push((deprecation, line, column));
+        match deprecation.check(self.version) {
+            Ok(Some(warning)) => {
+                self.warnings.push((line, warning));
+                Ok(())
+            }
+            Ok(None) => Ok(()),
+            Err(e) => Err(format!("Line {}: {}", line, e)),
+        }
+    }
+
+    /// Consume consecutive quacks, remembering where each one was
+    fn count_quacks(&mut self) {
+        let mut counted = Vec::new();
+        while self.check(TokenKind::Quack) {
+            let token = self.advance();
+            counted.push((token.line, token.column));
+        }
+
+        if counted.is_empty() {
+            return;
+        }
+
+        // Leftovers from the previous group at this level shift onto later blocks
+        let leftover: Vec<(usize, usize)> = self.pending_quacks.get(self.quack_floor..).unwrap_or_default().to_vec();
+        for (line, column) in leftover {
+            self.report_quack_issue(QuackIssueKind::Excess, line, column);
+        }
+
+        // A quack should be followed directly by its block
+        if let Some(next) = self.peek() {
+            if !matches!(
+                next.kind,
+                TokenKind::LeftBracket | TokenKind::RightBracket | TokenKind::Eof
+            ) {
+                let found = next.kind.clone();
+                for &(line, column) in &counted {
+                    self.report_quack_issue(
+                        QuackIssueKind::Separated { found: found.clone() },
+                        line,
+                        column,
+                    );
+                }
+            }
+        }
+
+        self.pending_quacks.extend(counted);
+    }
+
+    /// Start tracking quacks for a nested body, returning the outer floor
+    fn enter_quack_level(&mut self) -> usize {
+        std::mem::replace(&mut self.quack_floor, self.pending_quacks.len())
+    }
+
+    /// Report quacks counted inside a body that the body itself never used
+    fn exit_quack_level(&mut self, outer_floor: usize) {
+        let body_end_line = self.current_line();
+        let floor = self.quack_floor.min(self.pending_quacks.len());
+        let leaked: Vec<(usize, usize)> = self.pending_quacks[floor..].to_vec();
+        for (line, column) in leaked {
+            self.report_quack_issue(QuackIssueKind::Leaked { body_end_line }, line, column);
+        }
+        self.quack_floor = outer_floor.min(self.pending_quacks.len());
+    }
+
+    /// Take the quack authorizing the next block, if any
+    fn consume_quack(&mut self) -> bool {
+        if self.pending_quacks.len() > self.quack_floor {
+            // Quacks at this level authorize blocks in order, leaving already
+            // reported strays for last so each mistake is only reported once.
+            // Strays already scanned past are skipped, so a flood of them isn't
+            // rescanned for every block.
+            let (start, known) = self.reported_run;
+            let skip = if start == self.quack_floor { known } else { 0 };
+            let mut index = (self.quack_floor + skip).min(self.pending_quacks.len());
+            while index < self.pending_quacks.len() && self.is_quack_reported(self.pending_quacks[index]) {
+                index += 1;
+            }
+            self.reported_run = (self.quack_floor, index - self.quack_floor);
+            if index == self.pending_quacks.len() {
+                index = self.quack_floor;
+            }
+            self.remove_pending_quack(index);
+            true
+        } else if let Some(&(line, column)) = self.pending_quacks.last() {
+            // Borrowed from an enclosing body - it was meant for a block out there
+            self.remove_pending_quack(self.pending_quacks.len() - 1);
+            self.quack_floor = self.quack_floor.min(self.pending_quacks.len());
+            self.report_quack_issue(QuackIssueKind::Excess, line, column);
+            true
+        } else {
+            false
+        }
+    }
+
+    /// Remove a pending quack, keeping the stretch known to be reported accurate
+    fn remove_pending_quack(&mut self, index: usize) {
+        self.pending_quacks.remove(index);
+        let (start, known) = self.reported_run;
+        if index < start {
+            self.reported_run = (0, 0);
+        } else if index < start + known {
+            self.reported_run.1 -= 1;
+        }
+    }
+
+    /// Check whether the quack at this position already has a diagnosis
+    fn is_quack_reported(&self, position: (usize, usize)) -> bool {
+        self.reported_quacks.contains(&position)
+    }
+
+    /// Record a quack issue, keeping only the first diagnosis for each quack
+    fn report_quack_issue(&mut self, kind: QuackIssueKind, line: usize, column: usize) {
+        if self.reported_quacks.insert((line, column)) {
+            self.quack_issues.push(QuackIssue { kind, line, column });
+        }
+    }
+
     /// Parse a single block [...]
     fn parse_block(&mut self) -> Result<Block, String> {
-        let line = self.current_line();
         self.expect(TokenKind::LeftBracket)?;
 
         // Determine if this block is authorized (was preceded by quack)
-        let was_quacked = if self.quack_count > 0 {
-            self.quack_count -= 1;
-            true
-        } else {
-            false
-        };
+        let was_quacked = self.consume_quack();
+
+        // The block's line is where its statement starts, which may be below the '['
+        let line = self.current_line();
 
         // Parse the statement inside the block
-        let statement = self.parse_statement()?;
+        let statement = self.nested(1, Self::parse_statement)?;
 
         self.expect(TokenKind::RightBracket)?;
 
@@ -128,6 +406,12 @@ impl Parser {
             self.parse_attempt_statement()
         } else if self.check(TokenKind::Migrate) {
             self.parse_migrate_statement()
+        } else if self.check_identifier("test") && self.check_next(TokenKind::StringLiteral) {
+            self.parse_test_statement()
+        } else if (self.check_identifier("before") || self.check_identifier("after"))
+            && self.check_next(TokenKind::Each)
+        {
+            self.parse_test_hook()
         } else if self.check(TokenKind::Identifier) {
             self.parse_identifier_statement()
         } else {
@@ -137,30 +421,56 @@ impl Parser {
         }
     }
 
-    /// Parse: [let x be <expr>]
+    /// Parse: [let x be <expr>], [let [a, b] be <expr>], either ending in `always`
     fn parse_let_statement(&mut self) -> Result<Statement, String> {
         self.expect(TokenKind::Let)?;
 
+        // let [a, rest...] be value unpacks a list, using the match patterns
+        if self.check(TokenKind::LeftBracket) {
+            let pattern = self.parse_list_pattern()?;
+            let (value, constant) = self.parse_let_value()?;
+            return Ok(Statement::LetPattern { pattern, value, constant });
+        }
+
         let name = self.expect_identifier()?;
+        let (value, constant) = self.parse_let_value()?;
+
+        Ok(Statement::Let { name, value, constant })
+    }
 
+    /// Parse the `be value` part of a let, and whether it ends in `always`
+    fn parse_let_value(&mut self) -> Result<(Expr, bool), String> {
         self.expect(TokenKind::Be)?;
 
         let value = self.parse_expression()?;
 
-        Ok(Statement::Let { name, value })
+        // `always` is only special here, so it still works as a variable name
+        let constant = self.check_identifier("always");
+        if constant {
+            self.advance();
+        }
+
+        Ok((value, constant))
     }
 
     /// Parse: [define name taking [params] as ...]
+    /// or: [define method name on Struct taking [params] as ...]
     fn parse_function_definition(&mut self) -> Result<Statement, String> {
         self.expect(TokenKind::Define)?;
 
+        // `method` is only special when a method name follows it, so a
+        // function can still be called method
+        if self.check_identifier("method") && self.check_next(TokenKind::Identifier) {
+            return self.parse_method_definition();
+        }
+
         let name = self.expect_identifier()?;
 
         self.expect(TokenKind::Taking)?;
 
         // Parse parameter list [param1, param2, ...]
         self.expect(TokenKind::LeftBracket)?;
-        let params = self.parse_parameter_list()?;
+        let (params, rest) = self.parse_parameter_list()?;
         self.expect(TokenKind::RightBracket)?;
 
         self.expect(TokenKind::As)?;
@@ -168,41 +478,82 @@ impl Parser {
         // Parse function body - collect statements from nested blocks
         let body = self.parse_statement_body()?;
 
-        Ok(Statement::FunctionDef { name, params, body })
+        Ok(Statement::FunctionDef { name, params, rest, body: body.into() })
     }
 
-    /// Parse a list of identifiers separated by commas
-    fn parse_parameter_list(&mut self) -> Result<Vec<String>, String> {
+    /// Parse the rest of: [define method name on Struct taking [params] as ...]
+    fn parse_method_definition(&mut self) -> Result<Statement, String> {
+        self.advance(); // method
+        let name = self.expect_identifier()?;
+        if !self.check_identifier("on") {
+            return Err(format!(
+                "Expected 'on' and a struct name after 'define method {}' at line {}",
+                name,
+                self.current_line()
+            ));
+        }
+        self.advance();
+        let struct_name = self.expect_identifier()?;
+
+        self.expect(TokenKind::Taking)?;
+        self.expect(TokenKind::LeftBracket)?;
+        let (params, rest) = self.parse_parameter_list()?;
+        self.expect(TokenKind::RightBracket)?;
+        if params.iter().chain(&rest).any(|param| param == "self") {
+            return Err(format!(
+                "Method '{}' gets 'self' on its own - leave it out of the parameters at line {}",
+                name,
+                self.current_line()
+            ));
+        }
+
+        self.expect(TokenKind::As)?;
+        let body = self.parse_statement_body()?;
+
+        Ok(Statement::MethodDef { struct_name, name, params, rest, body: body.into() })
+    }
+
+    /// Parse a list of identifiers separated by commas. The last one may be
+    /// written `name...` to collect any extra arguments; it comes back separately.
+    fn parse_parameter_list(&mut self) -> Result<(Vec<String>, Option<String>), String> {
         let mut params = Vec::new();
 
         if self.check(TokenKind::RightBracket) {
-            return Ok(params);
+            return Ok((params, None));
         }
 
-        params.push(self.expect_identifier()?);
-
-        while self.check(TokenKind::Comma) {
+        loop {
+            let param = self.expect_identifier()?;
+            if self.check(TokenKind::Ellipsis) {
+                self.advance();
+                if !self.check(TokenKind::RightBracket) {
+                    return Err(format!(
+                        "The rest of the arguments ('{}...') has to be the last parameter, at line {}",
+                        param,
+                        self.current_line()
+                    ));
+                }
+                return Ok((params, Some(param)));
+            }
+            params.push(param);
+            if !self.check(TokenKind::Comma) {
+                return Ok((params, None));
+            }
             self.advance();
-            params.push(self.expect_identifier()?);
         }
-
-        Ok(params)
     }
 
     /// Parse a body consisting of quacks and nested blocks
-    fn parse_statement_body(&mut self) -> Result<Vec<Statement>, String> {
+    fn parse_statement_body(&mut self) -> Result<Vec<Block>, String> {
         let mut body = Vec::new();
+        let outer_floor = self.enter_quack_level();
 
         while !self.check(TokenKind::RightBracket) && !self.is_at_end() {
             // Count quacks
-            while self.check(TokenKind::Quack) {
-                self.advance();
-                self.quack_count += 1;
-            }
+            self.count_quacks();
 
             if self.check(TokenKind::LeftBracket) {
-                let block = self.parse_block()?;
-                body.push(block.statement);
+                body.push(self.parse_block()?);
             } else if self.check(TokenKind::RightBracket) || self.is_at_end() {
                 break;
             } else if self.check(TokenKind::Otherwise) {
@@ -214,10 +565,13 @@ impl Parser {
             }
         }
 
+        self.exit_quack_level(outer_floor);
+
         Ok(body)
     }
 
     /// Parse: [if <cond> then quack [...] otherwise quack [...]]
+    /// or a chain: [if <cond> then ... otherwise if <cond> then ... otherwise ...]
     fn parse_if_statement(&mut self) -> Result<Statement, String> {
         self.expect(TokenKind::If)?;
 
@@ -227,18 +581,32 @@ impl Parser {
 
         let then_block = self.parse_statement_body()?;
 
-        let otherwise_block = if self.check(TokenKind::Otherwise) {
+        // `otherwise if` is a nested if that doesn't need its own brackets or
+        // quack. Long chains are collected in a loop, then nested from the end.
+        let mut branches = Vec::new();
+        let mut otherwise_block = None;
+        while self.check(TokenKind::Otherwise) {
             self.advance();
-            Some(self.parse_statement_body()?)
-        } else {
-            None
-        };
+            if !self.check(TokenKind::If) {
+                otherwise_block = Some(self.parse_statement_body()?);
+                break;
+            }
+            let line = self.current_line();
+            self.advance();
+            let condition = self.parse_expression()?;
+            self.expect(TokenKind::Then)?;
+            branches.push((line, condition, self.parse_statement_body()?));
+        }
 
-        Ok(Statement::If {
-            condition,
-            then_block,
-            otherwise_block,
-        })
+        for (line, nested_condition, nested_then) in branches.into_iter().rev() {
+            let nested = Statement::If {
+                condition: nested_condition,
+                then_block: nested_then,
+                otherwise_block: otherwise_block.take(),
+            };
+            otherwise_block = Some(vec![Block { statement: nested, was_quacked: true, line }]);
+        }
+        Ok(Statement::If { condition, then_block, otherwise_block })
     }
 
     /// Parse: [match value with [when pattern then ...] ...]
@@ -260,6 +628,7 @@ impl Parser {
     }
 
     /// Parse a single match arm: [when pattern then quack [...]]
+    /// or with a guard: [when pattern if condition then quack [...]]
     fn parse_match_arm(&mut self) -> Result<MatchArm, String> {
         self.expect(TokenKind::LeftBracket)?;
 
@@ -267,6 +636,13 @@ impl Parser {
 
         let pattern = self.parse_pattern()?;
 
+        let guard = if self.check(TokenKind::If) {
+            self.advance();
+            Some(self.parse_expression()?)
+        } else {
+            None
+        };
+
         self.expect(TokenKind::Then)?;
 
         let body = self.parse_statement_body()?;
@@ -275,6 +651,7 @@ impl Parser {
 
         Ok(MatchArm {
             pattern,
+            guard,
             expression: None,
             body: Some(body),
         })
@@ -295,7 +672,7 @@ impl Parser {
             }
         } else if self.check(TokenKind::StringLiteral) {
             let token = self.advance();
-            Ok(Pattern::Literal(Literal::String(token.lexeme)))
+            Ok(Pattern::Literal(Literal::String(token.lexeme.as_str().into())))
         } else if self.check(TokenKind::True) {
             self.advance();
             Ok(Pattern::Literal(Literal::Bool(true)))
@@ -307,7 +684,21 @@ impl Parser {
             Ok(Pattern::Literal(Literal::Nil))
         } else if self.check(TokenKind::Identifier) {
             let name = self.expect_identifier()?;
+            if self.check(TokenKind::With) {
+                // Point with [x, y] matches a Point and binds its x and y fields
+                self.advance();
+                self.expect(TokenKind::LeftBracket)?;
+                let fields = self
+                    .parse_field_list()?
+                    .into_iter()
+                    .map(|field| (field.clone(), Pattern::Variable(field)))
+                    .collect();
+                self.expect(TokenKind::RightBracket)?;
+                return Ok(Pattern::Struct { name, fields });
+            }
             Ok(Pattern::Variable(name))
+        } else if self.check(TokenKind::LeftBracket) {
+            self.nested(1, Self::parse_list_pattern)
         } else {
             Err(format!(
                 "Expected pattern at line {}",
@@ -316,6 +707,42 @@ impl Parser {
         }
     }
 
+    /// Parse a list pattern: [a, b] matches exactly two items, and
+    /// [first, rest...] matches one or more, with `rest` holding the others
+    fn parse_list_pattern(&mut self) -> Result<Pattern, String> {
+        self.expect(TokenKind::LeftBracket)?;
+
+        let mut items = Vec::new();
+        let mut rest = None;
+        while !self.check(TokenKind::RightBracket) && !self.is_at_end() {
+            let item = self.parse_pattern()?;
+            if self.check(TokenKind::Ellipsis) {
+                self.advance();
+                if !matches!(item, Pattern::Variable(_) | Pattern::Wildcard) {
+                    return Err(format!(
+                        "Only a name (or _) can take the rest of a list, at line {}",
+                        self.current_line()
+                    ));
+                }
+                rest = Some(Box::new(item));
+                if !self.check(TokenKind::RightBracket) {
+                    return Err(format!(
+                        "The rest of a list has to come last in a pattern, at line {}",
+                        self.current_line()
+                    ));
+                }
+                break;
+            }
+            items.push(item);
+            if !self.check(TokenKind::RightBracket) {
+                self.expect(TokenKind::Comma)?;
+            }
+        }
+
+        self.expect(TokenKind::RightBracket)?;
+        Ok(Pattern::List { items, rest })
+    }
+
     /// Parse: [repeat N times quack [...]]
     fn parse_repeat_statement(&mut self) -> Result<Statement, String> {
         self.expect(TokenKind::Repeat)?;
@@ -342,16 +769,19 @@ impl Parser {
         Ok(Statement::While { condition, body })
     }
 
-    /// Parse: [for each [item] in collection do quack [...]]
+    /// Parse: [for each [item] in collection do quack [...]], or [for each [i, item] in ...]
     fn parse_for_statement(&mut self) -> Result<Statement, String> {
         self.expect(TokenKind::For)?;
 
         self.expect(TokenKind::Each)?;
 
-        // Parse variable binding [item]
-        self.expect(TokenKind::LeftBracket)?;
-        let variable = self.expect_identifier()?;
-        self.expect(TokenKind::RightBracket)?;
+        // [item] binds each item; [i, item] unpacks each one the way let [i, item] does
+        let pattern = match self.nested(1, Self::parse_list_pattern)? {
+            Pattern::List { mut items, rest: None } if items.len() == 1 && matches!(items[0], Pattern::Variable(_)) => {
+                items.remove(0)
+            }
+            pattern => pattern,
+        };
 
         self.expect(TokenKind::In)?;
 
@@ -362,7 +792,7 @@ impl Parser {
         let body = self.parse_statement_body()?;
 
         Ok(Statement::ForEach {
-            variable,
+            pattern,
             iterable,
             body,
         })
@@ -406,7 +836,7 @@ impl Parser {
         Ok(Statement::Honk { condition, message })
     }
 
-    /// Parse: [attempt ... rescue err ...]
+    /// Parse: [attempt ... rescue err ...] (or `rescue [err]`)
     fn parse_attempt_statement(&mut self) -> Result<Statement, String> {
         self.expect(TokenKind::Attempt)?;
 
@@ -415,8 +845,15 @@ impl Parser {
 
         self.expect(TokenKind::Rescue)?;
 
-        // Parse the error variable name
-        let rescue_var = self.expect_identifier()?;
+        // Parse the error variable name - `rescue err` or `rescue [err]`
+        let rescue_var = if self.check(TokenKind::LeftBracket) {
+            self.advance();
+            let name = self.expect_identifier()?;
+            self.expect(TokenKind::RightBracket)?;
+            name
+        } else {
+            self.expect_identifier()?
+        };
 
         // Parse the rescue block
         let rescue_block = self.parse_statement_body()?;
@@ -429,19 +866,17 @@ impl Parser {
     }
 
     /// Parse statements until we hit 'rescue' keyword
-    fn parse_attempt_body(&mut self) -> Result<Vec<Statement>, String> {
+    #[allow(clippy::if_same_then_else)]
+    fn parse_attempt_body(&mut self) -> Result<Vec<Block>, String> {
         let mut body = Vec::new();
+        let outer_floor = self.enter_quack_level();
 
         while !self.check(TokenKind::Rescue) && !self.check(TokenKind::RightBracket) && !self.is_at_end() {
             // Count quacks
-            while self.check(TokenKind::Quack) {
-                self.advance();
-                self.quack_count += 1;
-            }
+            self.count_quacks();
 
             if self.check(TokenKind::LeftBracket) {
-                let block = self.parse_block()?;
-                body.push(block.statement);
+                body.push(self.parse_block()?);
             } else if self.check(TokenKind::Rescue) || self.check(TokenKind::RightBracket) {
                 break;
             } else if !self.is_at_end() {
@@ -449,9 +884,38 @@ impl Parser {
             }
         }
 
+        self.exit_quack_level(outer_floor);
+
         Ok(body)
     }
 
+    /// Parse: [test "name" do quack [...]]
+    fn parse_test_statement(&mut self) -> Result<Statement, String> {
+        self.advance(); // 'test'
+        let name = self.advance().lexeme.clone();
+
+        self.expect(TokenKind::Do)?;
+
+        let body = self.parse_statement_body()?;
+
+        Ok(Statement::Test { name, body })
+    }
+
+    /// Parse: [before each do quack [...]] or [after each do quack [...]]
+    fn parse_test_hook(&mut self) -> Result<Statement, String> {
+        let is_before = self.advance().lexeme == "before";
+        self.expect(TokenKind::Each)?;
+        self.expect(TokenKind::Do)?;
+
+        let body = self.parse_statement_body()?;
+
+        if is_before {
+            Ok(Statement::BeforeEach(body))
+        } else {
+            Ok(Statement::AfterEach(body))
+        }
+    }
+
     /// Parse: [migrate "path" [as alias]]
     fn parse_migrate_statement(&mut self) -> Result<Statement, String> {
         self.expect(TokenKind::Migrate)?;
@@ -515,7 +979,10 @@ impl Parser {
         let name = self.expect_identifier()?;
 
         // Check what follows the identifier
-        if self.check(TokenKind::Becomes) {
+        if let Some(operator) = self.parse_compound_operator() {
+            // Compound assignment: [x grows by <expr>]
+            self.finish_compound_assignment(AssignTarget::Variable(name), operator)
+        } else if self.check(TokenKind::Becomes) {
             // Assignment: [x becomes <expr>]
             self.advance();
             let value = self.parse_expression()?;
@@ -528,12 +995,15 @@ impl Parser {
             self.advance();
             let field = self.expect_identifier()?;
 
-            if self.check(TokenKind::Becomes) {
+            if let Some(operator) = self.parse_compound_operator() {
+                let object = Box::new(Expr::identifier(name));
+                self.finish_compound_assignment(AssignTarget::Field { object, field }, operator)
+            } else if self.check(TokenKind::Becomes) {
                 self.advance();
                 let value = self.parse_expression()?;
                 Ok(Statement::Assign {
                     target: AssignTarget::Field {
-                        object: Box::new(Expr::Identifier(name)),
+                        object: Box::new(Expr::identifier(name)),
                         field,
                     },
                     value,
@@ -544,7 +1014,7 @@ impl Parser {
                 let value = self.parse_expression()?;
                 Ok(Statement::Push {
                     list: Expr::FieldAccess {
-                        object: Box::new(Expr::Identifier(name)),
+                        object: Box::new(Expr::identifier(name)),
                         field,
                     },
                     value,
@@ -552,7 +1022,7 @@ impl Parser {
             } else {
                 // Field access - might be method call with arguments
                 let field_expr = Expr::FieldAccess {
-                    object: Box::new(Expr::Identifier(name)),
+                    object: Box::new(Expr::identifier(name)),
                     field,
                 };
 
@@ -572,45 +1042,92 @@ impl Parser {
             self.advance();
             let value = self.parse_expression()?;
             Ok(Statement::Push {
-                list: Expr::Identifier(name),
+                list: Expr::identifier(name),
                 value,
             })
         } else if self.check(TokenKind::At) {
             // List index access or assignment
             self.advance();
-            let index = self.parse_primary_expression()?;
+            let index = self.parse_index_operand()?;
 
-            if self.check(TokenKind::Becomes) {
+            if let Some(operator) = self.parse_compound_operator() {
+                let object = Box::new(Expr::identifier(name));
+                self.finish_compound_assignment(AssignTarget::Index { object, index: Box::new(index) }, operator)
+            } else if self.check(TokenKind::Becomes) {
                 self.advance();
                 let value = self.parse_expression()?;
                 Ok(Statement::Assign {
                     target: AssignTarget::Index {
-                        object: Box::new(Expr::Identifier(name)),
+                        object: Box::new(Expr::identifier(name)),
                         index: Box::new(index),
                     },
                     value,
                 })
             } else {
-                let expr = Expr::Index {
-                    object: Box::new(Expr::Identifier(name)),
-                    index: Box::new(index),
-                };
+                let expr = self.finish_index(Expr::identifier(name), index)?;
                 Ok(Statement::Expression(expr))
             }
+        } else if self.check_binary_operator() {
+            // An expression that starts with a name: [x * 2]. Minus isn't
+            // here, since [f -1] calls f with -1.
+            self.pos -= 1;
+            Ok(Statement::Expression(self.parse_expression()?))
         } else {
+            // [f(a, b)] or [f(x) + 1] is an ordinary expression, as long as
+            // it fills the whole block; otherwise it's a command call
+            if self.check(TokenKind::LeftParen) {
+                let after_name = self.pos;
+                self.pos -= 1;
+                if let Ok(expr) = self.parse_expression() {
+                    if self.check(TokenKind::RightBracket) {
+                        return Ok(Statement::Expression(expr));
+                    }
+                }
+                self.pos = after_name;
+            }
+
             // Function call: [name arg1 arg2...] or just identifier
             let args = self.parse_call_arguments()?;
             if args.is_empty() {
-                Ok(Statement::Expression(Expr::Identifier(name)))
+                Ok(Statement::Expression(Expr::identifier(name)))
             } else {
                 Ok(Statement::Expression(Expr::Call {
-                    callee: Box::new(Expr::Identifier(name)),
+                    callee: Box::new(Expr::identifier(name)),
                     arguments: args,
                 }))
             }
         }
     }
 
+    /// After an assignment target, `grows by`, `shrinks by`, `multiplies by`
+    /// or `divides by` (consumed), as the operator it stands for. All four
+    /// words are contextual, so they still work as names elsewhere.
+    fn parse_compound_operator(&mut self) -> Option<BinaryOp> {
+        let operator = match self.peek() {
+            Some(token) if token.kind == TokenKind::Identifier => match token.lexeme.as_str() {
+                "grows" => BinaryOp::Add,
+                "shrinks" => BinaryOp::Sub,
+                "multiplies" => BinaryOp::Mul,
+                "divides" => BinaryOp::Div,
+                _ => return None,
+            },
+            _ => return None,
+        };
+        let by = self.tokens.get(self.pos + 1).is_some_and(|t| t.kind == TokenKind::Identifier && t.lexeme == "by");
+        if !by {
+            return None;
+        }
+        self.advance();
+        self.advance();
+        Some(operator)
+    }
+
+    /// The amount of a compound assignment: [x grows by 5] adds 5 to x
+    fn finish_compound_assignment(&mut self, target: AssignTarget, operator: BinaryOp) -> Result<Statement, String> {
+        let amount = self.parse_expression()?;
+        Ok(Statement::CompoundAssign { target, operator, amount })
+    }
+
     /// Parse function call arguments (expressions until ])
     fn parse_call_arguments(&mut self) -> Result<Vec<Expr>, String> {
         let mut args = Vec::new();
@@ -626,6 +1143,29 @@ impl Parser {
         Ok(args)
     }
 
+    /// Whether the next token is an infix operator that can't start an argument
+    fn check_binary_operator(&self) -> bool {
+        [
+            TokenKind::Plus,
+            TokenKind::PlusPlus,
+            TokenKind::Star,
+            TokenKind::StarStar,
+            TokenKind::Slash,
+            TokenKind::Percent,
+            TokenKind::EqualEqual,
+            TokenKind::NotEqual,
+            TokenKind::Less,
+            TokenKind::Greater,
+            TokenKind::LessEqual,
+            TokenKind::GreaterEqual,
+            TokenKind::And,
+            TokenKind::Or,
+            TokenKind::QuestionQuestion,
+        ]
+        .into_iter()
+        .any(|kind| self.check(kind))
+    }
+
     /// Check if we've reached the end of call arguments
     fn is_end_of_call_args(&self) -> bool {
         self.check(TokenKind::RightBracket)
@@ -643,7 +1183,42 @@ impl Parser {
 
     /// Parse an expression with proper precedence
     fn parse_expression(&mut self) -> Result<Expr, String> {
-        self.parse_or_expression()
+        self.nested(1, Self::parse_coalesce_expression)
+    }
+
+    /// Run `parse` `levels` deeper than we are now. Everything that recurses goes
+    /// through here, so on a STACK_SIZE thread hostile input hits MAX_NESTING
+    /// long before the stack runs out. Prefixes and `**` chains are parsed in
+    /// loops, so they don't count.
+    fn nested<T>(&mut self, levels: usize, parse: impl FnOnce(&mut Self) -> Result<T, String>) -> Result<T, String> {
+        if self.depth + levels > MAX_NESTING {
+            return Err(format!(
+                "Nested more than {} levels deep at line {} - the goose got lost. Try pulling some of it out with let",
+                MAX_NESTING,
+                self.current_line()
+            ));
+        }
+        self.depth += levels;
+        let result = parse(self);
+        self.depth -= levels;
+        result
+    }
+
+    /// Parse nil-coalescing: a ?? b (lowest precedence, so x or y ?? z is (x or y) ?? z)
+    fn parse_coalesce_expression(&mut self) -> Result<Expr, String> {
+        let mut left = self.parse_or_expression()?;
+
+        while self.check(TokenKind::QuestionQuestion) {
+            self.advance();
+            let right = self.parse_or_expression()?;
+            left = Expr::Binary {
+                left: Box::new(left),
+                operator: BinaryOp::Coalesce,
+                right: Box::new(right),
+            };
+        }
+
+        Ok(left)
     }
 
     /// Parse logical OR (lowest precedence)
@@ -651,7 +1226,11 @@ impl Parser {
         let mut left = self.parse_and_expression()?;
 
         while self.check(TokenKind::Or) {
-            self.advance();
+            let token = self.advance();
+            if token.lexeme == "||" {
+                let (line, column) = (token.line, token.column);
+                self.deprecated(Deprecation::SymbolicOr, line, column)?;
+            }
             let right = self.parse_and_expression()?;
             left = Expr::Binary {
                 left: Box::new(left),
@@ -668,7 +1247,11 @@ impl Parser {
         let mut left = self.parse_equality_expression()?;
 
         while self.check(TokenKind::And) {
-            self.advance();
+            let token = self.advance();
+            if token.lexeme == "&&" {
+                let (line, column) = (token.line, token.column);
+                self.deprecated(Deprecation::SymbolicAnd, line, column)?;
+            }
             let right = self.parse_equality_expression()?;
             left = Expr::Binary {
                 left: Box::new(left),
@@ -704,7 +1287,7 @@ impl Parser {
 
     /// Parse comparison (<, >, <=, >=)
     fn parse_comparison_expression(&mut self) -> Result<Expr, String> {
-        let mut left = self.parse_additive_expression()?;
+        let mut left = self.parse_range_expression()?;
 
         while self.check(TokenKind::Less)
             || self.check(TokenKind::Greater)
@@ -721,7 +1304,7 @@ impl Parser {
                 BinaryOp::GtEq
             };
             self.advance();
-            let right = self.parse_additive_expression()?;
+            let right = self.parse_range_expression()?;
             left = Expr::Binary {
                 left: Box::new(left),
                 operator: op,
@@ -732,6 +1315,39 @@ impl Parser {
         Ok(left)
     }
 
+    /// Parse ranges: `1 to 10` (stops before 10) or `1 through 10` (includes 10)
+    fn parse_range_expression(&mut self) -> Result<Expr, String> {
+        let start = self.parse_concat_expression()?;
+
+        let Some(inclusive) = self.range_word() else {
+            return Ok(start);
+        };
+        self.advance();
+        let end = self.parse_concat_expression()?;
+        Ok(Expr::Range {
+            start: Box::new(start),
+            end: Box::new(end),
+            inclusive,
+        })
+    }
+
+    /// Parse joining strings or lists with `++`
+    fn parse_concat_expression(&mut self) -> Result<Expr, String> {
+        let mut left = self.parse_additive_expression()?;
+
+        while self.check(TokenKind::PlusPlus) {
+            self.advance();
+            let right = self.parse_additive_expression()?;
+            left = Expr::Binary {
+                left: Box::new(left),
+                operator: BinaryOp::Concat,
+                right: Box::new(right),
+            };
+        }
+
+        Ok(left)
+    }
+
     /// Parse addition/subtraction
     fn parse_additive_expression(&mut self) -> Result<Expr, String> {
         let mut left = self.parse_multiplicative_expression()?;
@@ -781,27 +1397,102 @@ impl Parser {
         Ok(left)
     }
 
-    /// Parse unary operators (not, -)
+    /// Parse unary operators (not, -, length of)
     fn parse_unary_expression(&mut self) -> Result<Expr, String> {
-        if self.check(TokenKind::Not) {
-            self.advance();
-            let operand = self.parse_unary_expression()?;
-            return Ok(Expr::Unary {
-                operator: UnaryOp::Not,
-                operand: Box::new(operand),
-            });
+        let prefixes = self.parse_prefixes();
+        let operand = self.parse_power_expression()?;
+        Ok(Prefix::apply_all(prefixes, operand))
+    }
+
+    /// Collect `not`, `-` and `length (of)` in a loop rather than by recursion,
+    /// so `- - - ... 1` can't overflow the stack
+    fn parse_prefixes(&mut self) -> Vec<Prefix> {
+        let mut prefixes = Vec::new();
+        loop {
+            if self.check(TokenKind::Not) {
+                self.advance();
+                prefixes.push(Prefix::Operator(UnaryOp::Not));
+            } else if self.check(TokenKind::Minus) {
+                self.advance();
+                prefixes.push(Prefix::Operator(UnaryOp::Neg));
+            } else if self.check(TokenKind::Length) {
+                // length of <expr> / length <expr> is sugar for len(<expr>)
+                self.advance();
+                if self.check(TokenKind::Identifier) && self.peek().map(|t| t.lexeme.as_str()) == Some("of") {
+                    self.advance();
+                }
+                prefixes.push(Prefix::Length);
+            } else {
+                return prefixes;
+            }
         }
+    }
 
-        if self.check(TokenKind::Minus) {
+    /// Parse exponents. `**` groups to the right (2 ** 3 ** 2 is 2 ** 9) and
+    /// binds tighter than a leading minus (-2 ** 2 is -4). An exponent can have
+    /// prefixes of its own: 2 ** -3 ** 2 is 2 ** -(3 ** 2).
+    fn parse_power_expression(&mut self) -> Result<Expr, String> {
+        let base = self.parse_postfix_expression()?;
+        let mut exponents = Vec::new();
+        while self.check(TokenKind::StarStar) {
             self.advance();
-            let operand = self.parse_unary_expression()?;
-            return Ok(Expr::Unary {
-                operator: UnaryOp::Neg,
-                operand: Box::new(operand),
-            });
+            let prefixes = self.parse_prefixes();
+            let operand = self.parse_postfix_expression()?;
+            exponents.push((prefixes, operand));
         }
 
-        self.parse_postfix_expression()
+        // Group from the right without recursing, starting at the last exponent
+        let mut grouped = None;
+        for (prefixes, operand) in exponents.into_iter().rev() {
+            let power = match grouped.take() {
+                Some(exponent) => Expr::Binary {
+                    left: Box::new(operand),
+                    operator: BinaryOp::Pow,
+                    right: Box::new(exponent),
+                },
+                None => operand,
+            };
+            grouped = Some(Prefix::apply_all(prefixes, power));
+        }
+        Ok(match grouped {
+            Some(exponent) => Expr::Binary {
+                left: Box::new(base),
+                operator: BinaryOp::Pow,
+                right: Box::new(exponent),
+            },
+            None => base,
+        })
+    }
+
+    /// What comes after `at` (or `to` in a slice): a single value, which may be
+    /// negative to count from the end (`items at -1`)
+    fn parse_index_operand(&mut self) -> Result<Expr, String> {
+        if !self.check(TokenKind::Minus) {
+            return self.parse_primary_expression();
+        }
+        self.advance();
+        Ok(Expr::Unary {
+            operator: UnaryOp::Neg,
+            operand: Box::new(self.parse_primary_expression()?),
+        })
+    }
+
+    /// After `object at index`: a slice if `to`/`through` follows, otherwise an index
+    fn finish_index(&mut self, object: Expr, index: Expr) -> Result<Expr, String> {
+        let Some(inclusive) = self.range_word() else {
+            return Ok(Expr::Index {
+                object: Box::new(object),
+                index: Box::new(index),
+            });
+        };
+        self.advance();
+        let end = self.parse_index_operand()?;
+        Ok(Expr::Slice {
+            object: Box::new(object),
+            start: Box::new(index),
+            end: Box::new(end),
+            inclusive,
+        })
     }
 
     /// Parse postfix expressions (field access, list access, method calls)
@@ -818,8 +1509,27 @@ impl Parser {
                 };
             } else if self.check(TokenKind::At) {
                 self.advance();
-                let index = self.parse_primary_expression()?;
-                expr = Expr::Index {
+                let index = self.parse_index_operand()?;
+                expr = self.finish_index(expr, index)?;
+            } else if self.check(TokenKind::QuestionDot) {
+                // Optional chaining: person?.address is nil if person is
+                self.advance();
+                let field = self.expect_identifier()?;
+                expr = Expr::OptionalFieldAccess {
+                    object: Box::new(expr),
+                    field,
+                };
+            } else if self.check(TokenKind::Question) {
+                self.advance();
+                if !self.check(TokenKind::At) {
+                    return Err(format!(
+                        "Expected '?.' or '?at' - a lone '?' doesn't mean anything, at line {}",
+                        self.current_line()
+                    ));
+                }
+                self.advance();
+                let index = self.parse_index_operand()?;
+                expr = Expr::OptionalIndex {
                     object: Box::new(expr),
                     index: Box::new(index),
                 };
@@ -839,24 +1549,24 @@ impl Parser {
                     callee: Box::new(expr),
                     arguments: args,
                 };
-            } else if self.check(TokenKind::Arrow) {
+            } else if self.check(TokenKind::Arrow) && self.map_key_depth != Some(self.depth) {
                 // Lambda: [x] -> expr  or  x -> expr
                 self.advance();
 
                 // expr should be the parameter(s)
-                let params = self.extract_lambda_params(expr)?;
+                let params = self.extract_lambda_params(&expr)?;
                 let body = self.parse_expression()?;
 
                 expr = Expr::Lambda {
                     params,
-                    body: Box::new(body),
+                    body: Arc::new(body),
                 };
             } else if self.check(TokenKind::FatArrow) {
                 // Block lambda: [x] => [ quack [...] ... ]
                 self.advance();
 
                 // expr should be the parameter(s)
-                let params = self.extract_lambda_params(expr)?;
+                let params = self.extract_lambda_params(&expr)?;
 
                 // Expect a bracket for the block body
                 self.expect(TokenKind::LeftBracket)?;
@@ -865,7 +1575,7 @@ impl Parser {
 
                 expr = Expr::BlockLambda {
                     params,
-                    body,
+                    body: body.into(),
                 };
             } else {
                 break;
@@ -876,14 +1586,15 @@ impl Parser {
     }
 
     /// Extract lambda parameters from an expression
-    fn extract_lambda_params(&self, expr: Expr) -> Result<Vec<String>, String> {
+    fn extract_lambda_params(&self, expr: &Expr) -> Result<Vec<String>, String> {
         match expr {
-            Expr::Identifier(name) => Ok(vec![name]),
+            Expr::Identifier(name) => Ok(vec![name.clone()]),
+            Expr::Builtin(builtin) => Ok(vec![builtin.name().to_string()]),
             Expr::List(items) => {
                 let mut params = Vec::new();
                 for item in items {
-                    if let Expr::Identifier(name) = item {
-                        params.push(name);
+                    if let Some(name) = item.as_name() {
+                        params.push(name.to_string());
                     } else {
                         return Err("Lambda parameters must be identifiers".to_string());
                     }
@@ -913,7 +1624,7 @@ impl Parser {
         // String literal
         if self.check(TokenKind::StringLiteral) {
             let token = self.advance();
-            return Ok(Expr::Literal(Literal::String(token.lexeme)));
+            return Ok(Expr::Literal(Literal::String(token.lexeme.as_str().into())));
         }
 
         // String interpolation
@@ -943,6 +1654,11 @@ impl Parser {
             return self.parse_list_constructor();
         }
 
+        // Conditional expression: if cond then a otherwise b
+        if self.check(TokenKind::If) {
+            return self.parse_conditional_expression();
+        }
+
         // Parenthesized expression
         if self.check(TokenKind::LeftParen) {
             self.advance();
@@ -960,7 +1676,12 @@ impl Parser {
                 return self.parse_struct_or_call(name);
             }
 
-            return Ok(Expr::Identifier(name));
+            // Struct literal with named fields: Point { x: 1, y: 2 }
+            if self.check(TokenKind::LeftBrace) {
+                return self.parse_struct_init(name);
+            }
+
+            return Ok(Expr::identifier(name));
         }
 
         // Bracket list expression: [x, y, z] (used for lambda params and list literals)
@@ -987,6 +1708,54 @@ impl Parser {
         ))
     }
 
+    /// Parse: Name { field: value, ... }. Fields can come in any order; a
+    /// field named twice is caught here, missing ones when the struct is built.
+    fn parse_struct_init(&mut self, name: String) -> Result<Expr, String> {
+        self.expect(TokenKind::LeftBrace)?;
+        let mut fields: Vec<(String, Expr)> = Vec::new();
+        while !self.check(TokenKind::RightBrace) {
+            let field = self.expect_identifier()?;
+            if fields.iter().any(|(existing, _)| *existing == field) {
+                return Err(format!(
+                    "Field '{}' is given twice in '{}' at line {}",
+                    field,
+                    name,
+                    self.current_line()
+                ));
+            }
+            self.expect(TokenKind::Colon)?;
+            fields.push((field, self.parse_expression()?));
+            if !self.check(TokenKind::Comma) {
+                break;
+            }
+            self.advance();
+        }
+        self.expect(TokenKind::RightBrace)?;
+        Ok(Expr::StructInit { name, fields })
+    }
+
+    /// Parse: if <cond> then <expr> otherwise <expr>, the expression form of
+    /// an if. Both branches are required, since the expression needs a value.
+    fn parse_conditional_expression(&mut self) -> Result<Expr, String> {
+        self.expect(TokenKind::If)?;
+        let condition = self.parse_expression()?;
+        self.expect(TokenKind::Then)?;
+        let then_expr = self.parse_expression()?;
+        if !self.check(TokenKind::Otherwise) {
+            return Err(format!(
+                "An if used as a value needs an 'otherwise' branch too, at line {}",
+                self.current_line()
+            ));
+        }
+        self.advance();
+        let else_expr = self.parse_expression()?;
+        Ok(Expr::Ternary {
+            condition: Box::new(condition),
+            then_expr: Box::new(then_expr),
+            else_expr: Box::new(else_expr),
+        })
+    }
+
     /// Parse interpolated string: "hello {name}!"
     fn parse_interpolated_string(&mut self) -> Result<Expr, String> {
         let mut parts = Vec::new();
@@ -994,7 +1763,7 @@ impl Parser {
         // Get the start part
         let start_token = self.expect(TokenKind::StringStart)?;
         if !start_token.lexeme.is_empty() {
-            parts.push(StringPart::Literal(start_token.lexeme));
+            parts.push(StringPart::Literal(start_token.lexeme.clone()));
         }
 
         loop {
@@ -1004,9 +1773,15 @@ impl Parser {
             }
             self.advance();
 
-            // Parse the expression inside
+            // Parse the expression inside, and its format spec if it has one
             let expr = self.parse_expression()?;
-            parts.push(StringPart::Expr(expr));
+            if self.check(TokenKind::FormatSpec) {
+                let token = self.advance();
+                let spec = FormatSpec::parse(&token.lexeme).map_err(|e| format!("Line {}: {}", token.line, e))?;
+                parts.push(StringPart::Formatted(expr, spec));
+            } else {
+                parts.push(StringPart::Expr(expr));
+            }
 
             // Expect interpolation end
             self.expect(TokenKind::InterpolationEnd)?;
@@ -1015,12 +1790,12 @@ impl Parser {
             if self.check(TokenKind::StringMiddle) {
                 let middle_token = self.advance();
                 if !middle_token.lexeme.is_empty() {
-                    parts.push(StringPart::Literal(middle_token.lexeme));
+                    parts.push(StringPart::Literal(middle_token.lexeme.clone()));
                 }
             } else if self.check(TokenKind::StringEnd) {
                 let end_token = self.advance();
                 if !end_token.lexeme.is_empty() {
-                    parts.push(StringPart::Literal(end_token.lexeme));
+                    parts.push(StringPart::Literal(end_token.lexeme.clone()));
                 }
                 break;
             } else {
@@ -1058,7 +1833,34 @@ impl Parser {
 
         let mut args = Vec::new();
 
-        if !self.check(TokenKind::RightParen) {
+        if name == "map" {
+            // map() and map(key -> value, ...) make maps; map(list, fn) and
+            // map(fn, list) are the builtin, with or without brackets around a
+            // lambda's parameter
+            if self.check(TokenKind::RightParen) {
+                self.advance();
+                return Ok(Expr::MapLiteral(Vec::new()));
+            }
+            let bracketed = self.check(TokenKind::LeftBracket);
+            let mut first = self.parse_map_key()?;
+            if self.check(TokenKind::Arrow) {
+                if !bracketed {
+                    return self.parse_map_literal(first);
+                }
+                // A list can't be a map key, so map([x] -> x * 2, items) starts with a lambda
+                self.advance();
+                let params = self.extract_lambda_params(&first)?;
+                first = Expr::Lambda {
+                    params,
+                    body: Arc::new(self.parse_expression()?),
+                };
+            }
+            args.push(first);
+            while self.check(TokenKind::Comma) {
+                self.advance();
+                args.push(self.parse_expression()?);
+            }
+        } else if !self.check(TokenKind::RightParen) {
             args.push(self.parse_expression()?);
 
             while self.check(TokenKind::Comma) {
@@ -1073,11 +1875,60 @@ impl Parser {
         // For now, we treat it as a function call - struct instantiation can use
         // a different syntax: StructName { field: value }
         Ok(Expr::Call {
-            callee: Box::new(Expr::Identifier(name)),
+            callee: Box::new(Expr::identifier(name)),
             arguments: args,
         })
     }
 
+    /// Parse an expression that might be a map key, stopping at `->`. Only the
+    /// key's own level stops there, so lambdas inside calls in it still work.
+    fn parse_map_key(&mut self) -> Result<Expr, String> {
+        let outer = self.map_key_depth.replace(self.depth + 1);
+        let key = self.parse_expression();
+        self.map_key_depth = outer;
+        key
+    }
+
+    /// Parse the rest of map(k -> v, ...), given the first key
+    fn parse_map_literal(&mut self, first_key: Expr) -> Result<Expr, String> {
+        let mut entries = Vec::new();
+        let mut key = first_key;
+        loop {
+            self.expect(TokenKind::Arrow)?;
+            let outer = self.map_key_depth.take();
+            let value = self.parse_expression();
+            self.map_key_depth = outer;
+            entries.push((key, value?));
+
+            if !self.check(TokenKind::Comma) {
+                break;
+            }
+            self.advance();
+            key = self.parse_map_key()?;
+            // map(x -> x * 2, nums): a name, then something that isn't another
+            // `key -> value`, means the first argument was a lambda
+            if let ([(Expr::Identifier(_) | Expr::Builtin(_), _)], false) = (entries.as_slice(), self.check(TokenKind::Arrow)) {
+                let (param, body) = entries.remove(0);
+                let lambda = Expr::Lambda {
+                    params: self.extract_lambda_params(&param)?,
+                    body: Arc::new(body),
+                };
+                let mut arguments = vec![lambda, key];
+                while self.check(TokenKind::Comma) {
+                    self.advance();
+                    arguments.push(self.parse_expression()?);
+                }
+                self.expect(TokenKind::RightParen)?;
+                return Ok(Expr::Call {
+                    callee: Box::new(Expr::identifier("map")),
+                    arguments,
+                });
+            }
+        }
+        self.expect(TokenKind::RightParen)?;
+        Ok(Expr::MapLiteral(entries))
+    }
+
     // =============================================
     // Helper Methods
     // =============================================
@@ -1098,17 +1949,21 @@ impl Parser {
         self.peek().map(|t| t.line).unwrap_or(0)
     }
 
-    /// Advance and return current token
-    fn advance(&mut self) -> Token {
-        if !self.is_at_end() {
-            self.pos += 1;
-        }
-        self.tokens.get(self.pos - 1).cloned().unwrap_or(Token {
+    /// Advance and return current token. It's borrowed, since most callers
+    /// only skip past it and copying its lexeme and trivia adds up in big files.
+    fn advance(&mut self) -> &Token {
+        static EOF: Token = Token {
             kind: TokenKind::Eof,
             lexeme: String::new(),
             line: 0,
             column: 0,
-        })
+            span: 0..0,
+            leading_trivia: Vec::new(),
+        };
+        if !self.is_at_end() {
+            self.pos += 1;
+        }
+        self.pos.checked_sub(1).and_then(|i| self.tokens.get(i)).unwrap_or(&EOF)
     }
 
     /// Check if current token matches expected kind
@@ -1116,8 +1971,32 @@ impl Parser {
         self.peek().map(|t| t.kind == kind).unwrap_or(false)
     }
 
+    /// Check if the token after the current one matches expected kind
+    fn check_next(&self, kind: TokenKind) -> bool {
+        self.tokens.get(self.pos + 1).map(|t| t.kind == kind).unwrap_or(false)
+    }
+
+    /// Check for a contextual keyword (an identifier with special meaning in one spot)
+    fn check_identifier(&self, word: &str) -> bool {
+        self.peek()
+            .map(|t| t.kind == TokenKind::Identifier && t.lexeme == word)
+            .unwrap_or(false)
+    }
+
+    /// Whether `to` (Some(false)) or `through` (Some(true)) ends a range here.
+    /// They're only words after a range's start, so they still work as names.
+    fn range_word(&self) -> Option<bool> {
+        if self.check_identifier("to") {
+            Some(false)
+        } else if self.check_identifier("through") {
+            Some(true)
+        } else {
+            None
+        }
+    }
+
     /// Expect a specific token kind
-    fn expect(&mut self, kind: TokenKind) -> Result<Token, String> {
+    fn expect(&mut self, kind: TokenKind) -> Result<&Token, String> {
         if self.check(kind.clone()) {
             Ok(self.advance())
         } else {
@@ -1133,7 +2012,7 @@ impl Parser {
     /// Expect and return an identifier
     fn expect_identifier(&mut self) -> Result<String, String> {
         if self.check(TokenKind::Identifier) {
-            Ok(self.advance().lexeme)
+            Ok(self.advance().lexeme.clone())
         } else {
             Err(format!(
                 "Expected identifier, found {:?} at line {}",
@@ -1168,6 +2047,14 @@ impl Parser {
     }
 }
 
+/// Lex and parse source text in one go. Safe to call on anything, including
+/// untrusted or half-typed input: it never panics, and on failure returns
+/// every problem found (the first lexing error, or all the parse errors).
+pub fn parse_str(source: &str) -> Result<Vec<Block>, Vec<String>> {
+    let tokens = lexer::lex(source).map_err(|error| vec![error])?;
+    Parser::new(tokens).parse()
+}
+
 #[cfg(test)]
 mod tests {
     use super::*;
@@ -1209,4 +2096,387 @@ mod tests {
         assert!(result[0].was_quacked);
         assert!(!result[1].was_quacked);
     }
+
+    #[test]
+    fn test_body_blocks_keep_quack_status_and_lines() {
+        let result = parse_source("quack [define f taking [] as\n  quack [print 1]\n  [print 2]\n]").unwrap();
+        let Statement::FunctionDef { body, .. } = &result[0].statement else {
+            panic!("expected function definition");
+        };
+        assert_eq!(body.len(), 2);
+        assert!(body[0].was_quacked);
+        assert_eq!(body[0].line, 2);
+        assert!(!body[1].was_quacked);
+        assert_eq!(body[1].line, 3);
+    }
+
+    #[test]
+    fn test_block_line_is_statement_line() {
+        let result = parse_source("quack [define f taking [] as\n  quack [\n    print 1\n  ]\n]").unwrap();
+        assert_eq!(result[0].line, 1);
+        let Statement::FunctionDef { body, .. } = &result[0].statement else {
+            panic!("expected function definition");
+        };
+        assert_eq!(body[0].line, 3);
+    }
+
+    #[test]
+    fn test_parse_test_and_hooks() {
+        let result = parse_source(
+            "quack [before each do quack [let x be 1]]\nquack [test \"x is one\" do quack [honk x == 1]]\nquack [after each do quack [print x]]",
+        )
+        .unwrap();
+        assert!(matches!(&result[0].statement, Statement::BeforeEach(body) if body.len() == 1));
+        assert!(matches!(&result[1].statement, Statement::Test { name, body } if name == "x is one" && body.len() == 1));
+        assert!(matches!(&result[2].statement, Statement::AfterEach(body) if body.len() == 1));
+    }
+
+    #[test]
+    fn test_test_is_still_a_valid_identifier() {
+        let result = parse_source("quack [let test be 1]\nquack [print test]").unwrap();
+        assert_eq!(result.len(), 2);
+    }
+
+    #[test]
+    fn test_length_of_desugars_to_len() {
+        let expected = Statement::Print(Expr::Call {
+            callee: Box::new(Expr::identifier("len")),
+            arguments: vec![Expr::Identifier("my-list".to_string())],
+        });
+        let with_of = parse_source("quack [print length of my-list]").unwrap();
+        assert_eq!(with_of[0].statement, expected);
+        let without_of = parse_source("quack [print length my-list]").unwrap();
+        assert_eq!(without_of[0].statement, expected);
+    }
+
+    #[test]
+    fn test_builtin_names_are_resolved_when_parsed() {
+        let result = parse_source("quack [print len(xs) + abs]").unwrap();
+        let Statement::Print(Expr::Binary { left, right, .. }) = &result[0].statement else {
+            panic!("expected print of a sum, got {:?}", result[0].statement);
+        };
+        assert!(matches!(left.as_ref(), Expr::Call { callee, .. } if matches!(callee.as_ref(), Expr::Builtin(b) if b.name() == "len")));
+        assert!(matches!(right.as_ref(), Expr::Builtin(b) if b.name() == "abs"));
+    }
+
+    fn quack_issues_for(source: &str) -> Vec<QuackIssue> {
+        let tokens = lex(source).unwrap();
+        let mut parser = Parser::new(tokens);
+        let _ = parser.parse();
+        parser.quack_issues().to_vec()
+    }
+
+    #[test]
+    fn test_dangling_quack_at_eof() {
+        let issues = quack_issues_for("quack [print 1]\nquack quack");
+        assert_eq!(issues.len(), 2);
+        assert!(issues.iter().all(|i| i.kind == QuackIssueKind::Dangling));
+        assert_eq!((issues[0].line, issues[0].column), (2, 1));
+        assert_eq!((issues[1].line, issues[1].column), (2, 7));
+    }
+
+    #[test]
+    fn test_separated_quack() {
+        let issues = quack_issues_for("quack [if true then quack print [1] otherwise quack [print 2]]");
+        assert_eq!(issues.len(), 1);
+        assert_eq!(
+            issues[0].kind,
+            QuackIssueKind::Separated { found: TokenKind::Print }
+        );
+        assert_eq!(issues[0].column, 21);
+    }
+
+    #[test]
+    fn test_leaked_quack_from_body() {
+        let issues = quack_issues_for("quack [repeat 2 times quack quack [print 1]]\n[print 2]");
+        assert_eq!(issues.len(), 1);
+        assert_eq!(issues[0].kind, QuackIssueKind::Leaked { body_end_line: 1 });
+    }
+
+    #[test]
+    fn test_excess_quacks_before_next_group() {
+        let issues = quack_issues_for("quack quack [print 1]\nquack [print 2]");
+        assert_eq!(issues.len(), 1);
+        assert_eq!(issues[0].kind, QuackIssueKind::Excess);
+        assert_eq!((issues[0].line, issues[0].column), (1, 7));
+    }
+
+    #[test]
+    fn test_outer_quack_spilling_into_nested_block() {
+        let issues = quack_issues_for("quack quack [repeat 2 times [print 1]]");
+        assert_eq!(issues.len(), 1);
+        assert_eq!(issues[0].kind, QuackIssueKind::Excess);
+        assert_eq!(issues[0].column, 7);
+    }
+
+    #[test]
+    fn test_map_literal_and_map_call() {
+        let result = parse_source("quack [let m be map(\"a\" -> 1, \"b\" -> [x] -> x)]").unwrap();
+        let Statement::Let { value: Expr::MapLiteral(entries), .. } = &result[0].statement else {
+            panic!("expected map literal, got {:?}", result[0].statement);
+        };
+        assert_eq!(entries.len(), 2);
+        assert!(matches!(entries[1].1, Expr::Lambda { .. }));
+
+        let result = parse_source("quack [let empty be map()]\nquack [let doubled be map(nums, [x] -> x * 2)]").unwrap();
+        assert!(matches!(&result[0].statement, Statement::Let { value: Expr::MapLiteral(entries), .. } if entries.is_empty()));
+        assert!(matches!(&result[1].statement, Statement::Let { value: Expr::Call { arguments, .. }, .. } if arguments.len() == 2));
+
+        let result = parse_source("quack [let doubled be map(x -> x * 2, filter(y -> y > 1, nums))]").unwrap();
+        let Statement::Let { value: Expr::Call { arguments, .. }, .. } = &result[0].statement else {
+            panic!("expected map call, got {:?}", result[0].statement);
+        };
+        assert!(matches!(&arguments[0], Expr::Lambda { params, .. } if params == &["x"]));
+        assert!(matches!(&arguments[1], Expr::Call { arguments, .. } if matches!(arguments[0], Expr::Lambda { .. })));
+
+        let result = parse_source("quack [let m be map(a -> 1, b -> 2)]").unwrap();
+        assert!(matches!(&result[0].statement, Statement::Let { value: Expr::MapLiteral(entries), .. } if entries.len() == 2));
+    }
+
+    #[test]
+    fn test_otherwise_if_chain() {
+        let source = concat!(
+            "quack [if x < 0 then\n",
+            "    quack [print \"negative\"]\n",
+            "otherwise if x == 0 then\n",
+            "    quack [print \"zero\"]\n",
+            "otherwise\n",
+            "    quack [print \"positive\"]\n",
+            "]",
+        );
+        let result = parse_source(source).unwrap();
+        let Statement::If { otherwise_block: Some(otherwise), .. } = &result[0].statement else {
+            panic!("expected if, got {:?}", result[0].statement);
+        };
+        assert_eq!(otherwise.len(), 1);
+        assert_eq!(otherwise[0].line, 3);
+        let Statement::If { otherwise_block: Some(last), .. } = &otherwise[0].statement else {
+            panic!("expected nested if, got {:?}", otherwise[0].statement);
+        };
+        assert!(matches!(last[0].statement, Statement::Print(_)));
+        assert!(quack_issues_for(source).is_empty());
+    }
+
+    #[test]
+    fn test_list_patterns_with_rest() {
+        let result = parse_source("quack [match xs with\n    [when [first, rest...] then quack [print first]]\n]").unwrap();
+        let Statement::Match { arms, .. } = &result[0].statement else {
+            panic!("expected match, got {:?}", result[0].statement);
+        };
+        assert_eq!(
+            arms[0].pattern,
+            Pattern::List {
+                items: vec![Pattern::Variable("first".to_string())],
+                rest: Some(Box::new(Pattern::Variable("rest".to_string()))),
+            }
+        );
+        assert!(parse_source("quack [match xs with\n    [when [rest..., last] then quack [print last]]\n]").is_err());
+    }
+
+    #[test]
+    fn test_struct_patterns() {
+        let result = parse_source("quack [match p with\n    [when Point with [x, y] then quack [print x]]\n]").unwrap();
+        let Statement::Match { arms, .. } = &result[0].statement else {
+            panic!("expected match, got {:?}", result[0].statement);
+        };
+        assert_eq!(
+            arms[0].pattern,
+            Pattern::Struct {
+                name: "Point".to_string(),
+                fields: vec![
+                    ("x".to_string(), Pattern::Variable("x".to_string())),
+                    ("y".to_string(), Pattern::Variable("y".to_string())),
+                ],
+            }
+        );
+    }
+
+    #[test]
+    fn test_struct_literal() {
+        let result = parse_source("quack [let p be Point { y: 2, x: 1 }]").unwrap();
+        let Statement::Let { value, .. } = &result[0].statement else {
+            panic!("expected let, got {:?}", result[0].statement);
+        };
+        let Expr::StructInit { name, fields } = value else {
+            panic!("expected a struct literal, got {:?}", value);
+        };
+        assert_eq!(name, "Point");
+        let names: Vec<&str> = fields.iter().map(|(field, _)| field.as_str()).collect();
+        assert_eq!(names, vec!["y", "x"]);
+        assert!(parse_source("quack [let p be Point { x: 1, x: 2 }]").is_err());
+    }
+
+    #[test]
+    fn test_method_definition() {
+        let result = parse_source("quack [define method scale on Point taking [by] as\n    quack [return by]\n]").unwrap();
+        let Statement::MethodDef { struct_name, name, params, .. } = &result[0].statement else {
+            panic!("expected a method, got {:?}", result[0].statement);
+        };
+        assert_eq!((struct_name.as_str(), name.as_str()), ("Point", "scale"));
+        assert_eq!(params, &vec!["by".to_string()]);
+        // A plain function can still be called method
+        assert!(parse_source("quack [define method taking [x] as\n    quack [return x]\n]").is_ok());
+        assert!(parse_source("quack [define method scale on Point taking [self] as\n    quack [return 1]\n]").is_err());
+    }
+
+    #[test]
+    fn test_compound_assignment_parses() {
+        let result = parse_source("quack [x grows by 5]\nquack [p.n divides by 2]\nquack [grows by]").unwrap();
+        let Statement::CompoundAssign { target: AssignTarget::Variable(name), operator, amount } = &result[0].statement else {
+            panic!("expected a compound assignment, got {:?}", result[0].statement);
+        };
+        assert_eq!((name.as_str(), operator), ("x", &BinaryOp::Add));
+        assert_eq!(amount, &Expr::Literal(Literal::Int(5)));
+        assert!(matches!(
+            &result[1].statement,
+            Statement::CompoundAssign { target: AssignTarget::Field { .. }, operator: BinaryOp::Div, .. }
+        ));
+        // Without a target, the words are just names
+        assert!(matches!(&result[2].statement, Statement::Expression(Expr::Call { .. })));
+    }
+
+    #[test]
+    fn test_parse_str_never_panics_on_broken_input() {
+        let program = concat!(
+            "quack [struct duck with [name, age]]\n",
+            "quack [define greet taking [d, rest...] as\n",
+            "    quack [return f\"Hi {d.name}, {len(rest)} more\"]\n",
+            "]\n",
+            "quack [let [a, b] be list(1, 2.5)] -- note\n",
+            "quack [match a with [when 1 then \"one\" when _ then nil]]\n",
+            "quack [let m be map(\"k\" -> [x] -> x ** 2)]\n",
+            "quack [print m?.k ?? items at 1 to -1]",
+        );
+        // Every prefix, and every position with a troublesome character dropped in
+        let chars: Vec<char> = program.chars().collect();
+        for end in 0..=chars.len() {
+            let _ = parse_str(&chars[..end].iter().collect::<String>());
+        }
+        for position in 0..=chars.len() {
+            for extra in ['[', ']', '(', '{', '}', '"', '\\', '.', '?', '-', 'f', '\n', 'é'] {
+                let mut broken = chars.clone();
+                broken.insert(position, extra);
+                let _ = parse_str(&broken.iter().collect::<String>());
+            }
+        }
+        assert!(parse_str(program).is_ok());
+        assert_eq!(parse_str("quack [print f\"never closed]").unwrap_err().len(), 1);
+    }
+
+    #[test]
+    fn test_version_pragma_stages_deprecations() {
+        let parser_for = |source: &str| {
+            let mut parser = Parser::new(lex(source).unwrap());
+            let parsed = parser.parse();
+            (parser, parsed)
+        };
+
+        let (parser, parsed) = parser_for("quack [print true && false || true]");
+        assert_eq!(parsed.unwrap().len(), 1);
+        assert_eq!(parser.version(), Version::current());
+        let lines: Vec<usize> = parser.warnings().iter().map(|(line, _)| *line).collect();
+        assert_eq!(lines, [1, 1]);
+        assert!(parser.warnings()[0].1.contains("'and'"));
+
+        let (parser, parsed) = parser_for("[duck version \"0.2\"]\nquack [print true && false]");
+        assert_eq!(parsed.unwrap()[0].line, 2);
+        assert_eq!(parser.version(), Version::new(0, 2));
+        assert!(parser.warnings().is_empty());
+
+        let errors = parser_for("[duck version \"99.0\"]\nquack [print 1]").1.unwrap_err();
+        assert_eq!(errors.len(), 1);
+        assert!(errors[0].contains("written for Duck 99.0"), "{:?}", errors);
+        let errors = parser_for("quack [print 1]\n[duck version \"0.2\"]").1.unwrap_err();
+        assert_eq!(errors.len(), 1);
+        assert!(errors[0].contains("goes at the very top"), "{:?}", errors);
+    }
+
+    #[test]
+    fn test_deep_nesting_is_an_error_not_a_crash() {
+        let nest = |open: &str, inner: &str, close: &str, depth: usize| {
+            format!("quack [print {}{}{}]", open.repeat(depth), inner, close.repeat(depth))
+        };
+        let ifs = |depth: usize| format!("quack [{}print 1{}]", "if true then quack [".repeat(depth), "]".repeat(depth));
+        let check = move || {
+            assert!(parse_str(&nest("(", "1", ")", MAX_NESTING - 8)).is_ok());
+            assert!(parse_str(&nest("list(", "1", ")", MAX_NESTING - 8)).is_ok());
+            assert!(parse_str(&ifs(MAX_NESTING / 4)).is_ok());
+            // Prefixes and powers are parsed in loops, so they have no limit
+            assert!(parse_str(&nest("- ", "1", "", 10_000)).is_ok());
+            assert!(parse_str(&nest("not ", "true", "", 10_000)).is_ok());
+            assert!(parse_str(&nest("2 ** ", "2", "", 10_000)).is_ok());
+            let hostile = [
+                nest("(", "1", ")", 10_000),
+                nest("[", "1", "]", 10_000),
+                ifs(10_000),
+                format!("quack [match x with [when {}a{} then quack [print 1]]]", "[".repeat(10_000), "]".repeat(10_000)),
+            ];
+            for source in &hostile {
+                assert!(parse_str(source).unwrap_err()[0].contains("levels deep"), "{}", &source[..40]);
+            }
+            let strings = nest("f\"{", "1", "}\"", 10_000);
+            assert!(parse_str(&strings).unwrap_err()[0].contains("nested more than"));
+        };
+        std::thread::Builder::new().stack_size(STACK_SIZE).spawn(check).unwrap().join().unwrap();
+    }
+
+    #[test]
+    fn test_optional_chaining() {
+        let result = parse_source("quack [let city be person?.address?.city]\nquack [let first be items ?at 0]").unwrap();
+        let Statement::Let { value: Expr::OptionalFieldAccess { object, field }, .. } = &result[0].statement else {
+            panic!("expected optional field access, got {:?}", result[0].statement);
+        };
+        assert_eq!(field, "city");
+        assert!(matches!(object.as_ref(), Expr::OptionalFieldAccess { .. }));
+        assert!(matches!(&result[1].statement, Statement::Let { value: Expr::OptionalIndex { .. }, .. }));
+        assert!(parse_source("quack [let x be a ? b]").is_err());
+    }
+
+    #[test]
+    fn test_expression_statements_starting_with_a_name() {
+        let result = parse_source("quack [x * 2]\nquack [add(1, 2)]\nquack [f (1) 2]").unwrap();
+        assert!(matches!(&result[0].statement, Statement::Expression(Expr::Binary { .. })));
+        assert!(matches!(&result[1].statement, Statement::Expression(Expr::Call { arguments, .. }) if arguments.len() == 2));
+        // Not a whole expression, so still a command call with two arguments
+        assert!(matches!(&result[2].statement, Statement::Expression(Expr::Call { arguments, .. }) if arguments.len() == 2));
+    }
+
+    #[test]
+    fn test_let_list_pattern() {
+        let result = parse_source("quack [let [first, rest...] be items always]").unwrap();
+        let Statement::LetPattern { pattern, constant, .. } = &result[0].statement else {
+            panic!("expected an unpacking let, got {:?}", result[0].statement);
+        };
+        assert!(*constant);
+        assert_eq!(
+            pattern,
+            &Pattern::List {
+                items: vec![Pattern::Variable("first".to_string())],
+                rest: Some(Box::new(Pattern::Variable("rest".to_string()))),
+            }
+        );
+    }
+
+    #[test]
+    fn test_rest_parameter() {
+        let result = parse_source("quack [define greet taking [greeting, names...] as\n    quack [return names]\n]").unwrap();
+        let Statement::FunctionDef { params, rest, .. } = &result[0].statement else {
+            panic!("expected a function, got {:?}", result[0].statement);
+        };
+        assert_eq!(params, &vec!["greeting".to_string()]);
+        assert_eq!(rest.as_deref(), Some("names"));
+        assert!(parse_source("quack [define f taking [names..., last] as\n    quack [return 1]\n]").is_err());
+    }
+
+    #[test]
+    fn test_let_always_is_a_constant() {
+        let result = parse_source("quack [let pi be 3.14 always]\nquack [let always be 1]").unwrap();
+        assert!(matches!(&result[0].statement, Statement::Let { constant: true, .. }));
+        assert!(matches!(&result[1].statement, Statement::Let { name, constant: false, .. } if name == "always"));
+    }
+
+    #[test]
+    fn test_clean_program_has_no_quack_issues() {
+        assert!(quack_issues_for("quack quack [print 1] [print 2]").is_empty());
+    }
 }
@@ -0,0 +1,117 @@
+// Terminal charts behind `plot-bars` and `plot-line`
+// Plain ASCII, so they look the same in every terminal and in saved output
+
+use crate::values::Value;
+
+/// Longest bar `bars` draws, in characters
+pub const BAR_WIDTH: usize = 40;
+
+/// Rows in a line chart unless asked otherwise
+pub const LINE_HEIGHT: usize = 10;
+
+/// The most rows a line chart can ask for
+pub const MAX_LINE_HEIGHT: usize = 1000;
+
+/// Numbers the way Duck prints them (3, not 3.0)
+fn show(n: f64) -> String {
+    Value::Number(n).to_string()
+}
+
+/// A horizontal bar chart, one labelled row per value:
+///
+/// ```text
+/// apples  | ######################################## 40
+/// pears   | ##########                               10
+/// ```
+pub fn bars(labels: &[String], values: &[f64]) -> Result<String, String> {
+    if let Some(bad) = values.iter().find(|v| !v.is_finite() || **v < 0.0) {
+        return Err(format!("bar charts need numbers that are zero or more, got {}", show(*bad)));
+    }
+    let largest = values.iter().cloned().fold(0.0, f64::max);
+    let label_width = labels.iter().map(|label| label.chars().count()).max().unwrap_or(0);
+
+    let rows: Vec<String> = labels
+        .iter()
+        .zip(values)
+        .map(|(label, value)| {
+            let length = if largest > 0.0 { (value / largest * BAR_WIDTH as f64).round() as usize } else { 0 };
+            format!(
+                "{:label_width$} | {:bar_width$} {}",
+                label,
+                "#".repeat(length),
+                show(*value),
+                label_width = label_width,
+                bar_width = BAR_WIDTH
+            )
+        })
+        .collect();
+    Ok(rows.join("\n"))
+}
+
+/// A line chart with one column per value, the highest value at the top:
+///
+/// ```text
+/// 9 |    *
+///   |  *  *
+/// 1 |*      *
+///   +---------
+/// ```
+pub fn line(values: &[f64], height: usize) -> Result<String, String> {
+    if let Some(bad) = values.iter().find(|v| !v.is_finite()) {
+        return Err(format!("line charts need ordinary numbers, got {}", show(*bad)));
+    }
+    if height > MAX_LINE_HEIGHT {
+        return Err(format!("line charts can be at most {} rows tall", MAX_LINE_HEIGHT));
+    }
+    if values.is_empty() {
+        return Ok(String::new());
+    }
+    let height = height.max(2);
+    let low = values.iter().cloned().fold(f64::INFINITY, f64::min);
+    let high = values.iter().cloned().fold(f64::NEG_INFINITY, f64::max);
+    let row_of = |value: f64| {
+        if high > low {
+            ((value - low) / (high - low) * (height - 1) as f64).round() as usize
+        } else {
+            0
+        }
+    };
+
+    let (top_label, bottom_label) = (show(high), show(low));
+    let axis_width = top_label.len().max(bottom_label.len());
+    let mut lines = Vec::new();
+    for row in (0..height).rev() {
+        let label = if row == height - 1 {
+            top_label.as_str()
+        } else if row == 0 {
+            bottom_label.as_str()
+        } else {
+            ""
+        };
+        let points: String = values.iter().map(|v| if row_of(*v) == row { '*' } else { ' ' }).collect();
+        lines.push(format!("{:>axis_width$} |{}", label, points.trim_end(), axis_width = axis_width));
+    }
+    lines.push(format!("{:axis_width$} +{}", "", "-".repeat(values.len()), axis_width = axis_width));
+    Ok(lines.join("\n"))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_bars_scale_to_the_largest_value() {
+        let chart = bars(&["ducks".to_string(), "geese".to_string()], &[20.0, 40.0]).unwrap();
+        let rows: Vec<&str> = chart.lines().collect();
+        assert_eq!(rows[0], format!("ducks | {:40} 20", "#".repeat(20)));
+        assert_eq!(rows[1], format!("geese | {} 40", "#".repeat(40)));
+        assert!(bars(&["x".to_string()], &[-1.0]).is_err());
+    }
+
+    #[test]
+    fn test_line_puts_each_value_in_its_row() {
+        let chart = line(&[1.0, 3.0, 2.0], 3).unwrap();
+        assert_eq!(chart, "3 | *\n  |  *\n1 |*\n  +---");
+        assert_eq!(line(&[5.0, 5.0], 3).unwrap().lines().last(), Some("  +--"));
+    }
+}
@@ -0,0 +1,338 @@
+// Property testing - generators and shrinking for `for-all`
+// Generators are plain struct values, so they can be stored, passed around and nested
+
+use std::collections::HashMap;
+
+use crate::values::Value;
+
+/// Characters gen-string picks from
+const STRING_ALPHABET: &[u8] = b"abcdefghijklmnopqrstuvwxyzABCDEFGHIJKLMNOPQRSTUVWXYZ0123456789 ";
+
+/// Fixed seed so a failing property fails the same way every run
+const DEFAULT_SEED: u64 = 0x9E37_79B9_7F4A_7C15;
+
+/// A description of how to produce random inputs
+#[derive(Debug, Clone, PartialEq)]
+pub enum Generator {
+    /// Whole numbers between min and max (inclusive)
+    Int { min: i64, max: i64 },
+    /// Strings of up to max_len characters
+    Str { max_len: usize },
+    /// Lists of up to max_len elements from another generator
+    List { element: Box<Generator>, max_len: usize },
+}
+
+impl Generator {
+    /// Turn the generator into the struct value handed to Duck code
+    pub fn to_value(&self) -> Value {
+        let mut fields = HashMap::new();
+        match self {
+            Generator::Int { min, max } => {
+                fields.insert("kind".to_string(), Value::String("int".into()));
+                fields.insert("min".to_string(), Value::Number(*min as f64));
+                fields.insert("max".to_string(), Value::Number(*max as f64));
+            }
+            Generator::Str { max_len } => {
+                fields.insert("kind".to_string(), Value::String("string".into()));
+                fields.insert("max-length".to_string(), Value::Number(*max_len as f64));
+            }
+            Generator::List { element, max_len } => {
+                fields.insert("kind".to_string(), Value::String("list".into()));
+                fields.insert("element".to_string(), element.to_value());
+                fields.insert("max-length".to_string(), Value::Number(*max_len as f64));
+            }
+        }
+        Value::new_struct("generator".to_string(), fields)
+    }
+
+    /// Read a generator back out of a value made by gen-int/gen-string/gen-list
+    pub fn from_value(value: &Value) -> Result<Generator, String> {
+        let fields = match value {
+            Value::Struct { name, fields } if name == "generator" => fields.borrow(),
+            other => return Err(format!(
+                "expected a generator (from gen-int, gen-string or gen-list), got {}",
+                other.type_name()
+            )),
+        };
+
+        let number = |key: &str| match fields.get(key) {
+            Some(Value::Number(n)) => Ok(*n),
+            _ => Err(format!("generator is missing its '{}'", key)),
+        };
+
+        match fields.get("kind") {
+            Some(Value::String(kind)) if &**kind == "int" => Ok(Generator::Int {
+                min: number("min")? as i64,
+                max: number("max")? as i64,
+            }),
+            Some(Value::String(kind)) if &**kind == "string" => Ok(Generator::Str {
+                max_len: number("max-length")? as usize,
+            }),
+            Some(Value::String(kind)) if &**kind == "list" => {
+                let element = fields
+                    .get("element")
+                    .ok_or_else(|| "generator is missing its 'element'".to_string())?;
+                Ok(Generator::List {
+                    element: Box::new(Generator::from_value(element)?),
+                    max_len: number("max-length")? as usize,
+                })
+            }
+            _ => Err("generator has an unknown kind".to_string()),
+        }
+    }
+
+    /// Produce one random input
+    pub fn generate(&self, rng: &mut Rng) -> Value {
+        match self {
+            Generator::Int { min, max } => Value::Number(rng.between(*min, *max) as f64),
+            Generator::Str { max_len } => {
+                let len = rng.between(0, *max_len as i64) as usize;
+                let text = (0..len)
+                    .map(|_| STRING_ALPHABET[rng.below(STRING_ALPHABET.len())] as char)
+                    .collect::<String>();
+                Value::String(text.into())
+            }
+            Generator::List { element, max_len } => {
+                let len = rng.between(0, *max_len as i64) as usize;
+                Value::new_list((0..len).map(|_| element.generate(rng)).collect())
+            }
+        }
+    }
+
+    /// Smaller inputs to try in place of a failing one, simplest first
+    pub fn shrink(&self, value: &Value) -> Vec<Value> {
+        let mut candidates = Vec::new();
+        match (self, value) {
+            (Generator::Int { min, max }, Value::Number(n)) => {
+                let n = *n as i64;
+                let target = 0.clamp(*min, (*max).max(*min));
+                if n != target {
+                    let step = if n > target { -1 } else { 1 };
+                    for candidate in [target, target + (n - target) / 2, n + step] {
+                        let candidate = Value::Number(candidate as f64);
+                        if candidate != *value && !candidates.contains(&candidate) {
+                            candidates.push(candidate);
+                        }
+                    }
+                }
+            }
+            (Generator::Str { .. }, Value::String(s)) => {
+                let chars: Vec<char> = s.chars().collect();
+                for shorter in shorter_sequences(&chars) {
+                    candidates.push(Value::String(shorter.into_iter().collect::<String>().into()));
+                }
+                // Same length, plainer characters
+                if let Some(i) = chars.iter().position(|c| *c != 'a') {
+                    let mut plainer = chars.clone();
+                    plainer[i] = 'a';
+                    candidates.push(Value::String(plainer.into_iter().collect::<String>().into()));
+                }
+            }
+            (Generator::List { element, .. }, Value::List(items)) => {
+                let items = items.borrow().clone();
+                for shorter in shorter_sequences(&items) {
+                    candidates.push(Value::new_list(shorter));
+                }
+                for (i, item) in items.iter().enumerate() {
+                    for smaller in element.shrink(item) {
+                        let mut copy = items.clone();
+                        copy[i] = smaller;
+                        candidates.push(Value::new_list(copy));
+                    }
+                }
+            }
+            _ => {}
+        }
+        candidates
+    }
+}
+
+/// Empty, each half, then each single removal
+fn shorter_sequences<T: Clone>(items: &[T]) -> Vec<Vec<T>> {
+    if items.is_empty() {
+        return Vec::new();
+    }
+    let mut out = vec![Vec::new()];
+    if items.len() > 2 {
+        let mid = items.len() / 2;
+        out.push(items[..mid].to_vec());
+        out.push(items[mid..].to_vec());
+    }
+    if items.len() > 1 {
+        for i in 0..items.len() {
+            let mut copy = items.to_vec();
+            copy.remove(i);
+            out.push(copy);
+        }
+    }
+    out
+}
+
+/// Small xorshift generator - deterministic, so failures reproduce
+#[derive(Debug, Clone)]
+pub struct Rng {
+    state: u64,
+}
+
+impl Rng {
+    pub fn new(seed: u64) -> Self {
+        Rng { state: seed.max(1) }
+    }
+
+    fn next(&mut self) -> u64 {
+        let mut x = self.state;
+        x ^= x << 13;
+        x ^= x >> 7;
+        x ^= x << 17;
+        self.state = x;
+        x
+    }
+
+    /// A number in 0..bound
+    fn below(&mut self, bound: usize) -> usize {
+        (self.next() % bound.max(1) as u64) as usize
+    }
+
+    /// A number in 0.0..1.0
+    pub fn unit(&mut self) -> f64 {
+        (self.next() >> 11) as f64 / (1u64 << 53) as f64
+    }
+
+    /// A number in min..=max
+    fn between(&mut self, min: i64, max: i64) -> i64 {
+        if max <= min {
+            return min;
+        }
+        // Widened so that even i64::MIN..=i64::MAX (a span of 2^64) fits
+        let span = (max as i128 - min as i128 + 1) as u128;
+        (min as i128 + (self.next() as u128 % span) as i128) as i64
+    }
+}
+
+impl Default for Rng {
+    fn default() -> Self {
+        Rng::new(DEFAULT_SEED)
+    }
+}
+
+/// How a property failed: the input as generated, and after shrinking
+#[derive(Debug, Clone)]
+pub struct Counterexample {
+    pub original: Value,
+    pub shrunk: Value,
+    pub shrink_steps: usize,
+    /// Error raised by the property, if it failed by erroring rather than returning false
+    pub error: Option<String>,
+}
+
+/// Keep replacing a failing input with a smaller one that still fails.
+/// `fails` runs the property and returns `Some(error)` (possibly empty) on failure,
+/// or `Err` to stop shrinking altogether (the program was cancelled or exited).
+pub fn shrink_failure<F>(generator: &Generator, original: Value, error: Option<String>, mut fails: F) -> Result<Counterexample, String>
+where
+    F: FnMut(&Value) -> Result<Option<Option<String>>, String>,
+{
+    const MAX_SHRINK_STEPS: usize = 500;
+
+    let mut shrunk = original.clone();
+    let mut last_error = error;
+    let mut steps = 0;
+
+    'outer: while steps < MAX_SHRINK_STEPS {
+        for candidate in generator.shrink(&shrunk) {
+            if let Some(error) = fails(&candidate)? {
+                shrunk = candidate;
+                last_error = error;
+                steps += 1;
+                continue 'outer;
+            }
+        }
+        break;
+    }
+
+    Ok(Counterexample { original, shrunk, shrink_steps: steps, error: last_error })
+}
+
+/// Show a generated input the way it would be written in Duck
+pub fn describe(value: &Value) -> String {
+    match value {
+        Value::String(s) => format!("\"{}\"", s),
+        other => other.to_string(),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_generator_round_trips_through_value() {
+        let generator = Generator::List {
+            element: Box::new(Generator::Int { min: -5, max: 5 }),
+            max_len: 4,
+        };
+        assert_eq!(Generator::from_value(&generator.to_value()).unwrap(), generator);
+        assert!(Generator::from_value(&Value::Number(1.0)).is_err());
+    }
+
+    #[test]
+    fn test_generated_values_stay_in_bounds() {
+        let mut rng = Rng::default();
+        let ints = Generator::Int { min: 3, max: 7 };
+        let strings = Generator::Str { max_len: 5 };
+        for _ in 0..200 {
+            match ints.generate(&mut rng) {
+                Value::Number(n) => assert!((3.0..=7.0).contains(&n)),
+                other => panic!("expected number, got {:?}", other),
+            }
+            match strings.generate(&mut rng) {
+                Value::String(s) => assert!(s.len() <= 5),
+                other => panic!("expected string, got {:?}", other),
+            }
+        }
+    }
+
+    #[test]
+    fn test_extreme_int_bounds_dont_overflow() {
+        let mut rng = Rng::default();
+        for (min, max) in [(i64::MIN, i64::MAX), (i64::MIN, 0), (-1, i64::MAX), (i64::MAX, i64::MAX)] {
+            for _ in 0..50 {
+                let n = rng.between(min, max);
+                assert!((min..=max).contains(&n));
+            }
+        }
+    }
+
+    #[test]
+    fn test_shrinks_int_to_smallest_failure() {
+        let generator = Generator::Int { min: -1000, max: 1000 };
+        // Fails for anything 10 or bigger
+        let result = shrink_failure(&generator, Value::Number(873.0), None, |v| match v {
+            Value::Number(n) if *n >= 10.0 => Ok(Some(None)),
+            _ => Ok(None),
+        })
+        .unwrap();
+        assert_eq!(result.shrunk, Value::Number(10.0));
+        assert_eq!(result.original, Value::Number(873.0));
+    }
+
+    #[test]
+    fn test_shrinks_list_length_and_elements() {
+        let generator = Generator::List {
+            element: Box::new(Generator::Int { min: 0, max: 100 }),
+            max_len: 10,
+        };
+        let original = Value::new_list(vec![
+            Value::Number(4.0),
+            Value::Number(77.0),
+            Value::Number(12.0),
+        ]);
+        // Fails whenever any element is over 50
+        let result = shrink_failure(&generator, original, None, |v| match v {
+            Value::List(items) if items.borrow().iter().any(|i| matches!(i, Value::Number(n) if *n > 50.0)) => Ok(Some(None)),
+            _ => Ok(None),
+        })
+        .unwrap();
+        assert_eq!(result.shrunk, Value::new_list(vec![Value::Number(51.0)]));
+    }
+}
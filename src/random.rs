@@ -0,0 +1,163 @@
+// Random number sources - where `random()` gets its numbers
+// A seeded generator, the same generator with every number recorded, or a recording played back
+
+use std::cell::RefCell;
+use std::collections::VecDeque;
+use std::fs;
+use std::path::Path;
+use std::rc::Rc;
+use std::time::{SystemTime, UNIX_EPOCH};
+
+use serde::{Deserialize, Serialize};
+
+use crate::property::Rng;
+
+/// Somewhere `random()` can draw numbers from
+pub trait RandomSource {
+    /// The next number, in 0.0..1.0
+    fn next(&mut self) -> Result<f64, String>;
+}
+
+/// A seeded xorshift generator. The same seed always gives the same numbers.
+#[derive(Debug, Clone)]
+pub struct SeededRandom {
+    rng: Rng,
+}
+
+impl SeededRandom {
+    pub fn new(seed: u64) -> Self {
+        // Spread small seeds out first - xorshift starts slowly from 1, 2, 3...
+        let mut mixed = seed.wrapping_add(0x9E37_79B9_7F4A_7C15);
+        mixed = (mixed ^ (mixed >> 30)).wrapping_mul(0xBF58_476D_1CE4_E5B9);
+        mixed = (mixed ^ (mixed >> 27)).wrapping_mul(0x94D0_49BB_1331_11EB);
+        SeededRandom { rng: Rng::new(mixed ^ (mixed >> 31)) }
+    }
+
+    /// Seeded from the current time, so every run is different
+    pub fn from_time() -> Self {
+        let nanos = SystemTime::now()
+            .duration_since(UNIX_EPOCH)
+            .unwrap_or_default()
+            .as_nanos();
+        SeededRandom::new(nanos as u64)
+    }
+}
+
+impl RandomSource for SeededRandom {
+    fn next(&mut self) -> Result<f64, String> {
+        Ok(self.rng.unit())
+    }
+}
+
+/// Every number drawn during a run, as saved by `--record-rng` and read by `--replay-rng`
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+pub struct RandomLog {
+    pub numbers: Vec<f64>,
+}
+
+impl RandomLog {
+    pub fn load(path: &Path) -> Result<RandomLog, String> {
+        let text = fs::read_to_string(path)
+            .map_err(|e| format!("Couldn't read random number file '{}': {}", path.display(), e))?;
+        serde_json::from_str(&text)
+            .map_err(|e| format!("Random number file '{}' isn't valid: {}", path.display(), e))
+    }
+
+    pub fn save(&self, path: &Path) -> Result<(), String> {
+        let json = serde_json::to_string_pretty(self).map_err(|e| e.to_string())?;
+        fs::write(path, json)
+            .map_err(|e| format!("Couldn't write random number file '{}': {}", path.display(), e))
+    }
+}
+
+/// Passes draws through to another source, remembering every number
+pub struct RecordingRandom {
+    inner: Box<dyn RandomSource>,
+    log: Rc<RefCell<RandomLog>>,
+}
+
+impl RecordingRandom {
+    pub fn new(inner: Box<dyn RandomSource>) -> Self {
+        RecordingRandom {
+            inner,
+            log: Rc::new(RefCell::new(RandomLog::default())),
+        }
+    }
+
+    /// A handle to the numbers recorded so far (keep it to save them after the run)
+    pub fn log(&self) -> Rc<RefCell<RandomLog>> {
+        Rc::clone(&self.log)
+    }
+}
+
+impl RandomSource for RecordingRandom {
+    fn next(&mut self) -> Result<f64, String> {
+        let number = self.inner.next()?;
+        self.log.borrow_mut().numbers.push(number);
+        Ok(number)
+    }
+}
+
+/// Hands out the numbers from a recorded run, in order
+#[derive(Debug, Clone, Default)]
+pub struct ReplayRandom {
+    numbers: VecDeque<f64>,
+}
+
+impl ReplayRandom {
+    pub fn new(log: RandomLog) -> Self {
+        ReplayRandom { numbers: log.numbers.into() }
+    }
+}
+
+impl RandomSource for ReplayRandom {
+    fn next(&mut self) -> Result<f64, String> {
+        self.numbers.pop_front().ok_or_else(|| {
+            "The replay ran out of random numbers - the program drew more than were recorded".to_string()
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    #[allow(clippy::manual_range_contains)]
+    fn test_random() {
+        let result = SeededRandom::from_time().next();
+        match result {
+            Ok(n) => {
+                assert!(n >= 0.0 && n < 1.0);
+            }
+            _ => panic!("Expected number"),
+        }
+    }
+
+    #[test]
+    fn test_same_seed_same_numbers() {
+        let (mut a, mut b) = (SeededRandom::new(42), SeededRandom::new(42));
+        for _ in 0..100 {
+            let n = a.next().unwrap();
+            assert!((0.0..1.0).contains(&n));
+            assert_eq!(n, b.next().unwrap());
+        }
+        assert_ne!(SeededRandom::new(1).next().unwrap(), SeededRandom::new(2).next().unwrap());
+    }
+
+    #[test]
+    fn test_recorded_numbers_replay_in_order() {
+        let mut recorder = RecordingRandom::new(Box::new(SeededRandom::from_time()));
+        let log = recorder.log();
+        let drawn: Vec<f64> = (0..1000).map(|_| recorder.next().unwrap()).collect();
+
+        let path = std::env::temp_dir().join(format!("goose-rng-{}.json", std::process::id()));
+        log.borrow().save(&path).unwrap();
+        let mut replay = ReplayRandom::new(RandomLog::load(&path).unwrap());
+        fs::remove_file(&path).ok();
+        for number in drawn {
+            assert_eq!(replay.next().unwrap(), number);
+        }
+        assert!(replay.next().unwrap_err().contains("ran out"));
+    }
+}
@@ -0,0 +1,162 @@
+// Schema checks behind `validate(value, schema)`
+// A schema is a map (or struct) from field name to a rule. The rule is either
+// a type name like "number", or a map with "type", "required", "min" and "max".
+// Problems come back as readable sentences rather than a single error, so a
+// script can show the user everything that's wrong with their input at once.
+
+use crate::values::{sorted_entries, MapKey, Value};
+
+/// What a single field has to look like
+struct Rule {
+    type_name: Option<String>,
+    required: bool,
+    min: Option<f64>,
+    max: Option<f64>,
+}
+
+/// The (name, value) pairs of a map with string keys or a struct, in name order
+fn named_entries(value: &Value) -> Option<Vec<(String, Value)>> {
+    let mut entries: Vec<(String, Value)> = match value {
+        Value::Map(map) => sorted_entries(&map.borrow())
+            .into_iter()
+            .map(|(key, value)| (key.to_string(), value))
+            .collect(),
+        Value::Struct { fields, .. } => fields.borrow().iter().map(|(k, v)| (k.clone(), v.clone())).collect(),
+        _ => return None,
+    };
+    entries.sort_by(|a, b| a.0.cmp(&b.0));
+    Some(entries)
+}
+
+/// A field of a map or struct, treating nil the same as not being there
+fn field(value: &Value, name: &str) -> Option<Value> {
+    let found = match value {
+        Value::Map(map) => map.borrow().get(&MapKey::String(name.into())).cloned(),
+        Value::Struct { fields, .. } => fields.borrow().get(name).cloned(),
+        _ => None,
+    };
+    found.filter(|value| !matches!(value, Value::Null))
+}
+
+impl Rule {
+    fn parse(field_name: &str, spec: &Value) -> Result<Rule, String> {
+        if let Value::String(type_name) = spec {
+            return Ok(Rule { type_name: Some(type_name.to_string()), required: true, min: None, max: None });
+        }
+        if named_entries(spec).is_none() {
+            return Err(format!(
+                "validate() expects the rule for '{}' to be a type name or a map, got {}",
+                field_name,
+                spec.type_name()
+            ));
+        }
+        let number = |key: &str| match field(spec, key) {
+            None => Ok(None),
+            Some(Value::Number(n)) => Ok(Some(n)),
+            Some(other) => Err(format!("validate() expects '{}' for '{}' to be a number, got {}", key, field_name, other.type_name())),
+        };
+        let type_name = match field(spec, "type") {
+            None => None,
+            Some(Value::String(name)) => Some(name.to_string()),
+            Some(other) => {
+                return Err(format!("validate() expects 'type' for '{}' to be text, got {}", field_name, other.type_name()))
+            }
+        };
+        let required = match field(spec, "required") {
+            None => true,
+            Some(Value::Boolean(required)) => required,
+            Some(other) => {
+                return Err(format!("validate() expects 'required' for '{}' to be true or false, got {}", field_name, other.type_name()))
+            }
+        };
+        Ok(Rule { type_name, required, min: number("min")?, max: number("max")? })
+    }
+
+    /// Everything wrong with one field's value
+    fn check(&self, name: &str, value: Option<&Value>, problems: &mut Vec<String>) {
+        let Some(value) = value else {
+            if self.required {
+                problems.push(format!("'{}' is missing", name));
+            }
+            return;
+        };
+        if let Some(expected) = &self.type_name {
+            if expected != "any" && expected != value.type_name() {
+                problems.push(format!("'{}' should be a {}, got {}", name, expected, value.type_name()));
+                return;
+            }
+        }
+        // Numbers are checked by value; text and lists by length
+        let (size, unit) = match value {
+            Value::Number(n) => (*n, ""),
+            Value::Decimal(d) => (d.to_f64(), ""),
+            Value::Fraction(f) => (f.to_f64(), ""),
+            Value::String(s) => (s.chars().count() as f64, " characters long"),
+            Value::List(items) => (items.borrow().len() as f64, " items long"),
+            _ => return,
+        };
+        if let Some(min) = self.min.filter(|min| size < *min) {
+            problems.push(format!("'{}' should be at least {}{}, got {}", name, min, unit, size));
+        }
+        if let Some(max) = self.max.filter(|max| size > *max) {
+            problems.push(format!("'{}' should be at most {}{}, got {}", name, max, unit, size));
+        }
+    }
+}
+
+/// Check a map or struct against a schema, returning every problem found
+/// (an empty list means it passed). Err is for a schema that doesn't make sense.
+pub fn validate(value: &Value, schema: &Value) -> Result<Vec<String>, String> {
+    let rules = named_entries(schema)
+        .ok_or_else(|| format!("validate() expects the schema to be a map or struct, got {}", schema.type_name()))?;
+    let rules = rules
+        .iter()
+        .map(|(name, spec)| Ok((name, Rule::parse(name, spec)?)))
+        .collect::<Result<Vec<_>, String>>()?;
+    if named_entries(value).is_none() {
+        return Ok(vec![format!("expected a map or struct to check, got {}", value.type_name())]);
+    }
+    let mut problems = Vec::new();
+    for (name, rule) in rules {
+        rule.check(name, field(value, name).as_ref(), &mut problems);
+    }
+    Ok(problems)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn map(entries: Vec<(&str, Value)>) -> Value {
+        Value::new_map(entries.into_iter().map(|(k, v)| (MapKey::String(k.into()), v)).collect())
+    }
+
+    #[test]
+    fn test_reports_every_problem() {
+        let schema = map(vec![
+            ("name", Value::String("string".into())),
+            ("age", map(vec![("type", Value::String("number".into())), ("min", Value::Number(0.0))])),
+            ("nickname", map(vec![("required", Value::Boolean(false)), ("max", Value::Number(3.0))])),
+        ]);
+        let good = map(vec![("name", Value::String("Gerald".into())), ("age", Value::Number(5.0))]);
+        assert!(validate(&good, &schema).unwrap().is_empty());
+
+        let bad = map(vec![("age", Value::Number(-1.0)), ("nickname", Value::String("Gooseberry".into()))]);
+        assert_eq!(
+            validate(&bad, &schema).unwrap(),
+            vec![
+                "'age' should be at least 0, got -1".to_string(),
+                "'name' is missing".to_string(),
+                "'nickname' should be at most 3 characters long, got 10".to_string(),
+            ]
+        );
+    }
+
+    #[test]
+    fn test_rejects_a_confusing_schema() {
+        assert!(validate(&map(vec![]), &Value::Number(1.0)).is_err());
+        assert!(validate(&map(vec![]), &map(vec![("age", Value::Number(1.0))])).is_err());
+        let not_a_record = validate(&Value::Number(1.0), &map(vec![])).unwrap();
+        assert_eq!(not_a_record, vec!["expected a map or struct to check, got number".to_string()]);
+    }
+}
@@ -0,0 +1,264 @@
+// Command-line parsing for Duck scripts, behind `parse-args`
+// The script describes its flags and positional arguments in a map; this turns
+// the words after `goose run script.duck` into a struct, with usage text for free
+
+use std::collections::HashMap;
+
+use crate::values::{sorted_entries, MapKey, Value};
+
+/// What a script says it accepts
+#[derive(Debug, Clone, PartialEq)]
+struct Spec {
+    name: String,
+    description: Option<String>,
+    positional: Vec<String>,
+    /// Flag names and their defaults, in the order the usage text lists them
+    flags: Vec<(String, Value)>,
+}
+
+impl Spec {
+    fn from_value(spec: &Value) -> Result<Spec, String> {
+        let Value::Map(entries) = spec else {
+            return Err(format!("parse-args() expects a map describing the arguments, got {}", spec.type_name()));
+        };
+        let entries = entries.borrow();
+        let field = |name: &str| entries.get(&MapKey::String(name.into()));
+
+        let name = match field("name") {
+            Some(Value::String(name)) => name.to_string(),
+            Some(other) => return Err(format!("parse-args() \"name\" must be a string, got {}", other.type_name())),
+            None => "script".to_string(),
+        };
+        let description = match field("description") {
+            Some(Value::String(text)) => Some(text.to_string()),
+            Some(other) => {
+                return Err(format!("parse-args() \"description\" must be a string, got {}", other.type_name()))
+            }
+            None => None,
+        };
+        let positional = match field("positional") {
+            Some(Value::List(names)) => names
+                .borrow()
+                .iter()
+                .map(|name| match name {
+                    Value::String(name) => Ok(name.to_string()),
+                    other => Err(format!("parse-args() positional names must be strings, got {}", other.type_name())),
+                })
+                .collect::<Result<Vec<_>, _>>()?,
+            Some(other) => return Err(format!("parse-args() \"positional\" must be a list of names, got {}", other.type_name())),
+            None => Vec::new(),
+        };
+        let flags = match field("flags") {
+            Some(Value::Map(flags)) => sorted_entries(&flags.borrow())
+                .into_iter()
+                .map(|(name, default)| match (name, &default) {
+                    (Value::String(name), Value::Boolean(_) | Value::Number(_) | Value::String(_) | Value::Null) => {
+                        Ok((name.to_string(), default))
+                    }
+                    (Value::String(name), other) => Err(format!(
+                        "parse-args() flag --{} needs a default of true/false, a number, a string or nil, got {}",
+                        name,
+                        other.type_name()
+                    )),
+                    (other, _) => Err(format!("parse-args() flag names must be strings, got {}", other)),
+                })
+                .collect::<Result<Vec<_>, _>>()?,
+            Some(other) => return Err(format!("parse-args() \"flags\" must be a map of name -> default, got {}", other.type_name())),
+            None => Vec::new(),
+        };
+
+        for name in positional.iter().chain(flags.iter().map(|(name, _)| name)) {
+            if name == "help" || name == "usage" {
+                return Err(format!("parse-args() reserves \"{}\" for itself - pick another name", name));
+            }
+        }
+        Ok(Spec { name, description, positional, flags })
+    }
+
+    /// The one-line summary: "usage: backup [options] <source>"
+    fn synopsis(&self) -> String {
+        let mut synopsis = format!("usage: {}", self.name);
+        if !self.flags.is_empty() {
+            synopsis.push_str(" [options]");
+        }
+        for name in &self.positional {
+            synopsis.push_str(&format!(" <{}>", name));
+        }
+        synopsis
+    }
+
+    /// The full text shown for --help
+    fn usage(&self) -> String {
+        let mut usage = self.synopsis();
+        if let Some(description) = &self.description {
+            usage.push_str(&format!("\n\n{}", description));
+        }
+
+        let mut lines: Vec<(String, String)> = self
+            .flags
+            .iter()
+            .map(|(name, default)| match default {
+                Value::Boolean(_) => (format!("--{}", name), String::new()),
+                Value::Number(_) => (format!("--{} <number>", name), format!("(default: {})", default)),
+                Value::String(text) => (format!("--{} <text>", name), format!("(default: \"{}\")", text)),
+                _ => (format!("--{} <text>", name), "(required)".to_string()),
+            })
+            .collect();
+        lines.push(("--help".to_string(), "show this help".to_string()));
+        let width = lines.iter().map(|(flag, _)| flag.len()).max().unwrap_or(0);
+        usage.push_str("\n\noptions:");
+        for (flag, note) in lines {
+            usage.push_str(format!("\n  {:width$}  {}", flag, note, width = width).trim_end());
+        }
+        usage
+    }
+}
+
+/// Parse `args` against the spec, returning an `args` struct with a field per
+/// flag and positional argument, plus `help` and `usage`. Mistakes (unknown
+/// flags, missing arguments, a word where a number belongs) are errors that
+/// end with the usage line.
+pub fn parse(spec: &Value, args: &[String]) -> Result<Value, String> {
+    let spec = Spec::from_value(spec)?;
+    let synopsis = spec.synopsis();
+    let mistake = |problem: String| format!("{}\n{} (--help for more)", problem, synopsis);
+
+    let mut fields: HashMap<String, Value> = spec.flags.iter().cloned().collect();
+    let mut positional = Vec::new();
+    let mut help = false;
+    let mut only_positional = false;
+
+    let mut words = args.iter();
+    while let Some(word) = words.next() {
+        if only_positional || !word.starts_with("--") {
+            if word == "-h" && !only_positional {
+                help = true;
+            } else {
+                positional.push(word.clone());
+            }
+            continue;
+        }
+        if word == "--" {
+            only_positional = true;
+            continue;
+        }
+        if word == "--help" {
+            help = true;
+            continue;
+        }
+
+        let (name, inline_value) = match word[2..].split_once('=') {
+            Some((name, value)) => (name, Some(value.to_string())),
+            None => (&word[2..], None),
+        };
+        let negated = name.strip_prefix("no-");
+        let default = spec.flags.iter().find(|(flag, _)| flag == name).map(|(_, default)| default);
+        match (default, negated) {
+            (Some(Value::Boolean(_)), _) => {
+                let on = match inline_value.as_deref() {
+                    None | Some("true") => true,
+                    Some("false") => false,
+                    Some(other) => return Err(mistake(format!("--{} is a switch, so it can't be set to \"{}\"", name, other))),
+                };
+                fields.insert(name.to_string(), Value::Boolean(on));
+            }
+            (Some(default), _) => {
+                let value = match inline_value.or_else(|| words.next().cloned()) {
+                    Some(value) => value,
+                    None => return Err(mistake(format!("--{} needs a value after it", name))),
+                };
+                let value = match default {
+                    Value::Number(_) => match value.trim().parse::<f64>() {
+                        Ok(n) => Value::Number(n),
+                        Err(_) => return Err(mistake(format!("--{} needs a number, got \"{}\"", name, value))),
+                    },
+                    _ => Value::String(value.into()),
+                };
+                fields.insert(name.to_string(), value);
+            }
+            (None, Some(switch)) if matches!(spec.flags.iter().find(|(flag, _)| flag == switch), Some((_, Value::Boolean(_)))) => {
+                fields.insert(switch.to_string(), Value::Boolean(false));
+            }
+            (None, _) => return Err(mistake(format!("there's no --{} option", name))),
+        }
+    }
+
+    if !help {
+        if positional.len() > spec.positional.len() {
+            return Err(mistake(format!("too many arguments (didn't expect \"{}\")", positional[spec.positional.len()])));
+        }
+        if let Some(missing) = spec.positional.get(positional.len()) {
+            return Err(mistake(format!("missing <{}>", missing)));
+        }
+        if let Some((name, _)) = spec.flags.iter().find(|(name, _)| fields.get(name) == Some(&Value::Null)) {
+            return Err(mistake(format!("--{} is required", name)));
+        }
+    }
+
+    for (i, name) in spec.positional.iter().enumerate() {
+        let value = positional.get(i).map(|word| Value::String(word.clone().into())).unwrap_or(Value::Null);
+        fields.insert(name.clone(), value);
+    }
+    fields.insert("help".to_string(), Value::Boolean(help));
+    fields.insert("usage".to_string(), Value::String(spec.usage().into()));
+    Ok(Value::new_struct("args".to_string(), fields))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn spec() -> Value {
+        let flags = Value::new_map(HashMap::from([
+            (MapKey::String("verbose".into()), Value::Boolean(false)),
+            (MapKey::String("count".into()), Value::Number(3.0)),
+            (MapKey::String("out".into()), Value::String("backup.zip".into())),
+        ]));
+        Value::new_map(HashMap::from([
+            (MapKey::String("name".into()), Value::String("backup".into())),
+            (
+                MapKey::String("positional".into()),
+                Value::new_list(vec![Value::String("source".into())]),
+            ),
+            (MapKey::String("flags".into()), flags),
+        ]))
+    }
+
+    fn parse_words(words: &[&str]) -> Result<HashMap<String, Value>, String> {
+        let words: Vec<String> = words.iter().map(|w| w.to_string()).collect();
+        match parse(&spec(), &words)? {
+            Value::Struct { fields, .. } => Ok(fields.borrow().clone()),
+            other => panic!("expected a struct, got {}", other),
+        }
+    }
+
+    #[test]
+    fn test_flags_positionals_and_defaults() {
+        let args = parse_words(&["photos", "--verbose", "--count", "5"]).unwrap();
+        assert_eq!(args["source"], Value::String("photos".into()));
+        assert_eq!(args["verbose"], Value::Boolean(true));
+        assert_eq!(args["count"], Value::Number(5.0));
+        assert_eq!(args["out"], Value::String("backup.zip".into()));
+        assert_eq!(args["help"], Value::Boolean(false));
+
+        let args = parse_words(&["--out=week.zip", "--", "--weird-folder"]).unwrap();
+        assert_eq!(args["out"], Value::String("week.zip".into()));
+        assert_eq!(args["source"], Value::String("--weird-folder".into()));
+    }
+
+    #[test]
+    fn test_mistakes_come_with_usage() {
+        let error = parse_words(&["photos", "--colour"]).unwrap_err();
+        assert!(error.starts_with("there's no --colour option"));
+        assert!(error.ends_with("usage: backup [options] <source> (--help for more)"));
+
+        assert!(parse_words(&[]).unwrap_err().starts_with("missing <source>"));
+        assert!(parse_words(&["a", "--count", "lots"]).unwrap_err().contains("needs a number"));
+        assert!(parse_words(&["a", "b"]).unwrap_err().starts_with("too many arguments"));
+        // Asking for help is never a mistake
+        let args = parse_words(&["--help"]).unwrap();
+        assert_eq!(args["help"], Value::Boolean(true));
+        let Value::String(usage) = &args["usage"] else { panic!("usage should be text") };
+        assert!(usage.contains("\n  --count <number>  (default: 3)\n"));
+    }
+}
@@ -0,0 +1,313 @@
+// Tiny HTTP server behind `serve` - just enough HTTP/1.1 for classroom web apps
+// One request at a time, one connection per request, no keep-alive
+
+use std::collections::HashMap;
+use std::io::{BufRead, BufReader, Read, Write};
+use std::net::{TcpListener, TcpStream};
+use std::thread;
+use std::time::Duration;
+
+use crate::values::Value;
+
+/// How long `accept` waits between checks for cancellation
+const POLL_INTERVAL: Duration = Duration::from_millis(20);
+
+/// Biggest request body the goose will read
+const MAX_BODY_BYTES: usize = 10 * 1024 * 1024;
+
+/// Biggest request line and headers, together, the goose will read
+const MAX_HEAD_BYTES: u64 = 64 * 1024;
+
+/// Most headers one request can have
+const MAX_HEADERS: usize = 100;
+
+/// A request as it arrived
+#[derive(Debug, Clone, PartialEq)]
+pub struct HttpRequest {
+    pub method: String,
+    /// The path without the query string, e.g. "/ducks"
+    pub path: String,
+    /// Everything after the `?`, or empty
+    pub query: String,
+    pub headers: Vec<(String, String)>,
+    pub body: String,
+}
+
+impl HttpRequest {
+    /// The `request` struct handed to a Duck handler
+    pub fn to_value(&self) -> Value {
+        let mut fields = HashMap::new();
+        fields.insert("method".to_string(), Value::String(self.method.clone().into()));
+        fields.insert("path".to_string(), Value::String(self.path.clone().into()));
+        fields.insert("query".to_string(), Value::String(self.query.clone().into()));
+        fields.insert("body".to_string(), Value::String(self.body.clone().into()));
+        // Same flat [key, value, key, value] shape as http-get responses
+        let headers = self
+            .headers
+            .iter()
+            .flat_map(|(k, v)| [Value::String(k.clone().into()), Value::String(v.clone().into())])
+            .collect();
+        fields.insert("headers".to_string(), Value::new_list(headers));
+        Value::new_struct("request".to_string(), fields)
+    }
+}
+
+/// A response ready to send
+#[derive(Debug, Clone, PartialEq)]
+pub struct HttpResponse {
+    pub status: u16,
+    pub headers: Vec<(String, String)>,
+    pub body: String,
+}
+
+impl HttpResponse {
+    pub fn text(status: u16, body: &str) -> Self {
+        HttpResponse {
+            status,
+            headers: vec![("Content-Type".to_string(), "text/plain; charset=utf-8".to_string())],
+            body: body.to_string(),
+        }
+    }
+
+    /// Turn whatever a handler returned into a response: a struct with
+    /// `status`/`body`/`headers` fields (all optional), or anything else as a
+    /// 200 plain-text body
+    pub fn from_value(value: &Value) -> Result<Self, String> {
+        match value {
+            Value::Struct { fields, .. } => {
+                let fields = fields.borrow();
+                let status = match fields.get("status") {
+                    Some(Value::Number(n)) if (100.0..=999.0).contains(n) => *n as u16,
+                    Some(other) => return Err(format!("response status must be a number from 100 to 999, got {}", other)),
+                    None => 200,
+                };
+                let body = match fields.get("body") {
+                    Some(Value::String(s)) => s.to_string(),
+                    Some(Value::Null) | None => String::new(),
+                    Some(other) => other.to_string(),
+                };
+                let mut response = HttpResponse::text(status, &body);
+                if let Some(headers) = fields.get("headers") {
+                    let headers = crate::builtins::parse_headers(headers)?;
+                    for (key, value) in &headers {
+                        check_header(key, value)?;
+                    }
+                    // Handler headers win over the default content type
+                    response.headers.retain(|(k, _)| !headers.iter().any(|(h, _)| h.eq_ignore_ascii_case(k)));
+                    response.headers.extend(headers);
+                }
+                Ok(response)
+            }
+            Value::Null => Ok(HttpResponse::text(204, "")),
+            Value::String(s) => Ok(HttpResponse::text(200, s)),
+            other => Ok(HttpResponse::text(200, &other.to_string())),
+        }
+    }
+
+    fn write_to(&self, stream: &mut TcpStream) -> std::io::Result<()> {
+        let mut head = format!("HTTP/1.1 {} {}\r\n", self.status, reason_phrase(self.status));
+        for (key, value) in &self.headers {
+            check_header(key, value).map_err(|e| std::io::Error::new(std::io::ErrorKind::InvalidInput, e))?;
+            head.push_str(&format!("{}: {}\r\n", key, value));
+        }
+        head.push_str(&format!("Content-Length: {}\r\nConnection: close\r\n\r\n", self.body.len()));
+        stream.write_all(head.as_bytes())?;
+        stream.write_all(self.body.as_bytes())?;
+        stream.flush()
+    }
+}
+
+/// A header has to stay on its own line: a line break in it would let a
+/// handler (or whoever it got the text from) add headers or a whole response
+fn check_header(key: &str, value: &str) -> Result<(), String> {
+    if key.is_empty() || key.contains(':') || key.contains(['\r', '\n']) {
+        return Err(format!("response header name {:?} isn't allowed", key));
+    }
+    if value.contains(['\r', '\n']) {
+        return Err(format!("response header '{}' can't contain a line break", key));
+    }
+    Ok(())
+}
+
+fn reason_phrase(status: u16) -> &'static str {
+    match status {
+        200 => "OK",
+        201 => "Created",
+        204 => "No Content",
+        301 => "Moved Permanently",
+        302 => "Found",
+        400 => "Bad Request",
+        401 => "Unauthorized",
+        403 => "Forbidden",
+        404 => "Not Found",
+        405 => "Method Not Allowed",
+        500 => "Internal Server Error",
+        _ => "",
+    }
+}
+
+/// A listening socket that can be polled, so a serving program can still be
+/// cancelled or timed out
+pub struct Server {
+    listener: TcpListener,
+}
+
+impl Server {
+    pub fn bind(port: u16) -> Result<Self, String> {
+        let listener = TcpListener::bind(("127.0.0.1", port))
+            .map_err(|e| format!("couldn't listen on port {}: {}", port, e))?;
+        listener.set_nonblocking(true).map_err(|e| e.to_string())?;
+        Ok(Server { listener })
+    }
+
+    /// The port actually listened on (useful after binding port 0)
+    pub fn port(&self) -> u16 {
+        self.listener.local_addr().map(|addr| addr.port()).unwrap_or(0)
+    }
+
+    /// Wait for the next request. Stops early with the error from `interrupted`
+    /// if it returns one.
+    pub fn next_request(
+        &self,
+        interrupted: &dyn Fn() -> Option<String>,
+    ) -> Result<(HttpRequest, TcpStream), String> {
+        loop {
+            if let Some(reason) = interrupted() {
+                return Err(reason);
+            }
+            match self.listener.accept() {
+                Ok((mut stream, _)) => {
+                    stream.set_nonblocking(false).map_err(|e| e.to_string())?;
+                    stream.set_read_timeout(Some(Duration::from_secs(5))).ok();
+                    match read_request(&mut stream) {
+                        Ok(request) => return Ok((request, stream)),
+                        // Garbage from one client shouldn't stop the server
+                        Err(e) => {
+                            HttpResponse::text(400, &e).write_to(&mut stream).ok();
+                        }
+                    }
+                }
+                Err(e) if e.kind() == std::io::ErrorKind::WouldBlock => thread::sleep(POLL_INTERVAL),
+                Err(e) => return Err(format!("the server stopped accepting connections: {}", e)),
+            }
+        }
+    }
+
+    /// Send a response back and close the connection
+    pub fn respond(&self, mut stream: TcpStream, response: &HttpResponse) {
+        // The client hanging up early is the client's problem
+        response.write_to(&mut stream).ok();
+    }
+}
+
+/// Read one line of the request line and headers, out of what's left of `budget`
+fn read_head_line(reader: &mut impl BufRead, budget: &mut u64) -> Result<String, String> {
+    let mut line = String::new();
+    let read = reader.take(*budget).read_line(&mut line).map_err(|e| e.to_string())?;
+    *budget -= read as u64;
+    if *budget == 0 && !line.ends_with('\n') {
+        return Err("request headers are too large".to_string());
+    }
+    Ok(line)
+}
+
+fn read_request(stream: impl Read) -> Result<HttpRequest, String> {
+    let mut reader = BufReader::new(stream);
+    let mut budget = MAX_HEAD_BYTES;
+    let request_line = read_head_line(&mut reader, &mut budget)?;
+    let mut parts = request_line.split_whitespace();
+    let (method, target) = match (parts.next(), parts.next()) {
+        (Some(method), Some(target)) => (method.to_string(), target.to_string()),
+        _ => return Err("malformed request line".to_string()),
+    };
+    let (path, query) = match target.split_once('?') {
+        Some((path, query)) => (path.to_string(), query.to_string()),
+        None => (target, String::new()),
+    };
+
+    let mut headers = Vec::new();
+    loop {
+        let header = read_head_line(&mut reader, &mut budget)?;
+        let header = header.trim_end();
+        if header.is_empty() {
+            break;
+        }
+        if let Some((key, value)) = header.split_once(':') {
+            if headers.len() == MAX_HEADERS {
+                return Err(format!("too many request headers (the most is {})", MAX_HEADERS));
+            }
+            headers.push((key.trim().to_string(), value.trim().to_string()));
+        }
+    }
+
+    let length = headers
+        .iter()
+        .find(|(k, _)| k.eq_ignore_ascii_case("content-length"))
+        .and_then(|(_, v)| v.parse::<usize>().ok())
+        .unwrap_or(0);
+    if length > MAX_BODY_BYTES {
+        return Err("request body is too large".to_string());
+    }
+    let mut body = vec![0; length];
+    reader.read_exact(&mut body).map_err(|e| e.to_string())?;
+
+    Ok(HttpRequest {
+        method,
+        path,
+        query,
+        headers,
+        body: String::from_utf8_lossy(&body).into_owned(),
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_response_from_handler_values() {
+        let plain = HttpResponse::from_value(&Value::String("honk".into())).unwrap();
+        assert_eq!((plain.status, plain.body.as_str()), (200, "honk"));
+
+        let mut fields = HashMap::new();
+        fields.insert("status".to_string(), Value::Number(404.0));
+        fields.insert("body".to_string(), Value::String("no pond here".into()));
+        fields.insert(
+            "headers".to_string(),
+            Value::new_list(vec![Value::String("Content-Type".into()), Value::String("text/html".into())]),
+        );
+        let custom = HttpResponse::from_value(&Value::new_struct("response".to_string(), fields)).unwrap();
+        assert_eq!(custom.status, 404);
+        assert_eq!(custom.headers, vec![("Content-Type".to_string(), "text/html".to_string())]);
+
+        assert_eq!(HttpResponse::from_value(&Value::Null).unwrap().status, 204);
+    }
+
+    #[test]
+    fn test_handler_headers_cant_split_the_response() {
+        let response = |key: &str, value: &str| {
+            let mut fields = HashMap::new();
+            fields.insert(
+                "headers".to_string(),
+                Value::new_list(vec![Value::String(key.into()), Value::String(value.into())]),
+            );
+            HttpResponse::from_value(&Value::new_struct("response".to_string(), fields))
+        };
+        assert!(response("X-Pond", "calm").is_ok());
+        assert!(response("X-Pond", "calm\r\nSet-Cookie: stolen=1").unwrap_err().contains("line break"));
+        assert!(response("X-Pond\nSet-Cookie", "stolen=1").is_err());
+        assert!(response("X-Pond: a", "b").is_err());
+    }
+
+    #[test]
+    fn test_request_head_is_capped() {
+        let request = read_request(&b"GET /ducks?n=2 HTTP/1.1\r\nHost: pond\r\nContent-Length: 4\r\n\r\nhonk"[..]).unwrap();
+        assert_eq!((request.path.as_str(), request.query.as_str(), request.body.as_str()), ("/ducks", "n=2", "honk"));
+        assert_eq!(request.headers[0], ("Host".to_string(), "pond".to_string()));
+
+        let long = format!("GET / HTTP/1.1\r\nX-Long: {}\r\n\r\n", "a".repeat(MAX_HEAD_BYTES as usize));
+        assert!(read_request(long.as_bytes()).unwrap_err().contains("too large"));
+        let many = format!("GET / HTTP/1.1\r\n{}\r\n", "X-Duck: 1\r\n".repeat(MAX_HEADERS + 1));
+        assert!(read_request(many.as_bytes()).unwrap_err().contains("too many"));
+    }
+}
@@ -0,0 +1,77 @@
+// Shell commands - what `shell()` runs, for gluing other programs together
+// Off unless the embedder (or `goose run --allow-shell`) turns it on, since a
+// command can do anything the user can
+
+use std::io::Read;
+use std::process::{Command, Stdio};
+use std::thread;
+use std::time::Duration;
+
+/// What a finished command printed, and how it ended
+#[derive(Debug, Clone, PartialEq)]
+pub struct Output {
+    pub stdout: String,
+    pub stderr: String,
+    /// The exit code, or `None` if the command was killed by a signal
+    pub status: Option<i32>,
+}
+
+/// Run `command` with the platform's shell (`sh -c`, or `cmd /C` on Windows)
+/// and wait for it, killing it if `interrupted` says the program has to stop
+pub fn run(command: &str, interrupted: &dyn Fn() -> Option<String>) -> Result<Output, String> {
+    let (program, flag) = if cfg!(windows) { ("cmd", "/C") } else { ("sh", "-c") };
+    let mut child = Command::new(program)
+        .args([flag, command])
+        .stdin(Stdio::null())
+        .stdout(Stdio::piped())
+        .stderr(Stdio::piped())
+        .spawn()
+        .map_err(|e| format!("couldn't start {}: {}", program, e))?;
+
+    // Drain both pipes as the command runs, so a chatty one can't fill a pipe and stall
+    let drain = |pipe: Option<Box<dyn Read + Send>>| {
+        thread::spawn(move || {
+            let mut bytes = Vec::new();
+            if let Some(mut pipe) = pipe {
+                let _ = pipe.read_to_end(&mut bytes);
+            }
+            String::from_utf8_lossy(&bytes).into_owned()
+        })
+    };
+    let stdout = drain(child.stdout.take().map(|pipe| Box::new(pipe) as Box<dyn Read + Send>));
+    let stderr = drain(child.stderr.take().map(|pipe| Box::new(pipe) as Box<dyn Read + Send>));
+
+    let status = loop {
+        if let Some(status) = child.try_wait().map_err(|e| e.to_string())? {
+            break status;
+        }
+        if let Some(reason) = interrupted() {
+            let _ = child.kill();
+            let _ = child.wait();
+            return Err(reason);
+        }
+        thread::sleep(Duration::from_millis(5));
+    };
+    Ok(Output {
+        stdout: stdout.join().unwrap_or_default(),
+        stderr: stderr.join().unwrap_or_default(),
+        status: status.code(),
+    })
+}
+
+#[cfg(all(test, unix))]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_captures_both_streams_and_the_status() {
+        let output = run("echo honk; echo hiss >&2; exit 3", &|| None).unwrap();
+        assert_eq!(output, Output { stdout: "honk\n".to_string(), stderr: "hiss\n".to_string(), status: Some(3) });
+    }
+
+    #[test]
+    fn test_interruption_kills_the_command() {
+        let error = run("sleep 10", &|| Some("stop".to_string())).unwrap_err();
+        assert_eq!(error, "stop");
+    }
+}
@@ -0,0 +1,224 @@
+// Test runner - runs `test` blocks for `goose test`
+// Every test starts from the same snapshot, so tests can't leak state into each other
+// Time is virtual: `sleep` returns instantly and `now` starts from 0 in every test
+// Randomness is seeded: `random()` draws the same numbers in every test, every run
+
+use crate::ast::{Block, Statement};
+use crate::capability::Policy;
+use crate::clock::VirtualClock;
+use crate::interpreter::{Coverage, Interpreter};
+use crate::random::SeededRandom;
+
+/// The seed `random()` starts from in every test
+const TEST_SEED: u64 = 0x600D_5EED;
+
+/// How a single test went
+#[derive(Debug, Clone, PartialEq)]
+pub enum TestOutcome {
+    Passed,
+    /// The error (usually a failed honk) that stopped the test
+    Failed(String),
+    /// The test block wasn't quacked, so it never ran
+    Skipped,
+}
+
+/// The result of one `test` block
+#[derive(Debug, Clone)]
+pub struct TestResult {
+    pub name: String,
+    pub line: usize,
+    pub outcome: TestOutcome,
+    /// Quacked blocks and honks this test (and its fixtures) exercised
+    pub coverage: Coverage,
+}
+
+/// Run every test in a program.
+///
+/// Top-level blocks other than tests and fixtures run once as setup. Each test
+/// then runs against a fresh copy of that setup state: `before each` bodies,
+/// the test body, then `after each` bodies (even if the test failed).
+/// Returns an error if the setup itself fails.
+pub fn run_tests(blocks: &[Block], args: Vec<String>) -> Result<Vec<TestResult>, String> {
+    let mut setup = Vec::new();
+    let mut before_each = Vec::new();
+    let mut after_each = Vec::new();
+    let mut tests = Vec::new();
+
+    for block in blocks {
+        match &block.statement {
+            Statement::Test { name, body } => tests.push((name, body, block)),
+            Statement::BeforeEach(body) if block.was_quacked => before_each.push(body),
+            Statement::AfterEach(body) if block.was_quacked => after_each.push(body),
+            Statement::BeforeEach(_) | Statement::AfterEach(_) => {}
+            _ => setup.push(block.clone()),
+        }
+    }
+
+    let mut interpreter = Interpreter::with_args(args);
+    interpreter.set_clock(Box::new(VirtualClock::new()));
+    interpreter.set_random_source(Box::new(SeededRandom::new(TEST_SEED)));
+    interpreter.set_policy(Policy::all().with_desktop(false));
+    interpreter.run_body(&setup)?;
+    let snapshot = interpreter.snapshot();
+
+    let mut results = Vec::new();
+    for (name, body, block) in tests {
+        if !block.was_quacked {
+            results.push(TestResult {
+                name: name.clone(),
+                line: block.line,
+                outcome: TestOutcome::Skipped,
+                coverage: Coverage::default(),
+            });
+            continue;
+        }
+
+        interpreter.restore(&snapshot);
+        interpreter.set_clock(Box::new(VirtualClock::new()));
+        interpreter.set_random_source(Box::new(SeededRandom::new(TEST_SEED)));
+        interpreter.start_coverage();
+        let skipped_before = interpreter.stats().skipped_blocks.len();
+
+        let mut outcome = Ok(());
+        for fixture in &before_each {
+            outcome = outcome.and_then(|_| interpreter.run_body(fixture));
+        }
+        outcome = outcome.and_then(|_| interpreter.run_body(body));
+        for fixture in &after_each {
+            let cleanup = interpreter.run_body(fixture);
+            outcome = outcome.and(cleanup);
+        }
+        // An unquacked assertion never runs, which mustn't pass for a passing test
+        outcome = outcome.and_then(|_| never_ran(&interpreter.stats().skipped_blocks[skipped_before..]));
+
+        results.push(TestResult {
+            name: name.clone(),
+            line: block.line,
+            outcome: match outcome {
+                Ok(()) => TestOutcome::Passed,
+                Err(e) => TestOutcome::Failed(e),
+            },
+            coverage: interpreter.take_coverage(),
+        });
+    }
+
+    Ok(results)
+}
+
+/// An error naming the blocks the goose refused, if there were any
+pub(crate) fn never_ran(skipped: &[usize]) -> Result<(), String> {
+    if skipped.is_empty() {
+        return Ok(());
+    }
+    let lines: Vec<String> = skipped.iter().map(|line| line.to_string()).collect();
+    Err(format!("Never ran (no quack): line(s) {}", lines.join(", ")))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::lexer::lex;
+    use crate::parser::Parser;
+
+    fn run(source: &str) -> Vec<TestResult> {
+        let blocks = Parser::new(lex(source).unwrap()).parse().unwrap();
+        run_tests(&blocks, vec![]).unwrap()
+    }
+
+    #[test]
+    fn test_each_test_starts_from_setup_state() {
+        let results = run(concat!(
+            "quack [let pond be [1]]\n",
+            "quack [test \"first\" do quack [pond push 2] quack [honk len(pond) == 2]]\n",
+            "quack [test \"second\" do quack [honk len(pond) == 1]]",
+        ));
+        assert_eq!(results.len(), 2);
+        assert!(results.iter().all(|r| r.outcome == TestOutcome::Passed));
+    }
+
+    #[test]
+    fn test_unquacked_blocks_in_a_test_fail_it() {
+        let results = run("quack [test \"sneaky\" do [honk 1 == 2]]");
+        assert_eq!(results[0].outcome, TestOutcome::Failed("Never ran (no quack): line(s) 1".to_string()));
+    }
+
+    #[test]
+    fn test_fixtures_wrap_every_test() {
+        let results = run(concat!(
+            "quack [let log be []]\n",
+            "quack [before each do quack [log push \"before\"]]\n",
+            "quack [after each do quack [honk len(log) == 2]]\n",
+            "quack [test \"sees fixture\" do quack [honk log at 0 == \"before\"] quack [log push \"test\"]]",
+        ));
+        assert_eq!(results[0].outcome, TestOutcome::Passed);
+    }
+
+    #[test]
+    fn test_failures_skips_and_coverage() {
+        let results = run(concat!(
+            "quack [test \"fails\" do\n",
+            "  quack [honk 1 == 2]\n",
+            "]\n",
+            "[test \"unquacked\" do quack [honk true]]",
+        ));
+        assert!(matches!(results[0].outcome, TestOutcome::Failed(_)));
+        assert_eq!(results[0].coverage.quacks, vec![2]);
+        assert_eq!(results[0].coverage.honks, vec![2]);
+        assert_eq!(results[1].outcome, TestOutcome::Skipped);
+    }
+
+    #[test]
+    fn test_property_checks_are_reported() {
+        let results = run(concat!(
+            "quack [test \"holds\" do quack [let ok be for-all(gen-string(), [s] -> len(s) <= 10, 20)]]\n",
+            "quack [test \"breaks\" do quack [let ok be for-all(gen-int(), [n] -> n < 7)]]",
+        ));
+        assert_eq!(results[0].outcome, TestOutcome::Passed);
+        assert_eq!(results[0].coverage.properties, vec![(1, 20)]);
+        match &results[1].outcome {
+            TestOutcome::Failed(e) => assert!(e.contains("for input 7"), "unexpected error: {}", e),
+            other => panic!("expected failure, got {:?}", other),
+        }
+    }
+
+    #[test]
+    fn test_assertions_count_as_honks() {
+        let results = run(concat!(
+            "quack [test \"passes\" do quack [let ok be assert-eq(1 + 1, 2)]]\n",
+            "quack [test \"fails\" do quack [let ok be assert-eq(list(1), list(2))]]",
+        ));
+        assert_eq!(results[0].outcome, TestOutcome::Passed);
+        assert_eq!(results[0].coverage.honks, vec![1]);
+        match &results[1].outcome {
+            TestOutcome::Failed(e) => assert!(e.contains("at [0]: expected 2, got 1"), "unexpected error: {}", e),
+            other => panic!("expected failure, got {:?}", other),
+        }
+    }
+
+    #[test]
+    fn test_sleep_uses_a_fresh_virtual_clock_per_test() {
+        let started = std::time::Instant::now();
+        let results = run(concat!(
+            "quack [test \"long nap\" do quack [let x be sleep(3600000)] quack [honk now() == 3600000]]\n",
+            "quack [test \"fresh clock\" do quack [honk now() == 0]]",
+        ));
+        assert!(results.iter().all(|r| r.outcome == TestOutcome::Passed), "{:?}", results);
+        assert!(started.elapsed() < std::time::Duration::from_secs(5));
+    }
+
+    #[test]
+    fn test_random_is_the_same_in_every_test() {
+        let results = run(concat!(
+            "quack [let first be random()]\n",
+            "quack [test \"one\" do quack [honk random() == first]]\n",
+            "quack [test \"two\" do quack [honk random() == first]]",
+        ));
+        assert!(results.iter().all(|r| r.outcome == TestOutcome::Passed), "{:?}", results);
+    }
+
+    #[test]
+    fn test_setup_error_is_reported() {
+        let blocks = Parser::new(lex("quack [let x be nope]").unwrap()).parse().unwrap();
+        assert!(run_tests(&blocks, vec![]).is_err());
+    }
+}
@@ -0,0 +1,127 @@
+// `goose upgrade-syntax` - rewrite deprecated syntax into what's current
+//
+// The parser notes every piece of deprecated syntax it meets (see
+// `version::Deprecation`) along with where it starts. Upgrading writes the
+// file back out token by token - each with its trivia and its exact source
+// text - swapping in the replacement for each deprecated token and moving the
+// `[duck version "..."]` pragma up to the current version if it has one, so
+// comments, spacing and everything else stay exactly as they were written.
+
+use std::collections::HashMap;
+
+use crate::lexer::{lex, Token};
+use crate::parser::Parser;
+use crate::version::Version;
+
+/// One rewrite made to a file
+#[derive(Debug, Clone, PartialEq)]
+pub struct Change {
+    pub line: usize,
+    pub old: String,
+    pub new: String,
+}
+
+/// A file's source with its old syntax rewritten, and what changed
+#[derive(Debug, Clone, PartialEq)]
+pub struct Upgrade {
+    pub source: String,
+    pub changes: Vec<Change>,
+}
+
+/// Rewrite every piece of deprecated syntax in `source`, whatever version the
+/// file was written for. The file has to parse first - the goose doesn't
+/// rewrite what it can't read.
+pub fn upgrade_syntax(source: &str) -> Result<Upgrade, String> {
+    let tokens = lex(source)?;
+    let mut parser = Parser::new(tokens.clone());
+    parser.parse().map_err(|errors| errors.join("\n"))?;
+
+    // What replaces each rewritten token, by where its text starts
+    let mut edits: HashMap<usize, String> = HashMap::new();
+    let mut changes = Vec::new();
+    for &(deprecation, line, column) in parser.deprecations() {
+        let Some(token) = tokens.iter().find(|token| token.line == line && token.column == column) else {
+            continue;
+        };
+        let (old, new) = deprecation.replacement();
+        edits.insert(token.span.start, new.to_string());
+        changes.push(Change { line, old: old.to_string(), new: new.to_string() });
+    }
+
+    let pragma = parser.version_span().filter(|_| parser.version() < Version::current());
+    if let Some(token) = pragma.and_then(|span| tokens.iter().find(|token| token.span == span)) {
+        let (old, new) = (source[token.span.clone()].to_string(), format!("\"{}\"", Version::current()));
+        edits.insert(token.span.start, new.clone());
+        changes.push(Change { line: token.line, old, new });
+    }
+
+    changes.sort_by_key(|change| change.line);
+    Ok(Upgrade { source: rebuild(source, &tokens, &edits), changes })
+}
+
+/// Write the tokens back out with their trivia, swapping in the edits and
+/// adding a space on either side of a replacement where it would otherwise
+/// run into its neighbours (`a&&b` becomes `a and b`)
+fn rebuild(source: &str, tokens: &[Token], edits: &HashMap<usize, String>) -> String {
+    let mut out = String::with_capacity(source.len());
+    for token in tokens {
+        for trivia in &token.leading_trivia {
+            out.push_str(&trivia.text);
+        }
+        let Some(text) = edits.get(&token.span.start) else {
+            out.push_str(&source[token.span.clone()]);
+            continue;
+        };
+        let wordy = text.starts_with(|c: char| c.is_alphanumeric());
+        if wordy && out.chars().last().is_some_and(|c| !c.is_whitespace()) {
+            out.push(' ');
+        }
+        out.push_str(text);
+        if wordy && source[token.span.end..].chars().next().is_some_and(|c| !c.is_whitespace()) {
+            out.push(' ');
+        }
+    }
+    out
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_upgrade_rewrites_old_syntax_and_keeps_the_rest() {
+        let source = concat!(
+            "[duck version \"0.2\"]\n",
+            "-- ready when both are\n",
+            "quack [let ready be  true && false]   -- spacing stays\n",
+            "quack [print f\"{ready||(1 > 2)}\"]\n",
+        );
+        let upgrade = upgrade_syntax(source).unwrap();
+        assert_eq!(
+            upgrade.source,
+            format!(
+                concat!(
+                    "[duck version \"{}\"]\n",
+                    "-- ready when both are\n",
+                    "quack [let ready be  true and false]   -- spacing stays\n",
+                    "quack [print f\"{{ready or (1 > 2)}}\"]\n",
+                ),
+                Version::current()
+            )
+        );
+        let lines: Vec<usize> = upgrade.changes.iter().map(|change| change.line).collect();
+        assert_eq!(lines, [1, 3, 4]);
+
+        let current = upgrade_syntax(&upgrade.source).unwrap();
+        assert!(current.changes.is_empty() && current.source == upgrade.source);
+        assert!(upgrade_syntax("quack [print (]").is_err());
+    }
+
+    #[test]
+    fn test_upgrade_keeps_strings_as_written() {
+        let source = "quack [print \"two\nlines\" == \"x\\\"y\\n\" ||  f\"{1&&2}\\{\"]\n";
+        let upgrade = upgrade_syntax(source).unwrap();
+        assert_eq!(upgrade.source, "quack [print \"two\nlines\" == \"x\\\"y\\n\" or  f\"{1 and 2}\\{\"]\n");
+        assert_eq!(upgrade.changes.len(), 2);
+    }
+}
@@ -1,11 +1,17 @@
 // Runtime value types for Duck language
 
-use std::cell::RefCell;
+use std::cell::{Cell, RefCell};
+use std::cmp::Ordering;
 use std::collections::HashMap;
 use std::fmt;
+use std::ops::Deref;
 use std::rc::Rc;
+use std::sync::Arc;
 
-use crate::ast::{Block, Expr, Statement};
+use crate::ast::{Block, Expr};
+use crate::builtins::Builtin;
+use crate::decimal::Decimal;
+use crate::fraction::Fraction;
 
 /// Environment snapshot for closures - captures variables at function definition time
 #[derive(Debug, Clone)]
@@ -53,56 +59,159 @@ impl PartialEq for Closure {
     }
 }
 
+/// The contents of a list, struct or map, shared by every value that holds
+/// it, and whether `freeze` has made them immutable. Derefs to the `RefCell`,
+/// so `borrow()` and `borrow_mut()` work as they would on a plain one.
+#[derive(Debug, Default)]
+pub struct Shared<T> {
+    contents: RefCell<T>,
+    frozen: Cell<bool>,
+}
+
+impl<T> Shared<T> {
+    pub fn new(contents: T) -> Self {
+        Shared { contents: RefCell::new(contents), frozen: Cell::new(false) }
+    }
+}
+
+impl<T> Deref for Shared<T> {
+    type Target = RefCell<T>;
+
+    fn deref(&self) -> &RefCell<T> {
+        &self.contents
+    }
+}
+
+/// A map key. Only strings, numbers and booleans can be keys; numbers are
+/// stored by their bits (with -0 folded into 0) so they can be hashed.
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+pub enum MapKey {
+    String(Arc<str>),
+    Number(u64),
+    Boolean(bool),
+}
+
+impl MapKey {
+    pub fn from_value(value: &Value) -> Result<MapKey, String> {
+        match value {
+            Value::String(s) => Ok(MapKey::String(s.clone())),
+            Value::Number(n) if n.is_nan() => Err("NaN can't be a map key".to_string()),
+            Value::Number(n) => Ok(MapKey::Number(if *n == 0.0 { 0.0_f64 } else { *n }.to_bits())),
+            Value::Boolean(b) => Ok(MapKey::Boolean(*b)),
+            other => Err(format!("map keys must be strings, numbers or booleans, not {}", other.type_name())),
+        }
+    }
+
+    pub fn to_value(&self) -> Value {
+        match self {
+            MapKey::String(s) => Value::String(s.clone()),
+            MapKey::Number(bits) => Value::Number(f64::from_bits(*bits)),
+            MapKey::Boolean(b) => Value::Boolean(*b),
+        }
+    }
+}
+
+/// A number, decimal or fraction as an exact fraction, if it can be one
+/// (infinity, NaN and enormous numbers can't)
+pub fn exact_fraction(value: &Value) -> Option<Result<Fraction, String>> {
+    match value {
+        Value::Number(n) => Some(Fraction::from_f64(*n)),
+        Value::Decimal(d) => Some(Fraction::from_decimal(*d)),
+        Value::Fraction(f) => Some(Ok(*f)),
+        _ => None,
+    }
+}
+
+/// Order a mix of numbers, decimals and fractions by value, exactly where
+/// possible. NaN sorts after everything, as it does among numbers.
+fn compare_mixed_numbers(a: &Value, b: &Value) -> Ordering {
+    if let (Some(Ok(x)), Some(Ok(y))) = (exact_fraction(a), exact_fraction(b)) {
+        return x.cmp(&y);
+    }
+    let approximate = |value: &Value| match value {
+        Value::Number(n) => *n,
+        Value::Decimal(d) => d.to_f64(),
+        Value::Fraction(f) => f.to_f64(),
+        _ => f64::NAN,
+    };
+    match (approximate(a), approximate(b)) {
+        (x, y) if x.is_nan() && y.is_nan() => Ordering::Equal,
+        (x, _) if x.is_nan() => Ordering::Greater,
+        (_, y) if y.is_nan() => Ordering::Less,
+        (x, y) => x.partial_cmp(&y).unwrap_or(Ordering::Equal),
+    }
+}
+
+/// A map's entries in a predictable order (keys sorted with `Value::compare`)
+pub fn sorted_entries(map: &HashMap<MapKey, Value>) -> Vec<(Value, Value)> {
+    let mut entries: Vec<(Value, Value)> = map.iter().map(|(k, v)| (k.to_value(), v.clone())).collect();
+    entries.sort_by(|a, b| a.0.compare(&b.0));
+    entries
+}
+
 /// Runtime values in Duck language
 #[derive(Debug, Clone)]
 pub enum Value {
     /// A floating-point number (all numbers in Duck are f64)
     Number(f64),
 
+    /// An exact decimal, for money and anything else that can't be off by a hair
+    Decimal(Decimal),
+
+    /// An exact fraction in lowest terms, like 1/3
+    Fraction(Fraction),
+
     /// A UTF-8 string
-    String(String),
+    String(Arc<str>),
 
     /// A boolean value
     Boolean(bool),
 
     /// A list of values (mutable, reference-counted)
-    List(Rc<RefCell<Vec<Value>>>),
+    List(Rc<Shared<Vec<Value>>>),
+
+    /// A map from keys to values (mutable, reference-counted)
+    Map(Rc<Shared<HashMap<MapKey, Value>>>),
 
     /// A struct instance with named fields (mutable, reference-counted)
     Struct {
         name: String,
-        fields: Rc<RefCell<HashMap<String, Value>>>,
+        fields: Rc<Shared<HashMap<String, Value>>>,
     },
 
-    /// A user-defined function
+    /// A user-defined function. `rest` names the list that collects any
+    /// arguments beyond `params`, if the function takes one.
     Function {
         name: String,
         params: Vec<String>,
-        body: Vec<Block>,
+        rest: Option<String>,
+        body: Arc<[Block]>,
         closure: Closure,
     },
 
     /// A lambda/anonymous function (expression-bodied)
     Lambda {
         params: Vec<String>,
-        body: Box<Expr>,
+        body: Arc<Expr>,
         closure: Closure,
     },
 
     /// A block lambda (statement-bodied)
     BlockLambda {
         params: Vec<String>,
-        body: Vec<Statement>,
+        body: Arc<[Block]>,
         closure: Closure,
     },
 
-    /// A built-in function (identified by name)
-    BuiltinFunction(String),
+    /// A built-in function, already looked up
+    BuiltinFunction(Builtin),
 
-    /// A struct type definition (not an instance, but the type itself)
+    /// A struct type definition (not an instance, but the type itself).
+    /// Methods are shared, so ones defined later reach every copy of the type.
     StructType {
         name: String,
         fields: Vec<String>,
+        methods: Rc<RefCell<HashMap<String, Value>>>,
     },
 
     /// The null value
@@ -114,9 +223,12 @@ impl Value {
     pub fn type_name(&self) -> &str {
         match self {
             Value::Number(_) => "number",
+            Value::Decimal(_) => "decimal",
+            Value::Fraction(_) => "fraction",
             Value::String(_) => "string",
             Value::Boolean(_) => "boolean",
             Value::List(_) => "list",
+            Value::Map(_) => "map",
             Value::Struct { name, .. } => name,
             Value::Function { .. } => "function",
             Value::Lambda { .. } => "lambda",
@@ -128,14 +240,17 @@ impl Value {
     }
 
     /// Determine if this value is truthy
-    /// In Duck: false, null, 0, "", and empty lists are falsy; everything else is truthy
+    /// In Duck: false, null, 0, "", and empty lists and maps are falsy; everything else is truthy
     pub fn is_truthy(&self) -> bool {
         match self {
             Value::Boolean(b) => *b,
             Value::Null => false,
             Value::Number(n) => *n != 0.0,
+            Value::Decimal(d) => !d.is_zero(),
+            Value::Fraction(f) => !f.is_zero(),
             Value::String(s) => !s.is_empty(),
             Value::List(list) => !list.borrow().is_empty(),
+            Value::Map(map) => !map.borrow().is_empty(),
             // Functions, structs, and struct types are always truthy
             Value::Function { .. } => true,
             Value::Lambda { .. } => true,
@@ -176,7 +291,7 @@ impl Value {
     }
 
     /// Try to get this value as a list
-    pub fn as_list(&self) -> Option<Rc<RefCell<Vec<Value>>>> {
+    pub fn as_list(&self) -> Option<Rc<Shared<Vec<Value>>>> {
         match self {
             Value::List(list) => Some(Rc::clone(list)),
             _ => None,
@@ -185,14 +300,19 @@ impl Value {
 
     /// Create a new list value
     pub fn new_list(values: Vec<Value>) -> Value {
-        Value::List(Rc::new(RefCell::new(values)))
+        Value::List(Rc::new(Shared::new(values)))
+    }
+
+    /// Create a new map value
+    pub fn new_map(entries: HashMap<MapKey, Value>) -> Value {
+        Value::Map(Rc::new(Shared::new(entries)))
     }
 
     /// Create a new struct instance
     pub fn new_struct(name: String, fields: HashMap<String, Value>) -> Value {
         Value::Struct {
             name,
-            fields: Rc::new(RefCell::new(fields)),
+            fields: Rc::new(Shared::new(fields)),
         }
     }
 
@@ -200,48 +320,256 @@ impl Value {
     pub fn new_function(
         name: String,
         params: Vec<String>,
-        body: Vec<Block>,
+        rest: Option<String>,
+        body: Arc<[Block]>,
         closure: Closure,
     ) -> Value {
         Value::Function {
             name,
             params,
+            rest,
             body,
             closure,
         }
     }
 
     /// Create a new lambda value
-    pub fn new_lambda(params: Vec<String>, body: Expr, closure: Closure) -> Value {
+    pub fn new_lambda(params: Vec<String>, body: Arc<Expr>, closure: Closure) -> Value {
         Value::Lambda {
             params,
-            body: Box::new(body),
+            body,
             closure,
         }
     }
 
-    /// Deep clone a value, creating new Rc/RefCell wrappers for mutable types
+    /// Deep clone a value, creating new Rc/RefCell wrappers for mutable types.
+    /// Frozen lists, structs and maps stay frozen in the copy.
     pub fn deep_clone(&self) -> Value {
+        self.deep_clone_with(&mut HashMap::new())
+    }
+
+    /// Deep clone using a table of already-copied lists/structs/maps (keyed by
+    /// allocation), so data shared between values stays shared in the copy
+    pub fn deep_clone_with(&self, copied: &mut HashMap<usize, Value>) -> Value {
+        self.clone_deep(copied, true)
+    }
+
+    /// Deep copy for the `copy` builtin: like `deep_clone`, but the copy is
+    /// never frozen, so it can be changed even if the original can't
+    pub fn copy(&self) -> Value {
+        self.clone_deep(&mut HashMap::new(), false)
+    }
+
+    fn clone_deep(&self, copied: &mut HashMap<usize, Value>, keep_frozen: bool) -> Value {
         match self {
             Value::List(list) => {
-                let cloned: Vec<Value> = list.borrow().iter().map(|v| v.deep_clone()).collect();
-                Value::List(Rc::new(RefCell::new(cloned)))
+                let key = Rc::as_ptr(list) as usize;
+                if let Some(existing) = copied.get(&key) {
+                    return existing.clone();
+                }
+                let new_list = Rc::new(Shared::new(Vec::new()));
+                copied.insert(key, Value::List(Rc::clone(&new_list)));
+                let items: Vec<Value> = list.borrow().iter().map(|v| v.clone_deep(copied, keep_frozen)).collect();
+                *new_list.borrow_mut() = items;
+                let copy = Value::List(new_list);
+                if keep_frozen && self.is_frozen() {
+                    copy.freeze();
+                }
+                copy
+            }
+            Value::Map(map) => {
+                let key = Rc::as_ptr(map) as usize;
+                if let Some(existing) = copied.get(&key) {
+                    return existing.clone();
+                }
+                let new_map = Rc::new(Shared::new(HashMap::new()));
+                copied.insert(key, Value::Map(Rc::clone(&new_map)));
+                let entries: HashMap<MapKey, Value> = map
+                    .borrow()
+                    .iter()
+                    .map(|(k, v)| (k.clone(), v.clone_deep(copied, keep_frozen)))
+                    .collect();
+                *new_map.borrow_mut() = entries;
+                let copy = Value::Map(new_map);
+                if keep_frozen && self.is_frozen() {
+                    copy.freeze();
+                }
+                copy
             }
             Value::Struct { name, fields } => {
+                let key = Rc::as_ptr(fields) as usize;
+                if let Some(existing) = copied.get(&key) {
+                    return existing.clone();
+                }
+                let new_fields = Rc::new(Shared::new(HashMap::new()));
+                copied.insert(key, Value::Struct { name: name.clone(), fields: Rc::clone(&new_fields) });
                 let cloned: HashMap<String, Value> = fields
                     .borrow()
                     .iter()
-                    .map(|(k, v)| (k.clone(), v.deep_clone()))
+                    .map(|(k, v)| (k.clone(), v.clone_deep(copied, keep_frozen)))
                     .collect();
-                Value::Struct {
+                *new_fields.borrow_mut() = cloned;
+                let copy = Value::Struct {
                     name: name.clone(),
-                    fields: Rc::new(RefCell::new(cloned)),
+                    fields: new_fields,
+                };
+                if keep_frozen && self.is_frozen() {
+                    copy.freeze();
                 }
+                copy
             }
             // For other types, regular clone is fine
             other => other.clone(),
         }
     }
+
+    /// Allocation address of a list, struct or map, used as its identity.
+    /// Two values with the same identity are the same list, struct or map.
+    pub fn identity(&self) -> Option<usize> {
+        match self {
+            Value::List(list) => Some(Rc::as_ptr(list) as usize),
+            Value::Map(map) => Some(Rc::as_ptr(map) as usize),
+            Value::Struct { fields, .. } => Some(Rc::as_ptr(fields) as usize),
+            _ => None,
+        }
+    }
+
+    /// Make a list, struct or map, and everything inside it, immutable.
+    /// Other values are already immutable, so this does nothing to them.
+    pub fn freeze(&self) {
+        let frozen = match self {
+            Value::List(list) => &list.frozen,
+            Value::Struct { fields, .. } => &fields.frozen,
+            Value::Map(map) => &map.frozen,
+            _ => return,
+        };
+        if frozen.replace(true) {
+            return;
+        }
+
+        match self {
+            Value::List(list) => list.borrow().iter().for_each(Value::freeze),
+            Value::Struct { fields, .. } => fields.borrow().values().for_each(Value::freeze),
+            Value::Map(map) => map.borrow().values().for_each(Value::freeze),
+            _ => {}
+        }
+    }
+
+    /// Whether this list, struct or map has been frozen
+    pub fn is_frozen(&self) -> bool {
+        match self {
+            Value::List(list) => list.frozen.get(),
+            Value::Struct { fields, .. } => fields.frozen.get(),
+            Value::Map(map) => map.frozen.get(),
+            _ => false,
+        }
+    }
+
+    /// Error if this list, struct or map is frozen; call before changing it
+    pub fn ensure_mutable(&self) -> Result<(), String> {
+        if self.is_frozen() {
+            Err(format!("change a frozen {}", self.type_name()))
+        } else {
+            Ok(())
+        }
+    }
+
+    /// Where this value's type sits in the cross-type order used by `compare`
+    fn type_rank(&self) -> u8 {
+        match self {
+            Value::Number(_) | Value::Decimal(_) | Value::Fraction(_) => 0,
+            Value::String(_) => 1,
+            Value::List(_) => 2,
+            Value::Map(_) => 3,
+            Value::Struct { .. } => 4,
+            Value::Boolean(_) => 5,
+            Value::StructType { .. } => 6,
+            Value::Function { .. } => 7,
+            Value::Lambda { .. } | Value::BlockLambda { .. } => 8,
+            Value::BuiltinFunction(_) => 9,
+            Value::Null => 10,
+        }
+    }
+
+    /// Total order over all values, consistent with `==`.
+    /// Numbers, decimals and fractions compare by value with each other.
+    /// Different types order as numbers < strings < lists < maps < structs <
+    /// booleans < struct types < functions < lambdas < builtins < null. Within a
+    /// type, lists compare element by element, maps entry by entry (in key
+    /// order), structs by name then by field (in key order), and NaN sorts after
+    /// every other number.
+    pub fn compare(&self, other: &Value) -> Ordering {
+        match (self, other) {
+            (Value::Number(a), Value::Number(b)) => match (a.is_nan(), b.is_nan()) {
+                (true, true) => Ordering::Equal,
+                (true, false) => Ordering::Greater,
+                (false, true) => Ordering::Less,
+                (false, false) => a.partial_cmp(b).unwrap_or(Ordering::Equal),
+            },
+            (Value::Decimal(a), Value::Decimal(b)) => a.cmp(b),
+            (Value::Fraction(a), Value::Fraction(b)) => a.cmp(b),
+            (
+                Value::Number(_) | Value::Decimal(_) | Value::Fraction(_),
+                Value::Number(_) | Value::Decimal(_) | Value::Fraction(_),
+            ) => compare_mixed_numbers(self, other),
+            (Value::String(a), Value::String(b)) => a.cmp(b),
+            (Value::Boolean(a), Value::Boolean(b)) => a.cmp(b),
+            (Value::List(a), Value::List(b)) => {
+                if Rc::ptr_eq(a, b) {
+                    return Ordering::Equal;
+                }
+                let (a, b) = (a.borrow(), b.borrow());
+                a.iter()
+                    .zip(b.iter())
+                    .map(|(x, y)| x.compare(y))
+                    .find(|order| order.is_ne())
+                    .unwrap_or_else(|| a.len().cmp(&b.len()))
+            }
+            (Value::Map(a), Value::Map(b)) => {
+                if Rc::ptr_eq(a, b) {
+                    return Ordering::Equal;
+                }
+                let (a, b) = (sorted_entries(&a.borrow()), sorted_entries(&b.borrow()));
+                a.iter()
+                    .zip(b.iter())
+                    .map(|((k1, v1), (k2, v2))| k1.compare(k2).then_with(|| v1.compare(v2)))
+                    .find(|order| order.is_ne())
+                    .unwrap_or_else(|| a.len().cmp(&b.len()))
+            }
+            (Value::Struct { name: n1, fields: f1 }, Value::Struct { name: n2, fields: f2 }) => {
+                if n1 != n2 {
+                    return n1.cmp(n2);
+                }
+                if Rc::ptr_eq(f1, f2) {
+                    return Ordering::Equal;
+                }
+                let (f1, f2) = (f1.borrow(), f2.borrow());
+                let mut e1: Vec<_> = f1.iter().collect();
+                let mut e2: Vec<_> = f2.iter().collect();
+                e1.sort_by(|x, y| x.0.cmp(y.0));
+                e2.sort_by(|x, y| x.0.cmp(y.0));
+                e1.iter()
+                    .zip(e2.iter())
+                    .map(|((k1, v1), (k2, v2))| k1.cmp(k2).then_with(|| v1.compare(v2)))
+                    .find(|order| order.is_ne())
+                    .unwrap_or_else(|| e1.len().cmp(&e2.len()))
+            }
+            (Value::StructType { name: n1, fields: f1, .. }, Value::StructType { name: n2, fields: f2, .. }) => {
+                n1.cmp(n2).then_with(|| f1.cmp(f2))
+            }
+            (
+                Value::Function { name: n1, params: p1, rest: r1, .. },
+                Value::Function { name: n2, params: p2, rest: r2, .. },
+            ) => n1.cmp(n2).then_with(|| p1.cmp(p2)).then_with(|| r1.cmp(r2)),
+            (Value::Lambda { params: p1, .. }, Value::Lambda { params: p2, .. })
+            | (Value::BlockLambda { params: p1, .. }, Value::BlockLambda { params: p2, .. }) => p1.cmp(p2),
+            // Expression lambdas before block lambdas
+            (Value::Lambda { .. }, Value::BlockLambda { .. }) => Ordering::Less,
+            (Value::BlockLambda { .. }, Value::Lambda { .. }) => Ordering::Greater,
+            (Value::BuiltinFunction(a), Value::BuiltinFunction(b)) => a.name().cmp(b.name()),
+            _ => self.type_rank().cmp(&other.type_rank()),
+        }
+    }
 }
 
 impl fmt::Display for Value {
@@ -255,6 +583,8 @@ impl fmt::Display for Value {
                     write!(f, "{}", n)
                 }
             }
+            Value::Decimal(d) => write!(f, "{}", d),
+            Value::Fraction(fraction) => write!(f, "{}", fraction),
             Value::String(s) => write!(f, "{}", s),
             Value::Boolean(b) => write!(f, "{}", b),
             Value::List(list) => {
@@ -273,6 +603,21 @@ impl fmt::Display for Value {
                 }
                 write!(f, "]")
             }
+            Value::Map(map) => {
+                // Written the way a map literal is, with keys in order
+                let quoted = |item: &Value| match item {
+                    Value::String(s) => format!("\"{}\"", s),
+                    other => other.to_string(),
+                };
+                write!(f, "map(")?;
+                for (i, (key, value)) in sorted_entries(&map.borrow()).iter().enumerate() {
+                    if i > 0 {
+                        write!(f, ", ")?;
+                    }
+                    write!(f, "{} -> {}", quoted(key), quoted(value))?;
+                }
+                write!(f, ")")
+            }
             Value::Struct { name, fields } => {
                 let field_map = fields.borrow();
                 write!(f, "{} {{ ", name)?;
@@ -284,8 +629,10 @@ impl fmt::Display for Value {
                 }
                 write!(f, " }}")
             }
-            Value::Function { name, params, .. } => {
-                write!(f, "<function {}({})>", name, params.join(", "))
+            Value::Function { name, params, rest, .. } => {
+                let rest = rest.as_ref().map(|rest| format!("{}...", rest));
+                let shown: Vec<&str> = params.iter().map(String::as_str).chain(rest.as_deref()).collect();
+                write!(f, "<function {}({})>", name, shown.join(", "))
             }
             Value::Lambda { params, .. } => {
                 write!(f, "<lambda ({})>", params.join(", "))
@@ -293,8 +640,8 @@ impl fmt::Display for Value {
             Value::BlockLambda { params, .. } => {
                 write!(f, "<lambda ({})>", params.join(", "))
             }
-            Value::BuiltinFunction(name) => write!(f, "<builtin {}>", name),
-            Value::StructType { name, fields } => {
+            Value::BuiltinFunction(builtin) => write!(f, "<builtin {}>", builtin.name()),
+            Value::StructType { name, fields, .. } => {
                 write!(f, "<struct {} {{ {} }}>", name, fields.join(", "))
             }
             Value::Null => write!(f, "null"),
@@ -313,6 +660,8 @@ impl PartialEq for Value {
                     a == b
                 }
             }
+            (Value::Decimal(_) | Value::Fraction(_), Value::Number(_) | Value::Decimal(_) | Value::Fraction(_))
+            | (Value::Number(_), Value::Decimal(_) | Value::Fraction(_)) => self.compare(other) == Ordering::Equal,
             (Value::String(a), Value::String(b)) => a == b,
             (Value::Boolean(a), Value::Boolean(b)) => a == b,
             (Value::List(a), Value::List(b)) => {
@@ -329,6 +678,13 @@ impl PartialEq for Value {
                         .zip(b_borrowed.iter())
                         .all(|(x, y)| x == y)
             }
+            (Value::Map(a), Value::Map(b)) => {
+                if Rc::ptr_eq(a, b) {
+                    return true;
+                }
+                let (a, b) = (a.borrow(), b.borrow());
+                a.len() == b.len() && a.iter().all(|(k, v)| b.get(k).is_some_and(|v2| v == v2))
+            }
             (
                 Value::Struct {
                     name: n1,
@@ -360,16 +716,18 @@ impl PartialEq for Value {
                 Value::Function {
                     name: n1,
                     params: p1,
+                    rest: r1,
                     ..
                 },
                 Value::Function {
                     name: n2,
                     params: p2,
+                    rest: r2,
                     ..
                 },
             ) => {
                 // Functions are equal if they have the same name and parameters
-                n1 == n2 && p1 == p2
+                n1 == n2 && p1 == p2 && r1 == r2
             }
             (Value::Lambda { params: p1, .. }, Value::Lambda { params: p2, .. }) => {
                 // Lambdas with same parameter lists are considered equal
@@ -384,10 +742,12 @@ impl PartialEq for Value {
                 Value::StructType {
                     name: n1,
                     fields: f1,
+                    ..
                 },
                 Value::StructType {
                     name: n2,
                     fields: f2,
+                    ..
                 },
             ) => n1 == n2 && f1 == f2,
             (Value::Null, Value::Null) => true,
@@ -407,7 +767,7 @@ mod tests {
     #[test]
     fn test_value_type_names() {
         assert_eq!(Value::Number(42.0).type_name(), "number");
-        assert_eq!(Value::String("hello".to_string()).type_name(), "string");
+        assert_eq!(Value::String("hello".into()).type_name(), "string");
         assert_eq!(Value::Boolean(true).type_name(), "boolean");
         assert_eq!(Value::new_list(vec![]).type_name(), "list");
         assert_eq!(Value::Null.type_name(), "null");
@@ -419,14 +779,14 @@ mod tests {
         assert!(Value::Boolean(true).is_truthy());
         assert!(Value::Number(1.0).is_truthy());
         assert!(Value::Number(-1.0).is_truthy());
-        assert!(Value::String("hello".to_string()).is_truthy());
+        assert!(Value::String("hello".into()).is_truthy());
         assert!(Value::new_list(vec![Value::Number(1.0)]).is_truthy());
 
         // Falsy values
         assert!(!Value::Boolean(false).is_truthy());
         assert!(!Value::Null.is_truthy());
         assert!(!Value::Number(0.0).is_truthy());
-        assert!(!Value::String("".to_string()).is_truthy());
+        assert!(!Value::String("".into()).is_truthy());
         assert!(!Value::new_list(vec![]).is_truthy());
     }
 
@@ -438,12 +798,12 @@ mod tests {
 
         // Strings
         assert_eq!(
-            Value::String("hello".to_string()),
-            Value::String("hello".to_string())
+            Value::String("hello".into()),
+            Value::String("hello".into())
         );
         assert_ne!(
-            Value::String("hello".to_string()),
-            Value::String("world".to_string())
+            Value::String("hello".into()),
+            Value::String("world".into())
         );
 
         // Booleans
@@ -461,15 +821,16 @@ mod tests {
         assert_ne!(list1, list3);
 
         // Different types
-        assert_ne!(Value::Number(1.0), Value::String("1".to_string()));
+        assert_ne!(Value::Number(1.0), Value::String("1".into()));
         assert_ne!(Value::Boolean(false), Value::Null);
     }
 
     #[test]
+    #[allow(clippy::approx_constant)]
     fn test_value_display() {
         assert_eq!(format!("{}", Value::Number(42.0)), "42");
         assert_eq!(format!("{}", Value::Number(3.14)), "3.14");
-        assert_eq!(format!("{}", Value::String("hello".to_string())), "hello");
+        assert_eq!(format!("{}", Value::String("hello".into())), "hello");
         assert_eq!(format!("{}", Value::Boolean(true)), "true");
         assert_eq!(format!("{}", Value::Null), "null");
 
@@ -501,4 +862,80 @@ mod tests {
             assert_eq!(inner.borrow().len(), 2);
         }
     }
+
+    #[test]
+    fn test_deep_clone_keeps_shared_lists_shared() {
+        let shared = Value::new_list(vec![Value::Number(1.0)]);
+        let outer = Value::new_list(vec![shared.clone(), shared]);
+        let cloned = outer.deep_clone();
+
+        let Value::List(items) = &cloned else { panic!("expected list") };
+        let (Value::List(first), Value::List(second)) = (&items.borrow()[0], &items.borrow()[1]) else {
+            panic!("expected nested lists");
+        };
+        assert!(Rc::ptr_eq(first, second));
+    }
+
+    #[test]
+    fn test_compare_is_a_total_order_across_types() {
+        let point = |x: f64| Value::new_struct("point".to_string(), HashMap::from([("x".to_string(), Value::Number(x))]));
+        let ordered = vec![
+            Value::Number(-1.0),
+            Value::Number(2.0),
+            Value::Number(f64::NAN),
+            Value::String("a".into()),
+            Value::new_list(vec![Value::Number(1.0)]),
+            Value::new_list(vec![Value::Number(1.0), Value::Number(0.0)]),
+            point(1.0),
+            point(2.0),
+            Value::Boolean(false),
+            Value::Null,
+        ];
+        for (i, a) in ordered.iter().enumerate() {
+            for (j, b) in ordered.iter().enumerate() {
+                assert_eq!(a.compare(b), i.cmp(&j), "comparing {} with {}", a, b);
+            }
+        }
+        assert_eq!(point(1.0).compare(&point(1.0)), Ordering::Equal);
+    }
+
+    #[test]
+    fn test_freeze_is_deep_and_survives_deep_clone() {
+        let inner = Value::new_list(vec![Value::Number(1.0)]);
+        let outer = Value::new_list(vec![inner.clone()]);
+        assert!(outer.ensure_mutable().is_ok());
+
+        outer.freeze();
+        assert!(outer.is_frozen());
+        assert!(inner.is_frozen());
+        assert!(inner.ensure_mutable().unwrap_err().contains("frozen"));
+        assert!(outer.deep_clone().is_frozen());
+
+        // A list that merely looks the same isn't frozen
+        assert!(!Value::new_list(vec![Value::Number(1.0)]).is_frozen());
+        assert!(!Value::Number(1.0).is_frozen());
+    }
+
+    #[test]
+    fn test_map_keys_and_display() {
+        let mut entries = HashMap::new();
+        entries.insert(MapKey::from_value(&Value::String("b".into())).unwrap(), Value::Number(2.0));
+        entries.insert(MapKey::from_value(&Value::Number(-0.0)).unwrap(), Value::Boolean(true));
+        let map = Value::new_map(entries);
+        assert_eq!(map.to_string(), "map(0 -> true, \"b\" -> 2)");
+        // -0 and 0 are the same key
+        assert_eq!(MapKey::from_value(&Value::Number(0.0)), MapKey::from_value(&Value::Number(-0.0)));
+        assert!(MapKey::from_value(&Value::Number(f64::NAN)).is_err());
+        assert_eq!(map.deep_clone(), map);
+    }
+
+    #[test]
+    fn test_copy_is_separate_and_unfrozen() {
+        let original = Value::new_list(vec![Value::new_list(vec![Value::Number(1.0)])]);
+        original.freeze();
+        let copy = original.copy();
+        assert_eq!(copy, original);
+        assert!(!copy.is_frozen());
+        assert_ne!(copy.identity(), original.identity());
+    }
 }
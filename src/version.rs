@@ -0,0 +1,119 @@
+// Language versions, and the deprecations staged between them
+//
+// A file can say which version of Duck it was written for with a pragma at the
+// top: `[duck version "0.3"]`. Syntax on its way out is listed in `Deprecation`
+// with the version that deprecated it (and, once decided, the version that
+// removes it). Files written for an older version keep working quietly; newer
+// ones get a warning, and once the syntax is removed, an error. Files without a
+// pragma are treated as written for the current version.
+
+use std::fmt;
+use std::str::FromStr;
+
+/// A Duck language version: the major and minor parts of the goose's version
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord)]
+pub struct Version {
+    pub major: u32,
+    pub minor: u32,
+}
+
+impl Version {
+    pub const fn new(major: u32, minor: u32) -> Self {
+        Version { major, minor }
+    }
+
+    /// The version of Duck this goose speaks
+    pub fn current() -> Self {
+        env!("CARGO_PKG_VERSION").parse().expect("the crate version is a valid Duck version")
+    }
+}
+
+impl fmt::Display for Version {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "{}.{}", self.major, self.minor)
+    }
+}
+
+impl FromStr for Version {
+    type Err = String;
+
+    /// "0.4", or a full goose version like "0.4.2" (the patch part is ignored)
+    fn from_str(text: &str) -> Result<Self, String> {
+        let mut parts = text.split('.').map(|part| part.parse::<u32>());
+        match (parts.next(), parts.next(), parts.next(), parts.next()) {
+            (Some(Ok(major)), Some(Ok(minor)), None | Some(Ok(_)), None) => Ok(Version::new(major, minor)),
+            _ => Err(format!("'{}' isn't a Duck version - try something like \"0.4\"", text)),
+        }
+    }
+}
+
+/// Syntax that still works but is on its way out
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Deprecation {
+    /// `&&` instead of `and`
+    SymbolicAnd,
+    /// `||` instead of `or`
+    SymbolicOr,
+}
+
+impl Deprecation {
+    /// The version that started warning about it
+    pub fn since(self) -> Version {
+        match self {
+            Deprecation::SymbolicAnd | Deprecation::SymbolicOr => Version::new(0, 3),
+        }
+    }
+
+    /// The version that stops accepting it, once one has been picked
+    pub fn removed_in(self) -> Option<Version> {
+        match self {
+            Deprecation::SymbolicAnd | Deprecation::SymbolicOr => None,
+        }
+    }
+
+    /// The old spelling, and what to write instead
+    pub fn replacement(self) -> (&'static str, &'static str) {
+        match self {
+            Deprecation::SymbolicAnd => ("&&", "and"),
+            Deprecation::SymbolicOr => ("||", "or"),
+        }
+    }
+
+    /// What a file written for `declared` gets for using this: nothing if the
+    /// syntax was fine back then, a warning if it's deprecated, an error if it's gone
+    pub fn check(self, declared: Version) -> Result<Option<String>, String> {
+        let (old, new) = self.replacement();
+        match self.removed_in() {
+            Some(removed) if declared >= removed => Err(format!(
+                "'{}' was removed in Duck {}. Write '{}' - the goose has moved on, and so should you",
+                old, removed, new
+            )),
+            _ if declared < self.since() => Ok(None),
+            _ => Ok(Some(format!(
+                "'{}' works, but a proper duck says '{}'. It's deprecated since Duck {} - 'goose upgrade-syntax' can fix it.",
+                old, new, self.since()
+            ))),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_versions_parse_and_order() {
+        assert_eq!("0.4".parse::<Version>(), Ok(Version::new(0, 4)));
+        assert_eq!("0.3.1".parse::<Version>(), Ok(Version::new(0, 3)));
+        assert!("four".parse::<Version>().is_err() && "1".parse::<Version>().is_err() && "1.2.3.4".parse::<Version>().is_err());
+        assert!(Version::new(0, 10) > Version::new(0, 9));
+        assert_eq!(Version::current().to_string(), env!("CARGO_PKG_VERSION").rsplit_once('.').unwrap().0);
+    }
+
+    #[test]
+    fn test_deprecations_are_quiet_for_older_files() {
+        assert_eq!(Deprecation::SymbolicAnd.check(Version::new(0, 2)), Ok(None));
+        let warning = Deprecation::SymbolicOr.check(Version::current()).unwrap().unwrap();
+        assert!(warning.contains("'||' works, but a proper duck says 'or'"), "{}", warning);
+    }
+}
@@ -0,0 +1,529 @@
+// AST visitors - traverse and rewrite Duck programs
+//
+// Implement `Visitor` to inspect an AST or `VisitorMut` to rewrite one in place.
+// Override only the hooks you care about; every default hook calls the matching
+// `walk_*` function, which recurses into the node's children. Call the walk
+// function from your override to keep descending after handling a node.
+// Operators are the exception: walking one visits the operands underneath it
+// in order, but the operators nested inside it aren't visited on their own.
+
+use crate::ast::{AssignTarget, Block, Expr, MatchArm, Pattern, Statement, StringPart};
+use std::sync::Arc;
+
+/// Read-only traversal over blocks, statements, expressions, and patterns
+pub trait Visitor {
+    fn visit_block(&mut self, block: &Block) {
+        walk_block(self, block);
+    }
+
+    fn visit_statement(&mut self, stmt: &Statement) {
+        walk_statement(self, stmt);
+    }
+
+    fn visit_expr(&mut self, expr: &Expr) {
+        walk_expr(self, expr);
+    }
+
+    fn visit_pattern(&mut self, pattern: &Pattern) {
+        walk_pattern(self, pattern);
+    }
+}
+
+/// Visit every block in a program (or body) in order
+pub fn walk_blocks<V: Visitor + ?Sized>(visitor: &mut V, blocks: &[Block]) {
+    for block in blocks {
+        visitor.visit_block(block);
+    }
+}
+
+pub fn walk_block<V: Visitor + ?Sized>(visitor: &mut V, block: &Block) {
+    visitor.visit_statement(&block.statement);
+}
+
+pub fn walk_statement<V: Visitor + ?Sized>(visitor: &mut V, stmt: &Statement) {
+    match stmt {
+        Statement::Let { value, .. } => visitor.visit_expr(value),
+        Statement::LetPattern { pattern, value, .. } => {
+            visitor.visit_pattern(pattern);
+            visitor.visit_expr(value);
+        }
+        Statement::Assign { target, value } | Statement::CompoundAssign { target, amount: value, .. } => {
+            walk_assign_target(visitor, target);
+            visitor.visit_expr(value);
+        }
+        Statement::Expression(expr) | Statement::Print(expr) => visitor.visit_expr(expr),
+        Statement::Block(body)
+        | Statement::Test { body, .. }
+        | Statement::BeforeEach(body)
+        | Statement::AfterEach(body) => walk_blocks(visitor, body),
+        Statement::FunctionDef { body, .. } | Statement::MethodDef { body, .. } => walk_blocks(visitor, body),
+        Statement::If { condition, then_block, otherwise_block } => {
+            visitor.visit_expr(condition);
+            walk_blocks(visitor, then_block);
+            if let Some(otherwise) = otherwise_block {
+                walk_blocks(visitor, otherwise);
+            }
+        }
+        Statement::Match { value, arms } => {
+            visitor.visit_expr(value);
+            for arm in arms {
+                walk_match_arm(visitor, arm);
+            }
+        }
+        Statement::Repeat { count, body } => {
+            visitor.visit_expr(count);
+            walk_blocks(visitor, body);
+        }
+        Statement::While { condition, body } => {
+            visitor.visit_expr(condition);
+            walk_blocks(visitor, body);
+        }
+        Statement::ForEach { pattern, iterable, body } => {
+            visitor.visit_pattern(pattern);
+            visitor.visit_expr(iterable);
+            walk_blocks(visitor, body);
+        }
+        Statement::Return(value) => {
+            if let Some(value) = value {
+                visitor.visit_expr(value);
+            }
+        }
+        Statement::Honk { condition, message } => {
+            visitor.visit_expr(condition);
+            if let Some(message) = message {
+                visitor.visit_expr(message);
+            }
+        }
+        Statement::Push { list, value } => {
+            visitor.visit_expr(list);
+            visitor.visit_expr(value);
+        }
+        Statement::Attempt { try_block, rescue_block, .. } => {
+            walk_blocks(visitor, try_block);
+            walk_blocks(visitor, rescue_block);
+        }
+        Statement::StructDef { .. }
+        | Statement::Break
+        | Statement::Continue
+        | Statement::Migrate { .. } => {}
+    }
+}
+
+pub fn walk_assign_target<V: Visitor + ?Sized>(visitor: &mut V, target: &AssignTarget) {
+    match target {
+        AssignTarget::Variable(_) => {}
+        AssignTarget::Field { object, .. } => visitor.visit_expr(object),
+        AssignTarget::Index { object, index } => {
+            visitor.visit_expr(object);
+            visitor.visit_expr(index);
+        }
+    }
+}
+
+pub fn walk_match_arm<V: Visitor + ?Sized>(visitor: &mut V, arm: &MatchArm) {
+    visitor.visit_pattern(&arm.pattern);
+    if let Some(guard) = &arm.guard {
+        visitor.visit_expr(guard);
+    }
+    if let Some(expr) = &arm.expression {
+        visitor.visit_expr(expr);
+    }
+    if let Some(body) = &arm.body {
+        walk_blocks(visitor, body);
+    }
+}
+
+pub fn walk_expr<V: Visitor + ?Sized>(visitor: &mut V, expr: &Expr) {
+    match expr {
+        Expr::Literal(_) | Expr::Identifier(_) | Expr::Builtin(_) => {}
+        // Operator chains can be tens of thousands long, so walk them with a
+        // work list; only operands that aren't operators reach visit_expr
+        Expr::Binary { .. } | Expr::Unary { .. } => {
+            let mut pending = vec![expr];
+            while let Some(next) = pending.pop() {
+                match next {
+                    Expr::Binary { left, right, .. } => {
+                        pending.push(right);
+                        pending.push(left);
+                    }
+                    Expr::Unary { operand, .. } => pending.push(operand),
+                    operand => visitor.visit_expr(operand),
+                }
+            }
+        }
+        Expr::Call { callee, arguments } => {
+            visitor.visit_expr(callee);
+            for arg in arguments {
+                visitor.visit_expr(arg);
+            }
+        }
+        Expr::FieldAccess { object, .. } | Expr::OptionalFieldAccess { object, .. } => visitor.visit_expr(object),
+        Expr::Index { object, index } | Expr::OptionalIndex { object, index } => {
+            visitor.visit_expr(object);
+            visitor.visit_expr(index);
+        }
+        Expr::Slice { object, start, end, .. } => {
+            visitor.visit_expr(object);
+            visitor.visit_expr(start);
+            visitor.visit_expr(end);
+        }
+        Expr::List(items) => {
+            for item in items {
+                visitor.visit_expr(item);
+            }
+        }
+        Expr::MapLiteral(entries) => {
+            for (key, value) in entries {
+                visitor.visit_expr(key);
+                visitor.visit_expr(value);
+            }
+        }
+        Expr::Lambda { body, .. } => visitor.visit_expr(body),
+        Expr::BlockLambda { body, .. } => walk_blocks(visitor, body),
+        Expr::StructInit { fields, .. } => {
+            for (_, value) in fields {
+                visitor.visit_expr(value);
+            }
+        }
+        Expr::Ternary { condition, then_expr, else_expr } => {
+            visitor.visit_expr(condition);
+            visitor.visit_expr(then_expr);
+            visitor.visit_expr(else_expr);
+        }
+        Expr::Range { start, end, .. } => {
+            visitor.visit_expr(start);
+            visitor.visit_expr(end);
+        }
+        Expr::StringInterpolation(parts) => {
+            for part in parts {
+                if let StringPart::Expr(expr) | StringPart::Formatted(expr, _) = part {
+                    visitor.visit_expr(expr);
+                }
+            }
+        }
+        Expr::Match { value, arms } => {
+            visitor.visit_expr(value);
+            for arm in arms {
+                walk_match_arm(visitor, arm);
+            }
+        }
+    }
+}
+
+pub fn walk_pattern<V: Visitor + ?Sized>(visitor: &mut V, pattern: &Pattern) {
+    match pattern {
+        Pattern::Literal(_) | Pattern::Variable(_) | Pattern::Wildcard => {}
+        Pattern::List { items, rest } => {
+            for item in items {
+                visitor.visit_pattern(item);
+            }
+            if let Some(rest) = rest {
+                visitor.visit_pattern(rest);
+            }
+        }
+        Pattern::Struct { fields, .. } => {
+            for (_, field) in fields {
+                visitor.visit_pattern(field);
+            }
+        }
+    }
+}
+
+/// Rewriting traversal - same shape as `Visitor`, but nodes can be modified in place
+pub trait VisitorMut {
+    fn visit_block_mut(&mut self, block: &mut Block) {
+        walk_block_mut(self, block);
+    }
+
+    fn visit_statement_mut(&mut self, stmt: &mut Statement) {
+        walk_statement_mut(self, stmt);
+    }
+
+    fn visit_expr_mut(&mut self, expr: &mut Expr) {
+        walk_expr_mut(self, expr);
+    }
+
+    fn visit_pattern_mut(&mut self, pattern: &mut Pattern) {
+        walk_pattern_mut(self, pattern);
+    }
+}
+
+/// Rewrite every block in a program (or body) in order
+pub fn walk_blocks_mut<V: VisitorMut + ?Sized>(visitor: &mut V, blocks: &mut [Block]) {
+    for block in blocks {
+        visitor.visit_block_mut(block);
+    }
+}
+
+pub fn walk_block_mut<V: VisitorMut + ?Sized>(visitor: &mut V, block: &mut Block) {
+    visitor.visit_statement_mut(&mut block.statement);
+}
+
+pub fn walk_statement_mut<V: VisitorMut + ?Sized>(visitor: &mut V, stmt: &mut Statement) {
+    match stmt {
+        Statement::Let { value, .. } => visitor.visit_expr_mut(value),
+        Statement::LetPattern { pattern, value, .. } => {
+            visitor.visit_pattern_mut(pattern);
+            visitor.visit_expr_mut(value);
+        }
+        Statement::Assign { target, value } | Statement::CompoundAssign { target, amount: value, .. } => {
+            walk_assign_target_mut(visitor, target);
+            visitor.visit_expr_mut(value);
+        }
+        Statement::Expression(expr) | Statement::Print(expr) => visitor.visit_expr_mut(expr),
+        Statement::Block(body)
+        | Statement::Test { body, .. }
+        | Statement::BeforeEach(body)
+        | Statement::AfterEach(body) => walk_blocks_mut(visitor, body),
+        // Bodies may be shared with function values, so they're copied on write
+        Statement::FunctionDef { body, .. } | Statement::MethodDef { body, .. } => {
+            walk_blocks_mut(visitor, Arc::make_mut(body))
+        }
+        Statement::If { condition, then_block, otherwise_block } => {
+            visitor.visit_expr_mut(condition);
+            walk_blocks_mut(visitor, then_block);
+            if let Some(otherwise) = otherwise_block {
+                walk_blocks_mut(visitor, otherwise);
+            }
+        }
+        Statement::Match { value, arms } => {
+            visitor.visit_expr_mut(value);
+            for arm in arms {
+                walk_match_arm_mut(visitor, arm);
+            }
+        }
+        Statement::Repeat { count, body } => {
+            visitor.visit_expr_mut(count);
+            walk_blocks_mut(visitor, body);
+        }
+        Statement::While { condition, body } => {
+            visitor.visit_expr_mut(condition);
+            walk_blocks_mut(visitor, body);
+        }
+        Statement::ForEach { pattern, iterable, body } => {
+            visitor.visit_pattern_mut(pattern);
+            visitor.visit_expr_mut(iterable);
+            walk_blocks_mut(visitor, body);
+        }
+        Statement::Return(value) => {
+            if let Some(value) = value {
+                visitor.visit_expr_mut(value);
+            }
+        }
+        Statement::Honk { condition, message } => {
+            visitor.visit_expr_mut(condition);
+            if let Some(message) = message {
+                visitor.visit_expr_mut(message);
+            }
+        }
+        Statement::Push { list, value } => {
+            visitor.visit_expr_mut(list);
+            visitor.visit_expr_mut(value);
+        }
+        Statement::Attempt { try_block, rescue_block, .. } => {
+            walk_blocks_mut(visitor, try_block);
+            walk_blocks_mut(visitor, rescue_block);
+        }
+        Statement::StructDef { .. }
+        | Statement::Break
+        | Statement::Continue
+        | Statement::Migrate { .. } => {}
+    }
+}
+
+pub fn walk_assign_target_mut<V: VisitorMut + ?Sized>(visitor: &mut V, target: &mut AssignTarget) {
+    match target {
+        AssignTarget::Variable(_) => {}
+        AssignTarget::Field { object, .. } => visitor.visit_expr_mut(object),
+        AssignTarget::Index { object, index } => {
+            visitor.visit_expr_mut(object);
+            visitor.visit_expr_mut(index);
+        }
+    }
+}
+
+pub fn walk_match_arm_mut<V: VisitorMut + ?Sized>(visitor: &mut V, arm: &mut MatchArm) {
+    visitor.visit_pattern_mut(&mut arm.pattern);
+    if let Some(guard) = &mut arm.guard {
+        visitor.visit_expr_mut(guard);
+    }
+    if let Some(expr) = &mut arm.expression {
+        visitor.visit_expr_mut(expr);
+    }
+    if let Some(body) = &mut arm.body {
+        walk_blocks_mut(visitor, body);
+    }
+}
+
+pub fn walk_expr_mut<V: VisitorMut + ?Sized>(visitor: &mut V, expr: &mut Expr) {
+    match expr {
+        Expr::Literal(_) | Expr::Identifier(_) | Expr::Builtin(_) => {}
+        // Walked with a work list, like `walk_expr`
+        Expr::Binary { .. } | Expr::Unary { .. } => {
+            let mut pending = vec![expr];
+            while let Some(next) = pending.pop() {
+                match next {
+                    Expr::Binary { left, right, .. } => {
+                        pending.push(right);
+                        pending.push(left);
+                    }
+                    Expr::Unary { operand, .. } => pending.push(operand),
+                    operand => visitor.visit_expr_mut(operand),
+                }
+            }
+        }
+        Expr::Call { callee, arguments } => {
+            visitor.visit_expr_mut(callee);
+            for arg in arguments {
+                visitor.visit_expr_mut(arg);
+            }
+        }
+        Expr::FieldAccess { object, .. } | Expr::OptionalFieldAccess { object, .. } => visitor.visit_expr_mut(object),
+        Expr::Index { object, index } | Expr::OptionalIndex { object, index } => {
+            visitor.visit_expr_mut(object);
+            visitor.visit_expr_mut(index);
+        }
+        Expr::Slice { object, start, end, .. } => {
+            visitor.visit_expr_mut(object);
+            visitor.visit_expr_mut(start);
+            visitor.visit_expr_mut(end);
+        }
+        Expr::List(items) => {
+            for item in items {
+                visitor.visit_expr_mut(item);
+            }
+        }
+        Expr::MapLiteral(entries) => {
+            for (key, value) in entries {
+                visitor.visit_expr_mut(key);
+                visitor.visit_expr_mut(value);
+            }
+        }
+        Expr::Lambda { body, .. } => visitor.visit_expr_mut(Arc::make_mut(body)),
+        Expr::BlockLambda { body, .. } => walk_blocks_mut(visitor, Arc::make_mut(body)),
+        Expr::StructInit { fields, .. } => {
+            for (_, value) in fields {
+                visitor.visit_expr_mut(value);
+            }
+        }
+        Expr::Ternary { condition, then_expr, else_expr } => {
+            visitor.visit_expr_mut(condition);
+            visitor.visit_expr_mut(then_expr);
+            visitor.visit_expr_mut(else_expr);
+        }
+        Expr::Range { start, end, .. } => {
+            visitor.visit_expr_mut(start);
+            visitor.visit_expr_mut(end);
+        }
+        Expr::StringInterpolation(parts) => {
+            for part in parts {
+                if let StringPart::Expr(expr) | StringPart::Formatted(expr, _) = part {
+                    visitor.visit_expr_mut(expr);
+                }
+            }
+        }
+        Expr::Match { value, arms } => {
+            visitor.visit_expr_mut(value);
+            for arm in arms {
+                walk_match_arm_mut(visitor, arm);
+            }
+        }
+    }
+}
+
+pub fn walk_pattern_mut<V: VisitorMut + ?Sized>(visitor: &mut V, pattern: &mut Pattern) {
+    match pattern {
+        Pattern::Literal(_) | Pattern::Variable(_) | Pattern::Wildcard => {}
+        Pattern::List { items, rest } => {
+            for item in items {
+                visitor.visit_pattern_mut(item);
+            }
+            if let Some(rest) = rest {
+                visitor.visit_pattern_mut(rest);
+            }
+        }
+        Pattern::Struct { fields, .. } => {
+            for (_, field) in fields {
+                visitor.visit_pattern_mut(field);
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::ast::{BinaryOp, Literal};
+    use crate::lexer::lex;
+    use crate::parser::Parser;
+
+    fn parse_source(source: &str) -> Vec<Block> {
+        Parser::new(lex(source).unwrap()).parse().unwrap()
+    }
+
+    /// Collects every identifier the walk reaches
+    struct IdentifierCollector(Vec<String>);
+
+    impl Visitor for IdentifierCollector {
+        fn visit_expr(&mut self, expr: &Expr) {
+            if let Expr::Identifier(name) = expr {
+                self.0.push(name.clone());
+            }
+            walk_expr(self, expr);
+        }
+    }
+
+    #[test]
+    fn test_visitor_reaches_nested_expressions() {
+        let blocks = parse_source(
+            "quack [define f taking [a] as\n  quack [if a > limit then\n    quack [print f\"{a} {b}\"]\n  ]\n]",
+        );
+        let mut collector = IdentifierCollector(Vec::new());
+        walk_blocks(&mut collector, &blocks);
+        assert_eq!(collector.0, vec!["a", "limit", "a", "b"]);
+    }
+
+    #[test]
+    fn test_long_operator_chains_walk_without_recursing() {
+        let chain = vec!["a"; 20_000].join(" + ");
+        let mut blocks = parse_source(&format!("quack [print {} * - b]", chain));
+        let mut collector = IdentifierCollector(Vec::new());
+        walk_blocks(&mut collector, &blocks);
+        assert_eq!(collector.0.len(), 20_001);
+        assert_eq!(collector.0.last().map(String::as_str), Some("b"));
+
+        walk_blocks_mut(&mut Rename { from: "a", to: "c" }, &mut blocks);
+        let mut collector = IdentifierCollector(Vec::new());
+        walk_blocks(&mut collector, &blocks);
+        assert_eq!(collector.0.iter().filter(|name| *name == "c").count(), 20_000);
+    }
+
+    /// Renames every reference to one variable
+    struct Rename<'a> {
+        from: &'a str,
+        to: &'a str,
+    }
+
+    impl VisitorMut for Rename<'_> {
+        fn visit_expr_mut(&mut self, expr: &mut Expr) {
+            if let Expr::Identifier(name) = expr {
+                if name == self.from {
+                    *name = self.to.to_string();
+                }
+            }
+            walk_expr_mut(self, expr);
+        }
+    }
+
+    #[test]
+    fn test_visitor_mut_rewrites_in_place() {
+        let mut blocks = parse_source("quack [print old + 1]");
+        walk_blocks_mut(&mut Rename { from: "old", to: "new" }, &mut blocks);
+        let expected = Statement::Print(Expr::binary(
+            Expr::identifier("new"),
+            BinaryOp::Add,
+            Expr::Literal(Literal::Int(1)),
+        ));
+        assert_eq!(blocks[0].statement, expected);
+    }
+}
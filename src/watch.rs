@@ -0,0 +1,127 @@
+// File watching behind `watch-path` - polls for changes, no platform APIs needed
+// Changes that arrive close together are gathered into one batch (debounced)
+
+use std::collections::{BTreeMap, BTreeSet};
+use std::fs;
+use std::path::Path;
+use std::thread;
+use std::time::{Duration, Instant, SystemTime};
+
+/// How often the watched files are looked at
+const POLL_INTERVAL: Duration = Duration::from_millis(100);
+
+/// How long things must stay quiet before a batch of changes is reported,
+/// so an editor saving several files counts as one change
+const DEBOUNCE: Duration = Duration::from_millis(250);
+
+/// What a file looked like last time: when it changed and how big it was
+type Snapshot = BTreeMap<String, (Option<SystemTime>, u64)>;
+
+/// Watches a file, or a folder and everything below it
+pub struct Watcher {
+    root: String,
+    last: Snapshot,
+}
+
+impl Watcher {
+    pub fn new(path: &str) -> Result<Self, String> {
+        if !Path::new(path).exists() {
+            return Err(format!("The goose searched everywhere but couldn't find '{}' to watch", path));
+        }
+        Ok(Watcher {
+            root: path.to_string(),
+            last: snapshot(path),
+        })
+    }
+
+    /// Wait until something changes, then return every path that was added,
+    /// changed or removed, sorted. Stops early with the error from
+    /// `interrupted` if it returns one.
+    pub fn next_change(&mut self, interrupted: &dyn Fn() -> Option<String>) -> Result<Vec<String>, String> {
+        let mut changed = BTreeSet::new();
+        let mut quiet_since = Instant::now();
+        loop {
+            if let Some(reason) = interrupted() {
+                return Err(reason);
+            }
+            thread::sleep(POLL_INTERVAL);
+
+            let differences = self.poll();
+            if differences.is_empty() {
+                if !changed.is_empty() && quiet_since.elapsed() >= DEBOUNCE {
+                    return Ok(changed.into_iter().collect());
+                }
+            } else {
+                changed.extend(differences);
+                quiet_since = Instant::now();
+            }
+        }
+    }
+
+    /// Every path that changed since the last look, without waiting (or debouncing)
+    pub fn poll(&mut self) -> Vec<String> {
+        let now = snapshot(&self.root);
+        let differences = diff(&self.last, &now);
+        self.last = now;
+        differences
+    }
+}
+
+fn snapshot(root: &str) -> Snapshot {
+    let mut files = Snapshot::new();
+    record(Path::new(root), root.to_string(), &mut files);
+    files
+}
+
+fn record(path: &Path, name: String, files: &mut Snapshot) {
+    // symlink_metadata so a link loop can't send the goose in circles
+    let Ok(metadata) = fs::symlink_metadata(path) else { return };
+    if metadata.is_dir() {
+        let Ok(entries) = fs::read_dir(path) else { return };
+        for entry in entries.flatten() {
+            let child = format!("{}/{}", name.trim_end_matches('/'), entry.file_name().to_string_lossy());
+            record(&entry.path(), child, files);
+        }
+    } else {
+        files.insert(name, (metadata.modified().ok(), metadata.len()));
+    }
+}
+
+fn diff(before: &Snapshot, after: &Snapshot) -> Vec<String> {
+    let changed = after.iter().filter(|(path, state)| before.get(*path) != Some(state));
+    let removed = before.keys().filter(|path| !after.contains_key(*path));
+    changed.map(|(path, _)| path.clone()).chain(removed.cloned()).collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_reports_added_changed_and_removed_files() {
+        let dir = std::env::temp_dir()
+            .join(format!("goose-watch-test-{}", std::process::id()))
+            .to_string_lossy()
+            .into_owned();
+        fs::create_dir_all(&dir).unwrap();
+        fs::write(format!("{}/old.txt", dir), "one").unwrap();
+        fs::write(format!("{}/keep.txt", dir), "same").unwrap();
+        let mut watcher = Watcher::new(&dir).unwrap();
+
+        let writer = {
+            let dir = dir.clone();
+            thread::spawn(move || {
+                thread::sleep(Duration::from_millis(150));
+                fs::write(format!("{}/new.txt", dir), "hello").unwrap();
+                fs::remove_file(format!("{}/old.txt", dir)).unwrap();
+            })
+        };
+        let changed = watcher.next_change(&|| None).unwrap();
+        writer.join().unwrap();
+        assert_eq!(changed, vec![format!("{}/new.txt", dir), format!("{}/old.txt", dir)]);
+
+        assert_eq!(watcher.next_change(&|| Some("stop".to_string())).unwrap_err(), "stop");
+        fs::remove_dir_all(&dir).unwrap();
+        assert!(Watcher::new(&dir).is_err());
+    }
+}
@@ -0,0 +1,209 @@
+// WebSocket client behind ws-connect/ws-send/ws-receive/ws-close
+// Each interpreter keeps its connections in a `Sockets` table; Duck code holds a
+// `websocket` struct naming one. wss:// needs TLS, which comes with the `http` feature
+
+use std::collections::HashMap;
+use std::io::ErrorKind as IoErrorKind;
+use std::net::TcpStream;
+use std::time::{Duration, Instant};
+
+use tungstenite::stream::MaybeTlsStream;
+use tungstenite::{Message, WebSocket};
+
+use crate::values::Value;
+
+/// How long a receive blocks between checks for cancellation
+const POLL_INTERVAL: Duration = Duration::from_millis(50);
+
+type Socket = WebSocket<MaybeTlsStream<TcpStream>>;
+
+/// One interpreter's open connections. Dropping the table (with the
+/// interpreter that owns it) closes any the program left open.
+#[derive(Default)]
+pub struct Sockets {
+    open: HashMap<usize, Socket>,
+    next_id: usize,
+}
+
+impl Sockets {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Open a connection and return the handle Duck code passes to the other ws- builtins
+    pub fn connect(&mut self, url: &str) -> Result<Value, String> {
+        let (socket, _) = tungstenite::connect(url).map_err(|e| format!("couldn't connect to {}: {}", url, e))?;
+        self.next_id += 1;
+        let id = self.next_id;
+        self.open.insert(id, socket);
+
+        let mut fields = HashMap::new();
+        fields.insert("id".to_string(), Value::Number(id as f64));
+        fields.insert("url".to_string(), Value::String(url.into()));
+        Ok(Value::new_struct("websocket".to_string(), fields))
+    }
+
+    /// Check that `handle` names a connection that's still open
+    pub fn check_open(&self, handle: &Value) -> Result<(), String> {
+        match self.open.contains_key(&handle_id(handle)?) {
+            true => Ok(()),
+            false => Err("that websocket is closed".to_string()),
+        }
+    }
+
+    fn with_socket<T>(&mut self, handle: &Value, f: impl FnOnce(&mut Socket) -> Result<T, String>) -> Result<T, String> {
+        match self.open.get_mut(&handle_id(handle)?) {
+            Some(socket) => f(socket),
+            None => Err("that websocket is closed".to_string()),
+        }
+    }
+
+    pub fn send(&mut self, handle: &Value, message: &Value) -> Result<(), String> {
+        let text = match message {
+            Value::String(s) => s.to_string(),
+            other => other.to_string(),
+        };
+        self.with_socket(handle, |socket| {
+            socket.send(Message::text(text)).map_err(|e| format!("couldn't send: {}", e))
+        })
+    }
+
+    /// Wait for the next text (or binary) message. Returns `Value::Null` if
+    /// `timeout` passes first or the other side closes the connection.
+    pub fn receive(
+        &mut self,
+        handle: &Value,
+        timeout: Option<Duration>,
+        interrupted: &dyn Fn() -> Option<String>,
+    ) -> Result<Value, String> {
+        let started = Instant::now();
+        let id = handle_id(handle)?;
+        loop {
+            if let Some(reason) = interrupted() {
+                return Err(reason);
+            }
+            let remaining = match timeout {
+                Some(limit) => match limit.checked_sub(started.elapsed()) {
+                    Some(left) if !left.is_zero() => left.min(POLL_INTERVAL),
+                    _ => return Ok(Value::Null),
+                },
+                None => POLL_INTERVAL,
+            };
+
+            let outcome = self.with_socket(handle, |socket| {
+                set_read_timeout(socket, remaining);
+                match socket.read() {
+                    Ok(Message::Text(text)) => Ok(Some(Value::String(text.into()))),
+                    Ok(Message::Binary(bytes)) => Ok(Some(Value::String(String::from_utf8_lossy(&bytes).into_owned().into()))),
+                    Ok(Message::Close(_)) => Ok(Some(Value::Null)),
+                    // Pings and pongs are answered for us; keep waiting
+                    Ok(_) => Ok(None),
+                    Err(tungstenite::Error::Io(e)) if matches!(e.kind(), IoErrorKind::WouldBlock | IoErrorKind::TimedOut) => {
+                        Ok(None)
+                    }
+                    Err(tungstenite::Error::ConnectionClosed | tungstenite::Error::AlreadyClosed) => Ok(Some(Value::Null)),
+                    Err(e) => Err(format!("couldn't receive: {}", e)),
+                }
+            })?;
+            if let Some(value) = outcome {
+                if value == Value::Null {
+                    self.open.remove(&id);
+                }
+                return Ok(value);
+            }
+        }
+    }
+
+    /// Close the connection politely. Closing twice is fine.
+    pub fn close(&mut self, handle: &Value) -> Result<(), String> {
+        if let Some(socket) = self.open.remove(&handle_id(handle)?) {
+            close_politely(socket);
+        }
+        Ok(())
+    }
+}
+
+impl Drop for Sockets {
+    fn drop(&mut self) {
+        for (_, socket) in self.open.drain() {
+            close_politely(socket);
+        }
+    }
+}
+
+fn close_politely(mut socket: Socket) {
+    socket.close(None).ok();
+    // Let the close handshake go out
+    socket.flush().ok();
+}
+
+/// Which connection a handle refers to
+fn handle_id(handle: &Value) -> Result<usize, String> {
+    match handle {
+        Value::Struct { name, fields } if name == "websocket" => match fields.borrow().get("id") {
+            Some(Value::Number(id)) => Ok(*id as usize),
+            _ => Err("that websocket handle has lost its id".to_string()),
+        },
+        other => Err(format!("expected a websocket (from ws-connect), got {}", other.type_name())),
+    }
+}
+
+fn set_read_timeout(socket: &Socket, timeout: Duration) {
+    let stream = match socket.get_ref() {
+        MaybeTlsStream::Plain(stream) => stream,
+        #[cfg(feature = "http")]
+        MaybeTlsStream::NativeTls(stream) => stream.get_ref(),
+        _ => return,
+    };
+    stream.set_read_timeout(Some(timeout)).ok();
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::net::TcpListener;
+    use std::thread;
+
+    #[test]
+    fn test_echo_round_trip() {
+        let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+        let port = listener.local_addr().unwrap().port();
+        let server = thread::spawn(move || {
+            let (stream, _) = listener.accept().unwrap();
+            let mut socket = tungstenite::accept(stream).unwrap();
+            let message = socket.read().unwrap();
+            socket.send(Message::text(format!("echo: {}", message.to_text().unwrap()))).unwrap();
+            // Wait for the client to hang up
+            while socket.read().is_ok() {}
+        });
+
+        let mut sockets = Sockets::new();
+        let handle = sockets.connect(&format!("ws://127.0.0.1:{}", port)).unwrap();
+        sockets.send(&handle, &Value::String("honk".into())).unwrap();
+        let reply = sockets.receive(&handle, Some(Duration::from_secs(5)), &|| None).unwrap();
+        assert_eq!(reply, Value::String("echo: honk".into()));
+        // Nothing else is coming
+        assert_eq!(sockets.receive(&handle, Some(Duration::from_millis(60)), &|| None).unwrap(), Value::Null);
+        sockets.close(&handle).unwrap();
+        assert!(sockets.send(&handle, &Value::String("late".into())).is_err());
+        assert!(sockets.check_open(&handle).is_err());
+        server.join().unwrap();
+    }
+
+    #[test]
+    fn test_dropping_the_table_closes_its_sockets() {
+        let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+        let port = listener.local_addr().unwrap().port();
+        let server = thread::spawn(move || {
+            let (stream, _) = listener.accept().unwrap();
+            let mut socket = tungstenite::accept(stream).unwrap();
+            matches!(socket.read(), Ok(Message::Close(_)))
+        });
+
+        let mut sockets = Sockets::new();
+        let handle = sockets.connect(&format!("ws://127.0.0.1:{}", port)).unwrap();
+        assert!(sockets.check_open(&handle).is_ok());
+        drop(sockets);
+        assert!(server.join().unwrap());
+    }
+}
@@ -0,0 +1,317 @@
+// `goose check` on a folder - every Duck file under it, checked as one project
+//
+// Each file gets the same checks as `goose check file.duck` (see `lint`). On
+// top of that, migrates are followed across files: a migrate of a file that
+// isn't there is an issue, and so is `alias.name` when the migrated file never
+// defines `name`. Paths in migrates are resolved from the current directory,
+// the way the interpreter does.
+//
+// Files are lexed, parsed and linted on a few threads at once. That only works
+// because the lexer and parser keep no shared state - keep it that way.
+
+use std::collections::HashMap;
+use std::fs;
+use std::path::{Path, PathBuf};
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::thread;
+
+use crate::analysis::SymbolTable;
+use crate::ast::{Block, Expr, Statement};
+use crate::goose::ExecutionStats;
+use crate::lint::FileCheck;
+use crate::parser::{parse_str, STACK_SIZE};
+use crate::visit::{walk_block, walk_expr, walk_statement, Visitor};
+
+/// One file's check, plus what was wrong with its migrates
+#[derive(Debug, Clone)]
+pub struct WorkspaceFile {
+    pub path: PathBuf,
+    pub check: FileCheck,
+    /// Migrates of missing files and uses of names they don't define: (line, message)
+    pub migrate_issues: Vec<(usize, String)>,
+}
+
+impl WorkspaceFile {
+    pub fn issue_count(&self) -> usize {
+        self.check.issue_count() + self.migrate_issues.len()
+    }
+}
+
+/// Every .duck file under a folder, checked
+#[derive(Debug, Clone)]
+pub struct Workspace {
+    pub files: Vec<WorkspaceFile>,
+}
+
+impl Workspace {
+    /// Check every .duck file under `root`, in path order. Hidden folders and
+    /// `target` are skipped.
+    pub fn check(root: &Path) -> Result<Workspace, String> {
+        let mut paths = Vec::new();
+        find_duck_files(root, &mut paths)?;
+        paths.sort();
+
+        let threads = thread::available_parallelism().map_or(1, |n| n.get());
+        let checks = check_files(&paths, threads)?;
+        let mut files: Vec<WorkspaceFile> = paths
+            .into_iter()
+            .zip(checks)
+            .map(|(path, check)| WorkspaceFile { path, check, migrate_issues: Vec::new() })
+            .collect();
+
+        // Migrated files that are part of the workspace are already parsed
+        let mut exports: HashMap<PathBuf, Option<SymbolTable>> = HashMap::new();
+        for file in &files {
+            if let Ok(canonical) = file.path.canonicalize() {
+                exports.insert(canonical, exported_names(&file.check.blocks, file.check.errors.is_empty()));
+            }
+        }
+        for file in &mut files {
+            let mut migrates = Migrates::default();
+            for block in &file.check.blocks {
+                migrates.visit_block(block);
+            }
+            for migrate in migrates.found {
+                file.migrate_issues.extend(resolve(&migrate, &mut exports));
+            }
+            file.migrate_issues.sort_by_key(|(line, _)| *line);
+        }
+        Ok(Workspace { files })
+    }
+
+    pub fn issue_count(&self) -> usize {
+        self.files.iter().map(WorkspaceFile::issue_count).sum()
+    }
+
+    /// Stats for the whole workspace, for a goose rating: blocks from every
+    /// file, and the functions and structs each one defines
+    pub fn stats(&self) -> ExecutionStats {
+        let mut stats = ExecutionStats::default();
+        for file in &self.files {
+            let check = &file.check;
+            stats.total_blocks += check.total_blocks;
+            stats.quacked_blocks += check.quacked_blocks();
+            stats.unquacked_blocks += check.unquacked.len();
+            for block in &check.blocks {
+                match block.statement {
+                    Statement::FunctionDef { .. } | Statement::MethodDef { .. } => stats.functions_defined += 1,
+                    Statement::StructDef { .. } => stats.structs_defined += 1,
+                    _ => {}
+                }
+            }
+        }
+        stats
+    }
+}
+
+/// Check each file, sharing them out between up to `threads` threads.
+/// The checks come back in the same order as the paths.
+fn check_files(paths: &[PathBuf], threads: usize) -> Result<Vec<FileCheck>, String> {
+    let next = AtomicUsize::new(0);
+    let worker = || {
+        let mut done = Vec::new();
+        loop {
+            let i = next.fetch_add(1, Ordering::Relaxed);
+            let Some(path) = paths.get(i) else {
+                return done;
+            };
+            let check = match fs::read_to_string(path) {
+                Ok(source) => FileCheck::of(&source),
+                Err(e) => FileCheck { errors: vec![format!("The goose couldn't read it: {}", e)], ..FileCheck::default() },
+            };
+            done.push((i, check));
+        }
+    };
+
+    let mut checks: Vec<(usize, FileCheck)> = thread::scope(|scope| {
+        // Workers share one queue, so any that start get through every file
+        let mut spawn_error = None;
+        let workers: Vec<_> = (0..threads.clamp(1, paths.len().max(1)))
+            .filter_map(|_| {
+                let spawned = thread::Builder::new().stack_size(STACK_SIZE).spawn_scoped(scope, worker);
+                spawned.map_err(|e| spawn_error = Some(e)).ok()
+            })
+            .collect();
+        if let (true, Some(e)) = (workers.is_empty(), spawn_error) {
+            return Err(format!("The goose couldn't start a checking thread: {}", e));
+        }
+        let mut checks = Vec::new();
+        for handle in workers {
+            checks.extend(handle.join().map_err(|_| "A checking thread fell over - the goose gives up".to_string())?);
+        }
+        Ok(checks)
+    })?;
+    checks.sort_by_key(|(i, _)| *i);
+    Ok(checks.into_iter().map(|(_, check)| check).collect())
+}
+
+fn find_duck_files(folder: &Path, found: &mut Vec<PathBuf>) -> Result<(), String> {
+    let entries = fs::read_dir(folder).map_err(|e| format!("The goose couldn't look in '{}': {}", folder.display(), e))?;
+    for entry in entries.flatten() {
+        let path = entry.path();
+        let name = entry.file_name().to_string_lossy().to_string();
+        let Ok(kind) = entry.file_type() else { continue };
+        if kind.is_dir() {
+            if !name.starts_with('.') && name != "target" {
+                find_duck_files(&path, found)?;
+            }
+        } else if path.is_dir() {
+            // A link to a folder: following it could loop back forever
+            continue;
+        } else if path.extension().is_some_and(|extension| extension == "duck") {
+            found.push(path);
+        }
+    }
+    Ok(())
+}
+
+/// The names a file defines when it's migrated, or None if that can't be
+/// known: it didn't parse, or it migrates other files into itself
+fn exported_names(blocks: &[Block], parsed: bool) -> Option<SymbolTable> {
+    let quacked: Vec<&Block> = blocks.iter().filter(|block| block.was_quacked).collect();
+    let pulls_in_more = quacked.iter().any(|block| matches!(block.statement, Statement::Migrate { alias: None, .. }));
+    (parsed && !pulls_in_more).then(|| SymbolTable::build(quacked))
+}
+
+/// The problems with one migrate: a missing file, or names it doesn't define
+fn resolve(migrate: &Migrate, exports: &mut HashMap<PathBuf, Option<SymbolTable>>) -> Vec<(usize, String)> {
+    if migrate.path.starts_with("git+") {
+        return Vec::new();
+    }
+    let canonical = match Path::new(&migrate.path).canonicalize() {
+        Ok(canonical) => canonical,
+        Err(_) => {
+            return vec![(migrate.line, format!("migrate \"{}\" - the flock couldn't find that file", migrate.path))];
+        }
+    };
+    let table = exports.entry(canonical.clone()).or_insert_with(|| {
+        let blocks = fs::read_to_string(&canonical).ok().and_then(|source| parse_str(&source).ok());
+        blocks.and_then(|blocks| exported_names(&blocks, true))
+    });
+    let Some(table) = table else {
+        return Vec::new();
+    };
+    migrate
+        .uses
+        .iter()
+        .filter(|(name, _)| table.get(name).is_none())
+        .map(|(name, line)| {
+            let alias = migrate.alias.as_deref().unwrap_or_default();
+            (*line, format!("'{}.{}' - \"{}\" doesn't define '{}'", alias, name, migrate.path, name))
+        })
+        .collect()
+}
+
+/// A migrate, with the `alias.name` uses of its alias if it has one
+#[derive(Debug, Default)]
+struct Migrate {
+    path: String,
+    alias: Option<String>,
+    line: usize,
+    uses: Vec<(String, usize)>,
+}
+
+/// Collects a program's quacked migrates and what it uses through their aliases
+#[derive(Default)]
+struct Migrates {
+    found: Vec<Migrate>,
+    line: usize,
+}
+
+impl Visitor for Migrates {
+    fn visit_block(&mut self, block: &Block) {
+        if block.was_quacked {
+            self.line = block.line;
+            walk_block(self, block);
+        }
+    }
+
+    fn visit_statement(&mut self, stmt: &Statement) {
+        if let Statement::Migrate { path, alias } = stmt {
+            self.found.push(Migrate { path: path.clone(), alias: alias.clone(), line: self.line, uses: Vec::new() });
+        }
+        walk_statement(self, stmt);
+    }
+
+    fn visit_expr(&mut self, expr: &Expr) {
+        if let Expr::FieldAccess { object, field } | Expr::OptionalFieldAccess { object, field } = expr {
+            if let Expr::Identifier(name) = object.as_ref() {
+                let line = self.line;
+                if let Some(migrate) = self.found.iter_mut().rev().find(|m| m.alias.as_deref() == Some(name.as_str())) {
+                    migrate.uses.push((field.clone(), line));
+                }
+            }
+        }
+        walk_expr(self, expr);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_workspace_follows_migrates() {
+        let root = std::env::temp_dir().join(format!("goose-workspace-{}", std::process::id()));
+        fs::create_dir_all(root.join("lib")).unwrap();
+        let lib = root.join("lib").join("greet.duck");
+        fs::write(&lib, "quack [define hello taking [name] as quack [return name]]\n").unwrap();
+        fs::write(
+            root.join("main.duck"),
+            format!(
+                "quack [migrate \"{}\" as greet]\nquack [print greet.hello(\"pip\")]\nquack [print greet.goodbye(1)]\n[print 2]\nquack [migrate \"nowhere.duck\"]\n",
+                lib.display()
+            ),
+        )
+        .unwrap();
+        fs::write(root.join("notes.txt"), "not duck").unwrap();
+
+        let workspace = Workspace::check(&root).unwrap();
+        fs::remove_dir_all(&root).unwrap();
+
+        let names: Vec<_> = workspace.files.iter().map(|file| file.path.strip_prefix(&root).unwrap().to_path_buf()).collect();
+        assert_eq!(names, [Path::new("lib/greet.duck"), Path::new("main.duck")]);
+        let main = &workspace.files[1];
+        let lines: Vec<usize> = main.migrate_issues.iter().map(|(line, _)| *line).collect();
+        assert_eq!(lines, [3, 5]);
+        assert!(main.migrate_issues[0].1.contains("doesn't define 'goodbye'"));
+        assert_eq!(workspace.issue_count(), 3);
+
+        let stats = workspace.stats();
+        assert_eq!((stats.total_blocks, stats.quacked_blocks, stats.functions_defined), (7, 6, 1));
+    }
+
+    #[test]
+    fn test_threads_keep_files_in_order() {
+        let root = std::env::temp_dir().join(format!("goose-threads-{}", std::process::id()));
+        fs::create_dir_all(&root).unwrap();
+        let paths: Vec<PathBuf> = (0..40).map(|i| root.join(format!("{:02}.duck", i))).collect();
+        for (i, path) in paths.iter().enumerate() {
+            fs::write(path, "quack [print 1]\n[print 2]\n".repeat(i)).unwrap();
+        }
+        let checks = check_files(&paths, 8).unwrap();
+        fs::remove_dir_all(&root).unwrap();
+
+        let blocks: Vec<usize> = checks.iter().map(|check| check.total_blocks).collect();
+        assert_eq!(blocks, (0..40).map(|i| i * 2).collect::<Vec<_>>());
+        assert_eq!(check_files(&[root.join("gone.duck")], 8).unwrap()[0].errors.len(), 1);
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn test_linked_folders_are_not_followed() {
+        let root = std::env::temp_dir().join(format!("goose-links-{}", std::process::id()));
+        fs::create_dir_all(root.join("lib")).unwrap();
+        fs::write(root.join("lib").join("one.duck"), "quack [print 1]\n").unwrap();
+        // A loop back to the top, and a second way into lib
+        std::os::unix::fs::symlink(&root, root.join("lib").join("up")).unwrap();
+        std::os::unix::fs::symlink(root.join("lib"), root.join("again")).unwrap();
+
+        let mut found = Vec::new();
+        let result = find_duck_files(&root, &mut found);
+        fs::remove_dir_all(&root).unwrap();
+
+        assert!(result.is_ok());
+        assert_eq!(found, [root.join("lib").join("one.duck")]);
+    }
+}